@@ -176,3 +176,181 @@ mod group_4 {
         }
     }
 }
+
+mod group_5 {
+    use super::*;
+
+    /// Splits the fixture into many tiny streams, which simulates searching per-request payloads.
+    fn tiny_streams() -> Vec<Vec<u8>> {
+        let mut f = File::open("data/pride-and-prejudice.txt")
+            .expect("testing file is not existed");
+        let mut buf = Vec::new();
+        f.read_to_end(&mut buf).unwrap();
+        buf.chunks(256).take(1000).map(|chunk| chunk.to_vec()).collect()
+    }
+
+    mod tiny_stream_find_iter {
+        use super::*;
+
+        #[bench]
+        fn xfind(b: &mut Bencher) {
+            let streams = tiny_streams();
+
+            b.iter(|| {
+                for stream in &streams {
+                    let _matches: Vec<io::Result<usize>> =
+                        xfind::find_iter(b"dear", &mut &stream[..]).collect();
+                }
+            });
+        }
+
+        #[bench]
+        fn memchr(b: &mut Bencher) {
+            let streams = tiny_streams();
+
+            b.iter(|| {
+                for stream in &streams {
+                    let mut rdr = &stream[..];
+                    let mut haystack = Vec::new();
+                    rdr.read_to_end(&mut haystack).unwrap();
+
+                    let _matches: Vec<usize> =
+                        memmem::find_iter(&haystack, b"dear").collect();
+                }
+            });
+        }
+    }
+}
+
+mod group_6 {
+    use super::*;
+    use xfind::StreamFinderBuilder;
+
+    /// A reader that records the size of the largest buffer passed to `read`.
+    struct MaxRead<R> {
+        rdr: R,
+        max_read: usize,
+    }
+
+    impl<R: Read> Read for MaxRead<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.max_read = self.max_read.max(buf.len());
+            self.rdr.read(buf)
+        }
+    }
+
+    /// Searches the fixture with the given buffer capacity, and checks that the number of matches
+    /// doesn't depend on it, and that reads actually use the whole capacity.
+    fn find_iter_with_capacity(b: &mut Bencher, capacity: usize) {
+        let mut f = File::open("data/pride-and-prejudice.txt")
+            .expect("testing file is not existed");
+        let mut haystack = Vec::with_capacity(1000000);
+        f.read_to_end(&mut haystack).unwrap();
+        let expected = memmem::find_iter(&haystack, b"dear").count();
+
+        let finder = StreamFinderBuilder::new()
+            .buffer_capacity(capacity)
+            .build(b"dear")
+            .unwrap();
+        let mut rdr = MaxRead { rdr: &haystack[..], max_read: 0 };
+        assert_eq!(finder.find_iter(&mut rdr).count(), expected);
+        assert_eq!(rdr.max_read, capacity - b"dear".len());
+        b.bytes = haystack.len() as u64;
+        b.iter(|| {
+            let mut f = File::open("data/pride-and-prejudice.txt")
+                .expect("testing file is not existed");
+
+            let matches: Vec<io::Result<usize>> =
+                finder.find_iter(&mut f).collect();
+            assert_eq!(matches.len(), expected);
+        });
+    }
+
+    mod buffer_capacity_find_iter {
+        use super::*;
+
+        #[bench]
+        fn capacity_1kb(b: &mut Bencher) {
+            find_iter_with_capacity(b, 1 << 10);
+        }
+
+        #[bench]
+        fn capacity_8kb(b: &mut Bencher) {
+            find_iter_with_capacity(b, 8 << 10);
+        }
+
+        #[bench]
+        fn capacity_64kb(b: &mut Bencher) {
+            find_iter_with_capacity(b, 64 << 10);
+        }
+
+        #[bench]
+        fn capacity_256kb(b: &mut Bencher) {
+            find_iter_with_capacity(b, 256 << 10);
+        }
+    }
+}
+
+mod group_7 {
+    use super::*;
+
+    /// Searches the fixture backward for the given needle, and checks that the number of matches
+    /// agrees with `memmem`.
+    fn rfind_iter_with_needle(b: &mut Bencher, needle: &[u8]) {
+        let mut f = File::open("data/pride-and-prejudice.txt")
+            .expect("testing file is not existed");
+        let mut haystack = Vec::with_capacity(1000000);
+        f.read_to_end(&mut haystack).unwrap();
+        let expected = memmem::rfind_iter(&haystack, needle).count();
+
+        b.bytes = haystack.len() as u64;
+        b.iter(|| {
+            let mut f = File::open("data/pride-and-prejudice.txt")
+                .expect("testing file is not existed");
+
+            let matches: Vec<io::Result<usize>> =
+                xfind::rfind_iter(needle, &mut f).unwrap().collect();
+            assert_eq!(matches.len(), expected);
+        });
+    }
+
+    /// Like `rfind_iter_with_needle`, but reads the whole fixture and searches it with `memmem`.
+    fn memmem_rfind_iter_with_needle(b: &mut Bencher, needle: &[u8]) {
+        b.bytes = std::fs::metadata("data/pride-and-prejudice.txt")
+            .expect("testing file is not existed")
+            .len();
+        b.iter(|| {
+            let mut f = File::open("data/pride-and-prejudice.txt")
+                .expect("testing file is not existed");
+            let mut haystack = Vec::with_capacity(1000000);
+            f.read_to_end(&mut haystack).unwrap();
+
+            let _matches: Vec<usize> =
+                memmem::rfind_iter(&haystack, needle).collect();
+        });
+    }
+
+    mod stream_rfind_iter_needle_len {
+        use super::*;
+
+        #[bench]
+        fn xfind_1_byte(b: &mut Bencher) {
+            rfind_iter_with_needle(b, b"e");
+        }
+
+        #[bench]
+        fn memchr_1_byte(b: &mut Bencher) {
+            memmem_rfind_iter_with_needle(b, b"e");
+        }
+
+        #[bench]
+        fn xfind_16_bytes(b: &mut Bencher) {
+            rfind_iter_with_needle(b, b"Elizabeth Bennet");
+        }
+
+        #[bench]
+        fn memchr_16_bytes(b: &mut Bencher) {
+            memmem_rfind_iter_with_needle(b, b"Elizabeth Bennet");
+        }
+    }
+}
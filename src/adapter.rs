@@ -0,0 +1,579 @@
+//! Provides `Read` adapters that cut a stream at the first occurrence of a needle, that normalize
+//! line endings, or that turn other sources of bytes into a stream.
+use crate::buffer::DEFAULT_BUFFER_CAPACITY;
+use crate::finder::Searcher;
+use memchr::memmem;
+use std::cmp;
+use std::io::{self, Read};
+use std::sync::mpsc::Receiver;
+
+/// A reader that yields the bytes of another reader up to the first occurrence of a needle.
+///
+/// The reader reports EOF right before the needle would be read, so it truncates the stream at
+/// the start of the first match. This makes "everything before the delimiter" available to any
+/// consumer of [`Read`], like a parser or [`io::copy`]. If the needle doesn't occur, the whole
+/// stream is yielded.
+///
+/// To detect an occurrence that is split across reads, the last `needle.len() - 1` bytes read
+/// from the underlying reader are held back until the following bytes are known. The underlying
+/// reader is therefore read ahead of the bytes yielded, and usually past the needle.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor, Read};
+/// use xfind::TakeUntil;
+///
+/// fn main() -> io::Result<()> {
+///     let mut header = String::new();
+///     TakeUntil::new(b"\r\n\r\n", Cursor::new(b"Host: a\r\n\r\nbody"))
+///         .read_to_string(&mut header)?;
+///     assert_eq!(header, "Host: a");
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct TakeUntil<R> {
+    /// The underlying reader.
+    rdr: R,
+    /// The needle that ends the stream.
+    needle: Vec<u8>,
+    /// A buffer for the bytes read from `rdr`.
+    buf: Vec<u8>,
+    /// The start of the bytes in `buf` that haven't been yielded yet.
+    start: usize,
+    /// The end of the bytes in `buf` that are known to precede the needle.
+    ready: usize,
+    /// The end of the bytes in `buf`.
+    end: usize,
+    /// Whether `ready` is the end of the truncated stream.
+    done: bool,
+}
+
+impl<R: Read> TakeUntil<R> {
+    /// Creates a reader that yields the bytes of `rdr` up to the first occurrence of `needle`.
+    ///
+    /// An empty needle occurs at the very start, so the reader is empty.
+    pub fn new(needle: &[u8], rdr: R) -> TakeUntil<R> {
+        let capacity = cmp::max(DEFAULT_BUFFER_CAPACITY, needle.len() * 2);
+        TakeUntil {
+            rdr,
+            needle: needle.to_vec(),
+            buf: vec![0; capacity],
+            start: 0,
+            ready: 0,
+            end: 0,
+            done: false,
+        }
+    }
+}
+
+impl<R: Read> Read for TakeUntil<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while !buf.is_empty() {
+            if self.start < self.ready {
+                let n = cmp::min(buf.len(), self.ready - self.start);
+                buf[..n]
+                    .copy_from_slice(&self.buf[self.start..self.start + n]);
+                self.start += n;
+                return Ok(n);
+            }
+            if self.done {
+                break;
+            }
+
+            self.buf.copy_within(self.start..self.end, 0);
+            self.end -= self.start;
+            self.ready -= self.start;
+            self.start = 0;
+            // Read only after the state is consistent, so that an error (e.g. `Interrupted`)
+            // leaves it intact.
+            let n = self.rdr.read(&mut self.buf[self.end..])?;
+            self.end += n;
+            // Only the bytes that haven't been searched yet can start the needle.
+            match memmem::find(&self.buf[self.ready..self.end], &self.needle) {
+                Some(mat) => {
+                    self.ready += mat;
+                    self.done = true;
+                }
+                None if n == 0 => {
+                    self.ready = self.end;
+                    self.done = true;
+                }
+                None => {
+                    self.ready = cmp::max(
+                        self.ready,
+                        (self.end + 1).saturating_sub(self.needle.len()),
+                    );
+                }
+            }
+        }
+        Ok(0)
+    }
+}
+
+/// A reader that skips the bytes of another reader up to and including the first occurrence of
+/// a needle, and yields the rest.
+///
+/// This is the complement of [`TakeUntil`], and is useful for parsing the part of a stream that
+/// follows a marker, like the body after a header. The first read searches the needle, and then
+/// yields the bytes following it. If the needle doesn't occur, the whole stream is consumed and
+/// the reader is empty.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor, Read};
+/// use xfind::SkipPast;
+///
+/// fn main() -> io::Result<()> {
+///     let mut body = String::new();
+///     SkipPast::new(b"\r\n\r\n", Cursor::new(b"Host: a\r\n\r\nbody"))
+///         .read_to_string(&mut body)?;
+///     assert_eq!(body, "body");
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct SkipPast<R> {
+    /// The underlying reader.
+    rdr: R,
+    /// The needle that ends the skipped part of the stream.
+    needle: Vec<u8>,
+    /// The state of the search, until the needle has been found.
+    searcher: Option<Searcher>,
+    /// The bytes following the needle that the search has read already.
+    tail: Vec<u8>,
+    /// The start of the bytes in `tail` that haven't been yielded yet.
+    pos: usize,
+    /// Whether the needle has been found.
+    found: bool,
+}
+
+impl<R: Read> SkipPast<R> {
+    /// Creates a reader that yields the bytes of `rdr` following the first occurrence of `needle`.
+    ///
+    /// An empty needle occurs at the very start, so the reader yields the whole stream.
+    pub fn new(needle: &[u8], rdr: R) -> SkipPast<R> {
+        SkipPast {
+            rdr,
+            needle: needle.to_vec(),
+            searcher: Some(Searcher::new(needle.len())),
+            tail: Vec::new(),
+            pos: 0,
+            found: false,
+        }
+    }
+}
+
+impl<R: Read> Read for SkipPast<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(searcher) = self.searcher.as_mut() {
+            match searcher.next(&mut self.rdr, &self.needle) {
+                Some(Ok(pos)) => {
+                    // The buffer holds at least the whole match.
+                    let (offset, contents) = searcher.buffered();
+                    self.tail =
+                        contents[pos + self.needle.len() - offset..].to_vec();
+                    self.found = true;
+                }
+                Some(Err(err)) => return Err(err),
+                // The whole stream has been consumed.
+                None => {}
+            }
+            self.searcher = None;
+        }
+        if self.pos < self.tail.len() {
+            let n = cmp::min(buf.len(), self.tail.len() - self.pos);
+            buf[..n].copy_from_slice(&self.tail[self.pos..self.pos + n]);
+            self.pos += n;
+            return Ok(n);
+        }
+        if !self.found {
+            return Ok(0);
+        }
+        self.rdr.read(buf)
+    }
+}
+
+/// Returns a reader that yields the bytes of `rdr` with every `\r\n` replaced by `\n`.
+///
+/// All searches of this crate report raw byte offsets, so a match after a `\r\n` line ending is
+/// one byte further into a CRLF file than into the same text with LF line endings. Searching
+/// through this adapter reports offsets in the normalized stream instead, e.g. to agree with a
+/// tool that reads files with their line endings translated. Those offsets don't point into the
+/// original stream anymore. A bare `\r` is yielded unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+///
+/// fn main() -> io::Result<()> {
+///     let mut raw = Cursor::new(b"rusty\r\nrust");
+///     assert_eq!(xfind::find_iter(b"rust", &mut raw).into_offsets()?, vec![0, 7]);
+///
+///     let mut normalized = xfind::normalize_crlf(Cursor::new(b"rusty\r\nrust"));
+///     assert_eq!(xfind::find_iter(b"rust", &mut normalized).into_offsets()?, vec![0, 6]);
+///
+///     Ok(())
+/// }
+/// ```
+pub fn normalize_crlf<R: Read>(rdr: R) -> NormalizeCrlf<R> {
+    NormalizeCrlf {
+        rdr,
+        buf: vec![0; DEFAULT_BUFFER_CAPACITY],
+        start: 0,
+        end: 0,
+        eof: false,
+    }
+}
+
+/// A reader that replaces every `\r\n` of another reader by `\n`.
+///
+/// This reader is created by [`normalize_crlf`].
+#[derive(Debug)]
+pub struct NormalizeCrlf<R> {
+    /// The underlying reader.
+    rdr: R,
+    /// A buffer for the normalized bytes read from `rdr`.
+    buf: Vec<u8>,
+    /// The start of the bytes in `buf` that haven't been yielded yet.
+    start: usize,
+    /// The end of the bytes in `buf`.
+    end: usize,
+    /// Whether `rdr` has reached EOF.
+    eof: bool,
+}
+
+impl<R: Read> Read for NormalizeCrlf<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            // A trailing `\r` is held back until we know whether a `\n` follows it.
+            let held_back = !self.eof
+                && self.end > self.start
+                && self.buf[self.end - 1] == b'\r';
+            let ready = self.end - held_back as usize;
+            if ready > self.start {
+                let n = cmp::min(buf.len(), ready - self.start);
+                buf[..n]
+                    .copy_from_slice(&self.buf[self.start..self.start + n]);
+                self.start += n;
+                return Ok(n);
+            }
+            if self.eof {
+                return Ok(0);
+            }
+
+            // At most the held back `\r` is left, which is normalized again with the new bytes.
+            self.buf.copy_within(self.start..self.end, 0);
+            self.end -= self.start;
+            self.start = 0;
+            let n = self.rdr.read(&mut self.buf[self.end..])?;
+            if n == 0 {
+                self.eof = true;
+                continue;
+            }
+            let len = self.end + n;
+            let mut w = 0;
+            for r in 0..len {
+                if self.buf[r] == b'\r'
+                    && r + 1 < len
+                    && self.buf[r + 1] == b'\n'
+                {
+                    continue;
+                }
+                self.buf[w] = self.buf[r];
+                w += 1;
+            }
+            self.end = w;
+        }
+    }
+}
+
+/// A reader over the chunks of bytes received from a channel.
+///
+/// This plugs the searchers of this crate into producer/consumer pipelines, e.g. a thread that
+/// receives network packets and sends their payloads to a searching thread. Every read blocks
+/// until a chunk is available, and EOF is reported once the channel is closed, i.e. all senders
+/// have been dropped and all chunks have been read. Empty chunks are skipped, so they don't end
+/// the stream. Matches that span several chunks are found like in any other stream.
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+/// use std::sync::mpsc;
+/// use std::thread;
+/// use xfind::ChannelReader;
+///
+/// fn main() -> io::Result<()> {
+///     let (tx, rx) = mpsc::channel();
+///     let producer = thread::spawn(move || {
+///         for chunk in &[&b"rusty ru"[..], b"st"] {
+///             tx.send(chunk.to_vec()).unwrap();
+///         }
+///     });
+///
+///     let mut rdr = ChannelReader::new(rx);
+///     let matches = xfind::find_iter(b"rust", &mut rdr).into_offsets()?;
+///     assert_eq!(matches, vec![0, 6]);
+///
+///     producer.join().unwrap();
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ChannelReader {
+    /// The channel that the chunks are received from.
+    rx: Receiver<Vec<u8>>,
+    /// The current chunk.
+    chunk: Vec<u8>,
+    /// The start of the bytes in `chunk` that haven't been read yet.
+    pos: usize,
+}
+
+impl ChannelReader {
+    /// Creates a reader over the chunks received from `rx`.
+    pub fn new(rx: Receiver<Vec<u8>>) -> ChannelReader {
+        ChannelReader { rx, chunk: Vec::new(), pos: 0 }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.pos == self.chunk.len() {
+            match self.rx.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                // All senders have been dropped.
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = cmp::min(buf.len(), self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::FaultReader;
+    use std::io::Cursor;
+    use std::sync::mpsc;
+    use std::thread;
+
+    fn take_until(needle: &[u8], haystack: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        TakeUntil::new(needle, Cursor::new(haystack))
+            .read_to_end(&mut out)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn test_take_until() {
+        assert_eq!(take_until(b"dear", b"my dear, dear"), b"my ");
+        assert_eq!(take_until(b"dear", b"dear me"), b"");
+        assert_eq!(take_until(b"dear", b"my dea"), b"my dea");
+        assert_eq!(take_until(b"dear", b""), b"");
+        assert_eq!(take_until(b"", b"my dear"), b"");
+    }
+
+    #[test]
+    fn test_take_until_across_reads() {
+        let needle = b"--boundary--";
+        for &pos in &[
+            0,
+            1,
+            11,
+            DEFAULT_BUFFER_CAPACITY - 5,
+            DEFAULT_BUFFER_CAPACITY * 3,
+        ] {
+            let mut haystack = vec![b'-'; pos + DEFAULT_BUFFER_CAPACITY];
+            haystack[pos..pos + needle.len()].copy_from_slice(needle);
+            haystack.extend_from_slice(needle);
+
+            for k in 1..=needle.len() + 1 {
+                let rdr = FaultReader::new(Cursor::new(&haystack))
+                    .short_reads(k)
+                    .interrupt_every(7);
+                let mut out = Vec::new();
+                let mut take = TakeUntil::new(needle, rdr);
+                loop {
+                    let mut chunk = [0; 5];
+                    match take.read(&mut chunk) {
+                        Ok(0) => break,
+                        Ok(n) => out.extend_from_slice(&chunk[..n]),
+                        Err(err)
+                            if err.kind() == io::ErrorKind::Interrupted => {}
+                        Err(err) => panic!("{}", err),
+                    }
+                }
+                assert_eq!(out, &haystack[..pos]);
+            }
+        }
+    }
+
+    fn skip_past(needle: &[u8], haystack: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        SkipPast::new(needle, Cursor::new(haystack))
+            .read_to_end(&mut out)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn test_skip_past() {
+        assert_eq!(skip_past(b"dear", b"my dear, dear"), b", dear");
+        assert_eq!(skip_past(b"dear", b"my dear"), b"");
+        assert_eq!(skip_past(b"dear", b"my dea"), b"");
+        assert_eq!(skip_past(b"dear", b""), b"");
+        assert_eq!(skip_past(b"", b"my dear"), b"my dear");
+    }
+
+    #[test]
+    fn test_skip_past_across_reads() {
+        let needle = b"--boundary--";
+        for &pos in &[
+            0,
+            1,
+            11,
+            DEFAULT_BUFFER_CAPACITY - 5,
+            DEFAULT_BUFFER_CAPACITY * 3,
+        ] {
+            let mut haystack = vec![b'-'; pos];
+            haystack.extend_from_slice(needle);
+            haystack.extend((0..DEFAULT_BUFFER_CAPACITY * 2).map(|i| i as u8));
+            haystack.extend_from_slice(needle);
+            let expected = &haystack[pos + needle.len()..];
+
+            for k in 1..=needle.len() + 1 {
+                let rdr = FaultReader::new(Cursor::new(&haystack))
+                    .short_reads(k)
+                    .interrupt_every(7);
+                let mut out = Vec::new();
+                SkipPast::new(needle, rdr).read_to_end(&mut out).unwrap();
+                assert_eq!(out, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_skip_past_error() {
+        let rdr = FaultReader::new(&b"my dear, dear"[..]).error_at(4);
+        let mut skip = SkipPast::new(b"dear", rdr);
+        let mut out = Vec::new();
+        assert!(skip.read_to_end(&mut out).is_err());
+    }
+
+    #[test]
+    fn test_channel_reader_across_chunks() {
+        let needle = b"--boundary--";
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 3];
+        for &pos in &[0, 100, DEFAULT_BUFFER_CAPACITY - 3, haystack.len() - 12]
+        {
+            haystack[pos..pos + needle.len()].copy_from_slice(needle);
+        }
+        let expected = crate::find_iter(needle, &mut Cursor::new(&haystack))
+            .into_offsets()
+            .unwrap();
+        assert_eq!(expected.len(), 4);
+
+        let (tx, rx) = mpsc::channel();
+        let chunks = haystack.clone();
+        let producer = thread::spawn(move || {
+            // Chunks of varying sizes, including empty ones, split every match.
+            let mut rest = &chunks[..];
+            for size in (0..13).cycle() {
+                if rest.is_empty() {
+                    break;
+                }
+                let size = cmp::min(size, rest.len());
+                tx.send(rest[..size].to_vec()).unwrap();
+                rest = &rest[size..];
+            }
+        });
+
+        let mut rdr = ChannelReader::new(rx);
+        let matches =
+            crate::find_iter(needle, &mut rdr).into_offsets().unwrap();
+        assert_eq!(matches, expected);
+        producer.join().unwrap();
+
+        let mut rest = Vec::new();
+        assert_eq!(rdr.read_to_end(&mut rest).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_normalize_crlf() {
+        let normalize = |haystack: &[u8], k: usize| {
+            let rdr = FaultReader::new(Cursor::new(haystack)).short_reads(k);
+            let mut out = Vec::new();
+            normalize_crlf(rdr).read_to_end(&mut out).unwrap();
+            out
+        };
+        for k in 1..4 {
+            assert_eq!(normalize(b"a\r\nb\r\n", k), b"a\nb\n");
+            assert_eq!(normalize(b"a\rb\r", k), b"a\rb\r");
+            assert_eq!(normalize(b"\r\r\n\n\r", k), b"\r\n\n\r");
+            assert_eq!(normalize(b"", k), b"");
+        }
+    }
+
+    #[test]
+    fn test_normalize_crlf_offsets() {
+        let mut lf = Vec::new();
+        std::fs::File::open("data/pride-and-prejudice.txt")
+            .unwrap()
+            .read_to_end(&mut lf)
+            .unwrap();
+        let mut crlf = Vec::new();
+        for line in lf.split_inclusive(|&b| b == b'\n') {
+            match line.strip_suffix(b"\n") {
+                Some(line) => {
+                    crlf.extend_from_slice(line);
+                    crlf.extend_from_slice(b"\r\n");
+                }
+                None => crlf.extend_from_slice(line),
+            }
+        }
+
+        for &needle in &[&b"Elizabeth"[..], b"\n      Mr.", b".\n\n"] {
+            let raw_needle = needle
+                .split(|&b| b == b'\n')
+                .collect::<Vec<_>>()
+                .join(&b"\r\n"[..]);
+
+            // Raw offsets into the CRLF fixture agree with `memmem`.
+            let rdr = &mut FaultReader::new(&crlf[..]).short_reads(997);
+            let raw =
+                crate::find_iter(&raw_needle, rdr).into_offsets().unwrap();
+            let expected: Vec<_> =
+                memmem::find_iter(&crlf, &raw_needle).collect();
+            assert_eq!(raw, expected);
+
+            // Normalized offsets agree with the LF text.
+            let mut rdr =
+                normalize_crlf(FaultReader::new(&crlf[..]).short_reads(997));
+            let normalized =
+                crate::find_iter(needle, &mut rdr).into_offsets().unwrap();
+            let expected: Vec<_> = memmem::find_iter(&lf, needle).collect();
+            assert_eq!(normalized, expected);
+            assert!(!raw.is_empty() && raw.len() == normalized.len());
+            assert!(raw.last() > normalized.last());
+        }
+    }
+}
@@ -1,29 +1,183 @@
+use crate::error::SearchError;
 use std::cmp;
 use std::io;
+#[cfg(not(feature = "safe-buffer"))]
 use std::ptr;
 
 /// The default buffer capacity for the stream buffer is 8KB.
 pub const DEFAULT_BUFFER_CAPACITY: usize = 8 * (1 << 10);
 
+/// The initial number of bytes that a `Buffer` makes available for reading.
+///
+/// The rest of the capacity is initialized lazily, so that searching a stream that is much smaller
+/// than the buffer doesn't pay for zeroing the whole buffer.
+const INITIAL_BUFFER_LEN: usize = 512;
+
 /// A fairly simple roll buffer for supporting stream searching.
+///
+/// The searchers of this crate allocate their buffers themselves by default. A `Buffer` can be
+/// created from a caller-provided `Vec` with [`from_vec`](Buffer::from_vec) instead, and passed
+/// to [`StreamFinder::find_iter_with_buffer`](crate::StreamFinder::find_iter_with_buffer). The
+/// `Vec` can be recovered with [`into_vec`](Buffer::into_vec) afterwards, which allows recycling
+/// allocations across searches, e.g. with a pool.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+/// use xfind::{Buffer, StreamFinder};
+///
+/// fn main() -> io::Result<()> {
+///     let finder = StreamFinder::new(b"rust");
+///     let mut vec = Vec::with_capacity(64 * 1024);
+///     let mut results = Vec::new();
+///
+///     for haystack in &[&b"rusty rust"[..], b"crab"] {
+///         let mut stream = Cursor::new(haystack);
+///         let buffer = Buffer::from_vec(vec, finder.needle().len());
+///         let mut iter = finder.find_iter_with_buffer(&mut stream, buffer);
+///         let matches = iter.by_ref().collect::<io::Result<Vec<_>>>()?;
+///         vec = iter.into_buffer().into_vec();
+///         results.push(matches);
+///     }
+///     assert_eq!(results, vec![vec![0, 6], vec![]]);
+///     assert!(vec.capacity() >= 64 * 1024);
+///
+///     Ok(())
+/// }
+/// ```
 #[derive(Debug)]
 pub struct Buffer {
-    /// A fixed-size raw buffer.
+    /// A raw buffer that grows up to `capacity` bytes, and has a fixed size afterwards.
     buf: Vec<u8>,
+    /// The maximum size of the raw buffer.
+    capacity: usize,
     /// The minimum size of the buffer, which is equivalent to the length of the search string.
+    ///
+    /// Searchers rely on `min >= needle.len()` to find matches that span a roll, so it must be
+    /// raised with `set_min_buffer_len` before searching a longer needle.
     min: usize,
+    /// The number of bytes that a roll preserves, which is at least `min`.
+    retain: usize,
     /// The end of the contents of this buffer.
     end: usize,
+    /// Whether the reader has reported EOF.
+    eof: bool,
+    /// The number of bytes that a `fill` tries to read before returning.
+    min_read: usize,
+    /// Whether a zero-length read is confirmed by another read before it's treated as EOF.
+    strict_eof: bool,
 }
 
 impl Buffer {
     /// Creates a new buffer for stream searching.
     pub fn new(min_buffer_len: usize) -> Buffer {
         let min = cmp::max(1, min_buffer_len);
-        // The minimum buffer capacity is at least 1 byte bigger than our search string, but for
-        // performance reasons we choose a lower bound of `8 * min`.
-        let capacity = cmp::max(min * 8, DEFAULT_BUFFER_CAPACITY);
-        Buffer { buf: vec![0; capacity], min, end: 0 }
+        Buffer::with_retain(min, min, default_capacity(min))
+    }
+
+    /// Creates a new buffer for stream searching, which reuses the allocation of the given `Vec`
+    /// as its raw buffer.
+    ///
+    /// The capacity of `vec` becomes the capacity of the buffer, and its contents are discarded.
+    /// `min_buffer_len` is the length of the longest needle to be searched with the buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the capacity of `vec` isn't bigger than `min_buffer_len`.
+    pub fn from_vec(vec: Vec<u8>, min_buffer_len: usize) -> Buffer {
+        let min = cmp::max(1, min_buffer_len);
+        let capacity = vec.capacity();
+        assert!(
+            capacity > min,
+            "capacity must be bigger than the minimum length"
+        );
+        Buffer::from_parts(vec, min, min, capacity)
+    }
+
+    /// Consumes the buffer and returns its raw buffer, which is empty, but keeps its allocation.
+    pub fn into_vec(self) -> Vec<u8> {
+        let mut buf = self.buf;
+        buf.clear();
+        buf
+    }
+
+    /// Creates a new buffer for stream searching with the given capacity, which is raised to
+    /// `min_buffer_len + 1` if it's smaller than that.
+    pub(crate) fn with_capacity(
+        min_buffer_len: usize,
+        capacity: usize,
+    ) -> Buffer {
+        let min = cmp::max(1, min_buffer_len);
+        Buffer::with_retain(min, min, cmp::max(capacity, min + 1))
+    }
+
+    /// Creates a new buffer for stream searching, which preserves the last `retain` bytes on
+    /// every roll instead of the last `min_buffer_len` bytes.
+    ///
+    /// This allows keeping some context before the part of the buffer that remains to be
+    /// searched.
+    ///
+    /// `min_buffer_len` is the length of the longest needle to be searched with the buffer, and
+    /// `capacity` is the maximum number of bytes that the buffer holds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `retain` is smaller than `min_buffer_len`, or if `capacity` isn't bigger than
+    /// `retain`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::{Buffer, StreamFinder};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let finder = StreamFinder::new(b"rust");
+    ///     let buffer = Buffer::with_retain(finder.needle().len(), 64, 1024);
+    ///     assert_eq!(buffer.retain_len(), 64);
+    ///
+    ///     let mut stream = Cursor::new(b"rusty rust");
+    ///     let iter = finder.find_iter_with_buffer(&mut stream, buffer);
+    ///     assert_eq!(iter.collect::<io::Result<Vec<_>>>()?, vec![0, 6]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_retain(
+        min_buffer_len: usize,
+        retain: usize,
+        capacity: usize,
+    ) -> Buffer {
+        let min = cmp::max(1, min_buffer_len);
+        assert!(retain >= min, "retain must be at least the minimum length");
+        assert!(capacity > retain, "capacity must be bigger than retain");
+        Buffer::from_parts(Vec::with_capacity(capacity), min, retain, capacity)
+    }
+
+    /// Creates a new buffer on top of the given raw buffer, whose allocation must already hold
+    /// `capacity` bytes.
+    fn from_parts(
+        mut buf: Vec<u8>,
+        min: usize,
+        retain: usize,
+        capacity: usize,
+    ) -> Buffer {
+        buf.clear();
+        buf.resize(
+            cmp::min(capacity, cmp::max(retain * 2, INITIAL_BUFFER_LEN)),
+            0,
+        );
+        Buffer {
+            buf,
+            capacity,
+            min,
+            retain,
+            end: 0,
+            eof: false,
+            min_read: 0,
+            strict_eof: false,
+        }
     }
 
     /// Returns the minimum size of the buffer.
@@ -32,36 +186,125 @@ impl Buffer {
         self.min
     }
 
+    /// Sets the minimum size of the buffer, e.g. to search a different needle with it.
+    ///
+    /// The retained length changes by the same amount, so any extra context is kept. If the
+    /// capacity isn't bigger than the new retained length, it's raised like in `new`, and the raw
+    /// buffer grows to it lazily. The contents of the buffer are kept.
+    pub(crate) fn set_min_buffer_len(&mut self, min_buffer_len: usize) {
+        let min = cmp::max(1, min_buffer_len);
+        self.retain = self.retain - self.min + min;
+        self.min = min;
+        if self.capacity <= self.retain {
+            self.capacity = cmp::max(self.retain * 8, DEFAULT_BUFFER_CAPACITY);
+        }
+    }
+
+    /// Returns the maximum number of bytes that the buffer holds.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of bytes that a roll preserves.
+    #[inline]
+    pub fn retain_len(&self) -> usize {
+        self.retain
+    }
+
     /// Returns the contents of this buffer.
     #[inline]
-    pub fn buffer(&self) -> &[u8] {
+    pub(crate) fn buffer(&self) -> &[u8] {
         &self.buf[..self.end]
     }
 
     /// Returns the total length of the contents in this buffer.
     #[inline]
-    pub fn len(&self) -> usize {
+    pub(crate) fn len(&self) -> usize {
         self.end
     }
 
+    /// Returns true if a previous `fill` has observed the end of the stream.
+    #[inline]
+    pub(crate) fn is_eof(&self) -> bool {
+        self.eof
+    }
+
+    /// Returns the number of bytes allocated for the raw buffer.
+    #[cfg(test)]
+    pub(crate) fn allocated_len(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    /// Makes every `fill` keep reading until it has read at least `min_read` bytes (or the buffer
+    /// is full), instead of returning as soon as the buffer holds the minimum amount.
+    pub(crate) fn coalesce_reads(&mut self, min_read: usize) {
+        self.min_read = min_read;
+    }
+
+    /// Makes every `fill` confirm a zero-length read with another read, and fail with
+    /// `SearchError::DataAfterZeroRead` if that read returns more data.
+    pub(crate) fn error_on_zero_read(&mut self, strict: bool) {
+        self.strict_eof = strict;
+    }
+
     /// Returns all free capactiy in this buffer.
+    ///
+    /// The raw buffer is doubled first if its contents take up at least half of it, until it
+    /// reaches its full capacity.
     fn free_buffer(&mut self) -> &mut [u8] {
+        self.grow(self.end);
         &mut self.buf[self.end..]
     }
 
+    /// Doubles the raw buffer, up to its full capacity, if `filled` bytes take up at least half
+    /// of it.
+    fn grow(&mut self, filled: usize) {
+        if self.buf.len() < self.capacity && filled * 2 >= self.buf.len() {
+            let len = cmp::min(self.capacity, self.buf.len() * 2);
+            self.buf.resize(len, 0);
+        }
+    }
+
     /// Refill the contents of this buffer by reading as much as possible into this buffer's free
     /// capacity. If no more bytes could be read, then this returns false. Otherwise, this reads
     /// until it has filled the buffer past the minimum amount.
-    pub fn fill<R: io::Read>(&mut self, mut rdr: R) -> io::Result<bool> {
+    ///
+    /// If reads are coalesced (see `coalesce_reads`), this keeps reading until the configured
+    /// amount has been read, or the buffer is full.
+    ///
+    /// Once a read has returned zero bytes, `is_eof` returns true. Reads that fail with
+    /// `ErrorKind::Interrupted` are retried.
+    ///
+    /// In strict mode (see `error_on_zero_read`), a zero-length read is followed by another read.
+    /// If that read returns more data, the data is kept in the buffer, and an error is returned
+    /// instead of reporting EOF.
+    pub(crate) fn fill<R: io::Read>(
+        &mut self,
+        mut rdr: R,
+    ) -> io::Result<bool> {
         let mut readany = false;
+        let mut read = 0;
         loop {
-            let bytes_read = rdr.read(self.free_buffer())?;
+            let bytes_read = read_retrying(&mut rdr, self.free_buffer())?;
             if bytes_read == 0 {
+                if self.strict_eof {
+                    let bytes_read =
+                        read_retrying(&mut rdr, self.free_buffer())?;
+                    if bytes_read > 0 {
+                        self.end += bytes_read;
+                        return Err(SearchError::DataAfterZeroRead.into());
+                    }
+                }
+                self.eof = true;
                 return Ok(readany);
             }
             readany = true;
             self.end += bytes_read;
-            if self.len() >= self.min {
+            read += bytes_read;
+            if self.len() >= self.min
+                && (read >= self.min_read || self.end == self.capacity)
+            {
                 return Ok(true);
             }
         }
@@ -69,17 +312,24 @@ impl Buffer {
 
     /// Rolls the contents of the buffer so that the suffix of this buffer is moved to the front
     /// and all other contents are dropped. The size of the suffix corresponds precisely to the
-    /// minimum buffer length.
+    /// retained length (see `retain_len`), which defaults to the minimum buffer length.
     ///
     /// This should only be called when the entire contents of this buffer have been searched.
-    pub fn roll(&mut self) {
+    ///
+    /// Searchers roll after every fill, so the raw buffer grows here based on how much it held
+    /// before the roll, as it never fills up past the retained length otherwise.
+    pub(crate) fn roll(&mut self) {
+        let filled = self.end;
         let roll_start = self
             .end
-            .checked_sub(self.min)
-            .expect("buffer capacity should be bigger than minimum amount.");
-        let roll_len = self.min;
+            .checked_sub(self.retain)
+            .expect("buffer capacity should be bigger than retained amount.");
+        let roll_len = self.retain;
 
         assert!(roll_start + roll_len <= self.end);
+        #[cfg(feature = "safe-buffer")]
+        self.buf.copy_within(roll_start..roll_start + roll_len, 0);
+        #[cfg(not(feature = "safe-buffer"))]
         unsafe {
             // SAFETY: A buffer contains Copy data, so there's no problem moving it around. Safety
             // also depends on our indices being in bounds, which they always should be, given the
@@ -91,6 +341,27 @@ impl Buffer {
             );
         }
         self.end = roll_len;
+        self.grow(filled);
+    }
+}
+
+/// Returns the capacity of a buffer created by `Buffer::new` for the given minimum length.
+pub(crate) fn default_capacity(min_buffer_len: usize) -> usize {
+    // The minimum buffer capacity is at least 1 byte bigger than our search string, but for
+    // performance reasons we choose a lower bound of `8 * min`.
+    cmp::max(cmp::max(1, min_buffer_len) * 8, DEFAULT_BUFFER_CAPACITY)
+}
+
+/// Reads from the reader into `buf`, retrying reads that fail with `ErrorKind::Interrupted`.
+fn read_retrying<R: io::Read>(
+    rdr: &mut R,
+    buf: &mut [u8],
+) -> io::Result<usize> {
+    loop {
+        match rdr.read(buf) {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            res => return res,
+        }
     }
 }
 
@@ -100,6 +371,9 @@ pub struct BufferRev {
     /// A fixed-size raw buffer.
     buf: Vec<u8>,
     /// The minimum size of the buffer, which is equivalent to the length of the search string.
+    ///
+    /// Searchers rely on `min >= needle.len()` to find matches that span a roll, so it must be
+    /// raised with `set_min_buffer_len` before searching a longer needle.
     min: usize,
     /// The end of the contents of this buffer.
     end: usize,
@@ -182,6 +456,13 @@ impl BufferRev {
         let roll_len = self.min;
 
         assert!(roll_start + roll_len <= self.end);
+        #[cfg(feature = "safe-buffer")]
+        {
+            let start = self.capacity() - self.end;
+            let dest = self.capacity() - roll_len;
+            self.buf.copy_within(start..start + roll_len, dest);
+        }
+        #[cfg(not(feature = "safe-buffer"))]
         unsafe {
             // SAFETY: A buffer contains Copy data, so there's no problem moving it around. Safety
             // also depends on our indices being in bounds, which they always should be, given the
@@ -237,4 +518,285 @@ mod tests {
         assert_eq!(buf.buffer(), "01234567".as_bytes());
         assert_eq!(buf.len(), 8);
     }
+
+    #[test]
+    fn test_buffer_grows_lazily() {
+        let haystack: Vec<u8> = (0..=255).cycle().take(20000).collect();
+        let mut rdr = Cursor::new(&haystack);
+        let mut buf = Buffer::new(4);
+        assert_eq!(buf.buf.len(), INITIAL_BUFFER_LEN);
+
+        buf.fill(&mut rdr).unwrap();
+        assert_eq!(buf.buffer(), &haystack[..INITIAL_BUFFER_LEN]);
+
+        // Like the searchers, roll after every fill, so that the buffer never fills up on its
+        // own.
+        let (mut offset, mut max_len) = (0, 0);
+        loop {
+            assert!(buf.buf.len() <= DEFAULT_BUFFER_CAPACITY);
+            max_len = cmp::max(max_len, buf.len());
+            offset += buf.len() - 4;
+            buf.roll();
+            if !buf.fill(&mut rdr).unwrap() {
+                break;
+            }
+        }
+        assert!(buf.is_eof());
+        assert_eq!(buf.buf.len(), DEFAULT_BUFFER_CAPACITY);
+        assert_eq!(max_len, DEFAULT_BUFFER_CAPACITY);
+        assert_eq!(buf.buffer(), &haystack[offset..]);
+    }
+
+    // These tests run under both the `ptr::copy` and the `safe-buffer` implementation of the
+    // rolls (pass `--features safe-buffer` for the latter), which must behave identically. The
+    // safe implementation also lets `cargo miri test --features safe-buffer` check the buffers
+    // without auditing any unsafe code.
+    #[test]
+    fn test_buffer_roll_contents() {
+        let haystack: Vec<u8> = (0..=255).cycle().take(30000).collect();
+        let mut rdr = Cursor::new(&haystack);
+        let mut buf = Buffer::new(3);
+
+        let mut offset = 0;
+        while buf.fill(&mut rdr).unwrap() {
+            assert_eq!(buf.buffer(), &haystack[offset..offset + buf.len()]);
+            offset += buf.len() - 3;
+            buf.roll();
+            assert_eq!(buf.buffer(), &haystack[offset..offset + 3]);
+        }
+        assert_eq!(offset + 3, haystack.len());
+    }
+
+    #[test]
+    fn test_buffer_rev_roll_right_contents() {
+        let haystack: Vec<u8> = (0..=255).cycle().take(30000).collect();
+        let mut rdr = Cursor::new(&haystack);
+        let mut buf = BufferRev::new(3);
+
+        let mut offset = haystack.len();
+        while offset > 0 {
+            let amount = cmp::min(buf.free_buffer().len(), offset);
+            offset -= amount;
+            rdr.seek(SeekFrom::Start(offset as u64)).unwrap();
+            assert!(buf.fill_exact(&mut rdr, amount).unwrap());
+            assert_eq!(buf.buffer(), &haystack[offset..offset + buf.len()]);
+
+            buf.roll_right();
+            assert_eq!(buf.buffer(), &haystack[offset..offset + 3]);
+        }
+    }
+
+    #[test]
+    fn test_buffer_with_retain() {
+        let haystack: Vec<u8> = (0..=255).cycle().take(5000).collect();
+        let mut rdr = Cursor::new(&haystack);
+        let mut buf = Buffer::with_retain(2, 100, 1024);
+        assert_eq!(buf.min_buffer_len(), 2);
+        assert_eq!(buf.retain_len(), 100);
+
+        let mut offset = 0;
+        while buf.fill(&mut rdr).unwrap() {
+            assert!(buf.len() <= 1024);
+            if buf.len() >= buf.retain_len() {
+                offset += buf.len() - 100;
+                buf.roll();
+                assert_eq!(buf.len(), 100);
+                assert_eq!(buf.buffer(), &haystack[offset..offset + 100]);
+            }
+        }
+        assert_eq!(offset + buf.len(), haystack.len());
+    }
+
+    #[test]
+    fn test_buffer_with_retain_search() {
+        // Searching the retained suffix and the new bytes finds every match exactly once, as long
+        // as the search resumes after the bytes that have been searched already.
+        let mut haystack = vec![b'-'; 10000];
+        let expected: Vec<usize> = (0..10000 - 3).step_by(97).collect();
+        for &pos in &expected {
+            haystack[pos..pos + 3].copy_from_slice(b"abc");
+        }
+        let mut rdr = Cursor::new(&haystack);
+        let mut buf = Buffer::with_retain(3, 50, 256);
+
+        let mut matches = Vec::new();
+        let (mut offset, mut search_pos) = (0, 0);
+        while buf.fill(&mut rdr).unwrap() {
+            let contents = buf.buffer();
+            for mat in
+                memchr::memmem::find_iter(&contents[search_pos..], b"abc")
+            {
+                matches.push(offset + search_pos + mat);
+            }
+            // The last 2 bytes may start a match that ends in the next chunk.
+            search_pos = contents.len() - 2;
+            if buf.len() >= buf.retain_len() {
+                let dropped = buf.len() - buf.retain_len();
+                buf.roll();
+                offset += dropped;
+                search_pos -= dropped;
+            }
+        }
+        assert_eq!(matches, expected);
+    }
+
+    #[test]
+    fn test_buffer_with_capacity() {
+        let haystack: Vec<u8> = (0..=255).cycle().take(5000).collect();
+        // Capacities that can't hold more than the minimum length are raised to hold 1 byte more.
+        let capacities =
+            [(64, 64), (100_000, 100_000), (2, 5), (0, 5), (4, 5), (5, 5)];
+        for &(capacity, expected) in &capacities {
+            let mut rdr = Cursor::new(&haystack);
+            let mut buf = Buffer::with_capacity(4, capacity);
+            assert_eq!(buf.capacity, expected);
+
+            let mut offset = 0;
+            while buf.fill(&mut rdr).unwrap() {
+                assert!(buf.len() <= expected);
+                if buf.len() == expected {
+                    offset += buf.len() - 4;
+                    buf.roll();
+                }
+            }
+            assert_eq!(buf.buffer(), &haystack[offset..]);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_buffer_with_retain_too_small() {
+        Buffer::with_retain(4, 3, 1024);
+    }
+
+    /// A reader that returns one byte per read.
+    struct ByteByByte<R>(R);
+
+    impl<R: Read> Read for ByteByByte<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = cmp::min(1, buf.len());
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    /// Drains the stream through a buffer, and returns the contents read and the number of
+    /// successful fills.
+    fn drain(buf: &mut Buffer, rdr: &mut impl Read) -> (Vec<u8>, usize) {
+        let (mut contents, mut fills) = (Vec::new(), 0);
+        while buf.fill(&mut *rdr).unwrap() {
+            fills += 1;
+            if buf.len() >= buf.retain_len() {
+                let keep = buf.len() - buf.retain_len();
+                contents.extend_from_slice(&buf.buffer()[..keep]);
+                buf.roll();
+            }
+        }
+        contents.extend_from_slice(buf.buffer());
+        (contents, fills)
+    }
+
+    #[test]
+    fn test_buffer_coalesce_reads() {
+        let haystack: Vec<u8> = (0..=255).cycle().take(20000).collect();
+
+        let mut buf = Buffer::new(4);
+        let mut rdr = ByteByByte(Cursor::new(&haystack));
+        let (contents, fills) = drain(&mut buf, &mut rdr);
+        assert_eq!(contents, haystack);
+        assert!(fills > 19000, "{}", fills);
+
+        let mut buf = Buffer::new(4);
+        buf.coalesce_reads(1024);
+        let mut rdr = ByteByByte(Cursor::new(&haystack));
+        let (contents, fills) = drain(&mut buf, &mut rdr);
+        assert_eq!(contents, haystack);
+        assert!(fills <= 20000 / 1024 + 2, "{}", fills);
+
+        // A full buffer ends a fill early.
+        let mut buf = Buffer::with_retain(4, 4, 64);
+        buf.coalesce_reads(1024);
+        let mut rdr = ByteByByte(Cursor::new(&haystack));
+        assert!(buf.fill(&mut rdr).unwrap());
+        assert_eq!(buf.len(), 64);
+        assert!(!buf.is_eof());
+    }
+
+    /// A reader that returns zero bytes on its first read.
+    struct ZeroFirst<R> {
+        rdr: R,
+        started: bool,
+    }
+
+    impl<R: Read> Read for ZeroFirst<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if !self.started {
+                self.started = true;
+                return Ok(0);
+            }
+            self.rdr.read(buf)
+        }
+    }
+
+    #[test]
+    fn test_buffer_error_on_zero_read() {
+        let mut rdr = ZeroFirst { rdr: &b"late"[..], started: false };
+        let mut buf = Buffer::new(2);
+        assert!(!buf.fill(&mut rdr).unwrap());
+        assert!(buf.is_eof());
+
+        let mut rdr = ZeroFirst { rdr: &b"late"[..], started: false };
+        let mut buf = Buffer::new(2);
+        buf.error_on_zero_read(true);
+        assert!(buf.fill(&mut rdr).is_err());
+        assert!(!buf.is_eof());
+        assert_eq!(buf.buffer(), b"late");
+        assert!(!buf.fill(&mut rdr).unwrap());
+        assert!(buf.is_eof());
+    }
+
+    #[test]
+    fn test_buffer_from_vec() {
+        let haystack: Vec<u8> = (0..=255).cycle().take(5000).collect();
+        let mut vec = Vec::with_capacity(100);
+        vec.extend_from_slice(b"stale contents");
+        let ptr = vec.as_ptr();
+
+        let mut rdr = Cursor::new(&haystack);
+        let mut buf = Buffer::from_vec(vec, 4);
+        assert_eq!((buf.capacity(), buf.len()), (100, 0));
+        let mut offset = 0;
+        while buf.fill(&mut rdr).unwrap() {
+            if buf.len() == buf.capacity() {
+                offset += buf.len() - 4;
+                buf.roll();
+            }
+        }
+        assert_eq!(buf.buffer(), &haystack[offset..]);
+
+        // The allocation is reused, and the contents are discarded.
+        let vec = buf.into_vec();
+        assert_eq!((vec.as_ptr(), vec.capacity(), vec.len()), (ptr, 100, 0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_buffer_from_vec_too_small() {
+        Buffer::from_vec(Vec::with_capacity(4), 4);
+    }
+
+    #[test]
+    fn test_buffer_set_min_buffer_len() {
+        let mut buf = Buffer::with_retain(4, 6, 16);
+        buf.set_min_buffer_len(10);
+        assert_eq!((buf.min_buffer_len(), buf.retain_len()), (10, 12));
+        assert_eq!(buf.capacity(), 16);
+
+        buf.set_min_buffer_len(20);
+        assert_eq!((buf.min_buffer_len(), buf.retain_len()), (20, 22));
+        assert_eq!(buf.capacity(), DEFAULT_BUFFER_CAPACITY);
+
+        buf.set_min_buffer_len(0);
+        assert_eq!((buf.min_buffer_len(), buf.retain_len()), (1, 3));
+        assert_eq!(buf.capacity(), DEFAULT_BUFFER_CAPACITY);
+    }
 }
@@ -0,0 +1,302 @@
+//! Provides forward and backward searches over a stream that can only be read once.
+use memchr::memmem;
+use std::io::{self, Read};
+
+/// A cache of a whole stream that allows searching it both forward and backward.
+///
+/// Backward searches normally require [`Seek`](std::io::Seek). `SearchBoth` lifts that
+/// requirement for streams that can only be read once, like pipes, by reading the whole stream
+/// into memory upfront. Note that this trades memory for flexibility: unlike the other searchers
+/// of this crate, it holds the entire contents of the stream in memory, so it should only be used
+/// if both directions are needed and the stream can't be sought.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+/// use xfind::SearchBoth;
+///
+/// fn main() -> io::Result<()> {
+///     let cache = SearchBoth::new(&mut Cursor::new(b"rusty rust"))?;
+///
+///     assert_eq!(cache.forward(b"rust").collect::<Vec<_>>(), vec![0, 6]);
+///     assert_eq!(cache.reverse(b"rust").collect::<Vec<_>>(), vec![6, 0]);
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct SearchBoth {
+    /// The whole contents of the stream.
+    buf: Vec<u8>,
+}
+
+impl SearchBoth {
+    /// Reads the stream to its end and caches its contents.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if reading the stream failed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::SearchBoth;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let cache = SearchBoth::new(&mut Cursor::new(b"rusty rust"))?;
+    ///     assert_eq!(cache.len(), 10);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn new<R: Read>(rdr: &mut R) -> io::Result<SearchBoth> {
+        let mut buf = Vec::new();
+        rdr.read_to_end(&mut buf)?;
+        Ok(SearchBoth { buf })
+    }
+
+    /// Returns the length of the cached stream.
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns `true` if the cached stream is empty.
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Returns an iterator over all non-overlapping occurrences of the given needle in the cached
+    /// stream, from the start to the end.
+    ///
+    /// Since the stream is cached, no I/O errors can occur, so the iterator yields plain offsets.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::SearchBoth;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let cache = SearchBoth::new(&mut Cursor::new(b"rusty rust"))?;
+    ///
+    ///     let mut iter = cache.forward(b"rust");
+    ///     assert_eq!(iter.next(), Some(0));
+    ///     assert_eq!(iter.next(), Some(6));
+    ///     assert_eq!(iter.next(), None);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn forward<'c, 'n>(&'c self, needle: &'n [u8]) -> CachedIter<'c, 'n> {
+        CachedIter { inner: memmem::find_iter(&self.buf, needle) }
+    }
+
+    /// Returns an iterator over all non-overlapping occurrences of the given needle in the cached
+    /// stream, from the end to the start.
+    ///
+    /// Since the stream is cached, no I/O errors can occur, so the iterator yields plain offsets.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::SearchBoth;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let cache = SearchBoth::new(&mut Cursor::new(b"rusty rust"))?;
+    ///
+    ///     let mut iter = cache.reverse(b"rust");
+    ///     assert_eq!(iter.next(), Some(6));
+    ///     assert_eq!(iter.next(), Some(0));
+    ///     assert_eq!(iter.next(), None);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn reverse<'c, 'n>(
+        &'c self,
+        needle: &'n [u8],
+    ) -> CachedRevIter<'c, 'n> {
+        CachedRevIter { inner: memmem::rfind_iter(&self.buf, needle) }
+    }
+}
+
+/// A forward iterator over all non-overlapping occurrences of a substring in a cached stream.
+///
+/// This iterator is created by [`SearchBoth::forward`].
+#[derive(Debug)]
+pub struct CachedIter<'c, 'n> {
+    inner: memmem::FindIter<'c, 'n>,
+}
+
+/// A backward iterator over all non-overlapping occurrences of a substring in a cached stream.
+///
+/// This iterator is created by [`SearchBoth::reverse`].
+#[derive(Debug)]
+pub struct CachedRevIter<'c, 'n> {
+    inner: memmem::FindRevIter<'c, 'n>,
+}
+
+/// A backward iterator over all non-overlapping occurrences of a substring in a stream that has
+/// been read into memory.
+///
+/// This iterator is created by
+/// [`StreamFinder::rfind_iter_buffered`](crate::StreamFinder::rfind_iter_buffered).
+#[derive(Debug)]
+pub struct FindRevBufferedIter<'n> {
+    /// The whole contents of the stream.
+    buf: Vec<u8>,
+    /// The searcher for the needle.
+    finder: memmem::FinderRev<'n>,
+    /// The end (exclusive) of the contents that have not been searched yet, or `None` once the
+    /// whole buffer has been searched.
+    search_end: Option<usize>,
+}
+
+impl<'n> FindRevBufferedIter<'n> {
+    /// Reads the stream to its end and prepares searching it backward.
+    pub(crate) fn new<R: Read>(
+        rdr: &mut R,
+        needle: &'n [u8],
+    ) -> io::Result<FindRevBufferedIter<'n>> {
+        let mut buf = Vec::new();
+        rdr.read_to_end(&mut buf)?;
+        let search_end = Some(buf.len());
+        Ok(FindRevBufferedIter {
+            buf,
+            finder: memmem::FinderRev::new(needle),
+            search_end,
+        })
+    }
+}
+
+impl<'n> Iterator for FindRevBufferedIter<'n> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let end = self.search_end?;
+        let pos = match self.finder.rfind(&self.buf[..end]) {
+            Some(pos) => pos,
+            None => {
+                self.search_end = None;
+                return None;
+            }
+        };
+        // An empty needle matches at `pos` again, so skip it like `memmem::rfind_iter` does.
+        self.search_end = match self.finder.needle().len() {
+            0 => pos.checked_sub(1),
+            _ => Some(pos),
+        };
+        Some(Ok(pos))
+    }
+}
+
+impl<'c, 'n> Iterator for CachedIter<'c, 'n> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        self.inner.next()
+    }
+}
+
+impl<'c, 'n> Iterator for CachedRevIter<'c, 'n> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        self.inner.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::DEFAULT_BUFFER_CAPACITY;
+    use crate::testutil::FaultReader;
+    use std::io::Cursor;
+
+    /// A reader that panics if it's read again after reaching EOF.
+    struct ReadOnce<R> {
+        rdr: R,
+        eof: bool,
+    }
+
+    impl<R: Read> Read for ReadOnce<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            assert!(!self.eof, "stream read again after EOF");
+            let n = self.rdr.read(buf)?;
+            self.eof = n == 0 && !buf.is_empty();
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_search_both_forward_only_reader() {
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 3];
+        for &pos in &[0, 17, DEFAULT_BUFFER_CAPACITY - 2, haystack.len() - 4] {
+            haystack[pos..pos + 4].copy_from_slice(b"dear");
+        }
+        let expected: Vec<usize> =
+            crate::find_iter(b"dear", &mut Cursor::new(&haystack))
+                .into_offsets()
+                .unwrap();
+
+        let rdr = FaultReader::new(&haystack[..]).short_reads(1000);
+        let mut rdr = ReadOnce { rdr, eof: false };
+        let cache = SearchBoth::new(&mut rdr).unwrap();
+        assert_eq!(cache.len(), haystack.len());
+
+        assert_eq!(cache.forward(b"dear").collect::<Vec<_>>(), expected);
+        let mut reversed = cache.reverse(b"dear").collect::<Vec<_>>();
+        reversed.reverse();
+        assert_eq!(reversed, expected);
+        assert_eq!(cache.forward(b"absent").next(), None);
+    }
+
+    #[test]
+    fn test_search_both_error() {
+        let mut rdr = FaultReader::new(&b"rusty rust"[..]).error_at(4);
+        assert!(SearchBoth::new(&mut rdr).is_err());
+    }
+
+    #[test]
+    fn test_rfind_iter_buffered() {
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 3];
+        for &pos in &[0, 17, DEFAULT_BUFFER_CAPACITY - 2, haystack.len() - 4] {
+            haystack[pos..pos + 4].copy_from_slice(b"dear");
+        }
+        haystack.extend_from_slice(b"aaaaa");
+
+        for &needle in &[&b"dear"[..], b"aa", b"absent"] {
+            let finder = crate::StreamFinder::new(needle);
+            let expected = finder
+                .rfind_iter(&mut Cursor::new(&haystack))
+                .unwrap()
+                .collect::<io::Result<Vec<_>>>()
+                .unwrap();
+
+            let rdr = FaultReader::new(&haystack[..]).short_reads(1000);
+            let mut rdr = ReadOnce { rdr, eof: false };
+            let offsets = finder
+                .rfind_iter_buffered(&mut rdr)
+                .unwrap()
+                .collect::<io::Result<Vec<_>>>()
+                .unwrap();
+            assert_eq!(offsets, expected, "needle: {:?}", needle);
+        }
+
+        let finder = crate::StreamFinder::new(b"");
+        let offsets = finder
+            .rfind_iter_buffered(&mut &b"abc"[..])
+            .unwrap()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(offsets, vec![3, 2, 1, 0]);
+
+        let mut rdr = FaultReader::new(&b"rusty rust"[..]).error_at(4);
+        assert!(crate::StreamFinder::new(b"rust")
+            .rfind_iter_buffered(&mut rdr)
+            .is_err());
+    }
+}
@@ -0,0 +1,271 @@
+//! Provides case-insensitive stream searches that use Unicode case folding.
+use crate::finder::Searcher;
+use std::cmp;
+use std::io::{self, Read};
+use std::str;
+
+/// The size of the buffer holding bytes that haven't been folded yet.
+const INPUT_BUFFER_CAPACITY: usize = 4 * (1 << 10);
+
+/// Returns an iterator over all occurrences of the given needle in the stream, ignoring case
+/// differences of Unicode letters.
+///
+/// Both the needle and the stream are treated as UTF-8 text and case folded on the fly with the
+/// simple (1:1) Unicode case mapping, so `école` matches `ÉCOLE` and `σοφία` matches `ΣΟΦΊΑ`. Bytes
+/// that aren't valid UTF-8 are matched literally.
+///
+/// Only foldings that keep the encoded length of a character are applied, which means that the
+/// reported offsets are offsets in the original stream. Foldings that change the length, like the
+/// full folding of `ß` to `ss` or of the Kelvin sign `K` (3 bytes) to `k` (1 byte), are not
+/// handled, so `straße` does not match `STRASSE`.
+///
+/// This function is only available if the `unicode` feature is enabled.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+///
+/// fn main() -> io::Result<()> {
+///     let mut stream = Cursor::new("Café, CAFÉ, café".as_bytes());
+///
+///     let matches = xfind::find_iter_caseless("café".as_bytes(), &mut stream)
+///         .collect::<io::Result<Vec<_>>>()?;
+///     assert_eq!(matches, vec![0, 7, 14]);
+///
+///     Ok(())
+/// }
+/// ```
+pub fn find_iter_caseless<'s, R: Read>(
+    needle: &[u8],
+    rdr: &'s mut R,
+) -> FindCaselessIter<'s, R> {
+    let mut needle = needle.to_vec();
+    fold_in_place(&mut needle);
+    FindCaselessIter {
+        rdr: Folded {
+            rdr,
+            buf: vec![0; INPUT_BUFFER_CAPACITY],
+            start: 0,
+            ready: 0,
+            end: 0,
+            eof: false,
+        },
+        searcher: Searcher::new(needle.len()),
+        needle,
+    }
+}
+
+/// A forward iterator over all non-overlapping occurrences of a substring in a stream, ignoring
+/// case differences of Unicode letters.
+///
+/// This iterator is created by [`find_iter_caseless`].
+#[derive(Debug)]
+pub struct FindCaselessIter<'s, R: Read> {
+    /// The stream source we read from, which case folds the bytes read.
+    rdr: Folded<&'s mut R>,
+    /// The case folded needle we search for.
+    needle: Vec<u8>,
+    /// The state of the search.
+    searcher: Searcher,
+}
+
+impl<'s, R: Read> Iterator for FindCaselessIter<'s, R> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.searcher.next(&mut self.rdr, &self.needle)
+    }
+}
+
+/// A reader that case folds the UTF-8 text read from another reader.
+///
+/// A character that is split across reads of the underlying reader is held back until it is
+/// complete, so that it can be folded as a whole.
+#[derive(Debug)]
+struct Folded<R> {
+    /// The reader of the unfolded stream.
+    rdr: R,
+    /// A buffer for the bytes read from `rdr`.
+    buf: Vec<u8>,
+    /// The start of the folded bytes in `buf` that haven't been returned yet.
+    start: usize,
+    /// The end of the folded bytes in `buf`.
+    ready: usize,
+    /// The end of the bytes in `buf`. The bytes after `ready` form an incomplete character.
+    end: usize,
+    /// Whether the unfolded stream has reached EOF.
+    eof: bool,
+}
+
+impl<R: Read> Read for Folded<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while !buf.is_empty() {
+            if self.start < self.ready {
+                let n = cmp::min(buf.len(), self.ready - self.start);
+                buf[..n]
+                    .copy_from_slice(&self.buf[self.start..self.start + n]);
+                self.start += n;
+                return Ok(n);
+            }
+            if self.eof {
+                // An incomplete character at the end of the stream is passed through as is.
+                if self.ready == self.end {
+                    break;
+                }
+                self.ready = self.end;
+                continue;
+            }
+
+            self.buf.copy_within(self.ready..self.end, 0);
+            self.end -= self.ready;
+            self.start = 0;
+            self.ready = 0;
+            // Read only after the state is consistent, so that an error (e.g. `Interrupted`)
+            // leaves it intact.
+            let n = self.rdr.read(&mut self.buf[self.end..])?;
+            if n == 0 {
+                self.eof = true;
+                continue;
+            }
+            self.end += n;
+            self.ready =
+                self.end - incomplete_suffix_len(&self.buf[..self.end]);
+            fold_in_place(&mut self.buf[..self.ready]);
+        }
+        Ok(0)
+    }
+}
+
+/// Case folds the UTF-8 characters in `bytes` in place, leaving invalid bytes untouched.
+fn fold_in_place(bytes: &mut [u8]) {
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i].is_ascii() {
+            bytes[i].make_ascii_lowercase();
+            i += 1;
+            continue;
+        }
+
+        let width = utf8_width(bytes[i]);
+        let c = bytes
+            .get(i..i + width)
+            .and_then(|seq| str::from_utf8(seq).ok())
+            .and_then(|seq| seq.chars().next());
+        match c {
+            Some(c) => {
+                let folded = simple_fold(c);
+                if folded != c {
+                    folded.encode_utf8(&mut bytes[i..i + width]);
+                }
+                i += width;
+            }
+            None => i += 1,
+        }
+    }
+}
+
+/// Returns the simple case folding of `c`, if it has the same encoded length as `c`.
+fn simple_fold(c: char) -> char {
+    // The final sigma folds to the regular one, but isn't lowercased to it.
+    if c == 'ς' {
+        return 'σ';
+    }
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(folded), None) if folded.len_utf8() == c.len_utf8() => folded,
+        _ => c,
+    }
+}
+
+/// Returns the length of the UTF-8 sequence that starts with the given byte, or 0 if the byte
+/// can't start a sequence.
+fn utf8_width(b: u8) -> usize {
+    match b {
+        0x00..=0x7F => 1,
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => 0,
+    }
+}
+
+/// Returns the length of the incomplete UTF-8 sequence at the end of `bytes`, if any.
+fn incomplete_suffix_len(bytes: &[u8]) -> usize {
+    for i in 1..=cmp::min(3, bytes.len()) {
+        let b = bytes[bytes.len() - i];
+        // Skip continuation bytes until the start of the sequence.
+        if b & 0xC0 != 0x80 {
+            return if utf8_width(b) > i { i } else { 0 };
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::DEFAULT_BUFFER_CAPACITY;
+    use crate::testutil::FaultReader;
+    use std::io::Cursor;
+
+    fn caseless(needle: &str, haystack: &str) -> Vec<usize> {
+        find_iter_caseless(needle.as_bytes(), &mut Cursor::new(haystack))
+            .map(|x| x.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_caseless_accented_letters() {
+        assert_eq!(caseless("école", "ÉCOLE, École, école"), vec![0, 8, 16]);
+        assert_eq!(caseless("ÉCOLE", "école"), vec![0]);
+        assert_eq!(caseless("Ärger", "ÄRGER ärger"), vec![0, 7]);
+        assert_eq!(caseless("привет", "ПРИВЕТ, Привет"), vec![0, 14]);
+        assert_eq!(caseless("Σοφία", "ΣΟΦΊΑ σοφία"), vec![0, 11]);
+        // The final sigma matches the regular one.
+        assert_eq!(caseless("λόγος", "ΛΌΓΟΣ"), vec![0]);
+        assert_eq!(caseless("e", "é"), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_caseless_length_changing_foldings() {
+        assert_eq!(caseless("straße", "STRASSE"), Vec::<usize>::new());
+        assert_eq!(caseless("straße", "STRAßE"), vec![0]);
+        // The Kelvin sign is 3 bytes long, but its folding `k` only 1.
+        assert_eq!(caseless("k", "\u{212A}"), Vec::<usize>::new());
+        assert_eq!(caseless("\u{212A}", "\u{212A}"), vec![0]);
+    }
+
+    #[test]
+    fn test_caseless_invalid_utf8() {
+        let mut stream = Cursor::new(b"\xFFA\xC3\xFF\xC3\x89\xE2\x82");
+        let matches = find_iter_caseless(b"\xFFa\xC3", &mut stream)
+            .map(|x| x.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(matches, vec![0]);
+
+        let mut stream = Cursor::new(b"\xC3\x89\xE2\x82");
+        let matches = find_iter_caseless(b"\xC3\xA9\xE2\x82", &mut stream)
+            .map(|x| x.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(matches, vec![0]);
+    }
+
+    #[test]
+    fn test_caseless_characters_split_across_reads() {
+        let haystack = "-ÉCOLE ΣΟΦΊΑ €".repeat(DEFAULT_BUFFER_CAPACITY / 8);
+        let expected = caseless("école σοφία €", &haystack);
+        assert_eq!(expected.len(), DEFAULT_BUFFER_CAPACITY / 8);
+
+        for k in 1..=5 {
+            let mut stream = FaultReader::new(Cursor::new(&haystack))
+                .short_reads(k)
+                .interrupt_every(7);
+            let matches =
+                find_iter_caseless("école σοφία €".as_bytes(), &mut stream)
+                    .collect::<io::Result<Vec<_>>>()
+                    .unwrap();
+            assert_eq!(matches, expected);
+        }
+    }
+}
@@ -0,0 +1,379 @@
+//! Provides a substring searcher that only reports the matches in a given context.
+use crate::buffer::Buffer;
+use crate::finder::{FindIter, StreamFinder};
+use crate::split::{FindInFramesIter, MatchesPerLineIter, MatchingLinesIter};
+use std::io::{self, Read, Seek};
+use std::ops::ControlFlow;
+
+/// A substring searcher for stream searches, which only reports the matches of its needle that
+/// are preceded or followed by some bytes (or not followed by them).
+///
+/// A `ContextFinder` is created by [`StreamFinder::followed_by`],
+/// [`StreamFinder::not_followed_by`] or [`StreamFinder::preceded_by`]. The context is checked by
+/// the forward searches of [`FindIter`], so a `ContextFinder` provides the searches that are
+/// built on top of it, and nothing else. In particular, there are no reverse searches.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+/// use xfind::StreamFinder;
+///
+/// fn main() -> io::Result<()> {
+///     let mut stream = Cursor::new(b"[error] error: [error]: disk full");
+///     let finder = StreamFinder::new(b"error").preceded_by(b"[").followed_by(b"]:");
+///
+///     assert_eq!(finder.find_all(&mut stream)?, vec![16]);
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ContextFinder<'n> {
+    /// The finder that holds the needle together with its context.
+    finder: StreamFinder<'n>,
+}
+
+impl<'n> ContextFinder<'n> {
+    pub(crate) fn new(finder: StreamFinder<'n>) -> ContextFinder<'n> {
+        ContextFinder { finder }
+    }
+
+    /// Returns the needle that this finder searches for, without its context.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xfind::StreamFinder;
+    ///
+    /// let finder = StreamFinder::new(b"rust").followed_by(b"y");
+    /// assert_eq!(finder.needle(), b"rust");
+    /// ```
+    pub fn needle(&self) -> &[u8] {
+        self.finder.needle()
+    }
+
+    /// Returns the capacity of the buffer that searches read the stream into, in bytes.
+    ///
+    /// Like [`StreamFinder::buffer_capacity`], but the buffer must hold the context of a match as
+    /// well, so the default capacity is based on the length of the needle together with its
+    /// context.
+    pub fn buffer_capacity(&self) -> usize {
+        self.finder.buffer_capacity()
+    }
+
+    /// Only reports matches of the needle that are immediately followed by `suffix` in the
+    /// stream.
+    ///
+    /// See [`StreamFinder::followed_by`].
+    pub fn followed_by(mut self, suffix: &'n [u8]) -> ContextFinder<'n> {
+        self.finder.suffix = suffix;
+        self
+    }
+
+    /// Only reports matches of the needle that are not immediately followed by `suffix` in the
+    /// stream.
+    ///
+    /// See [`StreamFinder::not_followed_by`].
+    pub fn not_followed_by(mut self, suffix: &'n [u8]) -> ContextFinder<'n> {
+        self.finder.not_suffix = suffix;
+        self
+    }
+
+    /// Only reports matches of the needle that are immediately preceded by `prefix` in the
+    /// stream.
+    ///
+    /// See [`StreamFinder::preceded_by`].
+    pub fn preceded_by(mut self, prefix: &'n [u8]) -> ContextFinder<'n> {
+        self.finder.prefix = prefix;
+        self
+    }
+
+    /// Returns the index of the first occurrence of the needle in its context.
+    ///
+    /// See [`StreamFinder::find`].
+    pub fn find<R: Read>(&self, rdr: &mut R) -> Option<io::Result<usize>> {
+        self.finder.find(rdr)
+    }
+
+    /// Returns the index of the first occurrence of the needle in its context, reading as few
+    /// bytes past it as possible.
+    ///
+    /// See [`StreamFinder::find_minimal_read`]. The bytes read past the match include the context
+    /// after it.
+    pub fn find_minimal_read<R: Read>(
+        &self,
+        rdr: &mut R,
+    ) -> Option<io::Result<usize>> {
+        self.finder.find_minimal_read(rdr)
+    }
+
+    /// Returns an iterator over all occurrences of the needle in its context.
+    ///
+    /// See [`StreamFinder::find_iter`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"foobar foobaz foo");
+    ///     let finder = StreamFinder::new(b"foo").not_followed_by(b"bar");
+    ///
+    ///     let mut iter = finder.find_iter(&mut stream);
+    ///     assert_eq!(iter.next().transpose()?, Some(7));
+    ///     assert_eq!(iter.next().transpose()?, Some(14));
+    ///     assert_eq!(iter.next().transpose()?, None);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_iter<'s, R: Read>(
+        &'n self,
+        rdr: &'s mut R,
+    ) -> FindIter<'n, 's, R> {
+        self.finder.find_iter(rdr)
+    }
+
+    /// Returns an iterator over all occurrences of the needle in its context, which searches the
+    /// stream with the given buffer instead of allocating one.
+    ///
+    /// See [`StreamFinder::find_iter_with_buffer`]. The minimum length of the buffer is raised
+    /// to the length of the needle together with its context.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the capacity of the buffer isn't bigger than the length of the needle together
+    /// with its context.
+    pub fn find_iter_with_buffer<'s, R: Read>(
+        &'n self,
+        rdr: &'s mut R,
+        buffer: Buffer,
+    ) -> FindIter<'n, 's, R> {
+        self.finder.find_iter_with_buffer(rdr, buffer)
+    }
+
+    /// Returns an iterator over all occurrences of the needle in its context, starting at the
+    /// current position of the stream.
+    ///
+    /// See [`StreamFinder::find_iter_from_current`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if querying the position of the stream failed, or if the position is
+    /// greater than `usize::MAX` (see [`SearchError::StreamTooLong`](crate::SearchError)).
+    pub fn find_iter_from_current<'s, R: Read + Seek>(
+        &'n self,
+        rdr: &'s mut R,
+    ) -> io::Result<FindIter<'n, 's, R>> {
+        self.finder.find_iter_from_current(rdr)
+    }
+
+    /// Returns the indexes of all occurrences of the needle in its context.
+    ///
+    /// See [`StreamFinder::find_all`].
+    pub fn find_all<R: Read>(&self, rdr: &mut R) -> io::Result<Vec<usize>> {
+        self.finder.find_all(rdr)
+    }
+
+    /// Returns the indexes of all occurrences of the needle in its context, sorted by the given
+    /// key.
+    ///
+    /// See [`StreamFinder::find_all_sorted_by`].
+    pub fn find_all_sorted_by<R, K, F>(
+        &self,
+        rdr: &mut R,
+        key: F,
+    ) -> io::Result<Vec<usize>>
+    where
+        R: Read,
+        K: Ord,
+        F: Fn(usize) -> K,
+    {
+        self.finder.find_all_sorted_by(rdr, key)
+    }
+
+    /// Returns the index of the first occurrence of the needle in its context, together with
+    /// the total number of such occurrences.
+    ///
+    /// See [`StreamFinder::find_with_total`].
+    pub fn find_with_total<R: Read>(
+        &self,
+        rdr: &mut R,
+    ) -> io::Result<Option<(usize, usize)>> {
+        self.finder.find_with_total(rdr)
+    }
+
+    /// Returns the indexes of the first `max` occurrences of the needle in its context, and
+    /// whether there are more occurrences beyond them.
+    ///
+    /// See [`StreamFinder::find_limited_counted`].
+    pub fn find_limited_counted<R: Read>(
+        &self,
+        rdr: &mut R,
+        max: usize,
+    ) -> io::Result<(Vec<usize>, bool)> {
+        self.finder.find_limited_counted(rdr, max)
+    }
+
+    /// Calls `f` with the index and the bytes of every occurrence of the needle in its context,
+    /// until it returns `ControlFlow::Break`.
+    ///
+    /// See [`StreamFinder::for_each_match`]. The bytes passed to `f` are those of the needle,
+    /// without its context.
+    pub fn for_each_match<R, F>(&self, rdr: &mut R, f: F) -> io::Result<()>
+    where
+        R: Read,
+        F: FnMut(usize, &[u8]) -> ControlFlow<()>,
+    {
+        self.finder.for_each_match(rdr, f)
+    }
+
+    /// Returns the indexes of all occurrences of the needle in its context, together with the
+    /// 1-based numbers of the lines they start in.
+    ///
+    /// See [`StreamFinder::find_all_with_lines`].
+    pub fn find_all_with_lines<R: Read>(
+        &self,
+        rdr: &mut R,
+    ) -> io::Result<Vec<(usize, usize)>> {
+        self.finder.find_all_with_lines(rdr)
+    }
+
+    /// Returns an iterator over the occurrences of the needle in its context within the frames
+    /// of the stream.
+    ///
+    /// See [`StreamFinder::find_in_frames`]. The context must lie within the same frame as the
+    /// match.
+    pub fn find_in_frames<'d, 's, R: Read>(
+        &'n self,
+        rdr: &'s mut R,
+        frame_delim: &'d [u8],
+    ) -> FindInFramesIter<'n, 'd, 's, R> {
+        self.finder.find_in_frames(rdr, frame_delim)
+    }
+
+    /// Returns an iterator over the lines of the stream, which yields the 1-based number of each
+    /// line together with the number of occurrences of the needle in its context in it.
+    ///
+    /// See [`StreamFinder::matches_per_line_iter`]. The context must lie within the same line as
+    /// the match.
+    pub fn matches_per_line_iter<'s, R: Read>(
+        &'n self,
+        rdr: &'s mut R,
+    ) -> MatchesPerLineIter<'n, 's, R> {
+        self.finder.matches_per_line_iter(rdr)
+    }
+
+    /// Returns an iterator over the lines of the stream that contain an occurrence of the needle
+    /// in its context.
+    ///
+    /// See [`StreamFinder::matching_lines_iter`].
+    pub fn matching_lines_iter<'s, R: Read>(
+        &'n self,
+        rdr: &'s mut R,
+    ) -> MatchingLinesIter<'n, 's, R> {
+        self.finder.matching_lines_iter(rdr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buffer::DEFAULT_BUFFER_CAPACITY;
+    use crate::testutil::FaultReader;
+    use crate::StreamFinder;
+    use std::io::{self, Cursor};
+    use std::ops::ControlFlow;
+
+    #[test]
+    fn test_context_finder_empty_needle() {
+        let finder = StreamFinder::new(b"");
+        let haystack = &b"ab\nc"[..];
+
+        let followed_by = finder.clone().followed_by(b"b");
+        assert_eq!(followed_by.find_all(&mut &haystack[..]).unwrap(), vec![1]);
+        let preceded_by = finder.clone().preceded_by(b"\n");
+        assert_eq!(preceded_by.find_all(&mut &haystack[..]).unwrap(), vec![3]);
+        let not_followed_by = finder.clone().not_followed_by(b"b");
+        assert_eq!(
+            not_followed_by.find_all(&mut &haystack[..]).unwrap(),
+            vec![0, 2, 3, 4]
+        );
+        let rdr = &mut &haystack[..];
+        assert_eq!(followed_by.find_with_total(rdr).unwrap(), Some((1, 1)));
+        let rdr = &mut &haystack[..];
+        assert_eq!(
+            not_followed_by.find_limited_counted(rdr, 2).unwrap(),
+            (vec![0, 2], true)
+        );
+        let rdr = &mut &haystack[..];
+        assert_eq!(
+            preceded_by.find_all_with_lines(rdr).unwrap(),
+            vec![(3, 2)]
+        );
+    }
+
+    #[test]
+    fn test_context_finder_searches_agree() {
+        let mut haystack = b"[error] error: [error]: x\n".repeat(4);
+        haystack.extend_from_slice(&vec![b'-'; DEFAULT_BUFFER_CAPACITY]);
+        haystack.extend_from_slice(b"\n[error]:");
+        let finder =
+            StreamFinder::new(b"error").preceded_by(b"[").followed_by(b"]:");
+        let expected = vec![16, 42, 68, 94, haystack.len() - 7];
+
+        let rdr = &mut FaultReader::new(&haystack[..]).short_reads(5);
+        assert_eq!(finder.find_all(rdr).unwrap(), expected);
+        let rdr = &mut &haystack[..];
+        assert_eq!(finder.find(rdr).unwrap().unwrap(), expected[0]);
+        let rdr = &mut &haystack[..];
+        assert_eq!(
+            finder.find_minimal_read(rdr).unwrap().unwrap(),
+            expected[0]
+        );
+        let rdr = &mut &haystack[..];
+        assert_eq!(
+            finder.find_with_total(rdr).unwrap(),
+            Some((expected[0], expected.len()))
+        );
+        let rdr = &mut &haystack[..];
+        assert_eq!(
+            finder.find_limited_counted(rdr, 2).unwrap(),
+            (expected[..2].to_vec(), true)
+        );
+
+        let mut offsets = Vec::new();
+        finder
+            .for_each_match(&mut &haystack[..], |pos, bytes| {
+                assert_eq!(bytes, b"error");
+                offsets.push(pos);
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert_eq!(offsets, expected);
+
+        let lines = finder.find_all_with_lines(&mut &haystack[..]).unwrap();
+        assert_eq!(
+            lines,
+            vec![(16, 1), (42, 2), (68, 3), (94, 4), (expected[4], 6)]
+        );
+
+        let mut stream = Cursor::new(&haystack);
+        stream.set_position(20);
+        let iter = finder.find_iter_from_current(&mut stream).unwrap();
+        assert_eq!(iter.into_offsets().unwrap(), &expected[1..]);
+
+        let frames = finder
+            .find_in_frames(&mut &haystack[..], b"\n")
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(frames, vec![(0, 16), (1, 16), (2, 16), (3, 16), (5, 1)]);
+        let counts = finder
+            .matching_lines_iter(&mut &haystack[..])
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(counts.len(), 5);
+    }
+}
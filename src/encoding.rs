@@ -0,0 +1,289 @@
+//! Provides stream searches over text in encodings other than UTF-8.
+use crate::finder::Searcher;
+use encoding_rs::{CoderResult, Decoder, Encoding};
+use std::cmp;
+use std::fmt;
+use std::io::{self, Read};
+
+/// The size of the buffer holding undecoded bytes.
+const INPUT_BUFFER_CAPACITY: usize = 4 * (1 << 10);
+
+/// Returns an iterator over all occurrences of the given UTF-8 needle in a stream of text in the
+/// given encoding.
+///
+/// The stream is transcoded to UTF-8 on the fly, and then searched like
+/// [`find_iter`](crate::find_iter) does. This allows searching e.g. UTF-16 or Latin-1 files with
+/// a UTF-8 needle.
+///
+/// Note that the reported offsets are positions in the transcoded UTF-8 text, not in the original
+/// stream. Like [`Encoding::new_decoder`], a byte order mark at the start of the stream takes
+/// precedence over the given encoding and is not part of the transcoded text, and malformed
+/// sequences are replaced with the REPLACEMENT CHARACTER (U+FFFD).
+///
+/// This function is only available if the `encoding_rs` feature is enabled.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+///
+/// fn main() -> io::Result<()> {
+///     // "rusty rust" in UTF-16LE.
+///     let utf16: Vec<u8> =
+///         "rusty rust".encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+///     let mut stream = Cursor::new(utf16);
+///
+///     let mut iter =
+///         xfind::find_iter_encoded(b"rust", &mut stream, encoding_rs::UTF_16LE);
+///     assert_eq!(iter.next().transpose()?, Some(0));
+///     assert_eq!(iter.next().transpose()?, Some(6));
+///     assert_eq!(iter.next().transpose()?, None);
+///
+///     Ok(())
+/// }
+/// ```
+pub fn find_iter_encoded<'n, 's, R>(
+    needle: &'n [u8],
+    rdr: &'s mut R,
+    encoding: &'static Encoding,
+) -> FindEncodedIter<'n, 's, R>
+where
+    R: Read,
+{
+    FindEncodedIter {
+        rdr: Decoded::new(rdr, encoding),
+        needle,
+        searcher: Searcher::new(needle.len()),
+    }
+}
+
+/// A forward iterator over all non-overlapping occurrences of a UTF-8 substring in a stream of
+/// text in another encoding.
+///
+/// This iterator is created by [`find_iter_encoded`].
+pub struct FindEncodedIter<'n, 's, R: Read> {
+    /// The stream source we read from, which transcodes the bytes read to UTF-8.
+    rdr: Decoded<&'s mut R>,
+    /// The needle we search for.
+    needle: &'n [u8],
+    /// The state of the search.
+    searcher: Searcher,
+}
+
+impl<'n, 's, R: Read> Iterator for FindEncodedIter<'n, 's, R> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.searcher.next(&mut self.rdr, self.needle)
+    }
+}
+
+impl<'n, 's, R: Read + fmt::Debug> fmt::Debug for FindEncodedIter<'n, 's, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FindEncodedIter")
+            .field("rdr", &self.rdr.rdr)
+            .field("encoding", &self.rdr.decoder.encoding())
+            .field("needle", &self.needle)
+            .field("searcher", &self.searcher)
+            .finish()
+    }
+}
+
+/// A reader that transcodes the bytes read from another reader to UTF-8.
+struct Decoded<R> {
+    /// The reader of the undecoded stream.
+    rdr: R,
+    /// The streaming decoder, which keeps incomplete sequences across calls.
+    decoder: Decoder,
+    /// A buffer for undecoded bytes.
+    buf: Vec<u8>,
+    /// The start of the undecoded bytes in `buf`.
+    start: usize,
+    /// The end of the undecoded bytes in `buf`.
+    end: usize,
+    /// Whether the undecoded stream has reached EOF.
+    eof: bool,
+    /// Whether the decoder has been flushed after EOF.
+    finished: bool,
+    /// A buffer for a decoded UTF-8 sequence that didn't fit into the caller's buffer.
+    carry: [u8; 4],
+    /// The start of the bytes in `carry` that haven't been passed on yet.
+    carry_start: usize,
+    /// The end of the bytes in `carry`.
+    carry_end: usize,
+}
+
+impl<R: Read> Decoded<R> {
+    /// Creates a reader that transcodes the stream `rdr` in the given encoding to UTF-8.
+    fn new(rdr: R, encoding: &'static Encoding) -> Decoded<R> {
+        Decoded {
+            rdr,
+            decoder: encoding.new_decoder(),
+            buf: vec![0; INPUT_BUFFER_CAPACITY],
+            start: 0,
+            end: 0,
+            eof: false,
+            finished: false,
+            carry: [0; 4],
+            carry_start: 0,
+            carry_end: 0,
+        }
+    }
+
+    /// Decodes bytes into `buf`, which must be able to hold at least one UTF-8 sequence (i.e. 4
+    /// bytes) unless it is empty.
+    fn decode(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while !self.finished && !buf.is_empty() {
+            if self.start == self.end && !self.eof {
+                // Read first, so that an error (e.g. `Interrupted`) leaves the state intact.
+                let end = self.rdr.read(&mut self.buf)?;
+                self.start = 0;
+                self.end = end;
+                self.eof = self.end == 0;
+            }
+
+            let (result, read, written, _) = self.decoder.decode_to_utf8(
+                &self.buf[self.start..self.end],
+                buf,
+                self.eof,
+            );
+            self.start += read;
+            if self.eof && result == CoderResult::InputEmpty {
+                self.finished = true;
+            }
+            if written > 0 {
+                return Ok(written);
+            }
+        }
+        Ok(0)
+    }
+}
+
+impl<R: Read> Read for Decoded<R> {
+    /// Reads transcoded bytes into `buf`.
+    ///
+    /// If `buf` is too short to hold the next UTF-8 sequence, the sequence is decoded into an
+    /// internal buffer and passed on over several reads.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.carry_start == self.carry_end && buf.len() < self.carry.len() {
+            let mut carry = [0; 4];
+            self.carry_end = self.decode(&mut carry)?;
+            self.carry_start = 0;
+            self.carry = carry;
+        }
+        if self.carry_start < self.carry_end {
+            let carry = &self.carry[self.carry_start..self.carry_end];
+            let n = cmp::min(buf.len(), carry.len());
+            buf[..n].copy_from_slice(&carry[..n]);
+            self.carry_start += n;
+            return Ok(n);
+        }
+        self.decode(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::FaultReader;
+    use memchr::memmem;
+    use std::fs::File;
+    use std::io::Cursor;
+
+    fn encode_utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(|c| c.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_find_iter_encoded_utf16le_fixture() {
+        let mut f = File::open("data/pride-and-prejudice.txt")
+            .expect("test file not found");
+        let mut text = String::new();
+        f.read_to_string(&mut text).unwrap();
+        let utf16 = encode_utf16le(&text);
+
+        for needle in [&b"dear"[..], b"Elizabeth"] {
+            let matches: Vec<usize> = find_iter_encoded(
+                needle,
+                &mut Cursor::new(&utf16),
+                encoding_rs::UTF_16LE,
+            )
+            .map(|x| x.unwrap())
+            .collect();
+            let expected: Vec<usize> =
+                memmem::find_iter(text.as_bytes(), needle).collect();
+            assert_eq!(matches, expected);
+        }
+    }
+
+    #[test]
+    fn test_find_iter_encoded_multibyte() {
+        // Every character takes 2 bytes in UTF-8, and a surrogate pair in UTF-16.
+        let text = "ö😀".repeat(5000) + "straße" + &"ö".repeat(10);
+        let utf16 = encode_utf16le(&text);
+
+        let matches: Vec<usize> = find_iter_encoded(
+            "straße".as_bytes(),
+            &mut Cursor::new(&utf16),
+            encoding_rs::UTF_16LE,
+        )
+        .map(|x| x.unwrap())
+        .collect();
+        assert_eq!(matches, vec![6 * 5000]);
+    }
+
+    #[test]
+    fn test_decoded_short_reads() {
+        let text = "ö😀 café".repeat(100);
+        let utf16 = encode_utf16le(&text);
+
+        for &len in &[1, 2, 3, 5] {
+            let mut rdr =
+                Decoded::new(Cursor::new(&utf16), encoding_rs::UTF_16LE);
+            let mut decoded = Vec::new();
+            let mut buf = vec![0; len];
+            loop {
+                match rdr.read(&mut buf).unwrap() {
+                    0 => break,
+                    n => decoded.extend_from_slice(&buf[..n]),
+                }
+            }
+            assert_eq!(decoded, text.as_bytes(), "len: {}", len);
+        }
+    }
+
+    #[test]
+    fn test_find_iter_encoded_latin1() {
+        let latin1 = b"caf\xe9 au lait, caf\xe9 noir";
+
+        let matches: Vec<usize> = find_iter_encoded(
+            "café".as_bytes(),
+            &mut Cursor::new(&latin1[..]),
+            encoding_rs::WINDOWS_1252,
+        )
+        .map(|x| x.unwrap())
+        .collect();
+        assert_eq!(matches, vec![0, 15]);
+    }
+
+    #[test]
+    fn test_find_iter_encoded_interrupted_reads() {
+        let latin1 = b"caf\xe9 au lait, caf\xe9 noir".repeat(1000);
+        let mut stream = FaultReader::new(Cursor::new(&latin1[..]))
+            .short_reads(5)
+            .interrupt_every(2);
+
+        let matches: Vec<usize> = find_iter_encoded(
+            "café".as_bytes(),
+            &mut stream,
+            encoding_rs::WINDOWS_1252,
+        )
+        .map(|x| x.unwrap())
+        .collect();
+        assert_eq!(matches.len(), 2000);
+        assert_eq!(&matches[..3], &[0, 15, 25]);
+    }
+}
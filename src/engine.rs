@@ -0,0 +1,79 @@
+//! Provides the trait for the algorithms that search the buffered chunks of a stream.
+use memchr::memmem;
+
+/// An algorithm that searches a needle in an in-memory haystack.
+///
+/// The stream searchers of this crate take care of buffering the stream, and use a `MatchEngine`
+/// to search each buffered chunk. By default, they use [`MemmemEngine`], which is backed by
+/// [`memchr::memmem`](https://docs.rs/memchr/latest/memchr/memmem/). A custom engine allows
+/// experimenting with other algorithms, e.g. a SIMD kernel tuned for specific data. See
+/// [`StreamFinder::find_iter_with_engine`](crate::StreamFinder::find_iter_with_engine) and
+/// [`StreamFinder::rfind_iter_with_engine`](crate::StreamFinder::rfind_iter_with_engine).
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+/// use xfind::{MatchEngine, StreamFinder};
+///
+/// /// A naive engine that compares the needle at every position.
+/// struct Naive;
+///
+/// impl MatchEngine for Naive {
+///     fn find(&self, haystack: &[u8], needle: &[u8]) -> Option<usize> {
+///         (0..=haystack.len().checked_sub(needle.len())?)
+///             .find(|&i| haystack[i..].starts_with(needle))
+///     }
+///
+///     fn rfind(&self, haystack: &[u8], needle: &[u8]) -> Option<usize> {
+///         (0..=haystack.len().checked_sub(needle.len())?)
+///             .rev()
+///             .find(|&i| haystack[i..].starts_with(needle))
+///     }
+/// }
+///
+/// fn main() -> io::Result<()> {
+///     let mut stream = Cursor::new(b"rusty rust");
+///     let finder = StreamFinder::new(b"rust");
+///
+///     let offsets = finder.find_iter_with_engine(&mut stream, Naive).into_offsets()?;
+///     assert_eq!(offsets, vec![0, 6]);
+///
+///     Ok(())
+/// }
+/// ```
+pub trait MatchEngine {
+    /// Returns the index of the first occurrence of `needle` in `haystack`.
+    fn find(&self, haystack: &[u8], needle: &[u8]) -> Option<usize>;
+
+    /// Returns the index of the last occurrence of `needle` in `haystack`.
+    fn rfind(&self, haystack: &[u8], needle: &[u8]) -> Option<usize>;
+}
+
+/// The default [`MatchEngine`], which uses [`memchr::memmem`](https://docs.rs/memchr/latest/memchr/memmem/).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MemmemEngine;
+
+impl MatchEngine for MemmemEngine {
+    #[inline]
+    fn find(&self, haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        memmem::find(haystack, needle)
+    }
+
+    #[inline]
+    fn rfind(&self, haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        memmem::rfind(haystack, needle)
+    }
+}
+
+impl<E: MatchEngine + ?Sized> MatchEngine for &E {
+    #[inline]
+    fn find(&self, haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        (**self).find(haystack, needle)
+    }
+
+    #[inline]
+    fn rfind(&self, haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        (**self).rfind(haystack, needle)
+    }
+}
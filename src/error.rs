@@ -0,0 +1,201 @@
+//! Provides the error type returned by fallible search routines.
+use std::error;
+use std::fmt;
+use std::io;
+
+/// An error that can occur when searching a stream.
+///
+/// Most search routines return a plain [`io::Error`] for compatibility with the `?` operator in
+/// I/O code. Errors that don't stem from the underlying stream are wrapped in a `SearchError`
+/// first, which can be recovered by calling [`io::Error::get_ref`] and downcasting.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SearchError {
+    /// An I/O error occurred while reading or seeking the stream.
+    Io(io::Error),
+    /// The length of the stream cannot be represented as a `usize` on this platform.
+    StreamTooLong {
+        /// The length of the stream in bytes.
+        len: u64,
+    },
+    /// The needle is longer than the configured maximum (see
+    /// [`StreamFinderBuilder::max_needle_len`](crate::StreamFinderBuilder::max_needle_len)).
+    NeedleTooLong {
+        /// The length of the needle in bytes.
+        len: usize,
+        /// The maximum length of the needle in bytes.
+        max: usize,
+    },
+    /// The end of a requested region is past the end of the stream.
+    RegionOutOfBounds {
+        /// The end of the requested region.
+        end: u64,
+        /// The length of the stream in bytes.
+        len: u64,
+    },
+    /// A requested seek position is past the end of the stream.
+    SeekOutOfBounds {
+        /// The requested position.
+        pos: u64,
+        /// The length of the stream in bytes.
+        len: u64,
+    },
+    /// An offset relative to the current position of the stream is past the largest position a
+    /// stream can have.
+    OffsetOverflow {
+        /// The current position of the stream.
+        pos: u64,
+        /// The offset relative to `pos`.
+        offset: u64,
+    },
+    /// The stream returned more data after a read had returned zero bytes (see
+    /// [`FindIter::error_on_zero_read`](crate::FindIter::error_on_zero_read)).
+    DataAfterZeroRead,
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchError::Io(err) => write!(f, "I/O error: {}", err),
+            SearchError::StreamTooLong { len } => write!(
+                f,
+                "stream length {} exceeds usize::MAX on this platform",
+                len
+            ),
+            SearchError::NeedleTooLong { len, max } => write!(
+                f,
+                "needle length {} exceeds the maximum of {} bytes",
+                len, max
+            ),
+            SearchError::RegionOutOfBounds { end, len } => write!(
+                f,
+                "region end {} is past the end of the stream of length {}",
+                end, len
+            ),
+            SearchError::SeekOutOfBounds { pos, len } => write!(
+                f,
+                "seek position {} is past the end of the stream of length {}",
+                pos, len
+            ),
+            SearchError::OffsetOverflow { pos, offset } => write!(
+                f,
+                "offset {} from position {} overflows the stream position",
+                offset, pos
+            ),
+            SearchError::DataAfterZeroRead => {
+                write!(f, "stream returned more data after a zero-length read")
+            }
+        }
+    }
+}
+
+impl error::Error for SearchError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            SearchError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SearchError {
+    fn from(err: io::Error) -> SearchError {
+        SearchError::Io(err)
+    }
+}
+
+impl From<SearchError> for io::Error {
+    fn from(err: SearchError) -> io::Error {
+        match err {
+            SearchError::Io(err) => err,
+            err @ SearchError::DataAfterZeroRead => {
+                io::Error::new(io::ErrorKind::UnexpectedEof, err)
+            }
+            err => io::Error::new(io::ErrorKind::InvalidInput, err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_io() {
+        let err = SearchError::from(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "stream closed",
+        ));
+        assert_eq!(err.to_string(), "I/O error: stream closed");
+        assert!(error::Error::source(&err).is_some());
+    }
+
+    #[test]
+    fn test_display_stream_too_long() {
+        let err = SearchError::StreamTooLong { len: 5000000000 };
+        assert_eq!(
+            err.to_string(),
+            "stream length 5000000000 exceeds usize::MAX on this platform"
+        );
+        assert!(error::Error::source(&err).is_none());
+    }
+
+    #[test]
+    fn test_into_io_error() {
+        let err: io::Error = SearchError::StreamTooLong { len: 42 }.into();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let inner = err.get_ref().unwrap().downcast_ref::<SearchError>();
+        assert!(matches!(inner, Some(SearchError::StreamTooLong { len: 42 })));
+
+        let err: io::Error =
+            SearchError::Io(io::ErrorKind::Other.into()).into();
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+        assert!(err.get_ref().is_none());
+    }
+
+    #[test]
+    fn test_display_needle_too_long() {
+        let err = SearchError::NeedleTooLong { len: 1025, max: 1024 };
+        assert_eq!(
+            err.to_string(),
+            "needle length 1025 exceeds the maximum of 1024 bytes"
+        );
+    }
+
+    #[test]
+    fn test_display_region_out_of_bounds() {
+        let err = SearchError::RegionOutOfBounds { end: 20, len: 10 };
+        assert_eq!(
+            err.to_string(),
+            "region end 20 is past the end of the stream of length 10"
+        );
+    }
+
+    #[test]
+    fn test_data_after_zero_read_into_io_error() {
+        let err: io::Error = SearchError::DataAfterZeroRead.into();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(
+            err.to_string(),
+            "stream returned more data after a zero-length read"
+        );
+    }
+
+    #[test]
+    fn test_display_seek_out_of_bounds() {
+        let err = SearchError::SeekOutOfBounds { pos: 11, len: 10 };
+        assert_eq!(
+            err.to_string(),
+            "seek position 11 is past the end of the stream of length 10"
+        );
+    }
+
+    #[test]
+    fn test_display_offset_overflow() {
+        let err = SearchError::OffsetOverflow { pos: 3, offset: u64::MAX };
+        assert_eq!(
+            err.to_string(),
+            "offset 18446744073709551615 from position 3 overflows the stream position"
+        );
+    }
+}
@@ -0,0 +1,232 @@
+//! Provides stream searches that look through an escape sequence interrupting the needle.
+use crate::buffer::Buffer;
+use crate::span::MatchSpan;
+use memchr::memchr;
+use std::io::{self, Read};
+
+/// Returns an iterator over all non-overlapping occurrences of the given needle in the stream,
+/// where an occurrence may be interrupted by the given escape sequence.
+///
+/// This finds literals in formats where an escape can split them without changing their
+/// meaning, e.g. line continuations: with the escape `\` followed by a newline,
+/// `foo\<newline>bar` matches the needle `foobar`. An occurrence neither starts nor ends with an
+/// escape sequence, and at most one escape sequence is skipped between two bytes of the needle.
+/// This bounds the length of an occurrence to `needle.len() + (needle.len() - 1) *
+/// escape.len()` bytes, which is how much is buffered to verify it. An escape sequence is always
+/// skipped where it's allowed, so the needle shouldn't contain it.
+///
+/// Matches are reported as spans in the original stream, since a match is longer than the
+/// needle if it contains escape sequences. Verifying a match takes `O(needle.len() *
+/// escape.len())` time, and every occurrence of the first byte of the needle is verified, so
+/// this is slower than a plain search.
+///
+/// # Panics
+///
+/// Panics if `needle` or `escape` is empty.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+/// use xfind::MatchSpan;
+///
+/// fn main() -> io::Result<()> {
+///     let mut stream = Cursor::new(b"CFLAGS = -O2 -W\\\nall -Wall");
+///
+///     let spans = xfind::find_iter_skipping_escapes(b"-Wall", b"\\\n", &mut stream)
+///         .collect::<io::Result<Vec<_>>>()?;
+///     assert_eq!(spans, vec![MatchSpan::new(13, 20), MatchSpan::new(21, 26)]);
+///
+///     Ok(())
+/// }
+/// ```
+pub fn find_iter_skipping_escapes<'n, 'e, 's, R: Read>(
+    needle: &'n [u8],
+    escape: &'e [u8],
+    rdr: &'s mut R,
+) -> FindSkippingIter<'n, 'e, 's, R> {
+    assert!(!needle.is_empty(), "empty needle");
+    assert!(!escape.is_empty(), "empty escape sequence");
+    let max_len = needle.len() + (needle.len() - 1) * escape.len();
+    FindSkippingIter {
+        rdr,
+        needle,
+        escape,
+        buf: Buffer::new(max_len),
+        max_len,
+        buf_offset: 0,
+        search_pos: 0,
+    }
+}
+
+/// A forward iterator over all non-overlapping occurrences of a substring in a stream, which may
+/// be interrupted by an escape sequence.
+///
+/// This iterator is created by [`find_iter_skipping_escapes`].
+#[derive(Debug)]
+pub struct FindSkippingIter<'n, 'e, 's, R: Read> {
+    /// The stream source we read from.
+    rdr: &'s mut R,
+    /// The needle we search for.
+    needle: &'n [u8],
+    /// The escape sequence that is skipped within an occurrence.
+    escape: &'e [u8],
+    /// A fixed size buffer that we actually search for. It must be big enough to hold the longest
+    /// possible match.
+    buf: Buffer,
+    /// The length of the longest possible match.
+    max_len: usize,
+    /// The absolute position of the start of `self.buf` in the stream.
+    buf_offset: usize,
+    /// The first position in `self.buf` that hasn't been ruled out as the start of a match.
+    search_pos: usize,
+}
+
+impl<'n, 'e, 's, R: Read> FindSkippingIter<'n, 'e, 's, R> {
+    /// Returns the start and the length of the first match that starts in `self.buf` from
+    /// `self.search_pos` up to (excluding) `end`.
+    fn find_before(&self, end: usize) -> Option<(usize, usize)> {
+        let contents = self.buf.buffer();
+        let mut pos = self.search_pos;
+        while pos < end {
+            let start = pos + memchr(self.needle[0], &contents[pos..])?;
+            if start >= end {
+                return None;
+            }
+            if let Some(len) =
+                verify(&contents[start..], self.needle, self.escape)
+            {
+                return Some((start, len));
+            }
+            pos = start + 1;
+        }
+        None
+    }
+}
+
+impl<'n, 'e, 's, R: Read> Iterator for FindSkippingIter<'n, 'e, 's, R> {
+    type Item = io::Result<MatchSpan>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // A match starting before `end` is verified with all of its bytes in the buffer,
+            // unless the stream ends before it.
+            let len = self.buf.len();
+            let end = if self.buf.is_eof() {
+                len
+            } else {
+                (len + 1).saturating_sub(self.max_len)
+            };
+            if let Some((start, match_len)) = self.find_before(end) {
+                self.search_pos = start + match_len;
+                let start = self.buf_offset + start;
+                return Some(Ok(MatchSpan::new(start, start + match_len)));
+            }
+            if self.buf.is_eof() {
+                return None;
+            }
+            self.search_pos = end.max(self.search_pos);
+
+            // Roll our buffer if our buffer has at least the minimum amount of bytes in it. The
+            // positions in the retained suffix haven't been ruled out yet.
+            if len >= self.buf.retain_len() {
+                let dropped = len - self.buf.retain_len();
+                self.buf.roll();
+                self.buf_offset += dropped;
+                self.search_pos -= dropped;
+            }
+            if let Err(err) = self.buf.fill(&mut self.rdr) {
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+/// Returns the length of the match of the needle at the start of `haystack`, skipping at most one
+/// escape sequence between two bytes of the needle, if any.
+///
+/// The haystack must hold the longest possible match, or the rest of the stream.
+fn verify(haystack: &[u8], needle: &[u8], escape: &[u8]) -> Option<usize> {
+    let mut i = 0;
+    for (k, &b) in needle.iter().enumerate() {
+        if k > 0 && haystack[i..].starts_with(escape) {
+            i += escape.len();
+        }
+        if *haystack.get(i)? != b {
+            return None;
+        }
+        i += 1;
+    }
+    Some(i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::DEFAULT_BUFFER_CAPACITY;
+    use crate::testutil::FaultReader;
+
+    fn spans(needle: &[u8], haystack: &[u8]) -> Vec<(usize, usize)> {
+        let rdr = &mut FaultReader::new(haystack).short_reads(3);
+        find_iter_skipping_escapes(needle, b"\\\n", rdr)
+            .map(|span| span.map(|span| (span.start, span.end)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_skipping_escapes() {
+        assert_eq!(
+            spans(b"foobar", b"foobar foo\\\nbar f\\\no\\\no\\\nb\\\na\\\nr"),
+            vec![(0, 6), (7, 15), (16, 32),]
+        );
+        // Escapes neither start nor end a match, and only one escape is skipped at a time.
+        assert_eq!(spans(b"foo", b"\\\nfoo\\\n"), vec![(2, 5)]);
+        assert_eq!(spans(b"foobar", b"foo\\\n\\\nbar"), vec![]);
+        // An incomplete escape doesn't match.
+        assert_eq!(spans(b"foobar", b"foo\\bar foo\nbar"), vec![]);
+        // A failed candidate doesn't hide an overlapping match.
+        assert_eq!(spans(b"aab", b"aa\\\nab"), vec![(1, 6)]);
+        assert_eq!(spans(b"a", b"a\\\na"), vec![(0, 1), (3, 4)]);
+    }
+
+    #[test]
+    fn test_skipping_escapes_across_rolls() {
+        let mut haystack = Vec::new();
+        let mut expected = Vec::new();
+        while haystack.len() < DEFAULT_BUFFER_CAPACITY * 4 {
+            let start = haystack.len();
+            match expected.len() % 3 {
+                0 => haystack.extend_from_slice(b"#define MAX_LEN"),
+                1 => haystack.extend_from_slice(b"#define MAX_\\\nLEN"),
+                _ => {
+                    haystack.extend_from_slice(b"#de\\\nfine\\\n MAX_LE\\\nN")
+                }
+            }
+            let offset = if expected.len() % 3 == 2 { 12 } else { 8 };
+            expected.push((start + offset, haystack.len()));
+            haystack.extend_from_slice(b" 42 \\\n MAX\n");
+        }
+
+        for &k in &[1, 997, DEFAULT_BUFFER_CAPACITY] {
+            let rdr = &mut FaultReader::new(&haystack[..]).short_reads(k);
+            let found = find_iter_skipping_escapes(b"MAX_LEN", b"\\\n", rdr)
+                .map(|span| span.map(|span| (span.start, span.end)).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn test_skipping_escapes_error() {
+        let rdr = &mut FaultReader::new(&b"fo\\\no foo"[..]).error_at(8);
+        let mut iter = find_iter_skipping_escapes(b"foo", b"\\\n", rdr);
+        assert_eq!(iter.next().unwrap().unwrap(), MatchSpan::new(0, 5));
+        assert!(iter.next().unwrap().is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_skipping_escapes_empty_escape() {
+        find_iter_skipping_escapes(b"foo", b"", &mut &b"foo"[..]);
+    }
+}
@@ -1,7 +1,32 @@
 //! Provides forward and backward substring searchers that operate on stream.
-use crate::buffer::{Buffer, BufferRev};
+use crate::buffer::{self, Buffer, BufferRev, DEFAULT_BUFFER_CAPACITY};
+use crate::cached::FindRevBufferedIter;
+use crate::context::ContextFinder;
+use crate::engine::{MatchEngine, MemmemEngine};
+use crate::error::SearchError;
+use crate::glob::GlobFinder;
+use crate::multi::StreamMultiFinder;
+use crate::span::{FindClustersIter, FindRevSpansIter, FindSpansIter};
+use crate::split::{
+    split, FindInFramesIter, MatchesPerLineIter, MatchingLinesIter,
+};
 use memchr::memmem;
-use std::io::{self, Read, Seek, SeekFrom};
+use std::borrow::Cow;
+use std::cmp;
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::{ControlFlow, Range};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// The number of windows the sample of `StreamFinder::estimate_count` is spread across.
+const ESTIMATE_WINDOWS: usize = 8;
+
+/// The maximum number of offsets that `FindIter::into_offsets` reserves room for upfront.
+const MAX_RESERVED_OFFSETS: usize = 1024;
 
 /// Returns the index of the first occurrence of the given needle in the stream.
 ///
@@ -52,6 +77,208 @@ where
     }
 }
 
+/// Returns the index of the last occurrence of the given needle in a stream that cannot seek.
+///
+/// This is an escape hatch for sources like pipes or `/dev/stdin`, which don't implement [`Seek`]
+/// and therefore can't be used with [`rfind`]. It reads the entire stream into memory and then
+/// searches it backward, so its memory usage is proportional to the length of the stream. Prefer
+/// [`rfind`] whenever the source is seekable.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Read};
+///
+/// fn main() -> io::Result<()> {
+///     // `Chain` implements `Read`, but not `Seek`.
+///     let mut stream = (&b"rusty "[..]).chain(&b"rust"[..]);
+///
+///     let pos = xfind::rfind_buffering(b"rust", &mut stream)?;
+///     assert_eq!(pos, Some(6));
+///
+///     Ok(())
+/// }
+/// ```
+pub fn rfind_buffering<R>(
+    needle: &[u8],
+    rdr: &mut R,
+) -> io::Result<Option<usize>>
+where
+    R: Read,
+{
+    let mut buf = Vec::new();
+    rdr.read_to_end(&mut buf)?;
+    Ok(memmem::rfind(&buf, needle))
+}
+
+/// Returns the index of the first occurrence of the given needle within the first `n` bytes of
+/// the stream.
+///
+/// This is meant for scanning headers and magic numbers that must appear early in a stream. At
+/// most `n` bytes are read, and a match that extends past the first `n` bytes is not reported.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+///
+/// fn main() -> io::Result<()> {
+///     let mut stream = Cursor::new(b"%PDF-1.7 ... %PDF");
+///     assert_eq!(xfind::find_in_prefix(b"%PDF", &mut stream, 4)?, Some(0));
+///
+///     let mut stream = Cursor::new(b"garbage %PDF-1.7");
+///     assert_eq!(xfind::find_in_prefix(b"%PDF", &mut stream, 8)?, None);
+///
+///     Ok(())
+/// }
+/// ```
+pub fn find_in_prefix<R>(
+    needle: &[u8],
+    rdr: &mut R,
+    n: usize,
+) -> io::Result<Option<usize>>
+where
+    R: Read,
+{
+    let mut rdr = rdr.take(n as u64);
+    find(needle, &mut rdr).transpose()
+}
+
+/// Returns `true` if the given needle occurs at exactly the given offset of the stream.
+///
+/// The offset is relative to the current position of the stream, which is restored afterwards,
+/// even if an error occurs. This seeks to the offset and reads only `needle.len()` bytes, so it's
+/// much cheaper than a search.
+///
+/// # Errors
+///
+/// Returns an I/O error if seeking or reading the stream failed, or if the offset overflows the
+/// position of the stream (see [`SearchError::OffsetOverflow`]).
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+///
+/// fn main() -> io::Result<()> {
+///     let mut stream = Cursor::new(b"rusty rust");
+///     assert!(xfind::is_match_at(b"rust", &mut stream, 6)?);
+///     assert!(!xfind::is_match_at(b"rust", &mut stream, 3)?);
+///
+///     Ok(())
+/// }
+/// ```
+pub fn is_match_at<R>(
+    needle: &[u8],
+    rdr: &mut R,
+    offset: u64,
+) -> io::Result<bool>
+where
+    R: Read + Seek,
+{
+    with_restored_position(rdr, |rdr, start_pos| {
+        let pos = start_pos
+            .checked_add(offset)
+            .ok_or(SearchError::OffsetOverflow { pos: start_pos, offset })?;
+        rdr.seek(SeekFrom::Start(pos))?;
+        let mut bytes = Vec::with_capacity(needle.len());
+        rdr.take(needle.len() as u64).read_to_end(&mut bytes)?;
+        Ok(bytes == needle)
+    })
+}
+
+/// Returns `true` if the given marker occurs at exactly the given offset of the stream, and
+/// nowhere before it.
+///
+/// This is meant for format validators that check that e.g. a section marker is the first one
+/// of its kind. The offset is relative to the current position of the stream, which is restored
+/// afterwards, even if an error occurs. The marker is checked with [`is_match_at`] first, and
+/// only then the stream is searched for an earlier occurrence, reading no further than the end of
+/// the marker. An earlier occurrence that overlaps the marker counts as well.
+///
+/// # Errors
+///
+/// Returns an I/O error like [`is_match_at`], or if searching the stream failed.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+///
+/// fn main() -> io::Result<()> {
+///     let mut stream = Cursor::new(b"head: BEGIN; BEGIN");
+///     assert!(xfind::assert_marker_at(&mut stream, b"BEGIN", 6)?);
+///
+///     let mut stream = Cursor::new(b"head: BEGIN; BEGIN");
+///     assert!(!xfind::assert_marker_at(&mut stream, b"BEGIN", 13)?);
+///
+///     Ok(())
+/// }
+/// ```
+pub fn assert_marker_at<R: Read + Seek>(
+    rdr: &mut R,
+    marker: &[u8],
+    offset: u64,
+) -> io::Result<bool> {
+    if !is_match_at(marker, rdr, offset)? {
+        return Ok(false);
+    }
+    if offset == 0 {
+        return Ok(true);
+    }
+
+    with_restored_position(rdr, |rdr, _| {
+        // An earlier occurrence starts before `offset`, so it ends before the end of the marker.
+        let mut prefix = rdr.take(offset + marker.len() as u64 - 1);
+        let earlier = find(marker, &mut prefix).transpose()?;
+        Ok(earlier.is_none())
+    })
+}
+
+/// Returns the offset at which the given 1-based line of the stream begins.
+///
+/// Line 1 always begins at offset 0, and line `n` begins right after the `n - 1`-th `\n`. Returns
+/// `None` if the stream contains fewer newlines than that. Note that this means that the empty
+/// line after a final newline counts as a line, e.g. line 2 of `a\n` begins at offset 2. The stream
+/// is only read up to the start of the line.
+///
+/// # Panics
+///
+/// Panics if `line` is 0.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+///
+/// fn main() -> io::Result<()> {
+///     let text = b"first\nsecond\nthird";
+///     assert_eq!(xfind::line_offset(&mut Cursor::new(text), 1)?, Some(0));
+///     assert_eq!(xfind::line_offset(&mut Cursor::new(text), 3)?, Some(13));
+///     assert_eq!(xfind::line_offset(&mut Cursor::new(text), 4)?, None);
+///
+///     Ok(())
+/// }
+/// ```
+pub fn line_offset<R: Read>(
+    rdr: &mut R,
+    line: usize,
+) -> io::Result<Option<usize>> {
+    assert!(line > 0, "line numbers start at 1");
+    let mut remaining = line - 1;
+    if remaining == 0 {
+        return Ok(Some(0));
+    }
+    for pos in find_iter(b"\n", rdr) {
+        let pos = pos?;
+        remaining -= 1;
+        if remaining == 0 {
+            return Ok(Some(pos + 1));
+        }
+    }
+    Ok(None)
+}
+
 /// Returns an iterator over all occurrences of the given needle in the stream.
 ///
 /// # Examples
@@ -84,11 +311,8 @@ where
 ///
 /// # Errors
 ///
-/// Returns an I/O error if seeking to the end of the stream failed.
-///
-/// # Panics
-///
-/// Panics if the length of the stream is greater than `usize::MAX`.
+/// Returns an I/O error if seeking to the end of the stream failed, or if the length of the
+/// stream is greater than `usize::MAX` (see [`SearchError::StreamTooLong`]).
 ///
 /// # Examples
 ///
@@ -116,11 +340,135 @@ where
     FindRevIter::new_with_needle(rdr, needle)
 }
 
+/// Returns the absolute offsets of all occurrences of the given needle within the given byte
+/// range of a file.
+///
+/// The file is opened, seeked to `region.start`, and searched until `region.end`, so only that
+/// region is read. Matches must lie entirely inside the region. This is a convenience for e.g.
+/// searching bytes 1MB..2MB of a large file.
+///
+/// # Errors
+///
+/// Returns an I/O error if opening, seeking or reading the file failed. If `region.end` is past
+/// the end of the file, an error of kind [`InvalidInput`](io::ErrorKind::InvalidInput) wrapping
+/// [`SearchError::RegionOutOfBounds`] is returned.
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+///
+/// fn main() -> io::Result<()> {
+///     let path = "data/pride-and-prejudice.txt";
+///     let offsets = xfind::find_file_region(path, b"Elizabeth", 10000..20000)?;
+///     assert!(offsets.iter().all(|&pos| (10000..20000).contains(&pos)));
+///
+///     Ok(())
+/// }
+/// ```
+pub fn find_file_region<P: AsRef<Path>>(
+    path: P,
+    needle: &[u8],
+    region: Range<u64>,
+) -> io::Result<Vec<usize>> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    if region.end > len {
+        return Err(
+            SearchError::RegionOutOfBounds { end: region.end, len }.into()
+        );
+    }
+    if region.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let start = usize::try_from(region.start).map_err(|_| {
+        io::Error::from(SearchError::StreamTooLong { len: region.start })
+    })?;
+    file.seek(SeekFrom::Start(region.start))?;
+    let mut rdr = file.take(region.end - region.start);
+    let mut offsets = find_iter(needle, &mut rdr).into_offsets()?;
+    for pos in offsets.iter_mut() {
+        *pos += start;
+    }
+    Ok(offsets)
+}
+
+/// Returns the index of the first occurrence of the given needle in the file at the given path.
+///
+/// This is a shorthand for opening the file with [`File::open`] and searching it with [`find`].
+///
+/// # Errors
+///
+/// Returns an I/O error if opening or reading the file failed, e.g. one of kind
+/// [`NotFound`](io::ErrorKind::NotFound) if the file doesn't exist.
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+///
+/// fn main() -> io::Result<()> {
+///     let pos = xfind::find_in_file(b"Elizabeth", "data/pride-and-prejudice.txt")?;
+///     assert!(pos.is_some());
+///
+///     Ok(())
+/// }
+/// ```
+pub fn find_in_file<P: AsRef<Path>>(
+    needle: &[u8],
+    path: P,
+) -> io::Result<Option<usize>> {
+    find_iter_in_file(needle, path)?.next().transpose()
+}
+
+/// Returns an iterator over all occurrences of the given needle in the file at the given path.
+///
+/// This is a shorthand for opening the file with [`File::open`] and searching it with
+/// [`find_iter`]. The returned iterator owns the file, so it can be returned from functions.
+///
+/// # Errors
+///
+/// Returns an I/O error if opening the file failed, e.g. one of kind
+/// [`NotFound`](io::ErrorKind::NotFound) if the file doesn't exist. The iterator yields errors
+/// from reading the file.
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+///
+/// fn main() -> io::Result<()> {
+///     let offsets = xfind::find_iter_in_file(b"Elizabeth", "data/pride-and-prejudice.txt")?
+///         .take(10)
+///         .collect::<io::Result<Vec<_>>>()?;
+///     assert_eq!(offsets.len(), 10);
+///
+///     Ok(())
+/// }
+/// ```
+pub fn find_iter_in_file<P: AsRef<Path>>(
+    needle: &[u8],
+    path: P,
+) -> io::Result<FindInFileIter<'_>> {
+    let file = File::open(path)?;
+    Ok(FindInFileIter { file, needle, searcher: Searcher::new(needle.len()) })
+}
+
 /// A substring searcher for stream searches.
 #[derive(Clone, Debug)]
 pub struct StreamFinder<'n> {
     /// The string we want to search.
-    needle: &'n [u8],
+    needle: Cow<'n, [u8]>,
+    /// The bytes that must immediately precede a match, if any, which only a `ContextFinder`
+    /// sets.
+    pub(crate) prefix: &'n [u8],
+    /// The bytes that must immediately follow a match, if any.
+    pub(crate) suffix: &'n [u8],
+    /// The bytes that must not immediately follow a match, if any.
+    pub(crate) not_suffix: &'n [u8],
+    /// The capacity of the buffer of forward searches, if not the default.
+    buffer_capacity: Option<usize>,
 }
 
 impl<'n> StreamFinder<'n> {
@@ -134,24 +482,40 @@ impl<'n> StreamFinder<'n> {
     /// let finder = StreamFinder::new(b"rust");
     /// ```
     pub fn new(needle: &'n [u8]) -> StreamFinder<'n> {
-        StreamFinder { needle }
+        StreamFinder {
+            needle: Cow::Borrowed(needle),
+            prefix: &[],
+            suffix: &[],
+            not_suffix: &[],
+            buffer_capacity: None,
+        }
     }
 
-    /// Returns the needle that this finder searches for.
+    /// Creates a new `StreamFinder` for the little-endian byte representation of the given
+    /// `u16`.
     ///
     /// # Examples
     ///
     /// ```
+    /// use std::io::{self, Cursor};
     /// use xfind::StreamFinder;
     ///
-    /// let finder = StreamFinder::new(b"rust");
-    /// assert_eq!(finder.needle(), b"rust");
+    /// fn main() -> io::Result<()> {
+    ///     // The magic number of BMP files, "BM".
+    ///     let mut stream = Cursor::new(b"...BM...");
+    ///     let finder = StreamFinder::from_u16_le(0x4d42);
+    ///
+    ///     assert_eq!(finder.find(&mut stream).transpose()?, Some(3));
+    ///
+    ///     Ok(())
+    /// }
     /// ```
-    pub fn needle(&self) -> &[u8] {
-        self.needle
+    pub fn from_u16_le(v: u16) -> StreamFinder<'static> {
+        StreamFinder::from_owned(v.to_le_bytes().to_vec())
     }
 
-    /// Returns the index of the first occurrence of the given needle in the stream.
+    /// Creates a new `StreamFinder` for the big-endian byte representation of the given
+    /// `u16`.
     ///
     /// # Examples
     ///
@@ -160,24 +524,43 @@ impl<'n> StreamFinder<'n> {
     /// use xfind::StreamFinder;
     ///
     /// fn main() -> io::Result<()> {
-    ///     let mut stream = Cursor::new(b"rusty rust");
-    ///     let finder = StreamFinder::new(b"rust");
+    ///     let mut stream = Cursor::new(b"...BM...");
+    ///     let finder = StreamFinder::from_u16_be(0x424d);
     ///
-    ///     let pos = finder.find(&mut stream).transpose()?;
-    ///     assert_eq!(pos, Some(0));
+    ///     assert_eq!(finder.find(&mut stream).transpose()?, Some(3));
     ///
     ///     Ok(())
     /// }
     /// ```
-    pub fn find<R: Read>(&self, rdr: &mut R) -> Option<io::Result<usize>> {
-        self.find_iter(rdr).next()
+    pub fn from_u16_be(v: u16) -> StreamFinder<'static> {
+        StreamFinder::from_owned(v.to_be_bytes().to_vec())
     }
 
-    /// Returns the index of the last occurrence of the given needle in the stream.
+    /// Creates a new `StreamFinder` for the little-endian byte representation of the given
+    /// `u32`.
     ///
-    /// # Panics
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     // The signature of a local file header in ZIP archives, `PK\x03\x04`.
+    ///     let mut stream = Cursor::new(b"data PK\x03\x04");
+    ///     let finder = StreamFinder::from_u32_le(0x04034b50);
+    ///
+    ///     assert_eq!(finder.find(&mut stream).transpose()?, Some(5));
     ///
-    /// Panics if the length of the stream is greater than `usize::MAX`.
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn from_u32_le(v: u32) -> StreamFinder<'static> {
+        StreamFinder::from_owned(v.to_le_bytes().to_vec())
+    }
+
+    /// Creates a new `StreamFinder` for the big-endian byte representation of the given
+    /// `u32`.
     ///
     /// # Examples
     ///
@@ -186,26 +569,21 @@ impl<'n> StreamFinder<'n> {
     /// use xfind::StreamFinder;
     ///
     /// fn main() -> io::Result<()> {
-    ///     let mut stream = Cursor::new(b"rusty rust");
-    ///     let finder = StreamFinder::new(b"rust");
+    ///     // The magic number of Java class files.
+    ///     let mut stream = Cursor::new(b"\x00\xca\xfe\xba\xbe");
+    ///     let finder = StreamFinder::from_u32_be(0xcafebabe);
     ///
-    ///     let pos = finder.rfind(&mut stream).transpose()?;
-    ///     assert_eq!(pos, Some(6));
+    ///     assert_eq!(finder.find(&mut stream).transpose()?, Some(1));
     ///
     ///     Ok(())
     /// }
     /// ```
-    pub fn rfind<R: Read + Seek>(
-        &self,
-        rdr: &mut R,
-    ) -> Option<io::Result<usize>> {
-        match self.rfind_iter(rdr) {
-            Ok(mut iter) => iter.next(),
-            Err(e) => Some(Err(e)),
-        }
+    pub fn from_u32_be(v: u32) -> StreamFinder<'static> {
+        StreamFinder::from_owned(v.to_be_bytes().to_vec())
     }
 
-    /// Returns an iterator over all occurrences of the given needle in the stream.
+    /// Creates a new `StreamFinder` for the little-endian byte representation of the given
+    /// `u64`.
     ///
     /// # Examples
     ///
@@ -214,33 +592,54 @@ impl<'n> StreamFinder<'n> {
     /// use xfind::StreamFinder;
     ///
     /// fn main() -> io::Result<()> {
-    ///     let mut stream = Cursor::new(b"rusty rust");
-    ///     let finder = StreamFinder::new(b"rust");
+    ///     let mut stream = Cursor::new(b"\x08\x07\x06\x05\x04\x03\x02\x01");
+    ///     let finder = StreamFinder::from_u64_le(0x0102030405060708);
     ///
-    ///     let mut iter = finder.find_iter(&mut stream);
-    ///     assert_eq!(iter.next().transpose()?, Some(0));
-    ///     assert_eq!(iter.next().transpose()?, Some(6));
-    ///     assert_eq!(iter.next().transpose()?, None);
+    ///     assert_eq!(finder.find(&mut stream).transpose()?, Some(0));
     ///
     ///     Ok(())
     /// }
     /// ```
-    pub fn find_iter<'s, R: Read>(
-        &'n self,
-        rdr: &'s mut R,
-    ) -> FindIter<'n, 's, R> {
-        FindIter::new(rdr, self)
+    pub fn from_u64_le(v: u64) -> StreamFinder<'static> {
+        StreamFinder::from_owned(v.to_le_bytes().to_vec())
     }
 
-    /// Returns a reverse iterator over all occurrences of the given needle in the stream.
+    /// Creates a new `StreamFinder` for the big-endian byte representation of the given
+    /// `u64`.
     ///
-    /// # Errors
+    /// # Examples
     ///
-    /// Returns an I/O error if seeking to the end of the stream failed.
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
     ///
-    /// # Panics
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"-\x01\x02\x03\x04\x05\x06\x07\x08");
+    ///     let finder = StreamFinder::from_u64_be(0x0102030405060708);
+    ///
+    ///     assert_eq!(finder.find(&mut stream).transpose()?, Some(1));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn from_u64_be(v: u64) -> StreamFinder<'static> {
+        StreamFinder::from_owned(v.to_be_bytes().to_vec())
+    }
+
+    /// Creates a new `StreamFinder` that owns its needle.
+    fn from_owned(needle: Vec<u8>) -> StreamFinder<'static> {
+        StreamFinder {
+            needle: Cow::Owned(needle),
+            prefix: &[],
+            suffix: &[],
+            not_suffix: &[],
+            buffer_capacity: None,
+        }
+    }
+
+    /// Creates a finder for a glob pattern with a single `*` wildcard, e.g. `BEGIN*END`.
     ///
-    /// Panics if the length of the stream is greater than `usize::MAX`.
+    /// This is a shorthand for [`GlobFinder::new`].
     ///
     /// # Examples
     ///
@@ -249,578 +648,7617 @@ impl<'n> StreamFinder<'n> {
     /// use xfind::StreamFinder;
     ///
     /// fn main() -> io::Result<()> {
-    ///     let mut stream = Cursor::new(b"rusty rust");
-    ///     let finder = StreamFinder::new(b"rust");
+    ///     let mut stream = Cursor::new(b"BEGIN foo END bar END");
+    ///     let finder = StreamFinder::with_glob(b"BEGIN*END");
     ///
-    ///     let mut iter = finder.rfind_iter(&mut stream)?;
-    ///     assert_eq!(iter.next().transpose()?, Some(6));
-    ///     assert_eq!(iter.next().transpose()?, Some(0));
-    ///     assert_eq!(iter.next().transpose()?, None);
+    ///     let spans = finder.find_iter(&mut stream).collect::<io::Result<Vec<_>>>()?;
+    ///     assert_eq!(spans, vec![(0, 13)]);
     ///
     ///     Ok(())
     /// }
     /// ```
-    pub fn rfind_iter<'s, R: Read + Seek>(
-        &'n self,
-        rdr: &'s mut R,
-    ) -> io::Result<FindRevIter<'n, 's, R>> {
-        FindRevIter::new(rdr, self)
+    pub fn with_glob(pattern: &'n [u8]) -> GlobFinder<'n> {
+        GlobFinder::new(pattern)
     }
-}
 
-/// A forward iterator over all non-overlapping occurrences of a substring in a stream.
-///
-/// Matches are reported by the byte offset at which they begin.
-#[derive(Debug)]
-pub struct FindIter<'n, 's, R: Read> {
-    /// The stream source we read from.
-    rdr: &'s mut R,
-    /// The needle we search for.
-    needle: &'n [u8],
-    /// A fixed size buffer that we actually search for. It must be big enough to hold the needle.
-    buf: Buffer,
-    /// The current position at which to start the next search in `self.buf`.
-    search_pos: usize,
-    /// The absolute position of `search_pos` in the stream.
+    /// Returns the needle that this finder searches for.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xfind::StreamFinder;
+    ///
+    /// let finder = StreamFinder::new(b"rust");
+    /// assert_eq!(finder.needle(), b"rust");
+    /// ```
+    pub fn needle(&self) -> &[u8] {
+        &self.needle
+    }
+
+    /// Returns the capacity of the buffer that forward searches read the stream into, in bytes.
+    ///
+    /// This is the capacity set with [`StreamFinderBuilder::buffer_capacity`], raised to fit the
+    /// needle if needed, or the default capacity, which is the larger of 8KB and
+    /// `8 * needle.len()` bytes. It allows verifying that a tuning took effect. A buffer grows
+    /// to its capacity lazily, so searches of small streams allocate less than that.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xfind::{SearchError, StreamFinder, StreamFinderBuilder};
+    ///
+    /// fn main() -> Result<(), SearchError> {
+    ///     assert_eq!(StreamFinder::new(b"rust").buffer_capacity(), 8192);
+    ///
+    ///     let finder = StreamFinderBuilder::new().buffer_capacity(32768).build(b"rust")?;
+    ///     assert_eq!(finder.buffer_capacity(), 32768);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn buffer_capacity(&self) -> usize {
+        let min = cmp::max(1, self.pattern_len());
+        match self.buffer_capacity {
+            Some(capacity) => cmp::max(capacity, min + 1),
+            None => buffer::default_capacity(min),
+        }
+    }
+
+    /// Returns the number of bytes that forward searches must buffer to verify a match, i.e. the
+    /// length of the needle and its context.
+    fn pattern_len(&self) -> usize {
+        // The bytes after a match must be buffered along with the match to check them.
+        self.prefix.len()
+            + self.needle.len()
+            + self.suffix.len()
+            + self.not_suffix.len()
+    }
+
+    /// Returns a finder that only reports matches of the needle that are immediately followed by
+    /// `suffix` in the stream.
+    ///
+    /// This works like a lookahead in a regex: the suffix is not part of the match, so the next
+    /// match may start inside it. A match that is too close to the end of the stream to be
+    /// followed by the whole suffix is not reported.
+    ///
+    /// The returned [`ContextFinder`] only provides the searches that check the constraint, i.e.
+    /// the forward searches based on [`FindIter`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"no errors, error: disk full");
+    ///     let finder = StreamFinder::new(b"error").followed_by(b": ");
+    ///
+    ///     assert_eq!(finder.find_all(&mut stream)?, vec![11]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn followed_by(self, suffix: &'n [u8]) -> ContextFinder<'n> {
+        ContextFinder::new(self).followed_by(suffix)
+    }
+
+    /// Returns a finder that only reports matches of the needle that are not immediately
+    /// followed by `suffix` in the stream.
+    ///
+    /// This works like a negative lookahead in a regex, and is the complement of
+    /// [`followed_by`](StreamFinder::followed_by). A match that is too close to the end of the
+    /// stream to be followed by the whole suffix is reported, since it isn't followed by
+    /// `suffix`. To check the bytes after a match, the stream may be read up to `suffix.len()`
+    /// bytes past it. This can be combined with `followed_by` and
+    /// [`preceded_by`](StreamFinder::preceded_by) on the returned [`ContextFinder`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"foobar foobaz foo");
+    ///     let finder = StreamFinder::new(b"foo").not_followed_by(b"bar");
+    ///
+    ///     assert_eq!(finder.find_all(&mut stream)?, vec![7, 14]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn not_followed_by(self, suffix: &'n [u8]) -> ContextFinder<'n> {
+        ContextFinder::new(self).not_followed_by(suffix)
+    }
+
+    /// Returns a finder that only reports matches of the needle that are immediately preceded by
+    /// `prefix` in the stream.
+    ///
+    /// This works like a lookbehind in a regex: the prefix is not part of the match, so it may
+    /// overlap with the previous match. A match that is too close to the start of the stream to
+    /// be preceded by the whole prefix (e.g. at offset 0) is not reported. This can be combined
+    /// with [`followed_by`](StreamFinder::followed_by) on the returned [`ContextFinder`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"telecom.com");
+    ///     let finder = StreamFinder::new(b"com").preceded_by(b".");
+    ///
+    ///     assert_eq!(finder.find_all(&mut stream)?, vec![8]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn preceded_by(self, prefix: &'n [u8]) -> ContextFinder<'n> {
+        ContextFinder::new(self).preceded_by(prefix)
+    }
+
+    /// Returns the index of the first occurrence of the given needle in the stream.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let pos = finder.find(&mut stream).transpose()?;
+    ///     assert_eq!(pos, Some(0));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find<R: Read>(&self, rdr: &mut R) -> Option<io::Result<usize>> {
+        self.find_iter(rdr).next()
+    }
+
+    /// Returns the index of the first occurrence of the given needle in the stream, reading as
+    /// few bytes past it as possible.
+    ///
+    /// [`find`](StreamFinder::find) fills a whole buffer before searching it, so it usually reads
+    /// several kilobytes past the first match. This searches with a buffer that only holds twice
+    /// the needle (and its context) instead, so at most that many bytes past the start of the
+    /// match are read. This is meant for expensive sources, e.g. metered network streams, where
+    /// every byte counts. The tradeoff is many more, smaller reads, and thus a slower search if
+    /// the match is far into the stream.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(vec![b'a'; 64 * 1024]);
+    ///     let finder = StreamFinder::new(b"aaaa");
+    ///
+    ///     assert_eq!(finder.find_minimal_read(&mut stream).transpose()?, Some(0));
+    ///     assert!(stream.position() <= 8);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_minimal_read<R: Read>(
+        &self,
+        rdr: &mut R,
+    ) -> Option<io::Result<usize>> {
+        let finder = StreamFinder {
+            buffer_capacity: Some(self.pattern_len() * 2),
+            ..self.clone()
+        };
+        finder.find_iter(rdr).next()
+    }
+
+    /// Returns the index of the last occurrence of the given needle in the stream.
+    ///
+    /// # Errors
+    ///
+    /// Yields an I/O error if the length of the stream is greater than `usize::MAX` (see
+    /// [`SearchError::StreamTooLong`]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let pos = finder.rfind(&mut stream).transpose()?;
+    ///     assert_eq!(pos, Some(6));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn rfind<R: Read + Seek>(
+        &self,
+        rdr: &mut R,
+    ) -> Option<io::Result<usize>> {
+        match self.rfind_iter(rdr) {
+            Ok(mut iter) => iter.next(),
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    /// Returns the index of the last occurrence of the given needle in the stream, searching it
+    /// forward.
+    ///
+    /// This reports the same occurrence as [`rfind`](StreamFinder::rfind), including one that
+    /// overlaps an earlier occurrence, but it only requires [`Read`]. The whole stream is read, so
+    /// this is slower than `rfind` if the last occurrence is near the end of a long stream.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let pos = finder.find_last(&mut stream).transpose()?;
+    ///     assert_eq!(pos, Some(6));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_last<R: Read>(
+        &self,
+        rdr: &mut R,
+    ) -> Option<io::Result<usize>> {
+        let needle = self.needle();
+        // Advancing by a single byte finds overlapping occurrences as well.
+        let mut searcher = Searcher::new(needle.len());
+        let mut last = None;
+        while let Some(res) =
+            searcher.next_advancing_with(rdr, needle, 1, |_, _| Ok(()))
+        {
+            match res {
+                Ok(pos) => last = Some(pos),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        last.map(Ok)
+    }
+
+    /// Counts the bytes immediately preceding the non-overlapping occurrences of the needle in
+    /// the stream.
+    ///
+    /// Returns the number of matches preceded by each byte value, indexed by the byte, together
+    /// with the number of matches that aren't preceded by any byte, i.e. a match at offset 0.
+    /// This helps understanding the context of matches, e.g. which characters precede a token.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if reading the stream failed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rust, rusty (rust)");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let (counts, none) = finder.preceding_byte_histogram(&mut stream)?;
+    ///     assert_eq!((counts[b' ' as usize], counts[b'(' as usize], none), (1, 1, 1));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn preceding_byte_histogram<R: Read>(
+        &self,
+        rdr: &mut R,
+    ) -> io::Result<([u64; 256], u64)> {
+        let mut counts = [0; 256];
+        let needle = self.needle();
+        let mut none = 0;
+        let mut searcher = Searcher::new(needle.len());
+        // The byte before the start of the buffer, once bytes have been dropped from it.
+        let mut last_dropped = None;
+        loop {
+            let next = searcher.next_advancing_with(
+                rdr,
+                needle,
+                needle.len(),
+                |_, bytes| {
+                    if let Some(&b) = bytes.last() {
+                        last_dropped = Some(b);
+                    }
+                    Ok(())
+                },
+            );
+            let pos = match next {
+                Some(res) => res?,
+                None => return Ok((counts, none)),
+            };
+            let (offset, contents) = searcher.buffered();
+            let before = match pos - offset {
+                0 => last_dropped,
+                i => Some(contents[i - 1]),
+            };
+            match before {
+                Some(b) => counts[b as usize] += 1,
+                None => none += 1,
+            }
+        }
+    }
+
+    /// Returns the index of the last occurrence of the given needle in the stream, searching in
+    /// the direction in which it's expected to be found sooner.
+    ///
+    /// If `expect_near_end` is `true`, this is [`rfind`](StreamFinder::rfind), which scans the
+    /// stream backward from its end, and stops at the first match it finds. That's fast if the
+    /// last occurrence is close to the end, but slow if it's close to the start, since the rest of
+    /// the stream is scanned backward, which is slower than a forward scan. Otherwise, this is
+    /// [`find_last`](StreamFinder::find_last) over the whole stream, which always reads the whole
+    /// stream, but forward. So pick `false` if the needle is expected to occur only once and
+    /// early, e.g. in a header.
+    ///
+    /// Both directions report the same offset, relative to the start of the stream.
+    ///
+    /// # Errors
+    ///
+    /// Yields an I/O error if reading or seeking the stream failed, or if the length of the
+    /// stream is greater than `usize::MAX` (see [`SearchError::StreamTooLong`]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let finder = StreamFinder::new(b"%PDF");
+    ///
+    ///     let mut stream = Cursor::new(b"%PDF-1.7 ...");
+    ///     assert_eq!(finder.rfind_hint(&mut stream, false).transpose()?, Some(0));
+    ///     assert_eq!(finder.rfind_hint(&mut stream, true).transpose()?, Some(0));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn rfind_hint<R: Read + Seek>(
+        &self,
+        rdr: &mut R,
+        expect_near_end: bool,
+    ) -> Option<io::Result<usize>> {
+        if expect_near_end {
+            return self.rfind(rdr);
+        }
+        if let Err(err) = rdr.seek(SeekFrom::Start(0)) {
+            return Some(Err(err));
+        }
+        self.find_last(rdr)
+    }
+
+    /// Calls `f` with the offset and the bytes of every occurrence of the given needle in the
+    /// stream, until `f` returns [`ControlFlow::Break`].
+    ///
+    /// This is the callback counterpart to [`find_iter`](StreamFinder::find_iter). The bytes are
+    /// borrowed from the internal buffer, which is sound because they are only lent to `f` until
+    /// it returns. It also spares `f` from handling I/O errors: the first one stops the search
+    /// and is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if reading the stream failed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use std::ops::ControlFlow;
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust rust");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let mut offsets = Vec::new();
+    ///     finder.for_each_match(&mut stream, |pos, bytes| {
+    ///         assert_eq!(bytes, b"rust");
+    ///         offsets.push(pos);
+    ///         if offsets.len() == 2 {
+    ///             ControlFlow::Break(())
+    ///         } else {
+    ///             ControlFlow::Continue(())
+    ///         }
+    ///     })?;
+    ///     assert_eq!(offsets, vec![0, 6]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn for_each_match<R, F>(&self, rdr: &mut R, mut f: F) -> io::Result<()>
+    where
+        R: Read,
+        F: FnMut(usize, &[u8]) -> ControlFlow<()>,
+    {
+        let needle_len = self.needle().len();
+        let mut iter = self.find_iter(rdr);
+        while let Some(res) = iter.next() {
+            let pos = res?;
+            // The match stays buffered until the iterator is advanced again.
+            let (offset, contents) = iter.searcher.buffered();
+            let start = pos - offset;
+            if let ControlFlow::Break(()) =
+                f(pos, &contents[start..start + needle_len])
+            {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns an iterator over all occurrences of the given needle in the stream.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let mut iter = finder.find_iter(&mut stream);
+    ///     assert_eq!(iter.next().transpose()?, Some(0));
+    ///     assert_eq!(iter.next().transpose()?, Some(6));
+    ///     assert_eq!(iter.next().transpose()?, None);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_iter<'s, R: Read>(
+        &'n self,
+        rdr: &'s mut R,
+    ) -> FindIter<'n, 's, R> {
+        FindIter::new(rdr, self)
+    }
+
+    /// Returns an iterator over all occurrences of the given needle in the stream, which searches
+    /// the stream with the given buffer instead of allocating one.
+    ///
+    /// The buffer is reset before searching, and its minimum length is raised to the length of
+    /// the needle if it's smaller. Use [`FindIter::into_buffer`] to get the buffer back afterwards. See
+    /// [`Buffer`] for an example.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the capacity of the buffer isn't bigger than the length of the needle.
+    pub fn find_iter_with_buffer<'s, R: Read>(
+        &'n self,
+        rdr: &'s mut R,
+        buffer: Buffer,
+    ) -> FindIter<'n, 's, R> {
+        FindIter::with_buffer(rdr, self, buffer)
+    }
+
+    /// Returns a reverse iterator over all occurrences of the given needle in the stream.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if seeking to the end of the stream failed, or if the length of the
+    /// stream is greater than `usize::MAX` (see [`SearchError::StreamTooLong`]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let mut iter = finder.rfind_iter(&mut stream)?;
+    ///     assert_eq!(iter.next().transpose()?, Some(6));
+    ///     assert_eq!(iter.next().transpose()?, Some(0));
+    ///     assert_eq!(iter.next().transpose()?, None);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn rfind_iter<'s, R: Read + Seek>(
+        &'n self,
+        rdr: &'s mut R,
+    ) -> io::Result<FindRevIter<'n, 's, R>> {
+        FindRevIter::new(rdr, self)
+    }
+
+    /// Returns a reverse iterator over all occurrences of the given needle in a stream that
+    /// cannot seek, by reading it into memory.
+    ///
+    /// This is the counterpart of [`rfind_buffering`] for iterating. It reads the entire stream
+    /// into memory upfront, so its memory usage is proportional to the length of the stream. Only
+    /// use it for streams of bounded size, like small pipes, and prefer
+    /// [`rfind_iter`](StreamFinder::rfind_iter) whenever the source is seekable. It reports the
+    /// same offsets as `rfind_iter`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if reading the stream failed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Read};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     // `Chain` implements `Read`, but not `Seek`.
+    ///     let mut stream = (&b"rusty "[..]).chain(&b"rust"[..]);
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let mut iter = finder.rfind_iter_buffered(&mut stream)?;
+    ///     assert_eq!(iter.next().transpose()?, Some(6));
+    ///     assert_eq!(iter.next().transpose()?, Some(0));
+    ///     assert_eq!(iter.next().transpose()?, None);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn rfind_iter_buffered<R: Read>(
+        &'n self,
+        rdr: &mut R,
+    ) -> io::Result<FindRevBufferedIter<'n>> {
+        FindRevBufferedIter::new(rdr, self.needle())
+    }
+
+    /// Returns an iterator over all occurrences of the given needle in the stream, which searches
+    /// the buffered chunks of the stream with the given engine instead of the default
+    /// [`MemmemEngine`](crate::MemmemEngine).
+    ///
+    /// See [`MatchEngine`](crate::MatchEngine) for an example.
+    pub fn find_iter_with_engine<'s, R: Read, E: MatchEngine>(
+        &'n self,
+        rdr: &'s mut R,
+        engine: E,
+    ) -> FindIter<'n, 's, R, E> {
+        self.find_iter(rdr).with_engine(engine)
+    }
+
+    /// Returns a reverse iterator over all occurrences of the given needle in the stream, which
+    /// searches the buffered chunks of the stream with the given engine instead of the default
+    /// [`MemmemEngine`](crate::MemmemEngine).
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if seeking to the end of the stream failed, or if the length of the
+    /// stream is greater than `usize::MAX` (see [`SearchError::StreamTooLong`]).
+    pub fn rfind_iter_with_engine<'s, R: Read + Seek, E: MatchEngine>(
+        &'n self,
+        rdr: &'s mut R,
+        engine: E,
+    ) -> io::Result<FindRevIter<'n, 's, R, E>> {
+        Ok(self.rfind_iter(rdr)?.with_engine(engine))
+    }
+
+    /// Returns an iterator over all occurrences of the given needle in the stream, which reports
+    /// each match by its [`MatchSpan`](crate::MatchSpan).
+    ///
+    /// This is like [`find_iter`](StreamFinder::find_iter), but interoperates with code that
+    /// expects `Range`-like spans of matches.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let spans = finder.find_spans_iter(&mut stream).collect::<io::Result<Vec<_>>>()?;
+    ///     assert_eq!(spans[1].as_range(), 6..10);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_spans_iter<'s, R: Read>(
+        &'n self,
+        rdr: &'s mut R,
+    ) -> FindSpansIter<'n, 's, R> {
+        FindSpansIter::new(self.find_iter(rdr), self.needle().len())
+    }
+
+    /// Returns an iterator over the clusters of occurrences of the given needle in the stream,
+    /// which reports each cluster by the range from the start of its first match to the end of
+    /// its last match.
+    ///
+    /// A cluster is a run of non-overlapping matches where at most `max_gap` bytes lie between the
+    /// end of each match and the start of the next one. This collapses dense regions of matches,
+    /// e.g. to highlight the hot regions of a file. A lone match is a cluster of `needle.len()`
+    /// bytes. With a `max_gap` of 0, only adjacent matches are merged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rust rust, ..., rust");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let clusters = finder.find_clusters_iter(&mut stream, 2);
+    ///     assert_eq!(clusters.collect::<io::Result<Vec<_>>>()?, vec![0..9, 16..20]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_clusters_iter<'s, R: Read>(
+        &'n self,
+        rdr: &'s mut R,
+        max_gap: usize,
+    ) -> FindClustersIter<'n, 's, R> {
+        FindClustersIter::new(
+            self.find_iter(rdr),
+            self.needle().len(),
+            max_gap,
+        )
+    }
+
+    /// Returns an iterator over the occurrences of the given needle within the frames of the
+    /// stream that are separated by `frame_delim`, which yields the index of each frame together
+    /// with the offset of the match within the frame.
+    ///
+    /// This models protocols where matches must not span frames: a match that straddles a
+    /// delimiter is never reported. Frames are split like with [`split`](crate::split), so a
+    /// delimiter at the very end of the stream doesn't start another frame. Every frame is
+    /// buffered while it's searched.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frame_delim` is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty ru|st|rust rust");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let matches = finder.find_in_frames(&mut stream, b"|");
+    ///     assert_eq!(matches.collect::<io::Result<Vec<_>>>()?, vec![(0, 0), (2, 0), (2, 5)]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_in_frames<'d, 's, R: Read>(
+        &'n self,
+        rdr: &'s mut R,
+        frame_delim: &'d [u8],
+    ) -> FindInFramesIter<'n, 'd, 's, R> {
+        FindInFramesIter::new(self, split(frame_delim, rdr))
+    }
+
+    /// Returns the range of the longest run of adjacent occurrences of the given needle in the
+    /// stream, i.e. of non-overlapping matches where each match ends right where the next one
+    /// starts.
+    ///
+    /// A lone match is a run of one match. If several runs are equally long, the first one is
+    /// returned. For an empty needle, whose matches are empty, this returns the empty range at
+    /// the start of the stream. This is [`find_clusters_iter`](StreamFinder::find_clusters_iter)
+    /// with a `max_gap` of 0, but only keeps track of the current and the longest run.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if reading the stream failed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"abcabcabc x abcabc");
+    ///     let finder = StreamFinder::new(b"abc");
+    ///
+    ///     assert_eq!(finder.longest_run(&mut stream)?, Some(0..9));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn longest_run<R: Read>(
+        &self,
+        rdr: &mut R,
+    ) -> io::Result<Option<Range<usize>>> {
+        if self.needle().is_empty() {
+            return Ok(Some(0..0));
+        }
+        let mut longest: Option<Range<usize>> = None;
+        for run in self.find_clusters_iter(rdr, 0) {
+            let run = run?;
+            // `Option::is_none_or` is only stable on recent toolchains.
+            #[allow(clippy::unnecessary_map_or)]
+            if longest
+                .as_ref()
+                .map_or(true, |longest| run.len() > longest.len())
+            {
+                longest = Some(run);
+            }
+        }
+        Ok(longest)
+    }
+
+    /// Returns a reverse iterator over all occurrences of the given needle in the stream, which
+    /// reports each match by its [`MatchSpan`](crate::MatchSpan).
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if seeking to the end of the stream failed, or if the length of the
+    /// stream is greater than `usize::MAX` (see [`SearchError::StreamTooLong`]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::{MatchSpan, StreamFinder};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let mut iter = finder.rfind_spans_iter(&mut stream)?;
+    ///     assert_eq!(iter.next().transpose()?, Some(MatchSpan::new(6, 10)));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn rfind_spans_iter<'s, R: Read + Seek>(
+        &'n self,
+        rdr: &'s mut R,
+    ) -> io::Result<FindRevSpansIter<'n, 's, R>> {
+        let iter = self.rfind_iter(rdr)?;
+        Ok(FindRevSpansIter::new(iter, self.needle().len()))
+    }
+
+    /// Returns a reverse iterator over all occurrences of the given needle in the stream, which
+    /// reports each match by its distance from the end of the stream.
+    ///
+    /// The distance of a match is the number of bytes after it, i.e. `stream_len - match_end`. So
+    /// a match at the very end of the stream is reported as 0.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if seeking to the end of the stream failed, or if the length of the
+    /// stream is greater than `usize::MAX` (see [`SearchError::StreamTooLong`]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust!");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let mut iter = finder.rfind_iter_from_end(&mut stream)?;
+    ///     assert_eq!(iter.next().transpose()?, Some(1));
+    ///     assert_eq!(iter.next().transpose()?, Some(7));
+    ///     assert_eq!(iter.next().transpose()?, None);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn rfind_iter_from_end<'s, R: Read + Seek>(
+        &'n self,
+        rdr: &'s mut R,
+    ) -> io::Result<FindFromEndIter<'n, 's, R>> {
+        Ok(FindFromEndIter { iter: self.rfind_iter(rdr)? })
+    }
+
+    /// Returns a reverse iterator over all occurrences of the given needle in a stream of the
+    /// given length, without seeking to the end of the stream first.
+    ///
+    /// See [`FindRevIter::with_len`] for details. Passing a length other than the actual length
+    /// of the stream is a logic error.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use std::io;
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut f = File::open("foo.txt")?;
+    ///     let len = f.metadata()?.len() as usize;
+    ///
+    ///     let finder = StreamFinder::new(b"rust");
+    ///     let last = finder.rfind_iter_with_len(&mut f, len).next().transpose()?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn rfind_iter_with_len<'s, R: Read + Seek>(
+        &'n self,
+        rdr: &'s mut R,
+        len: usize,
+    ) -> FindRevIter<'n, 's, R> {
+        FindRevIter::with_len(rdr, self.needle(), len)
+    }
+
+    /// Returns the indexes of all occurrences of the given needle in the stream.
+    ///
+    /// This is a convenience for collecting [`find_iter`](StreamFinder::find_iter), which stops
+    /// at the first I/O error. An empty needle occurs at every offset, including the end of the
+    /// stream.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     assert_eq!(finder.find_all(&mut stream)?, vec![0, 6]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_all<R: Read>(&self, rdr: &mut R) -> io::Result<Vec<usize>> {
+        self.find_iter(rdr).collect()
+    }
+
+    /// Returns the index of the first occurrence of the given needle in the stream, together with
+    /// the total number of occurrences.
+    ///
+    /// This is meant for "match 1 of N" displays, which would otherwise need two searches. The
+    /// whole stream is read to count the occurrences like [`find_all`](StreamFinder::find_all),
+    /// but their offsets aren't collected. Returns `None` if there is no occurrence.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"crab, rusty rust");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     assert_eq!(finder.find_with_total(&mut stream)?, Some((6, 2)));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_with_total<R: Read>(
+        &self,
+        rdr: &mut R,
+    ) -> io::Result<Option<(usize, usize)>> {
+        let mut iter = self.find_iter(rdr);
+        let first = match iter.next().transpose()? {
+            Some(pos) => pos,
+            None => return Ok(None),
+        };
+        let mut total = 1;
+        for res in iter {
+            res?;
+            total += 1;
+        }
+        Ok(Some((first, total)))
+    }
+
+    /// Returns the indexes of the first `max` occurrences of the given needle in the stream, and
+    /// whether there are more occurrences beyond them.
+    ///
+    /// This is meant for "showing 100+ results" displays. After `max` matches, the search goes on
+    /// to the next match only, so the stream is read just far enough to find out whether it
+    /// exists, rather than to its end.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let mut stream = Cursor::new(b"rusty rust, rustacean");
+    ///     assert_eq!(finder.find_limited_counted(&mut stream, 2)?, (vec![0, 6], true));
+    ///
+    ///     let mut stream = Cursor::new(b"rusty rust");
+    ///     assert_eq!(finder.find_limited_counted(&mut stream, 2)?, (vec![0, 6], false));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_limited_counted<R: Read>(
+        &self,
+        rdr: &mut R,
+        max: usize,
+    ) -> io::Result<(Vec<usize>, bool)> {
+        let mut iter = self.find_iter(rdr);
+        let offsets = iter.by_ref().take(max).collect::<io::Result<_>>()?;
+        let more = iter.next().transpose()?.is_some();
+        Ok((offsets, more))
+    }
+
+    /// Returns an iterator over the lines of the stream, which yields the 1-based number of each
+    /// line together with the number of non-overlapping occurrences of the needle in it.
+    ///
+    /// Every line is yielded, including the lines without a match, unless
+    /// [`nonzero_only`](MatchesPerLineIter::nonzero_only) is set. Lines are separated by `\n`,
+    /// and a final line without a newline is yielded as well. The stream is read line by line,
+    /// and each line is searched in memory, so a needle that contains a newline never matches.
+    /// An empty needle matches at every offset of a line, including its end.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let mut stream = Cursor::new(b"rusty rust\ncrab\ntrust");
+    ///     let counts = finder.matches_per_line_iter(&mut stream);
+    ///     assert_eq!(counts.collect::<io::Result<Vec<_>>>()?, vec![(1, 2), (2, 0), (3, 1)]);
+    ///
+    ///     let mut stream = Cursor::new(b"rusty rust\ncrab\ntrust");
+    ///     let counts = finder.matches_per_line_iter(&mut stream).nonzero_only();
+    ///     assert_eq!(counts.collect::<io::Result<Vec<_>>>()?, vec![(1, 2), (3, 1)]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn matches_per_line_iter<'s, R: Read>(
+        &'n self,
+        rdr: &'s mut R,
+    ) -> MatchesPerLineIter<'n, 's, R> {
+        MatchesPerLineIter::new(self, split(b"\n", rdr))
+    }
+
+    /// Returns an iterator over the lines of the stream that contain the needle, which yields the
+    /// 1-based number of each line together with its bytes.
+    ///
+    /// This is the iterator form of [`grep`](crate::grep), for processing the matching lines
+    /// programmatically. Lines are separated by `\n`, and are yielded without it. Every line is
+    /// yielded as a whole, however long it is, and only the current line is buffered. Lines are
+    /// searched like with [`matches_per_line_iter`](StreamFinder::matches_per_line_iter).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust\ncrab\ntrust");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let lines = finder.matching_lines_iter(&mut stream).collect::<io::Result<Vec<_>>>()?;
+    ///     assert_eq!(lines, vec![(1, b"rusty rust".to_vec()), (3, b"trust".to_vec())]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn matching_lines_iter<'s, R: Read>(
+        &'n self,
+        rdr: &'s mut R,
+    ) -> MatchingLinesIter<'n, 's, R> {
+        MatchingLinesIter::new(self.matches_per_line_iter(rdr).nonzero_only())
+    }
+
+    /// Returns the indexes of all non-overlapping occurrences of the given needle in the stream,
+    /// together with metadata about the stream that is collected in the same pass.
+    ///
+    /// The metadata counts the line endings by style and detects a UTF-8 byte order mark, e.g. for
+    /// tools that report file information alongside search results. Every byte is inspected
+    /// exactly once as it streams through, so no second pass is needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if reading the stream failed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"\xEF\xBB\xBFrusty\r\nrust\n");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let (matches, meta) = finder.scan_metadata(&mut stream)?;
+    ///     assert_eq!(matches, vec![3, 10]);
+    ///     assert_eq!((meta.crlf_count, meta.lf_count), (1, 1));
+    ///     assert!(meta.has_bom);
+    ///     assert_eq!(meta.byte_count, 15);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn scan_metadata<R: Read>(
+        &self,
+        rdr: &mut R,
+    ) -> io::Result<(Vec<usize>, StreamMeta)> {
+        let mut scanner = MetaScanner::default();
+        let mut matches = Vec::new();
+        // Every byte is dropped from the buffer exactly once, except for the bytes that are still
+        // buffered at the end.
+        let mut iter = self.find_iter(rdr);
+        while let Some(res) =
+            iter.next_observing(|_, bytes| scanner.update(bytes))
+        {
+            matches.push(res?);
+        }
+        scanner.update(iter.searcher.buffered().1);
+        Ok((matches, scanner.finish()))
+    }
+
+    /// Returns the indexes of all non-overlapping occurrences of the given needle in the stream,
+    /// together with the 1-based numbers of the lines they start on.
+    ///
+    /// Line numbers are computed in the same pass as the search, by counting the newlines (`\n`)
+    /// of the bytes as they stream through, so this is as fast as
+    /// [`find_all`](StreamFinder::find_all) plus a [`memchr`](https://docs.rs/memchr) count, and
+    /// needs neither a second pass nor `Seek`. A match of a needle that contains a newline is on
+    /// the line it starts on.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if reading the stream failed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty\n\nrust rust");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     assert_eq!(finder.find_all_with_lines(&mut stream)?, vec![(0, 1), (7, 3), (12, 3)]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_all_with_lines<R: Read>(
+        &self,
+        rdr: &mut R,
+    ) -> io::Result<Vec<(usize, usize)>> {
+        let mut matches = Vec::new();
+        // The current line number, and the offset up to which newlines have been counted.
+        let (mut line, mut counted) = (1, 0);
+        let mut iter = self.find_iter(rdr);
+        loop {
+            let next = iter.next_observing(|offset, bytes| {
+                let end = offset + bytes.len();
+                if end > counted {
+                    line +=
+                        memchr::memchr_iter(b'\n', &bytes[counted - offset..])
+                            .count();
+                    counted = end;
+                }
+            });
+            let pos = match next {
+                Some(res) => res?,
+                None => return Ok(matches),
+            };
+            let (offset, contents) = iter.searcher.buffered();
+            line += memchr::memchr_iter(
+                b'\n',
+                &contents[counted - offset..pos - offset],
+            )
+            .count();
+            counted = pos;
+            matches.push((pos, line));
+        }
+    }
+
+    /// Returns the indexes of all non-overlapping occurrences of the given needle in the stream,
+    /// preferring later matches over earlier ones where they overlap.
+    ///
+    /// The forward searches of this crate are leftmost: a match is reported as soon as it's found,
+    /// and the search resumes after it, so a later match that overlaps it is dropped. This is the
+    /// opposite, rightmost choice: where matches overlap, the latest one wins. The two only differ
+    /// for needles that can overlap themselves, like `abab` in `ababab`, which has a leftmost
+    /// match at 0 but a rightmost match at 2. Since which matches win depends on how the stream
+    /// ends, the stream is searched backward, and the offsets are returned in ascending order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if reading or seeking the stream failed, or if the length of the stream
+    /// is greater than `usize::MAX` (see [`SearchError::StreamTooLong`]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let finder = StreamFinder::new(b"abab");
+    ///
+    ///     assert_eq!(finder.find_all(&mut Cursor::new(b"ababab"))?, vec![0]);
+    ///     assert_eq!(finder.non_overlapping_rightmost(&mut Cursor::new(b"ababab"))?, vec![2]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn non_overlapping_rightmost<R: Read + Seek>(
+        &self,
+        rdr: &mut R,
+    ) -> io::Result<Vec<usize>> {
+        let mut offsets =
+            self.rfind_iter(rdr)?.collect::<io::Result<Vec<_>>>()?;
+        offsets.reverse();
+        Ok(offsets)
+    }
+
+    /// Returns the indexes of the first `count` occurrences of the given needle in the stream, or
+    /// of all of them if there are fewer.
+    ///
+    /// This is like collecting `find_iter(rdr).take(count)`: it stops at the first I/O error, and
+    /// stops reading the stream right after the last match it needs. If `count` is 0, the stream
+    /// isn't read at all.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rust rusty rust trust");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     assert_eq!(finder.find_cluster(&mut stream, 3)?, vec![0, 5, 11]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_cluster<R: Read>(
+        &self,
+        rdr: &mut R,
+        count: usize,
+    ) -> io::Result<Vec<usize>> {
+        let mut offsets = Vec::new();
+        if count == 0 {
+            return Ok(offsets);
+        }
+        for pos in self.find_iter(rdr) {
+            offsets.push(pos?);
+            if offsets.len() == count {
+                break;
+            }
+        }
+        Ok(offsets)
+    }
+
+    /// Returns the indexes of all occurrences of the given needle in the stream, sorted by the
+    /// given key function.
+    ///
+    /// The sort is stable, so matches with equal keys are kept in stream order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let matches = finder.find_all_sorted_by(&mut stream, |pos| std::cmp::Reverse(pos))?;
+    ///     assert_eq!(matches, vec![6, 0]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_all_sorted_by<R, K, F>(
+        &self,
+        rdr: &mut R,
+        key: F,
+    ) -> io::Result<Vec<usize>>
+    where
+        R: Read,
+        K: Ord,
+        F: Fn(usize) -> K,
+    {
+        let mut matches = self.find_all(rdr)?;
+        matches.sort_by_key(|&pos| key(pos));
+        Ok(matches)
+    }
+
+    /// Returns an iterator over all occurrences of the given needle in the stream, starting at
+    /// the current position of the stream.
+    ///
+    /// Unlike [`find_iter`](StreamFinder::find_iter), whose offsets are relative to where the
+    /// stream was positioned when the search started, the offsets reported by this iterator are
+    /// absolute positions in the stream. This matters if some bytes have been read from the
+    /// stream already.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if querying the position of the stream failed, or if the position is
+    /// greater than `usize::MAX` (see [`SearchError::StreamTooLong`]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor, Read};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     stream.read_exact(&mut [0; 3])?;
+    ///     let mut iter = finder.find_iter_from_current(&mut stream)?;
+    ///     assert_eq!(iter.next().transpose()?, Some(6));
+    ///     assert_eq!(iter.next().transpose()?, None);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_iter_from_current<'s, R: Read + Seek>(
+        &'n self,
+        rdr: &'s mut R,
+    ) -> io::Result<FindIter<'n, 's, R>> {
+        let pos = rdr.stream_position()?;
+        let pos = usize::try_from(pos)
+            .map_err(|_| SearchError::StreamTooLong { len: pos })?;
+        Ok(FindIter::with_offset(rdr, self, pos))
+    }
+
+    /// Returns an iterator over all occurrences of the given needle in the stream, after skipping
+    /// a header of `header_len` bytes.
+    ///
+    /// The header is read and discarded before searching, so the stream doesn't need to be
+    /// seekable, and no match overlaps the header. If `absolute` is `false`, the offsets reported
+    /// by the iterator are relative to the end of the header, otherwise they count the bytes of the
+    /// header as well. A stream shorter than the header yields no matches.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if reading the header failed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let mut stream = Cursor::new(b"rust: rusty rust");
+    ///     let offsets = finder.find_iter_skip_header(&mut stream, 6, false)?.into_offsets()?;
+    ///     assert_eq!(offsets, vec![0, 6]);
+    ///
+    ///     let mut stream = Cursor::new(b"rust: rusty rust");
+    ///     let offsets = finder.find_iter_skip_header(&mut stream, 6, true)?.into_offsets()?;
+    ///     assert_eq!(offsets, vec![6, 12]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_iter_skip_header<'s, R: Read>(
+        &'n self,
+        rdr: &'s mut R,
+        header_len: usize,
+        absolute: bool,
+    ) -> io::Result<FindIter<'n, 's, R>> {
+        io::copy(&mut (&mut *rdr).take(header_len as u64), &mut io::sink())?;
+        let offset = if absolute { header_len } else { 0 };
+        Ok(FindIter::with_offset(rdr, self, offset))
+    }
+
+    /// Returns an iterator over all occurrences of the given needle in the stream, after applying
+    /// `transform` to the bytes read.
+    ///
+    /// This allows normalizing the stream on the fly, e.g. searching case-insensitively by
+    /// uppercasing ASCII letters. `transform` is called on every chunk read from the stream
+    /// exactly once, before the chunk is searched. Note that the needle is matched against the
+    /// transformed bytes, so it should be normalized the same way.
+    ///
+    /// The transform must change bytes in place: since it works on a mutable slice, it can't
+    /// change the number of bytes, which keeps the reported offsets valid for the original stream.
+    /// It must also operate on each byte independently, because a chunk boundary can fall at any
+    /// position of the stream.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"Rusty rust");
+    ///     let finder = StreamFinder::new(b"RUST");
+    ///
+    ///     let mut iter = finder
+    ///         .find_iter_transformed(&mut stream, |buf| buf.make_ascii_uppercase());
+    ///     assert_eq!(iter.next().transpose()?, Some(0));
+    ///     assert_eq!(iter.next().transpose()?, Some(6));
+    ///     assert_eq!(iter.next().transpose()?, None);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_iter_transformed<'s, R, F>(
+        &'n self,
+        rdr: &'s mut R,
+        transform: F,
+    ) -> FindTransformedIter<'n, 's, R, F>
+    where
+        R: Read,
+        F: Fn(&mut [u8]),
+    {
+        FindTransformedIter {
+            rdr: Transformed { rdr, transform },
+            needle: self.needle(),
+            searcher: Searcher::new(self.needle().len()),
+        }
+    }
+
+    /// Returns an iterator over all occurrences of the given needle in the stream, which stops at
+    /// the first occurrence of `boundary`.
+    ///
+    /// This allows searching only the current section of a stream whose sections are delimited
+    /// by a marker, like the log of a single run. Only matches that end at or before the start of
+    /// the boundary are reported, and the stream is not read much further than the boundary. The
+    /// boundary is searched alongside the needle in the same buffer, so the stream is read only
+    /// once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust\n---\nrust");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let matches = finder
+    ///         .find_until(&mut stream, b"\n---\n")
+    ///         .collect::<io::Result<Vec<usize>>>()?;
+    ///     assert_eq!(matches, vec![0, 6]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_until<'b, 's, R: Read>(
+        &'n self,
+        rdr: &'s mut R,
+        boundary: &'b [u8],
+    ) -> FindUntilIter<'n, 'b, 's, R> {
+        let needle = self.needle();
+        let min = cmp::max(needle.len(), boundary.len());
+        // A match must end before a boundary that may start in the last `boundary.len() - 1`
+        // bytes, so it can't be reported before more bytes have been read.
+        let retain = cmp::max(1, needle.len() + boundary.len());
+        let capacity = cmp::max(retain * 8, DEFAULT_BUFFER_CAPACITY);
+        FindUntilIter {
+            rdr,
+            needle,
+            boundary,
+            buf: Buffer::with_retain(min, retain, capacity),
+            buf_offset: 0,
+            search_pos: 0,
+            boundary_pos: 0,
+            boundary_found: false,
+        }
+    }
+
+    /// Returns an iterator over all occurrences of the given needle in the stream, which also
+    /// copies every byte read from the stream to `wtr`.
+    ///
+    /// This allows scanning a stream while forwarding it, like `tee` does. Every byte is written
+    /// exactly once and in order, and the writer is flushed when the end of the stream is reached.
+    /// Errors from writing or flushing are yielded like errors from reading. Note that the stream is
+    /// only copied as far as it has been read, so the iterator must run to completion for the
+    /// whole stream to be copied.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust");
+    ///     let mut copy = Vec::new();
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let matches = finder
+    ///         .find_iter_tee(&mut stream, &mut copy)
+    ///         .collect::<io::Result<Vec<usize>>>()?;
+    ///     assert_eq!(matches, vec![0, 6]);
+    ///     assert_eq!(copy, b"rusty rust");
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_iter_tee<'s, R: Read, W: Write>(
+        &'n self,
+        rdr: &'s mut R,
+        wtr: &'s mut W,
+    ) -> FindTeeIter<'n, 's, R, W> {
+        FindTeeIter {
+            rdr: Tee { rdr, wtr },
+            needle: self.needle(),
+            searcher: Searcher::new(self.needle().len()),
+        }
+    }
+
+    /// Returns an iterator over all occurrences of the given needle in the stream, which also
+    /// reports whether the stream ends with a truncated occurrence of the needle.
+    ///
+    /// All full matches are yielded as [`Event::Match`]. If the stream ends with a proper prefix
+    /// of the needle that doesn't overlap the last full match, the final item is an
+    /// [`Event::PartialTail`] describing the longest such prefix. This is useful for protocol
+    /// scanners that need to know whether more data is expected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::{Event, StreamFinder};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"END. EN");
+    ///     let finder = StreamFinder::new(b"END.");
+    ///
+    ///     let mut iter = finder.find_iter_with_partial(&mut stream);
+    ///     assert_eq!(iter.next().transpose()?, Some(Event::Match(0)));
+    ///     assert_eq!(
+    ///         iter.next().transpose()?,
+    ///         Some(Event::PartialTail { offset: 5, matched_len: 2 })
+    ///     );
+    ///     assert_eq!(iter.next().transpose()?, None);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_iter_with_partial<'s, R: Read>(
+        &'n self,
+        rdr: &'s mut R,
+    ) -> FindPartialIter<'n, 's, R> {
+        FindPartialIter {
+            rdr,
+            needle: self.needle(),
+            searcher: Searcher::new(self.needle().len()),
+            match_end: 0,
+            done: false,
+        }
+    }
+
+    /// Returns an iterator over the number of matches in each consecutive `window`-sized span of
+    /// the stream.
+    ///
+    /// Each item is a `(window_start_offset, match_count)` pair, and every window of the stream is
+    /// reported, including the ones without matches. The last window may be shorter than `window`.
+    /// A match that spans a window boundary counts toward the window containing its start offset.
+    ///
+    /// This is useful for histograms or heatmaps of where a term appears in a large file.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rust rust   --   rust");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let density = finder
+    ///         .density_iter(&mut stream, 8)
+    ///         .collect::<io::Result<Vec<(usize, usize)>>>()?;
+    ///     assert_eq!(density, vec![(0, 2), (8, 0), (16, 1)]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn density_iter<'s, R: Read>(
+        &'n self,
+        rdr: &'s mut R,
+        window: usize,
+    ) -> DensityIter<'n, 's, R> {
+        assert!(window > 0, "window must be greater than 0");
+        DensityIter {
+            rdr,
+            needle: self.needle(),
+            searcher: Searcher::new(self.needle().len()),
+            window,
+            window_start: 0,
+            pending: None,
+        }
+    }
+
+    /// Returns an iterator over all occurrences of the given needle in the stream, together with
+    /// the `window` bytes of the stream starting at each match.
+    ///
+    /// Each item is a `(match_offset, bytes)` pair, where `bytes` holds exactly `window` bytes
+    /// (including the match itself), unless the stream ends earlier. This is useful for
+    /// inspecting e.g. the header following a magic number. The window may be larger than the
+    /// internal buffer: the missing bytes are read ahead and kept until the search reaches them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"\x7fELF\x02\x01 ... \x7fELF\x01");
+    ///     let finder = StreamFinder::new(b"\x7fELF");
+    ///
+    ///     let mut iter = finder.find_windows_iter(&mut stream, 6);
+    ///     assert_eq!(iter.next().transpose()?, Some((0, b"\x7fELF\x02\x01".to_vec())));
+    ///     assert_eq!(iter.next().transpose()?, Some((11, b"\x7fELF\x01".to_vec())));
+    ///     assert_eq!(iter.next().transpose()?, None);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_windows_iter<'s, R: Read>(
+        &'n self,
+        rdr: &'s mut R,
+        window: usize,
+    ) -> FindWindowsIter<'n, 's, R> {
+        FindWindowsIter {
+            rdr: ReadAhead { rdr, buf: Vec::new(), pos: 0 },
+            needle: self.needle(),
+            searcher: Searcher::new(self.needle().len()),
+            window,
+        }
+    }
+
+    /// Returns an iterator over all occurrences of the given needle in the stream, together with
+    /// the start of the record each match belongs to.
+    ///
+    /// Each item is a `(match_offset, record_start_offset)` pair, where `record_start_offset` is
+    /// the offset right after the last `record_delim` that ends at or before the match, or 0 if
+    /// there is none. This allows extracting the whole record (e.g. the line) containing a match.
+    ///
+    /// The record start is found by searching backward from each match, but never past the start
+    /// of the previous record, so every byte is searched for the delimiter at most once (plus
+    /// some overlap). The reader is seeked back to where the forward search left off afterwards.
+    /// Like [`find_iter`](StreamFinder::find_iter), offsets are relative to the position of the
+    /// stream when the iteration starts.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"ok\nsome error\nok\nerror");
+    ///     let finder = StreamFinder::new(b"error");
+    ///
+    ///     let mut iter = finder.find_with_record_start_iter(&mut stream, b"\n");
+    ///     assert_eq!(iter.next().transpose()?, Some((8, 3)));
+    ///     assert_eq!(iter.next().transpose()?, Some((17, 17)));
+    ///     assert_eq!(iter.next().transpose()?, None);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_with_record_start_iter<'s, R: Read + Seek>(
+        &'n self,
+        rdr: &'s mut R,
+        record_delim: &[u8],
+    ) -> FindRecordStartIter<'n, 's, R> {
+        FindRecordStartIter {
+            rdr,
+            needle: self.needle(),
+            record_delim: record_delim.to_vec(),
+            searcher: Searcher::new(self.needle().len()),
+            start_pos: None,
+            record_start: 0,
+            scanned_end: 0,
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Returns the byte range of the smallest `open..close` block that encloses the first
+    /// occurrence of the needle in the stream.
+    ///
+    /// Blocks may be nested: the block starts at the nearest `open` before the match that isn't
+    /// closed before the match, and ends after the matching `close` after the match. The range
+    /// includes both delimiters. Returns `Ok(None)` if the needle doesn't occur, or if no block
+    /// encloses it. Like [`find`](StreamFinder::find), offsets are relative to the position of the
+    /// stream when this is called, and the reader is left at an unspecified position afterwards.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `open` or `close` is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"{ a { needle } b }");
+    ///     let finder = StreamFinder::new(b"needle");
+    ///
+    ///     let block = finder.enclosing_block(&mut stream, b"{", b"}")?;
+    ///     assert_eq!(block, Some(4..14));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn enclosing_block<R: Read + Seek>(
+        &self,
+        rdr: &mut R,
+        open: &[u8],
+        close: &[u8],
+    ) -> io::Result<Option<Range<usize>>> {
+        assert!(
+            !open.is_empty() && !close.is_empty(),
+            "empty block delimiter"
+        );
+        let start_pos = rdr.stream_position()?;
+        let mat = match self.find(rdr).transpose()? {
+            Some(mat) => mat,
+            None => return Ok(None),
+        };
+
+        // Walk backward over the delimiters before the match, skipping balanced blocks.
+        let mut scratch = Vec::new();
+        let (mut last_open, mut last_close) = (None, None);
+        let mut end = start_pos + mat as u64;
+        let mut depth = 0;
+        let block_start = loop {
+            let open_pos = cached_rfind(
+                &mut last_open,
+                rdr,
+                open,
+                start_pos..end,
+                &mut scratch,
+            )?;
+            let close_pos = cached_rfind(
+                &mut last_close,
+                rdr,
+                close,
+                start_pos..end,
+                &mut scratch,
+            )?;
+            match (open_pos, close_pos) {
+                (None, _) => return Ok(None),
+                (Some(open_pos), Some(close_pos)) if close_pos > open_pos => {
+                    depth += 1;
+                    end = close_pos;
+                }
+                (Some(open_pos), _) if depth == 0 => break open_pos,
+                (Some(open_pos), _) => {
+                    depth -= 1;
+                    end = open_pos;
+                }
+            }
+        };
+
+        // Walk forward over the delimiters after the match, skipping balanced blocks.
+        let match_end = mat + self.needle().len();
+        rdr.seek(SeekFrom::Start(start_pos + match_end as u64))?;
+        let delims = StreamMultiFinder::new(&[open, close]);
+        let mut depth = 0;
+        for delim in delims.find_iter_leftmost_first(rdr) {
+            let delim = delim?;
+            if delim.needle_index == 0 {
+                depth += 1;
+            } else if depth > 0 {
+                depth -= 1;
+            } else {
+                let block_start = (block_start - start_pos) as usize;
+                let block_end = match_end + delim.offset + close.len();
+                return Ok(Some(block_start..block_end));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Estimates the number of matches in the stream by searching only about `sample_bytes` bytes
+    /// of it.
+    ///
+    /// The sample is split into a few windows spread evenly across the rest of the stream, and
+    /// the number of matches found in the windows is extrapolated to the full length. This is
+    /// only an estimate: it is accurate if the matches are distributed uniformly across the
+    /// stream, but can be far off otherwise, and matches that straddle the edge of a window are
+    /// not counted. If the rest of the stream is no longer than `sample_bytes`, all of it is
+    /// searched and the count is exact. The reader is left at an unspecified position.
+    ///
+    /// Every window is at least as long as the needle, so a `sample_bytes` shorter than the
+    /// needle is raised to its length. An empty needle is counted at every offset of a window,
+    /// but not at its end, which is the start of the rest of the stream.
+    ///
+    /// This is useful for sizing a progress bar before a full scan.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sample_bytes` is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust ".repeat(1000));
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let estimate = finder.estimate_count(&mut stream, 1000)?;
+    ///     assert!(1800 <= estimate && estimate <= 2200);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn estimate_count<R: Read + Seek>(
+        &self,
+        rdr: &mut R,
+        sample_bytes: usize,
+    ) -> io::Result<usize> {
+        assert!(sample_bytes > 0, "sample must not be empty");
+        let start_pos = rdr.stream_position()?;
+        let len = (seek_stream_len(rdr)? as u64).saturating_sub(start_pos);
+        if len <= sample_bytes as u64 {
+            rdr.seek(SeekFrom::Start(start_pos))?;
+            return self.find_iter(rdr).try_fold(0, |n, x| x.map(|_| n + 1));
+        }
+
+        let mut windows = ESTIMATE_WINDOWS;
+        if sample_bytes / windows < self.needle().len() {
+            windows = 1;
+        }
+        let window_len =
+            cmp::max(sample_bytes / windows, self.needle().len()) as u64;
+        let mut count: u64 = 0;
+        for i in 0..windows as u64 {
+            let window_start = start_pos + len * i / windows as u64;
+            rdr.seek(SeekFrom::Start(window_start))?;
+            let mut window = rdr.by_ref().take(window_len);
+            for mat in self.find_iter(&mut window) {
+                if (mat? as u64) < window_len {
+                    count += 1;
+                }
+            }
+        }
+
+        let sampled = window_len * windows as u64;
+        let estimate = count as u128 * len as u128 / sampled as u128;
+        // The estimate can't exceed the stream length, which fits in `usize`.
+        Ok(estimate as usize)
+    }
+
+    /// Returns the offset of the `n`-th (0-based) occurrence of the needle in the stream, together
+    /// with the bytes surrounding it.
+    ///
+    /// The returned bytes span from `before` bytes before the match to `after` bytes after the
+    /// match, including the match itself, and are cut off at the start and the end of the stream.
+    /// Once the match is found, the stream is sought back to read the context, so the stream is
+    /// scanned only once. Like [`find`](StreamFinder::find), offsets are relative to the position
+    /// of the stream when this is called. Returns `Ok(None)` if there are at most `n` matches.
+    ///
+    /// This is the core of a pager that shows e.g. the 5th match with some context.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust, trusty rust");
+    ///     let finder = StreamFinder::new(b"rust");
+    ///
+    ///     let context = finder.match_context(&mut stream, 2, 3, 1)?;
+    ///     assert_eq!(context, Some((13, b", trusty".to_vec())));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn match_context<R: Read + Seek>(
+        &self,
+        rdr: &mut R,
+        n: usize,
+        before: usize,
+        after: usize,
+    ) -> io::Result<Option<(usize, Vec<u8>)>> {
+        let start_pos = rdr.stream_position()?;
+        let mut matches = self.find_iter(rdr);
+        let mut mat = None;
+        for _ in 0..=n {
+            mat = matches.next().transpose()?;
+            if mat.is_none() {
+                return Ok(None);
+            }
+        }
+        let mat = mat.expect("at least one match has been found");
+
+        let context_start = mat.saturating_sub(before);
+        let context_len =
+            (mat - context_start + self.needle().len()).saturating_add(after);
+        rdr.seek(SeekFrom::Start(start_pos + context_start as u64))?;
+        let mut context = Vec::new();
+        rdr.take(context_len as u64).read_to_end(&mut context)?;
+        Ok(Some((mat, context)))
+    }
+
+    /// Reads the stream up to and including the first occurrence of the needle, and returns the
+    /// bytes preceding the match together with the offset of the match, if any.
+    ///
+    /// Unlike [`find`](StreamFinder::find), this never reads past the end of the match, so the
+    /// reader is left positioned right after the needle. This makes it a "take until delimiter"
+    /// primitive for tokenizers. To achieve this, reads are issued in chunks no larger than the
+    /// needle, so wrapping an unbuffered source in a [`BufReader`](std::io::BufReader) is
+    /// recommended.
+    ///
+    /// If the needle is absent, the whole stream is consumed (and buffered in memory), and the
+    /// remainder of the stream is returned with no offset, so no bytes are lost.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"key=value");
+    ///     let finder = StreamFinder::new(b"=");
+    ///
+    ///     let (key, pos) = finder.find_and_split(&mut stream)?;
+    ///     assert_eq!(key, b"key");
+    ///     assert_eq!(pos, Some(3));
+    ///
+    ///     let (value, pos) = finder.find_and_split(&mut stream)?;
+    ///     assert_eq!(value, b"value");
+    ///     assert_eq!(pos, None);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_and_split<R: Read>(
+        &self,
+        rdr: &mut R,
+    ) -> io::Result<(Vec<u8>, Option<usize>)> {
+        let needle = self.needle();
+        if needle.is_empty() {
+            return Ok((Vec::new(), Some(0)));
+        }
+
+        let table = prefix_table(needle);
+        let mut buf = Vec::new();
+        // The length of the longest prefix of the needle that is a suffix of `buf`.
+        let mut state = 0;
+        loop {
+            // The earliest possible match ends `needle.len() - state` bytes from here, so reading
+            // that many bytes never overshoots the end of the first match.
+            let start = buf.len();
+            let want = needle.len() - state;
+            buf.resize(start + want, 0);
+            let bytes_read = match rdr.read(&mut buf[start..]) {
+                Ok(bytes_read) => bytes_read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                    buf.truncate(start);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            buf.truncate(start + bytes_read);
+            if bytes_read == 0 {
+                return Ok((buf, None));
+            }
+
+            for &byte in &buf[start..] {
+                state = kmp_step(needle, &table, state, byte);
+            }
+            if state == needle.len() {
+                let pos = buf.len() - needle.len();
+                buf.truncate(pos);
+                return Ok((buf, Some(pos)));
+            }
+        }
+    }
+}
+
+/// A builder for configuring a [`StreamFinder`].
+///
+/// # Examples
+///
+/// ```
+/// use xfind::StreamFinderBuilder;
+///
+/// let finder = StreamFinderBuilder::new().max_needle_len(4).build(b"rust");
+/// assert!(finder.is_ok());
+///
+/// let finder = StreamFinderBuilder::new().max_needle_len(4).build(b"rusty");
+/// assert!(finder.is_err());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct StreamFinderBuilder {
+    /// The maximum length of a needle, if any.
+    max_needle_len: Option<usize>,
+    /// The capacity of the buffer of forward searches, if not the default.
+    buffer_capacity: Option<usize>,
+}
+
+impl StreamFinderBuilder {
+    /// Creates a new builder with the default configuration.
+    pub fn new() -> StreamFinderBuilder {
+        StreamFinderBuilder::default()
+    }
+
+    /// Builds a `StreamFinder` for the given needle.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SearchError::NeedleTooLong`] if the needle is longer than the configured
+    /// [`max_needle_len`](StreamFinderBuilder::max_needle_len).
+    pub fn build<'n>(
+        &self,
+        needle: &'n [u8],
+    ) -> Result<StreamFinder<'n>, SearchError> {
+        if let Some(max) = self.max_needle_len {
+            if needle.len() > max {
+                return Err(SearchError::NeedleTooLong {
+                    len: needle.len(),
+                    max,
+                });
+            }
+        }
+        let mut finder = StreamFinder::new(needle);
+        finder.buffer_capacity = self.buffer_capacity;
+        Ok(finder)
+    }
+
+    /// Sets the maximum length of needles accepted by [`build`](StreamFinderBuilder::build).
+    ///
+    /// Searchers allocate a buffer of at least `8 * needle.len()` bytes, so services that search
+    /// user-supplied needles should set a limit to guard against giant allocations. By default,
+    /// the length of needles is unbounded.
+    pub fn max_needle_len(&mut self, max: usize) -> &mut StreamFinderBuilder {
+        self.max_needle_len = Some(max);
+        self
+    }
+
+    /// Sets the capacity of the buffer that forward searches read the stream into, in bytes.
+    ///
+    /// By default, the buffer holds the larger of 8KB and `8 * needle.len()` bytes. A bigger
+    /// buffer means fewer reads and rolls, and a smaller buffer means less memory per search. A
+    /// buffer must hold at least 1 byte more than the needle to make progress between rolls, so
+    /// the capacity is raised to `needle.len() + 1` bytes if it's smaller than that, including a
+    /// capacity of 0. The needle includes its context here (see [`ContextFinder`]), and
+    /// [`StreamFinder::buffer_capacity`] returns the raised capacity. This applies to
+    /// the iterators created by [`StreamFinder::find_iter`] and the routines built on top of it,
+    /// but not to reverse searches.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::StreamFinderBuilder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let finder = StreamFinderBuilder::new().buffer_capacity(64 * 1024).build(b"rust")?;
+    ///
+    ///     let matches = finder.find_iter(&mut Cursor::new(b"rusty rust")).into_offsets()?;
+    ///     assert_eq!(matches, vec![0, 6]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn buffer_capacity(
+        &mut self,
+        capacity: usize,
+    ) -> &mut StreamFinderBuilder {
+        self.buffer_capacity = Some(capacity);
+        self
+    }
+}
+
+/// A forward iterator over all non-overlapping occurrences of a substring in a stream.
+///
+/// Matches are reported by the byte offset at which they begin.
+#[derive(Debug)]
+pub struct FindIter<'n, 's, R: Read, E = MemmemEngine> {
+    /// The stream source we read from.
+    rdr: &'s mut R,
+    /// The needle we search for.
+    needle: &'n [u8],
+    /// The needle surrounded by its required context, if any.
+    pattern: Option<Vec<u8>>,
+    /// The length of the context that precedes the needle in `pattern`.
+    prefix_len: usize,
+    /// The bytes that must not immediately follow a match, if any.
+    not_suffix: &'n [u8],
+    /// The offset of a match that awaits the check against `not_suffix`, and the offset right
+    /// after its needle, if any.
+    pending: Option<(usize, usize)>,
+    /// The state of the search.
+    searcher: Searcher,
+    /// The length of the stream, if known.
+    stream_len: Option<usize>,
+    /// The engine that searches the buffer.
+    engine: E,
+    /// The checksum of the bytes that have been dropped from the buffer so far, if enabled with
+    /// `with_crc`.
+    #[cfg(feature = "crc")]
+    crc: Option<crc32fast::Hasher>,
+}
+
+/// The state of a forward stream search.
+///
+/// This is decoupled from the reader, so that it can back different forward iterators.
+#[derive(Debug)]
+pub(crate) struct Searcher {
+    /// A fixed size buffer that we actually search for. It must be big enough to hold the needle.
+    buf: Buffer,
+    /// The current position at which to start the next search in `self.buf`.
+    search_pos: usize,
+    /// The absolute position of `search_pos` in the stream.
     stream_pos: usize,
-    /// The position we report to the caller.
-    report_pos: usize,
 }
 
-/// A backward iterator over all non-overlapping occurrences of a substring in a stream.
-///
-/// Matches are reported by the byte offset at which they begin.
-#[derive(Debug)]
-pub struct FindRevIter<'n, 's, R: Read + Seek> {
-    /// The stream source we read from.
-    rdr: &'s mut R,
-    /// The needle we search for.
-    needle: &'n [u8],
-    /// A fixed size buffer that we actually search for. It must be big enough to hold the needle.
-    buf: BufferRev,
-    /// The current position at which to start the next search in `self.buf`.
-    search_pos: usize,
-    /// The absolute position of `search_pos` in the stream.
-    stream_pos: usize,
-    /// The position we report to the caller.
-    report_pos: usize,
-    /// The current seek position.
-    seek_pos: usize,
-    /// The length of the stream.
-    stream_len: usize,
-}
+/// The result of polling a [`FindIter`] with [`try_next`](FindIter::try_next).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PollMatch {
+    /// An occurrence of the needle starting at the given offset.
+    Match(usize),
+    /// The reader has no data available right now, and would block.
+    Pending,
+}
+
+/// Metadata about the bytes of a stream, which is collected by
+/// [`StreamFinder::scan_metadata`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StreamMeta {
+    /// The number of `\r\n` line endings.
+    pub crlf_count: usize,
+    /// The number of `\n` line endings that aren't preceded by `\r`.
+    pub lf_count: usize,
+    /// Whether the stream starts with a UTF-8 byte order mark (`EF BB BF`).
+    pub has_bom: bool,
+    /// The length of the stream in bytes.
+    pub byte_count: usize,
+}
+
+/// Collects a [`StreamMeta`] from the bytes of a stream, which are passed in order, in chunks of
+/// any size.
+#[derive(Debug, Default)]
+struct MetaScanner {
+    /// The metadata collected so far.
+    meta: StreamMeta,
+    /// The last byte passed so far, if any.
+    last: Option<u8>,
+    /// The number of bytes of the byte order mark that the stream starts with.
+    bom_len: usize,
+}
+
+impl MetaScanner {
+    /// The UTF-8 byte order mark.
+    const BOM: [u8; 3] = [0xEF, 0xBB, 0xBF];
+
+    fn update(&mut self, bytes: &[u8]) {
+        let seen = self.meta.byte_count;
+        for (at, &b) in
+            bytes.iter().enumerate().take(3usize.saturating_sub(seen))
+        {
+            if self.bom_len == seen + at && b == MetaScanner::BOM[seen + at] {
+                self.bom_len += 1;
+            }
+        }
+        for pos in memchr::memchr_iter(b'\n', bytes) {
+            let prev =
+                pos.checked_sub(1).map_or(self.last, |i| Some(bytes[i]));
+            if prev == Some(b'\r') {
+                self.meta.crlf_count += 1;
+            } else {
+                self.meta.lf_count += 1;
+            }
+        }
+        if let Some(&b) = bytes.last() {
+            self.last = Some(b);
+        }
+        self.meta.byte_count += bytes.len();
+    }
+
+    fn finish(mut self) -> StreamMeta {
+        self.meta.has_bom = self.bom_len == MetaScanner::BOM.len();
+        self.meta
+    }
+}
+
+/// An item yielded by [`FindPartialIter`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// A full occurrence of the needle starting at the given offset.
+    Match(usize),
+    /// The stream ends with the first `matched_len` bytes of the needle, starting at `offset`.
+    PartialTail {
+        /// The offset at which the truncated needle begins.
+        offset: usize,
+        /// The number of bytes of the needle present at the end of the stream.
+        matched_len: usize,
+    },
+}
+
+/// A forward iterator over all non-overlapping occurrences of a substring in a stream, which
+/// reports a truncated occurrence at the end of the stream.
+///
+/// This iterator is created by [`StreamFinder::find_iter_with_partial`].
+#[derive(Debug)]
+pub struct FindPartialIter<'n, 's, R: Read> {
+    /// The stream source we read from.
+    rdr: &'s mut R,
+    /// The needle we search for.
+    needle: &'n [u8],
+    /// The state of the search.
+    searcher: Searcher,
+    /// The end of the last full match, which a partial match must not overlap.
+    match_end: usize,
+    /// Whether the end of the stream has been handled.
+    done: bool,
+}
+
+/// An iterator over the number of matches in consecutive fixed-size windows of a stream.
+///
+/// This iterator is created by [`StreamFinder::density_iter`].
+#[derive(Debug)]
+pub struct DensityIter<'n, 's, R: Read> {
+    /// The stream source we read from.
+    rdr: &'s mut R,
+    /// The needle we search for.
+    needle: &'n [u8],
+    /// The state of the search.
+    searcher: Searcher,
+    /// The size of each window.
+    window: usize,
+    /// The offset of the next window to report.
+    window_start: usize,
+    /// A match that has been found, but belongs to a later window.
+    pending: Option<usize>,
+}
+
+/// A forward iterator over all non-overlapping occurrences of a substring in a stream, together
+/// with a fixed-size window of bytes starting at each match.
+///
+/// This iterator is created by [`StreamFinder::find_windows_iter`].
+#[derive(Debug)]
+pub struct FindWindowsIter<'n, 's, R: Read> {
+    /// The stream source we read from, which replays the bytes read ahead for windows.
+    rdr: ReadAhead<&'s mut R>,
+    /// The needle we search for.
+    needle: &'n [u8],
+    /// The state of the search.
+    searcher: Searcher,
+    /// The number of bytes to report for each match.
+    window: usize,
+}
+
+/// A forward iterator over all non-overlapping occurrences of a substring in a stream, together
+/// with the bytes right before and after each match.
+///
+/// This iterator is created by [`FindIter::with_neighbors`].
+#[derive(Debug)]
+pub struct FindNeighborsIter<'n, 's, R: Read, E = MemmemEngine> {
+    /// The stream source we read from, which replays the bytes read ahead for the byte after a
+    /// match.
+    rdr: ReadAhead<&'s mut R>,
+    /// The needle we search for.
+    needle: &'n [u8],
+    /// The needle together with the required prefix and suffix, if any.
+    pattern: Option<Vec<u8>>,
+    /// The length of the required prefix.
+    prefix_len: usize,
+    /// The bytes that must not immediately follow a match, if any.
+    not_suffix: &'n [u8],
+    /// The offset of a match that awaits the check against `not_suffix`, and the offset right
+    /// after its needle, if any.
+    pending: Option<(usize, usize)>,
+    /// The state of the search.
+    searcher: Searcher,
+    /// The engine that searches the buffer.
+    engine: E,
+    /// The last byte that has been dropped from the buffer, if any.
+    last_dropped: Option<u8>,
+}
+
+/// A reader that allows peeking at the bytes following the bytes read so far.
+#[derive(Debug)]
+struct ReadAhead<R> {
+    rdr: R,
+    /// Bytes that have been read from `rdr` ahead of time.
+    buf: Vec<u8>,
+    /// The start of the bytes in `buf` that haven't been passed on yet.
+    pos: usize,
+}
+
+/// A forward iterator over all non-overlapping occurrences of a substring in a stream, which also
+/// reports the start of the record containing each match.
+///
+/// This iterator is created by [`StreamFinder::find_with_record_start_iter`].
+#[derive(Debug)]
+pub struct FindRecordStartIter<'n, 's, R: Read + Seek> {
+    /// The stream source we read from.
+    rdr: &'s mut R,
+    /// The needle we search for.
+    needle: &'n [u8],
+    /// The delimiter that separates records.
+    record_delim: Vec<u8>,
+    /// The state of the forward search.
+    searcher: Searcher,
+    /// The absolute position of the stream when the iteration started, once known.
+    start_pos: Option<u64>,
+    /// The start of the record of the last match.
+    record_start: usize,
+    /// The end of the range that has been searched for delimiters already.
+    scanned_end: usize,
+    /// A buffer for the backward search for delimiters.
+    scratch: Vec<u8>,
+}
+
+/// A forward iterator over all non-overlapping occurrences of a substring in a stream, which
+/// stops at the first occurrence of a boundary.
+///
+/// This iterator is created by [`StreamFinder::find_until`].
+#[derive(Debug)]
+pub struct FindUntilIter<'n, 'b, 's, R: Read> {
+    /// The stream source we read from.
+    rdr: &'s mut R,
+    /// The needle we search for.
+    needle: &'n [u8],
+    /// The boundary at which the search stops.
+    boundary: &'b [u8],
+    /// A fixed size buffer that we actually search for. It must be big enough to hold both the
+    /// needle and the boundary.
+    buf: Buffer,
+    /// The absolute position of the start of `self.buf` in the stream.
+    buf_offset: usize,
+    /// The current position at which to start the next search for the needle in `self.buf`.
+    search_pos: usize,
+    /// The position of the boundary in `self.buf` if it has been found, or else the position at
+    /// which to start the next search for it.
+    boundary_pos: usize,
+    /// Whether the boundary has been found.
+    boundary_found: bool,
+}
+
+/// A forward iterator over all non-overlapping occurrences of a substring in a stream, which
+/// copies the stream to a writer.
+///
+/// This iterator is created by [`StreamFinder::find_iter_tee`].
+#[derive(Debug)]
+pub struct FindTeeIter<'n, 's, R: Read, W: Write> {
+    /// The stream source we read from, which copies the bytes read to the writer.
+    rdr: Tee<&'s mut R, &'s mut W>,
+    /// The needle we search for.
+    needle: &'n [u8],
+    /// The state of the search.
+    searcher: Searcher,
+}
+
+/// A reader that writes every chunk it reads to a writer.
+#[derive(Debug)]
+struct Tee<R, W> {
+    rdr: R,
+    wtr: W,
+}
+
+/// A forward iterator over all non-overlapping occurrences of a substring in a transformed
+/// stream.
+///
+/// This iterator is created by [`StreamFinder::find_iter_transformed`].
+pub struct FindTransformedIter<'n, 's, R: Read, F> {
+    /// The stream source we read from, which transforms the bytes read.
+    rdr: Transformed<&'s mut R, F>,
+    /// The needle we search for.
+    needle: &'n [u8],
+    /// The state of the search.
+    searcher: Searcher,
+}
+
+/// A reader that applies a same-length transform to every chunk it reads.
+struct Transformed<R, F> {
+    rdr: R,
+    transform: F,
+}
+
+/// A forward iterator over all non-overlapping occurrences of a substring in a file, which owns
+/// the file.
+///
+/// This iterator is created by [`find_iter_in_file`].
+#[derive(Debug)]
+pub struct FindInFileIter<'n> {
+    /// The file we read from.
+    file: File,
+    /// The needle we search for.
+    needle: &'n [u8],
+    /// The state of the search.
+    searcher: Searcher,
+}
+
+/// A forward iterator over the occurrences of a substring in a stream whose offsets satisfy a
+/// predicate.
+///
+/// This iterator is created by [`FindIter::filter_offsets`].
+pub struct FilterOffsets<'n, 's, R: Read, P, E = MemmemEngine> {
+    /// The iterator over all matches.
+    iter: FindIter<'n, 's, R, E>,
+    /// The predicate that the offsets of the yielded matches satisfy.
+    pred: P,
+}
+
+/// A forward iterator over all non-overlapping occurrences of a substring in a stream, which
+/// yields the matched bytes along with their offsets.
+///
+/// This iterator is created by [`FindIter::match_slices`].
+#[derive(Debug)]
+pub struct MatchSlices<'n, 's, R: Read, E = MemmemEngine> {
+    /// The iterator over the offsets of the matches.
+    iter: FindIter<'n, 's, R, E>,
+}
+
+/// A forward iterator over all non-overlapping occurrences of a substring in a stream, which
+/// yields the time it took to find each match along with its offset.
+///
+/// This iterator is created by [`FindIter::timed`].
+#[derive(Debug)]
+pub struct Timed<'n, 's, R: Read, E = MemmemEngine> {
+    /// The iterator over the offsets of the matches.
+    iter: FindIter<'n, 's, R, E>,
+    /// The time at which the previous match was found, or at which the iterator was created.
+    last: Instant,
+}
+
+/// A forward iterator over the distances between the starts of consecutive non-overlapping
+/// occurrences of a substring in a stream.
+///
+/// This iterator is created by [`FindIter::deltas`].
+#[derive(Debug)]
+pub struct Deltas<'n, 's, R: Read, E = MemmemEngine> {
+    /// The iterator over the offsets of the matches.
+    iter: FindIter<'n, 's, R, E>,
+    /// The offset of the previous match, or 0 before the first match.
+    last: usize,
+}
+
+/// A forward iterator over all non-overlapping occurrences of a substring in a stream, which
+/// yields the number of matches found so far along with each offset.
+///
+/// This iterator is created by [`FindIter::running_count`].
+#[derive(Debug)]
+pub struct RunningCount<'n, 's, R: Read, E = MemmemEngine> {
+    /// The iterator over the offsets of the matches.
+    iter: FindIter<'n, 's, R, E>,
+    /// The number of matches found so far.
+    count: usize,
+}
+
+/// A forward iterator over every `step`-th non-overlapping occurrence of a substring in a
+/// stream.
+///
+/// This iterator is created by [`FindIter::step_by_matches`].
+#[derive(Debug)]
+pub struct StepByMatches<'n, 's, R: Read, E = MemmemEngine> {
+    /// The iterator over the offsets of the matches.
+    iter: FindIter<'n, 's, R, E>,
+    /// The distance between the matches that are yielded.
+    step: usize,
+    /// The number of matches to skip before the next one is yielded.
+    skip: usize,
+}
+
+/// A forward iterator over all non-overlapping occurrences of a substring in a stream, which
+/// yields the distances of each match to the start and the end of the stream.
+///
+/// This iterator is created by [`FindIter::with_boundary_distance`].
+#[derive(Debug)]
+pub struct BoundaryDistance<'n, 's, R: Read, E = MemmemEngine> {
+    /// The iterator over the offsets of the matches.
+    iter: FindIter<'n, 's, R, E>,
+    /// The length of the stream.
+    stream_len: usize,
+}
+
+/// A backward iterator over all non-overlapping occurrences of a substring in a stream.
+///
+/// Matches are reported by the byte offset at which they begin.
+#[derive(Debug)]
+pub struct FindRevIter<'n, 's, R: Read + Seek, E = MemmemEngine> {
+    /// The stream source we read from.
+    rdr: &'s mut R,
+    /// The needle we search for.
+    needle: &'n [u8],
+    /// A fixed size buffer that we actually search for. It must be big enough to hold the needle.
+    buf: BufferRev,
+    /// The end (exclusive) of the contents in `self.buf` that have not been searched yet.
+    search_end: usize,
+    /// Whether an empty needle has been reported at `search_end`, which mustn't be reported again.
+    end_reported: bool,
+    /// The current seek position, which is also the absolute position of the start of
+    /// `self.buf` in the stream.
+    seek_pos: usize,
+    /// The length of the stream.
+    stream_len: usize,
+    /// The engine that searches the buffer.
+    engine: E,
+}
+
+/// The saved progress of a [`FindRevIter`].
+///
+/// A checkpoint is created by [`FindRevIter::save_position`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RevCheckpoint {
+    /// The end of the part of the stream that hasn't been searched yet.
+    end: usize,
+    /// Whether an empty needle has been reported at `end`.
+    end_reported: bool,
+    /// The length of the stream.
+    stream_len: usize,
+}
+
+/// A backward iterator over all non-overlapping occurrences of a substring in a stream, which
+/// reports matches by their distance from the end of the stream.
+///
+/// This iterator is created by [`StreamFinder::rfind_iter_from_end`].
+#[derive(Debug)]
+pub struct FindFromEndIter<'n, 's, R: Read + Seek> {
+    /// The iterator over the absolute offsets of the matches.
+    iter: FindRevIter<'n, 's, R>,
+}
+
+impl<'n, 's, R: Read> FindIter<'n, 's, R> {
+    pub(crate) fn new(rdr: &'s mut R, fdr: &'n StreamFinder<'n>) -> Self {
+        FindIter::with_offset(rdr, fdr, 0)
+    }
+
+    pub(crate) fn with_offset(
+        rdr: &'s mut R,
+        fdr: &'n StreamFinder<'n>,
+        offset: usize,
+    ) -> Self {
+        FindIter::with_parts(rdr, fdr, offset, None)
+    }
+
+    pub(crate) fn with_buffer(
+        rdr: &'s mut R,
+        fdr: &'n StreamFinder<'n>,
+        buffer: Buffer,
+    ) -> Self {
+        FindIter::with_parts(rdr, fdr, 0, Some(buffer))
+    }
+
+    fn with_parts(
+        rdr: &'s mut R,
+        fdr: &'n StreamFinder<'n>,
+        offset: usize,
+        buffer: Option<Buffer>,
+    ) -> Self {
+        let pattern = if fdr.prefix.is_empty() && fdr.suffix.is_empty() {
+            None
+        } else {
+            Some([fdr.prefix, fdr.needle(), fdr.suffix].concat())
+        };
+        let pattern_len = fdr.pattern_len();
+        FindIter {
+            rdr,
+            needle: fdr.needle(),
+            pattern,
+            prefix_len: fdr.prefix.len(),
+            not_suffix: fdr.not_suffix,
+            pending: None,
+            searcher: match (buffer, fdr.buffer_capacity) {
+                // A caller-provided buffer is reset, and must hold the pattern.
+                (Some(buffer), _) => {
+                    let min = cmp::max(buffer.min_buffer_len(), pattern_len);
+                    Searcher::with_buffer(
+                        Buffer::from_vec(buffer.into_vec(), min),
+                        offset,
+                    )
+                }
+                (None, Some(capacity)) => Searcher::with_buffer(
+                    Buffer::with_capacity(pattern_len, capacity),
+                    offset,
+                ),
+                (None, None) => Searcher::with_offset(pattern_len, offset),
+            },
+            stream_len: None,
+            engine: MemmemEngine,
+            #[cfg(feature = "crc")]
+            crc: None,
+        }
+    }
+
+    pub(crate) fn new_with_needle(rdr: &'s mut R, needle: &'n [u8]) -> Self {
+        FindIter {
+            rdr,
+            needle,
+            pattern: None,
+            prefix_len: 0,
+            not_suffix: &[],
+            pending: None,
+            searcher: Searcher::new(needle.len()),
+            stream_len: None,
+            engine: MemmemEngine,
+            #[cfg(feature = "crc")]
+            crc: None,
+        }
+    }
+}
+
+impl<'n, 's, R: Read, E: MatchEngine> FindIter<'n, 's, R, E> {
+    /// Replaces the engine that searches the buffer.
+    pub(crate) fn with_engine<F: MatchEngine>(
+        self,
+        engine: F,
+    ) -> FindIter<'n, 's, R, F> {
+        FindIter {
+            rdr: self.rdr,
+            needle: self.needle,
+            pattern: self.pattern,
+            prefix_len: self.prefix_len,
+            not_suffix: self.not_suffix,
+            pending: self.pending,
+            searcher: self.searcher,
+            stream_len: self.stream_len,
+            engine,
+            #[cfg(feature = "crc")]
+            crc: self.crc,
+        }
+    }
+
+    /// Makes the iterator keep reading until at least `min_chunk` bytes have been read (or its
+    /// buffer is full, or the stream ends), before it searches the bytes read.
+    ///
+    /// Some readers, like serial ports and TTYs, return only a byte or so per read. Such reads are
+    /// always handled correctly, but by default every read is followed by a search, which adds up
+    /// for tiny reads. Coalescing them reduces the number of searches. The downside is that a
+    /// match is only reported once the chunk it is in has been read, which may delay matches on
+    /// interactive streams.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust");
+    ///
+    ///     let offsets = xfind::find_iter(b"rust", &mut stream)
+    ///         .coalesce_reads(4096)
+    ///         .into_offsets()?;
+    ///     assert_eq!(offsets, vec![0, 6]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn coalesce_reads(mut self, min_chunk: usize) -> Self {
+        self.searcher.buf.coalesce_reads(min_chunk);
+        self
+    }
+
+    /// Makes the iterator yield an error if the stream returns more data after a read returned
+    /// zero bytes.
+    ///
+    /// By [`Read`]'s contract, a read that returns zero bytes signals EOF, so by default the
+    /// iterator stops at the first such read. For some readers, a zero-length read in the middle
+    /// of the stream indicates a bug, and stopping silently would hide the rest of the data. In
+    /// strict mode, every zero-length read is confirmed by another read. If that read returns
+    /// data, the iterator yields an error of kind [`UnexpectedEof`](io::ErrorKind::UnexpectedEof)
+    /// wrapping [`SearchError::DataAfterZeroRead`]. The data isn't lost: calling `next` again
+    /// resumes the search with it.
+    ///
+    /// This check is only a heuristic. A reader that returns zero bytes several times in a row
+    /// before returning data again is still considered finished. Also, the confirming read costs
+    /// an extra read at the actual end of the stream; on a terminal, it waits for a second EOF.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust");
+    ///
+    ///     let offsets = xfind::find_iter(b"rust", &mut stream)
+    ///         .error_on_zero_read(true)
+    ///         .into_offsets()?;
+    ///     assert_eq!(offsets, vec![0, 6]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn error_on_zero_read(mut self, strict: bool) -> Self {
+        self.searcher.buf.error_on_zero_read(strict);
+        self
+    }
+
+    /// Consumes the iterator and collects the offsets of all remaining matches, stopping at the
+    /// first I/O error.
+    ///
+    /// If the length of the stream is known (see [`with_stream_len`](FindIter::with_stream_len)),
+    /// the returned `Vec` is allocated upfront with room for the maximum number of matches, up to
+    /// 1024 offsets, which avoids reallocations while collecting a few matches. Beyond that, or
+    /// if the length is unknown, the `Vec` grows as needed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust");
+    ///
+    ///     let offsets = xfind::find_iter(b"rust", &mut stream).into_offsets()?;
+    ///     assert_eq!(offsets, vec![0, 6]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn into_offsets(self) -> io::Result<Vec<usize>> {
+        let upper = self.size_hint().1.unwrap_or(0);
+        let mut offsets =
+            Vec::with_capacity(cmp::min(upper, MAX_RESERVED_OFFSETS));
+        for pos in self {
+            offsets.push(pos?);
+        }
+        Ok(offsets)
+    }
+
+    /// Consumes the iterator and collects the offsets of all remaining matches into a sorted set,
+    /// stopping at the first I/O error.
+    ///
+    /// This is handy for membership and range queries, e.g. whether there is a match in
+    /// `1000..2000`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust");
+    ///
+    ///     let offsets = xfind::find_iter(b"rust", &mut stream).into_set()?;
+    ///     assert!(offsets.contains(&6));
+    ///     assert_eq!(offsets.range(1..6).next(), None);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn into_set(self) -> io::Result<BTreeSet<usize>> {
+        self.collect()
+    }
+
+    /// Returns an iterator that only yields the matches whose offset satisfies `pred`.
+    ///
+    /// Unlike [`Iterator::filter`], `pred` is only called with the offsets of matches, and I/O
+    /// errors are always passed through, regardless of the predicate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust rust");
+    ///
+    ///     let offsets = xfind::find_iter(b"rust", &mut stream)
+    ///         .filter_offsets(|pos| pos % 2 == 0)
+    ///         .collect::<io::Result<Vec<_>>>()?;
+    ///     assert_eq!(offsets, vec![0, 6]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn filter_offsets<P>(self, pred: P) -> FilterOffsets<'n, 's, R, P, E>
+    where
+        P: FnMut(usize) -> bool,
+    {
+        FilterOffsets { iter: self, pred }
+    }
+
+    /// Returns an iterator that yields the bytes of each match along with its offset.
+    ///
+    /// The slices can't borrow from the internal buffer, since it's overwritten as the stream is
+    /// read further, while a yielded item may outlive the next call to `next`. They don't need to,
+    /// though: the bytes of an exact match are the needle itself, so every slice is borrowed from
+    /// the needle and no bytes are copied. Use [`StreamFinder::for_each_match`] to look at the
+    /// matches in the buffer with a callback instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust");
+    ///
+    ///     let mut iter = xfind::find_iter(b"rust", &mut stream).match_slices();
+    ///     assert_eq!(iter.next().transpose()?, Some((0, &b"rust"[..])));
+    ///     assert_eq!(iter.next().transpose()?.map(|(pos, _)| pos), Some(6));
+    ///     assert!(iter.next().is_none());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn match_slices(self) -> MatchSlices<'n, 's, R, E> {
+        MatchSlices { iter: self }
+    }
+
+    /// Returns the next match like [`next`](Iterator::next), but reports a read that fails with
+    /// [`WouldBlock`](io::ErrorKind::WouldBlock) as [`PollMatch::Pending`] instead of an error.
+    ///
+    /// This allows driving a search over a nonblocking reader, e.g. a socket, from a custom event
+    /// loop without async machinery: call `try_next` whenever the reader becomes readable, until
+    /// it returns `Pending` again. The bytes read so far stay buffered across `Pending` returns,
+    /// so no match is lost or reported twice. `Ok(None)` marks the end of the stream.
+    ///
+    /// # Errors
+    ///
+    /// Returns any other I/O error from reading the stream.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Read};
+    /// use xfind::PollMatch;
+    ///
+    /// /// A reader that would block between its chunks, like a nonblocking socket.
+    /// struct Chunks(Vec<Option<&'static [u8]>>);
+    ///
+    /// impl Read for Chunks {
+    ///     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    ///         match self.0.pop() {
+    ///             Some(None) => Err(io::ErrorKind::WouldBlock.into()),
+    ///             Some(Some(chunk)) => (&chunk[..]).read(buf),
+    ///             None => Ok(0),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Chunks(vec![Some(b"st"), None, Some(b"rusty ru")]);
+    ///     let mut iter = xfind::find_iter(b"rust", &mut stream);
+    ///
+    ///     assert_eq!(iter.try_next()?, Some(PollMatch::Match(0)));
+    ///     assert_eq!(iter.try_next()?, Some(PollMatch::Pending));
+    ///     assert_eq!(iter.try_next()?, Some(PollMatch::Match(6)));
+    ///     assert_eq!(iter.try_next()?, None);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn try_next(&mut self) -> io::Result<Option<PollMatch>> {
+        match self.next() {
+            Some(Ok(pos)) => Ok(Some(PollMatch::Match(pos))),
+            Some(Err(ref err)) if err.kind() == io::ErrorKind::WouldBlock => {
+                Ok(Some(PollMatch::Pending))
+            }
+            Some(Err(err)) => Err(err),
+            None => Ok(None),
+        }
+    }
+
+    /// Replaces the needle, so that the rest of the stream is searched for another needle.
+    ///
+    /// The search for `needle` continues where the search for the previous needle stopped, i.e.
+    /// right after the last match if [`next`](Iterator::next) just returned one. Any context of
+    /// the finder (see [`ContextFinder`]) applies to the new needle as well.
+    ///
+    /// Finding matches that span a roll of the buffer requires the buffer to hold at least a
+    /// whole needle and its context. So if `needle` is longer than the previous one, the minimum
+    /// length of the buffer is raised, and its capacity grows if needed. This also applies to
+    /// buffers passed to [`StreamFinder::find_iter_with_buffer`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"HEADER rust BODY rusty rust");
+    ///     let mut iter = xfind::find_iter(b"BODY", &mut stream);
+    ///     assert_eq!(iter.next().transpose()?, Some(12));
+    ///
+    ///     iter.set_needle(b"rust");
+    ///     assert_eq!(iter.into_offsets()?, vec![17, 23]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn set_needle(&mut self, needle: &'n [u8]) {
+        if let Some(ref mut pattern) = self.pattern {
+            let end = self.prefix_len + self.needle.len();
+            pattern.splice(self.prefix_len..end, needle.iter().copied());
+        }
+        self.needle = needle;
+        let pattern_len = self.pattern.as_ref().map_or(needle.len(), Vec::len)
+            + self.not_suffix.len();
+        self.searcher.set_pattern_len(pattern_len);
+    }
+
+    /// Consumes the iterator and returns its buffer, e.g. to recover an allocation passed to
+    /// [`StreamFinder::find_iter_with_buffer`] with [`Buffer::into_vec`].
+    pub fn into_buffer(self) -> Buffer {
+        self.searcher.into_buffer()
+    }
+
+    /// Returns an iterator that also yields the time elapsed since the previous match, or since
+    /// this call for the first match.
+    ///
+    /// This helps profiling slow streams, e.g. to see where a network stream stalls. The clock is
+    /// only read by the returned iterator, so iterators that aren't timed don't pay for it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust");
+    ///
+    ///     let start = Instant::now();
+    ///     let matches = xfind::find_iter(b"rust", &mut stream)
+    ///         .timed()
+    ///         .collect::<io::Result<Vec<_>>>()?;
+    ///     assert_eq!(matches.iter().map(|&(pos, _)| pos).collect::<Vec<_>>(), vec![0, 6]);
+    ///     let total: Duration = matches.iter().map(|&(_, elapsed)| elapsed).sum();
+    ///     assert!(total <= start.elapsed());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn timed(self) -> Timed<'n, 's, R, E> {
+        Timed { iter: self, last: Instant::now() }
+    }
+
+    /// Returns an iterator over the distances between the starts of consecutive matches, where
+    /// the first item is the offset of the first match.
+    ///
+    /// This is meant for delta-encoding the offsets of the matches for compact storage. Summing up
+    /// the items up to any point yields the offset of the corresponding match.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust, rustacean");
+    ///
+    ///     let deltas = xfind::find_iter(b"rust", &mut stream).deltas();
+    ///     assert_eq!(deltas.collect::<io::Result<Vec<_>>>()?, vec![0, 6, 6]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn deltas(self) -> Deltas<'n, 's, R, E> {
+        Deltas { iter: self, last: 0 }
+    }
+
+    /// Returns an iterator that also yields the number of matches found so far, including the
+    /// current one.
+    ///
+    /// This feeds live displays like "found 42 so far" without a separate counter. Only matches
+    /// are counted, so the count stays the same across errors if the search resumes afterwards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust, rustacean");
+    ///
+    ///     let matches = xfind::find_iter(b"rust", &mut stream).running_count();
+    ///     assert_eq!(matches.collect::<io::Result<Vec<_>>>()?, vec![(0, 1), (6, 2), (12, 3)]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn running_count(self) -> RunningCount<'n, 's, R, E> {
+        RunningCount { iter: self, count: 0 }
+    }
+
+    /// Returns an iterator over every `step`-th match, starting with the first one.
+    ///
+    /// This yields the 1st, the `(1 + step)`-th, the `(1 + 2 * step)`-th match and so on, e.g. for
+    /// sampling or paginating matches. Unlike [`Iterator::step_by`], errors aren't counted as
+    /// items: each error is yielded as soon as it occurs, and doesn't change which matches are
+    /// yielded if the search resumes afterwards.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `step` is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"a a a a a a a");
+    ///
+    ///     let matches = xfind::find_iter(b"a", &mut stream).step_by_matches(3);
+    ///     assert_eq!(matches.collect::<io::Result<Vec<_>>>()?, vec![0, 6, 12]);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn step_by_matches(self, step: usize) -> StepByMatches<'n, 's, R, E> {
+        assert!(step != 0, "step must not be 0");
+        StepByMatches { iter: self, step, skip: 0 }
+    }
+
+    /// Returns an iterator that also yields the bytes right before and right after each match.
+    ///
+    /// Each item is a `(byte_before, offset, byte_after)` triple, where `byte_before` is `None`
+    /// for a match at the start of the stream, and `byte_after` is `None` for a match at its end.
+    /// This is a lighter alternative to [`StreamFinder::find_windows_iter`] for lookbehind and
+    /// lookahead, e.g. to check word boundaries. To know the byte after a match, the stream is
+    /// read one byte past it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust");
+    ///
+    ///     let mut iter = xfind::find_iter(b"rust", &mut stream).with_neighbors();
+    ///     assert_eq!(iter.next().transpose()?, Some((None, 0, Some(b'y'))));
+    ///     assert_eq!(iter.next().transpose()?, Some((Some(b' '), 6, None)));
+    ///     assert_eq!(iter.next().transpose()?, None);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_neighbors(self) -> FindNeighborsIter<'n, 's, R, E> {
+        FindNeighborsIter {
+            rdr: ReadAhead { rdr: self.rdr, buf: Vec::new(), pos: 0 },
+            needle: self.needle,
+            pattern: self.pattern,
+            prefix_len: self.prefix_len,
+            not_suffix: self.not_suffix,
+            pending: self.pending,
+            searcher: self.searcher,
+            engine: self.engine,
+            last_dropped: None,
+        }
+    }
+
+    /// Makes the iterator compute the CRC32 checksum of every byte it reads, which is returned
+    /// by [`finish_crc`](FindIter::finish_crc).
+    ///
+    /// Iterators that aren't created with this don't checksum anything. This method is only
+    /// available if the `crc` feature is enabled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the iterator has already read from the stream, since those bytes would be
+    /// missing from the checksum.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"123456789");
+    ///
+    ///     let mut iter = xfind::find_iter(b"5", &mut stream).with_crc();
+    ///     assert_eq!(iter.next().transpose()?, Some(4));
+    ///     assert_eq!(iter.finish_crc()?, (vec![], 0xcbf43926));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "crc")]
+    pub fn with_crc(mut self) -> Self {
+        // The buffer is never empty once bytes have been read, as a roll retains some of them.
+        assert!(
+            self.searcher.buffered().1.is_empty(),
+            "with_crc must be called before reading from the stream"
+        );
+        self.crc = Some(crc32fast::Hasher::new());
+        self
+    }
+
+    /// Consumes the iterator and collects the offsets of all remaining matches, together with the
+    /// CRC32 checksum of the whole stream read by this iterator.
+    ///
+    /// This allows searching and verifying a large file in a single pass. Every byte read is
+    /// checksummed exactly once, including the bytes of matches that have already been yielded.
+    ///
+    /// This method is only available if the `crc` feature is enabled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if checksumming hasn't been enabled with [`with_crc`](FindIter::with_crc).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"123456789");
+    ///
+    ///     let (offsets, crc) = xfind::find_iter(b"5", &mut stream).with_crc().finish_crc()?;
+    ///     assert_eq!(offsets, vec![4]);
+    ///     assert_eq!(crc, 0xcbf43926);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "crc")]
+    pub fn finish_crc(mut self) -> io::Result<(Vec<usize>, u32)> {
+        assert!(
+            self.crc.is_some(),
+            "checksumming must be enabled with with_crc"
+        );
+        let mut offsets = Vec::new();
+        for pos in &mut self {
+            offsets.push(pos?);
+        }
+        // The bytes that are still buffered have never been dropped.
+        let (_, contents) = self.searcher.buffered();
+        let mut crc = self.crc.take().expect("checked above");
+        crc.update(contents);
+        Ok((offsets, crc.finalize()))
+    }
+}
+
+impl<'n, 's, R: Read + Seek, E: MatchEngine> FindIter<'n, 's, R, E> {
+    /// Queries the length of the underlying stream, so that [`size_hint`](Iterator::size_hint)
+    /// reports an upper bound of the remaining matches.
+    ///
+    /// The position of the underlying stream is restored afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if seeking the stream failed, or if the length of the stream is
+    /// greater than `usize::MAX` (see [`SearchError::StreamTooLong`]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust");
+    ///
+    ///     let iter = xfind::find_iter(b"rust", &mut stream).with_stream_len()?;
+    ///     assert_eq!(iter.size_hint(), (0, Some(2)));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_stream_len(mut self) -> io::Result<Self> {
+        let cur = self.rdr.stream_position()?;
+        let end = self.rdr.seek(SeekFrom::End(0))?;
+        self.rdr.seek(SeekFrom::Start(cur))?;
+
+        let (offset, contents) = self.searcher.buffered();
+        let remaining = end.saturating_sub(cur);
+        let len = (offset + contents.len()) as u64 + remaining;
+        self.stream_len = Some(usize::try_from(len).map_err(|_| {
+            io::Error::from(SearchError::StreamTooLong { len })
+        })?);
+        Ok(self)
+    }
+
+    /// Returns an iterator that also yields the distances of each match to the start and the end
+    /// of the stream.
+    ///
+    /// Every item is a tuple of `(offset, bytes_from_start, bytes_to_end)`, where
+    /// `bytes_from_start` is the number of bytes before the match and `bytes_to_end` the number
+    /// of bytes after it, so `bytes_from_start + needle.len() + bytes_to_end` is the length of the
+    /// stream. This helps finding matches near the beginning or the end of a file. The length is
+    /// queried once like in [`with_stream_len`](FindIter::with_stream_len).
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if querying the length of the stream failed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust, rustacean");
+    ///
+    ///     let mut iter = xfind::find_iter(b"rust", &mut stream).with_boundary_distance()?;
+    ///     assert_eq!(iter.next().transpose()?, Some((0, 0, 17)));
+    ///     assert_eq!(iter.next().transpose()?, Some((6, 6, 11)));
+    ///     assert_eq!(iter.next().transpose()?, Some((12, 12, 5)));
+    ///     assert_eq!(iter.next().transpose()?, None);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_boundary_distance(
+        self,
+    ) -> io::Result<BoundaryDistance<'n, 's, R, E>> {
+        let iter = self.with_stream_len()?;
+        let stream_len = iter.stream_len.unwrap_or_default();
+        Ok(BoundaryDistance { iter, stream_len })
+    }
+}
+
+impl<'n, 's, R: Read + Seek> FindRevIter<'n, 's, R> {
+    pub(crate) fn new(
+        rdr: &'s mut R,
+        fdr: &'n StreamFinder<'n>,
+    ) -> io::Result<Self> {
+        FindRevIter::new_with_needle(rdr, fdr.needle())
+    }
+
+    pub(crate) fn new_with_needle(
+        rdr: &'s mut R,
+        needle: &'n [u8],
+    ) -> io::Result<Self> {
+        let stream_len = seek_stream_len(rdr)?;
+        Ok(FindRevIter::with_len(rdr, needle, stream_len))
+    }
+
+    /// Creates a reverse iterator over all occurrences of the given needle in a stream of the
+    /// given length.
+    ///
+    /// Unlike [`rfind_iter`], this trusts `len` instead of seeking to the end of the stream to
+    /// find out its length, which is redundant if the length is already known, e.g. from file
+    /// metadata. The stream is only sought to the positions it's read from.
+    ///
+    /// Passing a length other than the actual length of the stream is a logic error. The results
+    /// are unspecified in that case, e.g. matches may be missed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::FindRevIter;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rusty rust");
+    ///
+    ///     let mut iter = FindRevIter::with_len(&mut stream, b"rust", 10);
+    ///     assert_eq!(iter.next().transpose()?, Some(6));
+    ///     assert_eq!(iter.next().transpose()?, Some(0));
+    ///     assert_eq!(iter.next().transpose()?, None);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_len(rdr: &'s mut R, needle: &'n [u8], len: usize) -> Self {
+        FindRevIter {
+            rdr,
+            needle,
+            buf: BufferRev::new(needle.len()),
+            search_end: 0,
+            end_reported: false,
+            seek_pos: len,
+            stream_len: len,
+            engine: MemmemEngine,
+        }
+    }
+}
+
+impl<'n, 's, R: Read + Seek, E: MatchEngine> FindRevIter<'n, 's, R, E> {
+    /// Replaces the engine that searches the buffer.
+    pub(crate) fn with_engine<F: MatchEngine>(
+        self,
+        engine: F,
+    ) -> FindRevIter<'n, 's, R, F> {
+        FindRevIter {
+            rdr: self.rdr,
+            needle: self.needle,
+            buf: self.buf,
+            search_end: self.search_end,
+            end_reported: self.end_reported,
+            seek_pos: self.seek_pos,
+            stream_len: self.stream_len,
+            engine,
+        }
+    }
+
+    /// Returns the length of the underlying stream.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Read, Cursor};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"hello rustaceans");
+    ///     let mut iter = xfind::rfind_iter(b"rust", &mut stream)?;
+    ///     assert_eq!(iter.stream_len(), 16);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn stream_len(&self) -> usize {
+        self.stream_len
+    }
+
+    /// Saves the progress of this iterator, so that the iteration can be resumed later with
+    /// [`restore_position`](FindRevIter::restore_position).
+    ///
+    /// This allows pausing a long reverse scan, e.g. across UI events, without keeping the
+    /// iterator (and its borrow of the stream) alive.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"rust rusty rust");
+    ///
+    ///     let mut iter = xfind::rfind_iter(b"rust", &mut stream)?;
+    ///     assert_eq!(iter.next().transpose()?, Some(11));
+    ///     let checkpoint = iter.save_position();
+    ///
+    ///     let mut iter = xfind::rfind_iter(b"rust", &mut stream)?;
+    ///     iter.restore_position(checkpoint);
+    ///     assert_eq!(iter.next().transpose()?, Some(5));
+    ///     assert_eq!(iter.next().transpose()?, Some(0));
+    ///     assert_eq!(iter.next().transpose()?, None);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn save_position(&self) -> RevCheckpoint {
+        RevCheckpoint {
+            end: self.seek_pos + self.search_end,
+            end_reported: self.end_reported,
+            stream_len: self.stream_len,
+        }
+    }
+
+    /// Resumes the iteration from a checkpoint taken by
+    /// [`save_position`](FindRevIter::save_position).
+    ///
+    /// The remaining matches are the ones that were remaining when the checkpoint was taken.
+    /// The checkpoint must have been taken by an iterator over the same stream and needle,
+    /// otherwise the results are unspecified.
+    pub fn restore_position(&mut self, checkpoint: RevCheckpoint) {
+        // Nothing that has been buffered is needed, since the next match must lie entirely
+        // before the checkpoint.
+        self.buf = BufferRev::new(self.needle.len());
+        self.search_end = 0;
+        self.end_reported = checkpoint.end_reported;
+        self.seek_pos = checkpoint.end;
+        self.stream_len = checkpoint.stream_len;
+    }
+
+    /// Moves the cursor of the underlying stream to the given position.
+    ///
+    /// This is equivalent to call `rdr.seek(SeekFrom::Start(pos))`, except that `pos` must lie
+    /// within the stream. The iteration is not affected: the iterator seeks to the bytes it needs
+    /// before reading them, so calling `next` afterwards continues right where it left off (and
+    /// moves the cursor again).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SearchError::SeekOutOfBounds`] (as an I/O error of kind
+    /// [`InvalidInput`](io::ErrorKind::InvalidInput)) if `pos` is greater than the length of the
+    /// stream, or the error of the seek itself.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Read, Cursor};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"hello rustaceans");
+    ///     let mut iter = xfind::rfind_iter(b"rust", &mut stream)?;
+    ///     let mut buf = Vec::new();
+    ///
+    ///     let pos = iter.next().unwrap()?;
+    ///     iter.seek_to(pos)?;
+    ///     stream.read_to_end(&mut buf)?;
+    ///     assert_eq!(buf, b"rustaceans");
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn seek_to(&mut self, pos: usize) -> io::Result<()> {
+        if pos > self.stream_len {
+            return Err(SearchError::SeekOutOfBounds {
+                pos: pos as u64,
+                len: self.stream_len as u64,
+            }
+            .into());
+        }
+        self.rdr.seek(SeekFrom::Start(pos as u64)).map(|_| ())
+    }
+}
+
+impl<'n, 's, R: Read, E: MatchEngine> Iterator for FindIter<'n, 's, R, E> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_observing(|_, _| {})
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let upper = self.stream_len.map(|len| {
+            let pos = self.searcher.position();
+            match self.needle.len() {
+                // An empty needle matches at every offset up to and including `len`, and the
+                // searcher moves past `len` after reporting the last one.
+                0 => (len + 1).saturating_sub(pos),
+                n => len.saturating_sub(pos) / n,
+            }
+        });
+        (0, upper)
+    }
+}
+
+impl<'n, 's, R: Read, E: MatchEngine> FindIter<'n, 's, R, E> {
+    /// Returns the next match like `next`, and calls `dropped` with the absolute offset and the
+    /// contents of every chunk of bytes that is dropped from the buffer meanwhile.
+    ///
+    /// Together with the buffered contents, this lets callers look at every byte of the stream up
+    /// to a match exactly once.
+    pub(crate) fn next_observing<F>(
+        &mut self,
+        mut dropped: F,
+    ) -> Option<io::Result<usize>>
+    where
+        F: FnMut(usize, &[u8]),
+    {
+        // The next needle may start right after the end of this match, so the next pattern may
+        // start `needle.len()` bytes after this one.
+        let pattern = self.pattern.as_deref().unwrap_or(self.needle);
+        #[cfg(feature = "crc")]
+        let crc = &mut self.crc;
+        let mut discard = |offset, bytes: &[u8]| {
+            #[cfg(feature = "crc")]
+            if let Some(crc) = crc {
+                crc.update(bytes);
+            }
+            dropped(offset, bytes);
+            Ok(())
+        };
+        loop {
+            // A candidate whose check failed with an error is checked again, as the searcher has
+            // already moved past it.
+            let (pos, start) = match self.pending {
+                Some(pending) => pending,
+                None => {
+                    let pos = match self.searcher.next_matching_with(
+                        &mut self.rdr,
+                        &self.engine,
+                        pattern,
+                        self.needle.len(),
+                        &mut discard,
+                    )? {
+                        Ok(pos) => pos + self.prefix_len,
+                        Err(err) => return Some(Err(err)),
+                    };
+                    if self.not_suffix.is_empty() {
+                        return Some(Ok(pos));
+                    }
+                    (pos, pos + self.needle.len())
+                }
+            };
+
+            self.pending = Some((pos, start));
+            let end = start + self.not_suffix.len();
+            if let Err(err) =
+                self.searcher.buffer_until(&mut self.rdr, end, &mut discard)
+            {
+                return Some(Err(err));
+            }
+            self.pending = None;
+            let (offset, contents) = self.searcher.buffered();
+            if !contents[start - offset..].starts_with(self.not_suffix) {
+                return Some(Ok(pos));
+            }
+        }
+    }
+}
+
+impl Searcher {
+    /// Creates the state for searching a needle of the given length.
+    pub(crate) fn new(needle_len: usize) -> Searcher {
+        Searcher::with_offset(needle_len, 0)
+    }
+
+    /// Creates the state for searching a needle of the given length, where the first byte read
+    /// is at the given absolute offset of the stream.
+    pub(crate) fn with_offset(needle_len: usize, offset: usize) -> Searcher {
+        Searcher::with_buffer(Buffer::new(needle_len), offset)
+    }
+
+    /// Creates the state for searching with the given buffer, where the first byte read is at the
+    /// given absolute offset of the stream.
+    pub(crate) fn with_buffer(buf: Buffer, offset: usize) -> Searcher {
+        Searcher { buf, search_pos: 0, stream_pos: offset }
+    }
+
+    /// Makes the buffer hold patterns of the given length, which must be done before searching a
+    /// different pattern.
+    pub(crate) fn set_pattern_len(&mut self, len: usize) {
+        self.buf.set_min_buffer_len(len);
+    }
+
+    /// Consumes the state and returns its buffer.
+    pub(crate) fn into_buffer(self) -> Buffer {
+        self.buf
+    }
+
+    /// Returns the absolute offset at which the next search starts.
+    pub(crate) fn position(&self) -> usize {
+        self.stream_pos
+    }
+
+    /// Returns the absolute offset of the buffer in the stream, together with its contents.
+    pub(crate) fn buffered(&self) -> (usize, &[u8]) {
+        (self.stream_pos - self.search_pos, self.buf.buffer())
+    }
+
+    /// Reads from the reader until the buffer holds the bytes up to the given absolute offset,
+    /// or the stream ends.
+    ///
+    /// The bytes from the start of the last match up to `end` must fit into the retained length
+    /// of the buffer, as they would be dropped from the buffer otherwise.
+    pub(crate) fn buffer_until<R, F>(
+        &mut self,
+        rdr: &mut R,
+        end: usize,
+        mut discard: F,
+    ) -> io::Result<()>
+    where
+        R: Read,
+        F: FnMut(usize, &[u8]) -> io::Result<()>,
+    {
+        loop {
+            let (offset, contents) = self.buffered();
+            if offset + contents.len() >= end || self.buf.is_eof() {
+                return Ok(());
+            }
+            if self.buf.len() >= self.buf.retain_len() {
+                let dropped = contents.len() - self.buf.retain_len();
+                discard(offset, &contents[..dropped])?;
+                self.buf.roll();
+                self.search_pos -= dropped;
+                trace!(
+                    "rolled forward buffer: dropped {} bytes at offset {}",
+                    dropped,
+                    offset
+                );
+            }
+            self.buf.fill(&mut *rdr)?;
+        }
+    }
+
+    /// Returns the offset of the next occurrence of the needle, reading more bytes from the
+    /// reader as needed.
+    pub(crate) fn next<R: Read>(
+        &mut self,
+        rdr: &mut R,
+        needle: &[u8],
+    ) -> Option<io::Result<usize>> {
+        self.next_with(rdr, needle, |_, _| Ok(()))
+    }
+
+    /// Like `next`, but calls `discard` with the absolute offset and the contents of every range
+    /// of bytes that is about to be dropped from the buffer.
+    ///
+    /// Together with `buffered`, this allows callers to observe every byte of the stream.
+    pub(crate) fn next_with<R, F>(
+        &mut self,
+        rdr: &mut R,
+        needle: &[u8],
+        discard: F,
+    ) -> Option<io::Result<usize>>
+    where
+        R: Read,
+        F: FnMut(usize, &[u8]) -> io::Result<()>,
+    {
+        self.next_advancing_with(rdr, needle, needle.len(), discard)
+    }
+
+    /// Returns the offset of the next occurrence of `pattern` like `next_with`, but only skips
+    /// `advance` bytes of it before searching again.
+    ///
+    /// This allows searching a needle together with some context (the rest of the pattern),
+    /// where only the needle itself must not overlap with the next match.
+    pub(crate) fn next_advancing_with<R, F>(
+        &mut self,
+        rdr: &mut R,
+        pattern: &[u8],
+        advance: usize,
+        discard: F,
+    ) -> Option<io::Result<usize>>
+    where
+        R: Read,
+        F: FnMut(usize, &[u8]) -> io::Result<()>,
+    {
+        self.next_matching_with(rdr, &MemmemEngine, pattern, advance, discard)
+    }
+
+    /// Like `next_advancing_with`, but searches the buffer with the given engine.
+    pub(crate) fn next_matching_with<R, E, F>(
+        &mut self,
+        rdr: &mut R,
+        engine: &E,
+        pattern: &[u8],
+        advance: usize,
+        mut discard: F,
+    ) -> Option<io::Result<usize>>
+    where
+        R: Read,
+        E: MatchEngine,
+        F: FnMut(usize, &[u8]) -> io::Result<()>,
+    {
+        debug_assert!(pattern.len() <= self.buf.min_buffer_len());
+        // An empty needle matches at every offset, so the search moves on by at least one byte to
+        // make progress.
+        let advance = cmp::max(advance, 1);
+        debug_assert!(advance <= cmp::max(pattern.len(), 1));
+        loop {
+            let len = self.buf.len();
+            // An empty pattern also matches at the very end of the stream.
+            let at_end = pattern.is_empty() && self.buf.is_eof();
+            if self.search_pos < len || (at_end && self.search_pos == len) {
+                if let Some(mat) =
+                    engine.find(&self.buf.buffer()[self.search_pos..], pattern)
+                {
+                    let report_pos = self.stream_pos + mat;
+                    self.stream_pos += mat + advance;
+                    self.search_pos += mat + advance;
+                    return Some(Ok(report_pos));
+                }
+
+                // An occurrence may still start in the last `pattern.len() - 1` bytes, and end in
+                // the bytes that haven't been read yet.
+                let next_pos = cmp::max(
+                    self.search_pos,
+                    (len + 1).saturating_sub(pattern.len()),
+                );
+                self.stream_pos += next_pos - self.search_pos;
+                self.search_pos = next_pos;
+            }
+
+            // The whole stream has been searched if we've already seen EOF. This is the common
+            // case for streams that are smaller than the buffer, which don't need to roll at all.
+            if self.buf.is_eof() {
+                return None;
+            }
+
+            // Roll our buffer if our buffer has at least the retained amount of bytes in it. The
+            // next search starts within the retained suffix, because that holds at least the
+            // last `pattern.len()` bytes.
+            //
+            // The invariant is that every position before `search_pos` has either been reported
+            // as the start of a match, or been ruled out, and that no position at or after it has
+            // been ruled out yet. The retained bytes are never matched on their own: they are
+            // searched again together with the bytes read next. So a retained suffix that only
+            // partially matches, which is common for periodic needles like `abab`, is completed
+            // or ruled out by the new bytes, and since `search_pos` never moves back, overlapping
+            // occurrences are resolved leftmost first, exactly like in a single search of the
+            // whole stream.
+            if len >= self.buf.retain_len() {
+                let (offset, contents) = self.buffered();
+                let dropped = contents.len() - self.buf.retain_len();
+                if let Err(err) = discard(offset, &contents[..dropped]) {
+                    return Some(Err(err));
+                }
+                self.buf.roll();
+                self.search_pos -= dropped;
+                trace!(
+                    "rolled forward buffer: dropped {} bytes at offset {}",
+                    dropped,
+                    offset
+                );
+            }
+            trace!(
+                "filling forward buffer at offset {}",
+                self.stream_pos - self.search_pos + self.buf.len()
+            );
+            match self.buf.fill(&mut *rdr) {
+                // report any I/O errors.
+                Err(err) => return Some(Err(err)),
+                // we've reach EOF, return `None` now, unless an empty pattern still matches at
+                // the end of the stream.
+                Ok(false) if !pattern.is_empty() => {
+                    return None;
+                }
+                // fallthrough for another search.
+                Ok(_) => {}
+            }
+        }
+    }
+}
+
+impl<'n, 's, R: Read> Iterator for FindPartialIter<'n, 's, R> {
+    type Item = io::Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.searcher.next(&mut self.rdr, self.needle) {
+            Some(Ok(pos)) => {
+                self.match_end = pos + self.needle.len();
+                return Some(Ok(Event::Match(pos)));
+            }
+            Some(Err(err)) => return Some(Err(err)),
+            None => self.done = true,
+        }
+
+        // The buffer always retains at least the last `needle.len()` bytes of the stream, which
+        // is enough to hold any proper prefix of the needle.
+        let (offset, contents) = self.searcher.buffered();
+        let stream_len = offset + contents.len();
+        let max_len = cmp::min(
+            self.needle.len().saturating_sub(1),
+            stream_len - self.match_end,
+        );
+        (1..=max_len)
+            .rev()
+            .find(|&len| contents.ends_with(&self.needle[..len]))
+            .map(|len| {
+                Ok(Event::PartialTail {
+                    offset: stream_len - len,
+                    matched_len: len,
+                })
+            })
+    }
+}
+
+impl<'n, 's, R: Read> Iterator for DensityIter<'n, 's, R> {
+    type Item = io::Result<(usize, usize)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let window_end = self.window_start.saturating_add(self.window);
+        let mut count = 0;
+        loop {
+            let pos = match self.pending.take() {
+                Some(pos) => pos,
+                None => match self.searcher.next(&mut self.rdr, self.needle) {
+                    Some(Ok(pos)) => pos,
+                    Some(Err(err)) => return Some(Err(err)),
+                    None => break,
+                },
+            };
+            if pos >= window_end {
+                self.pending = Some(pos);
+                break;
+            }
+            count += 1;
+        }
+
+        // Without a pending match we have reached EOF, so the window must start inside the
+        // stream to be reported.
+        if self.pending.is_none() {
+            let (offset, contents) = self.searcher.buffered();
+            if self.window_start >= offset + contents.len() {
+                return None;
+            }
+        }
+        let window_start = self.window_start;
+        self.window_start = window_end;
+        Some(Ok((window_start, count)))
+    }
+}
+
+impl<'n, 's, R: Read + Seek> FindRecordStartIter<'n, 's, R> {
+    /// Returns the start of the record containing the match at the given offset.
+    fn record_start(&mut self, mat: usize) -> io::Result<usize> {
+        let start_pos = match self.start_pos {
+            Some(pos) => pos,
+            None => {
+                // Nothing has been read before the first match is found, so the current position
+                // is where the forward search left off.
+                let (offset, contents) = self.searcher.buffered();
+                let pos = self.rdr.stream_position()?;
+                pos - (offset + contents.len()) as u64
+            }
+        };
+        self.start_pos = Some(start_pos);
+
+        // A delimiter that ends before `scanned_end` would have been found by a previous search.
+        let delim_len = self.record_delim.len();
+        let lo = cmp::max(
+            self.record_start,
+            self.scanned_end.saturating_sub(delim_len.saturating_sub(1)),
+        );
+        let resume_pos = self.rdr.stream_position()?;
+        let found = rfind_in_range(
+            self.rdr,
+            &self.record_delim,
+            start_pos + lo as u64,
+            start_pos + mat as u64,
+            &mut self.scratch,
+        )?;
+        self.rdr.seek(SeekFrom::Start(resume_pos))?;
+
+        if let Some(pos) = found {
+            self.record_start = (pos - start_pos) as usize + delim_len;
+        }
+        self.scanned_end = mat;
+        Ok(self.record_start)
+    }
+}
+
+impl<'n, 's, R: Read + Seek> Iterator for FindRecordStartIter<'n, 's, R> {
+    type Item = io::Result<(usize, usize)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mat = match self.searcher.next(&mut self.rdr, self.needle)? {
+            Ok(mat) => mat,
+            Err(err) => return Some(Err(err)),
+        };
+        Some(self.record_start(mat).map(|start| (mat, start)))
+    }
+}
+
+impl<'n, 's, R: Read, F: Fn(&mut [u8])> Iterator
+    for FindTransformedIter<'n, 's, R, F>
+{
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.searcher.next(&mut self.rdr, self.needle)
+    }
+}
+
+impl<'n, 's, R: Read + fmt::Debug, F> fmt::Debug
+    for FindTransformedIter<'n, 's, R, F>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FindTransformedIter")
+            .field("rdr", &self.rdr.rdr)
+            .field("needle", &self.needle)
+            .field("searcher", &self.searcher)
+            .finish()
+    }
+}
+
+impl<'n, 's, R, P, E> Iterator for FilterOffsets<'n, 's, R, P, E>
+where
+    R: Read,
+    P: FnMut(usize) -> bool,
+    E: MatchEngine,
+{
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pred = &mut self.pred;
+        self.iter.find(|res| res.as_ref().map_or(true, |&pos| pred(pos)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+impl<'n, 's, R: Read + fmt::Debug, P, E: fmt::Debug> fmt::Debug
+    for FilterOffsets<'n, 's, R, P, E>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FilterOffsets").field("iter", &self.iter).finish()
+    }
+}
+
+impl<'n> Iterator for FindInFileIter<'n> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.searcher.next(&mut self.file, self.needle)
+    }
+}
+
+impl<'n, 's, R: Read, E: MatchEngine> Iterator for Timed<'n, 's, R, E> {
+    type Item = io::Result<(usize, Duration)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pos = match self.iter.next()? {
+            Ok(pos) => pos,
+            Err(err) => return Some(Err(err)),
+        };
+        let now = Instant::now();
+        let elapsed = now - self.last;
+        self.last = now;
+        Some(Ok((pos, elapsed)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'n, 's, R: Read, E: MatchEngine> Iterator for Deltas<'n, 's, R, E> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pos = match self.iter.next()? {
+            Ok(pos) => pos,
+            Err(err) => return Some(Err(err)),
+        };
+        let delta = pos - self.last;
+        self.last = pos;
+        Some(Ok(delta))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'n, 's, R: Read, E: MatchEngine> Iterator for RunningCount<'n, 's, R, E> {
+    type Item = io::Result<(usize, usize)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pos = match self.iter.next()? {
+            Ok(pos) => pos,
+            Err(err) => return Some(Err(err)),
+        };
+        self.count += 1;
+        Some(Ok((pos, self.count)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'n, 's, R: Read, E: MatchEngine> Iterator
+    for StepByMatches<'n, 's, R, E>
+{
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.iter.next()? {
+                Ok(pos) if self.skip == 0 => {
+                    self.skip = self.step - 1;
+                    return Some(Ok(pos));
+                }
+                Ok(_) => self.skip -= 1,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+impl<'n, 's, R: Read, E: MatchEngine> Iterator
+    for BoundaryDistance<'n, 's, R, E>
+{
+    type Item = io::Result<(usize, usize, usize)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let end = self.stream_len.saturating_sub(self.iter.needle.len());
+        self.iter
+            .next()
+            .map(|res| res.map(|pos| (pos, pos, end.saturating_sub(pos))))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'n, 's, R: Read, E: MatchEngine> Iterator for MatchSlices<'n, 's, R, E> {
+    type Item = io::Result<(usize, &'n [u8])>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let needle = self.iter.needle;
+        self.iter.next().map(|res| res.map(|pos| (pos, needle)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'n, 's, R: Read> Iterator for FindWindowsIter<'n, 's, R> {
+    type Item = io::Result<(usize, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pos = match self.searcher.next(&mut self.rdr, self.needle)? {
+            Ok(pos) => pos,
+            Err(err) => return Some(Err(err)),
+        };
+        let (offset, contents) = self.searcher.buffered();
+        let contents = &contents[pos - offset..];
+        let mut bytes =
+            contents[..cmp::min(self.window, contents.len())].to_vec();
+        let missing = self.window - bytes.len();
+        Some(self.rdr.peek_into(&mut bytes, missing).map(|_| (pos, bytes)))
+    }
+}
+
+impl<'n, 's, R: Read, E: MatchEngine> Iterator
+    for FindNeighborsIter<'n, 's, R, E>
+{
+    type Item = io::Result<(Option<u8>, usize, Option<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pattern = self.pattern.as_deref().unwrap_or(self.needle);
+        let last_dropped = &mut self.last_dropped;
+        let mut discard = |_, bytes: &[u8]| {
+            if let Some(&b) = bytes.last() {
+                *last_dropped = Some(b);
+            }
+            Ok(())
+        };
+        // Like in `FindIter`, a candidate whose check against `not_suffix` failed with an error
+        // is checked again.
+        let pos = loop {
+            let (pos, start) = match self.pending {
+                Some(pending) => pending,
+                None => {
+                    let pos = match self.searcher.next_matching_with(
+                        &mut self.rdr,
+                        &self.engine,
+                        pattern,
+                        self.needle.len(),
+                        &mut discard,
+                    )? {
+                        Ok(pos) => pos + self.prefix_len,
+                        Err(err) => return Some(Err(err)),
+                    };
+                    if self.not_suffix.is_empty() {
+                        break pos;
+                    }
+                    (pos, pos + self.needle.len())
+                }
+            };
+
+            self.pending = Some((pos, start));
+            let end = start + self.not_suffix.len();
+            if let Err(err) =
+                self.searcher.buffer_until(&mut self.rdr, end, &mut discard)
+            {
+                return Some(Err(err));
+            }
+            self.pending = None;
+            let (offset, contents) = self.searcher.buffered();
+            if !contents[start - offset..].starts_with(self.not_suffix) {
+                break pos;
+            }
+        };
+
+        let (offset, contents) = self.searcher.buffered();
+        let before = match pos - offset {
+            0 => self.last_dropped,
+            i => Some(contents[i - 1]),
+        };
+        let after = match contents.get(pos - offset + self.needle.len()) {
+            Some(&b) => Some(b),
+            None => {
+                let mut ahead = Vec::with_capacity(1);
+                if let Err(err) = self.rdr.peek_into(&mut ahead, 1) {
+                    return Some(Err(err));
+                }
+                ahead.first().copied()
+            }
+        };
+        Some(Ok((before, pos, after)))
+    }
+}
+
+impl<R: Read> ReadAhead<R> {
+    /// Appends the next `n` bytes of the stream to `out` without consuming them, or less if the
+    /// stream ends earlier.
+    ///
+    /// `n` may be much larger than the stream, so the bytes are read in chunks no larger than
+    /// [`DEFAULT_BUFFER_CAPACITY`], and only the bytes actually read are kept.
+    fn peek_into(&mut self, out: &mut Vec<u8>, n: usize) -> io::Result<()> {
+        let ahead = &self.buf[self.pos..];
+        let ahead_len = cmp::min(n, ahead.len());
+        out.extend_from_slice(&ahead[..ahead_len]);
+
+        let mut missing = n - ahead_len;
+        while missing > 0 {
+            let start = self.buf.len();
+            let chunk_len = cmp::min(missing, DEFAULT_BUFFER_CAPACITY);
+            self.buf.resize(start + chunk_len, 0);
+            let bytes_read = match self.rdr.read(&mut self.buf[start..]) {
+                Ok(bytes_read) => bytes_read,
+                Err(err) => {
+                    self.buf.truncate(start);
+                    if err.kind() == io::ErrorKind::Interrupted {
+                        continue;
+                    }
+                    return Err(err);
+                }
+            };
+            self.buf.truncate(start + bytes_read);
+            if bytes_read == 0 {
+                break;
+            }
+            out.extend_from_slice(&self.buf[start..]);
+            missing -= bytes_read;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for ReadAhead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.buf.len() {
+            return self.rdr.read(buf);
+        }
+        let n = cmp::min(buf.len(), self.buf.len() - self.pos);
+        buf[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        if self.pos == self.buf.len() {
+            self.buf.clear();
+            self.pos = 0;
+        }
+        Ok(n)
+    }
+}
+
+impl<'n, 'b, 's, R: Read> Iterator for FindUntilIter<'n, 'b, 's, R> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let len = self.buf.len();
+            if !self.boundary_found {
+                match memmem::find(
+                    &self.buf.buffer()[self.boundary_pos..],
+                    self.boundary,
+                ) {
+                    Some(mat) => {
+                        self.boundary_pos += mat;
+                        self.boundary_found = true;
+                    }
+                    None => {
+                        self.boundary_pos = cmp::max(
+                            self.boundary_pos,
+                            (len + 1).saturating_sub(self.boundary.len()),
+                        );
+                    }
+                }
+            }
+
+            // Matches must end before the earliest position at which the boundary may start.
+            let limit = if self.boundary_found || !self.buf.is_eof() {
+                self.boundary_pos
+            } else {
+                len
+            };
+            if let Some(mat) = self
+                .buf
+                .buffer()
+                .get(self.search_pos..limit)
+                .and_then(|haystack| memmem::find(haystack, self.needle))
+            {
+                let pos = self.search_pos + mat;
+                self.search_pos = pos + cmp::max(1, self.needle.len());
+                return Some(Ok(self.buf_offset + pos));
+            }
+            if self.boundary_found || self.buf.is_eof() {
+                return None;
+            }
+            self.search_pos = cmp::max(
+                self.search_pos,
+                (limit + 1).saturating_sub(cmp::max(1, self.needle.len())),
+            );
+
+            // Roll our buffer if our buffer has at least the retained amount of bytes in it. Both
+            // searches resume within the retained suffix, because it holds the last
+            // `needle.len() + boundary.len()` bytes.
+            if len >= self.buf.retain_len() {
+                let dropped = len - self.buf.retain_len();
+                self.buf.roll();
+                self.buf_offset += dropped;
+                self.search_pos -= dropped;
+                self.boundary_pos -= dropped;
+            }
+            if let Err(err) = self.buf.fill(&mut self.rdr) {
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+impl<'n, 's, R: Read, W: Write> Iterator for FindTeeIter<'n, 's, R, W> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.searcher.next(&mut self.rdr, self.needle)
+    }
+}
+
+impl<R: Read, W: Write> Read for Tee<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.rdr.read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            self.wtr.flush()?;
+        } else {
+            self.wtr.write_all(&buf[..n])?;
+        }
+        Ok(n)
+    }
+}
+
+impl<R: Read, F: Fn(&mut [u8])> Read for Transformed<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.rdr.read(buf)?;
+        (self.transform)(&mut buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<'n, 's, R: Read + Seek, E: MatchEngine> Iterator
+    for FindRevIter<'n, 's, R, E>
+{
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // An empty needle matches at `search_end` itself, so once it has been reported there,
+            // the search moves on by one byte to make progress.
+            let end = if self.end_reported && self.needle.is_empty() {
+                self.search_end.checked_sub(1)
+            } else {
+                Some(self.search_end)
+            };
+            if let Some(mat) = end.and_then(|end| {
+                self.engine.rfind(&self.buf.buffer()[..end], self.needle)
+            }) {
+                self.search_end = mat;
+                self.end_reported = true;
+                return Some(Ok(self.seek_pos + mat));
+            }
+
+            // We have nothing left to search if seek position is 0.
+            if self.seek_pos == 0 {
+                return None;
+            }
+
+            // Roll our buffer if our buffer has at least the minimum amount of bytes in it. The
+            // retained prefix has been searched already, so a match found in it later must span
+            // into the newly read bytes. But if the previous match ended inside the retained
+            // prefix, we must not search past its start again.
+            //
+            // This mirrors the forward invariant: no match may end after `search_end`, since the
+            // bytes from there on either belong to a reported match or have been ruled out as the
+            // end of one. Overlapping occurrences of
+            // periodic needles are thus resolved rightmost first, as in a single backward search
+            // of the whole stream.
+            if self.buf.len() >= self.buf.min_buffer_len() {
+                self.buf.roll_right();
+                self.search_end =
+                    cmp::min(self.search_end, self.buf.min_buffer_len());
+                trace!(
+                    "rolled backward buffer: retained {} bytes at offset {}",
+                    self.buf.min_buffer_len(),
+                    self.seek_pos
+                );
+            }
+
+            let amount = cmp::min(self.buf.free_buffer().len(), self.seek_pos);
+            self.seek_pos -= amount;
+            trace!(
+                "filling backward buffer with {} bytes at offset {}",
+                amount,
+                self.seek_pos
+            );
+            // The cursor is left right after the bytes read by the previous fill, so every fill
+            // seeks once, no matter how many matches have been found in between.
+            if let Err(err) =
+                self.rdr.seek(SeekFrom::Start(self.seek_pos as u64))
+            {
+                return Some(Err(err));
+            }
+            match self.buf.fill_exact(&mut self.rdr, amount) {
+                // report any I/O errors.
+                Err(err) => return Some(Err(err)),
+                // we've reach EOF, return `None` now.
+                Ok(false) => {
+                    return None;
+                }
+                // fallthrough for another search.
+                Ok(true) => self.search_end += amount,
+            }
+        }
+    }
+}
+
+impl<'n, 's, R: Read + Seek> Iterator for FindFromEndIter<'n, 's, R> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (len, needle_len) =
+            (self.iter.stream_len(), self.iter.needle.len());
+        self.iter.next().map(|res| res.map(|pos| len - (pos + needle_len)))
+    }
+}
+
+/// Seeks to the end of the stream and returns its length.
+/// Calls `f` with the reader and its current position, and seeks back to that position
+/// afterwards, whether `f` succeeded or not.
+///
+/// If both `f` and seeking back fail, the error of `f` is returned.
+fn with_restored_position<R, T, F>(rdr: &mut R, f: F) -> io::Result<T>
+where
+    R: Seek,
+    F: FnOnce(&mut R, u64) -> io::Result<T>,
+{
+    let start_pos = rdr.stream_position()?;
+    let res = f(rdr, start_pos);
+    let restored = rdr.seek(SeekFrom::Start(start_pos));
+    let value = res?;
+    restored?;
+    Ok(value)
+}
+
+fn seek_stream_len<R: Seek>(rdr: &mut R) -> io::Result<usize> {
+    let len = rdr.seek(SeekFrom::End(0))?;
+    usize::try_from(len).map_err(|_| SearchError::StreamTooLong { len }.into())
+}
+
+/// Returns the absolute offset of the last occurrence of the needle that lies entirely within the
+/// range `start..end` of the stream.
+///
+/// The range is read backward in chunks, using `buf` as scratch space. The stream position is left
+/// unspecified.
+fn rfind_in_range<R: Read + Seek>(
+    rdr: &mut R,
+    needle: &[u8],
+    start: u64,
+    end: u64,
+    buf: &mut Vec<u8>,
+) -> io::Result<Option<u64>> {
+    if end < start + needle.len() as u64 {
+        return Ok(None);
+    }
+    let chunk_len = cmp::max(DEFAULT_BUFFER_CAPACITY, needle.len() * 2);
+    buf.resize(chunk_len, 0);
+
+    let mut chunk_end = end;
+    loop {
+        let chunk_start =
+            cmp::max(start, chunk_end.saturating_sub(chunk_len as u64));
+        let chunk = &mut buf[..(chunk_end - chunk_start) as usize];
+        rdr.seek(SeekFrom::Start(chunk_start))?;
+        rdr.read_exact(chunk)?;
+        if let Some(mat) = memmem::rfind(chunk, needle) {
+            return Ok(Some(chunk_start + mat as u64));
+        }
+        if chunk_start == start {
+            return Ok(None);
+        }
+        // The needle is not empty here, since an empty needle always matches. An occurrence
+        // that hasn't been searched yet must start before `chunk_start`.
+        chunk_end = chunk_start + needle.len() as u64 - 1;
+    }
+}
+
+/// Returns the last occurrence of the needle within `range` like `rfind_in_range`, but reuses the
+/// result of a previous search over a larger range (with the same start) stored in `cache`.
+fn cached_rfind<R: Read + Seek>(
+    cache: &mut Option<Option<u64>>,
+    rdr: &mut R,
+    needle: &[u8],
+    range: Range<u64>,
+    buf: &mut Vec<u8>,
+) -> io::Result<Option<u64>> {
+    match *cache {
+        // The needle doesn't occur in a smaller range either.
+        Some(None) => return Ok(None),
+        Some(Some(pos)) if pos + needle.len() as u64 <= range.end => {
+            return Ok(Some(pos));
+        }
+        _ => {}
+    }
+    let found = rfind_in_range(rdr, needle, range.start, range.end, buf)?;
+    *cache = Some(found);
+    Ok(found)
+}
+
+/// Computes the KMP failure table of the given needle, where `table[i]` is the length of the
+/// longest proper prefix of `needle[..=i]` that is also a suffix of it.
+fn prefix_table(needle: &[u8]) -> Vec<usize> {
+    let mut table = vec![0; needle.len()];
+    let mut k = 0;
+    for i in 1..needle.len() {
+        while k > 0 && needle[i] != needle[k] {
+            k = table[k - 1];
+        }
+        if needle[i] == needle[k] {
+            k += 1;
+        }
+        table[i] = k;
+    }
+    table
+}
+
+/// Advances the KMP matching state by one byte.
+///
+/// `state` is the length of the needle prefix matched so far, and the returned value is the new
+/// length. A state equal to `needle.len()` means a full match just ended at `byte`.
+fn kmp_step(
+    needle: &[u8],
+    table: &[usize],
+    mut state: usize,
+    byte: u8,
+) -> usize {
+    if state == needle.len() {
+        state = table[state - 1];
+    }
+    while state > 0 && needle[state] != byte {
+        state = table[state - 1];
+    }
+    if needle[state] == byte {
+        state += 1;
+    }
+    state
+}
+
+#[cfg(test)]
+// `iter::repeat_n` is only stable on recent toolchains.
+#[allow(clippy::manual_repeat_n)]
+mod tests {
+    use super::*;
+    use crate::testutil::FaultReader;
+    use std::io::Cursor;
+    use std::iter::repeat;
+
+    #[test]
+    fn test_find_iter_n1s1() {
+        let haystack = b"1";
+        let mut haystack = Cursor::new(haystack);
+
+        let finder = StreamFinder::new(b"1");
+        let matches: Vec<usize> =
+            finder.find_iter(&mut haystack).map(|x| x.unwrap()).collect();
+        let expected: Vec<usize> = vec![0];
+        assert_eq!(matches, expected);
+
+        let finder = StreamFinder::new(b"0");
+        let matches: Vec<usize> =
+            finder.find_iter(&mut haystack).map(|x| x.unwrap()).collect();
+        let expected: Vec<usize> = vec![];
+        assert_eq!(matches, expected);
+    }
+
+    #[test]
+    fn test_find_rev_iter_n1s1() {
+        let haystack = b"1";
+        let mut haystack = Cursor::new(haystack);
+
+        let finder = StreamFinder::new(b"1");
+        let matches: Vec<usize> = finder
+            .rfind_iter(&mut haystack)
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect();
+        let expected: Vec<usize> = vec![0];
+        assert_eq!(matches, expected);
+
+        let finder = StreamFinder::new(b"0");
+        let matches: Vec<usize> = finder
+            .rfind_iter(&mut haystack)
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect();
+        let expected: Vec<usize> = vec![];
+        assert_eq!(matches, expected);
+    }
+
+    #[test]
+    fn test_find_iter_n2s1() {
+        let haystack = b"1";
+        let mut haystack = Cursor::new(haystack);
+
+        let finder = StreamFinder::new(b"11");
+        let matches: Vec<usize> =
+            finder.find_iter(&mut haystack).map(|x| x.unwrap()).collect();
+        let expected: Vec<usize> = vec![];
+        assert_eq!(matches, expected);
+    }
+
+    #[test]
+    fn test_find_rev_iter_n2s1() {
+        let haystack = b"1";
+        let mut haystack = Cursor::new(haystack);
+
+        let finder = StreamFinder::new(b"11");
+        let matches: Vec<usize> = finder
+            .rfind_iter(&mut haystack)
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect();
+        let expected: Vec<usize> = vec![];
+        assert_eq!(matches, expected);
+    }
+
+    #[test]
+    fn test_find_iter_n1s21() {
+        let haystack = b"11 - 1 - 11  - 1 - 11";
+        let mut haystack = Cursor::new(haystack);
+
+        let finder = StreamFinder::new(b"1");
+        let matches: Vec<usize> =
+            finder.find_iter(&mut haystack).map(|x| x.unwrap()).collect();
+        let expected: Vec<usize> = vec![0, 1, 5, 9, 10, 15, 19, 20];
+        assert_eq!(matches, expected);
+    }
+
+    #[test]
+    fn test_find_rev_iter_n1s21() {
+        let haystack = b"11 - 1 - 11  - 1 - 11";
+        let mut haystack = Cursor::new(haystack);
+
+        let finder = StreamFinder::new(b"1");
+        let matches: Vec<usize> = finder
+            .rfind_iter(&mut haystack)
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect();
+        let expected: Vec<usize> =
+            vec![0, 1, 5, 9, 10, 15, 19, 20].into_iter().rev().collect();
+        assert_eq!(matches, expected);
+    }
+
+    #[test]
+    fn test_find_iter_n1s8213() {
+        let haystack: Vec<u8> = repeat(&0u8)
+            .take(DEFAULT_BUFFER_CAPACITY)
+            .chain("42 0 42 42 0 42".as_bytes())
+            .copied()
+            .collect();
+        let mut haystack = Cursor::new(haystack);
+
+        let finder = StreamFinder::new(b"4");
+        let matches: Vec<usize> =
+            finder.find_iter(&mut haystack).map(|x| x.unwrap()).collect();
+        let expected: Vec<usize> = vec![0, 5, 8, 13]
+            .into_iter()
+            .map(|x| x + DEFAULT_BUFFER_CAPACITY)
+            .collect();
+        assert_eq!(matches, expected);
+    }
+
+    #[test]
+    fn test_find_rev_iter_n1s8213() {
+        let haystack: Vec<u8> = repeat(&0u8)
+            .take(DEFAULT_BUFFER_CAPACITY)
+            .chain("42 0 42 42 0 42".as_bytes())
+            .copied()
+            .collect();
+        let mut haystack = Cursor::new(haystack);
+
+        let finder = StreamFinder::new(b"4");
+        let matches: Vec<usize> = finder
+            .rfind_iter(&mut haystack)
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect();
+        let expected: Vec<usize> = vec![0, 5, 8, 13]
+            .into_iter()
+            .map(|x| x + DEFAULT_BUFFER_CAPACITY)
+            .rev()
+            .collect();
+        assert_eq!(matches, expected);
+    }
+
+    #[test]
+    fn test_find_iter_n2s8213() {
+        let haystack: Vec<u8> = repeat(&0u8)
+            .take(DEFAULT_BUFFER_CAPACITY)
+            .chain("42 0 42 42 0 42".as_bytes())
+            .copied()
+            .collect();
+        let mut haystack = Cursor::new(haystack);
+
+        let finder = StreamFinder::new(b"42");
+        let matches: Vec<usize> =
+            finder.find_iter(&mut haystack).map(|x| x.unwrap()).collect();
+        let expected: Vec<usize> = vec![0, 5, 8, 13]
+            .into_iter()
+            .map(|x| x + DEFAULT_BUFFER_CAPACITY)
+            .collect();
+        assert_eq!(matches, expected);
+    }
+
+    #[test]
+    fn test_find_rev_iter_n2s8213() {
+        let haystack: Vec<u8> = repeat(&0u8)
+            .take(DEFAULT_BUFFER_CAPACITY)
+            .chain("42 0 42 42 0 42".as_bytes())
+            .copied()
+            .collect();
+        let mut haystack = Cursor::new(haystack);
+
+        let finder = StreamFinder::new(b"42");
+        let matches: Vec<usize> = finder
+            .rfind_iter(&mut haystack)
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect();
+        let expected: Vec<usize> = vec![0, 5, 8, 13]
+            .into_iter()
+            .map(|x| x + DEFAULT_BUFFER_CAPACITY)
+            .rev()
+            .collect();
+        assert_eq!(matches, expected);
+    }
+
+    #[test]
+    fn test_find_iter_n2s8212() {
+        let haystack: Vec<u8> = repeat(&0u8)
+            .take(DEFAULT_BUFFER_CAPACITY - 1)
+            .chain("42 0 42 42 0 42".as_bytes())
+            .copied()
+            .collect();
+        let mut haystack = Cursor::new(haystack);
+
+        let finder = StreamFinder::new(b"42");
+        let matches: Vec<usize> =
+            finder.find_iter(&mut haystack).map(|x| x.unwrap()).collect();
+        let expected: Vec<usize> = vec![0, 5, 8, 13]
+            .into_iter()
+            .map(|x| x + DEFAULT_BUFFER_CAPACITY - 1)
+            .collect();
+        assert_eq!(matches, expected);
+    }
+
+    #[test]
+    fn test_find_rev_iter_n2s8212() {
+        let haystack: Vec<u8> = repeat(&0u8)
+            .take(DEFAULT_BUFFER_CAPACITY - 1)
+            .chain("42 0 42 42 0 42".as_bytes())
+            .copied()
+            .collect();
+        let mut haystack = Cursor::new(haystack);
+
+        let finder = StreamFinder::new(b"42");
+        let matches: Vec<usize> = finder
+            .rfind_iter(&mut haystack)
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect();
+        let expected: Vec<usize> = vec![0, 5, 8, 13]
+            .into_iter()
+            .map(|x| x + DEFAULT_BUFFER_CAPACITY - 1)
+            .rev()
+            .collect();
+        assert_eq!(matches, expected);
+    }
+
+    #[test]
+    fn test_find_iter_n3s8212() {
+        let haystack: Vec<u8> = repeat(&0u8)
+            .take(DEFAULT_BUFFER_CAPACITY - 1)
+            .chain("42 0 42 42 0 42".as_bytes())
+            .copied()
+            .collect();
+        let mut haystack = Cursor::new(haystack);
+
+        let finder = StreamFinder::new(b"42 ");
+        let matches: Vec<usize> =
+            finder.find_iter(&mut haystack).map(|x| x.unwrap()).collect();
+        let expected: Vec<usize> = vec![0, 5, 8]
+            .into_iter()
+            .map(|x| x + DEFAULT_BUFFER_CAPACITY - 1)
+            .collect();
+        assert_eq!(matches, expected);
+    }
+
+    #[test]
+    fn test_find_rev_iter_n3s8212() {
+        let haystack: Vec<u8> = repeat(&0u8)
+            .take(DEFAULT_BUFFER_CAPACITY - 1)
+            .chain("42 0 42 42 0 42".as_bytes())
+            .copied()
+            .collect();
+        let mut haystack = Cursor::new(haystack);
+
+        let finder = StreamFinder::new(b"42 ");
+        let matches: Vec<usize> = finder
+            .rfind_iter(&mut haystack)
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect();
+        let expected: Vec<usize> = vec![0, 5, 8]
+            .into_iter()
+            .map(|x| x + DEFAULT_BUFFER_CAPACITY - 1)
+            .rev()
+            .collect();
+        assert_eq!(matches, expected);
+    }
+
+    /// Builds a haystack of `len` bytes that contains `needle` at offset 0 and at the very end.
+    fn haystack_with_edges(needle: &[u8], len: usize) -> Vec<u8> {
+        let mut haystack = vec![b'-'; len];
+        haystack[..needle.len()].copy_from_slice(needle);
+        haystack[len - needle.len()..].copy_from_slice(needle);
+        haystack
+    }
+
+    fn assert_rfind_iter_matches_memmem(needle: &[u8], haystack: &[u8]) {
+        let mut stream = Cursor::new(haystack);
+        let matches: Vec<usize> = StreamFinder::new(needle)
+            .rfind_iter(&mut stream)
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect();
+        let expected: Vec<usize> =
+            memmem::find_iter(haystack, needle).collect();
+        let expected: Vec<usize> = expected.into_iter().rev().collect();
+        assert_eq!(matches, expected);
+    }
+
+    #[test]
+    fn test_find_rev_iter_stream_len_eq_capacity() {
+        for needle in [&b"4"[..], b"42", b"dear"] {
+            let haystack =
+                haystack_with_edges(needle, DEFAULT_BUFFER_CAPACITY);
+            assert_rfind_iter_matches_memmem(needle, &haystack);
+        }
+    }
+
+    #[test]
+    fn test_find_rev_iter_stream_len_lt_capacity() {
+        for needle in [&b"4"[..], b"42", b"dear"] {
+            let haystack =
+                haystack_with_edges(needle, DEFAULT_BUFFER_CAPACITY - 1);
+            assert_rfind_iter_matches_memmem(needle, &haystack);
+        }
+    }
+
+    #[test]
+    fn test_find_rev_iter_stream_len_gt_capacity() {
+        for needle in [&b"4"[..], b"42", b"dear"] {
+            let haystack =
+                haystack_with_edges(needle, DEFAULT_BUFFER_CAPACITY + 1);
+            assert_rfind_iter_matches_memmem(needle, &haystack);
+        }
+    }
+
+    #[test]
+    fn test_find_rev_iter_match_spans_roll() {
+        // Places a match across every possible split point of the first roll.
+        let needle = b"dear";
+        for shift in 0..needle.len() + 1 {
+            let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 2];
+            let pos = DEFAULT_BUFFER_CAPACITY - shift;
+            haystack[pos..pos + needle.len()].copy_from_slice(needle);
+            haystack[..needle.len()].copy_from_slice(needle);
+            assert_rfind_iter_matches_memmem(needle, &haystack);
+        }
+    }
+
+    #[test]
+    fn test_find_and_split_early() {
+        let mut haystack = Cursor::new(b"ab\r\ncd\r\nef".to_vec());
+        let finder = StreamFinder::new(b"\r\n");
+
+        let (head, pos) = finder.find_and_split(&mut haystack).unwrap();
+        assert_eq!(head, b"ab");
+        assert_eq!(pos, Some(2));
+        assert_eq!(haystack.position(), 4);
+
+        let (head, pos) = finder.find_and_split(&mut haystack).unwrap();
+        assert_eq!(head, b"cd");
+        assert_eq!(pos, Some(2));
+        assert_eq!(haystack.position(), 8);
+
+        let (head, pos) = finder.find_and_split(&mut haystack).unwrap();
+        assert_eq!(head, b"ef");
+        assert_eq!(pos, None);
+        assert_eq!(haystack.position(), 10);
+    }
+
+    #[test]
+    fn test_find_and_split_late() {
+        let mut haystack: Vec<u8> =
+            repeat(&b'a').take(DEFAULT_BUFFER_CAPACITY * 2).copied().collect();
+        haystack.extend_from_slice(b"aab--");
+        let mut haystack = Cursor::new(haystack);
+
+        let finder = StreamFinder::new(b"aab");
+        let (head, pos) = finder.find_and_split(&mut haystack).unwrap();
+        let pos = pos.unwrap();
+        assert_eq!(pos, DEFAULT_BUFFER_CAPACITY * 2);
+        assert_eq!(head.len(), pos);
+        assert!(head.iter().all(|&b| b == b'a'));
+        assert_eq!(haystack.position() as usize, pos + 3);
+    }
+
+    #[test]
+    fn test_find_and_split_absent() {
+        let mut haystack = Cursor::new(b"no delimiter here".to_vec());
+        let finder = StreamFinder::new(b";");
+
+        assert_eq!(
+            finder.find_and_split(&mut haystack).unwrap(),
+            (b"no delimiter here".to_vec(), None)
+        );
+        assert_eq!(haystack.position(), 17);
+    }
+
+    /// A reader that deliberately implements `Read` only.
+    struct ReadOnly<R>(R);
+
+    impl<R: Read> Read for ReadOnly<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    #[test]
+    fn test_rfind_buffering() {
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 3];
+        haystack[10..14].copy_from_slice(b"dear");
+        haystack[DEFAULT_BUFFER_CAPACITY * 2..][..4].copy_from_slice(b"dear");
+
+        for needle in [&b"dear"[..], b"-", b"absent"] {
+            let mut rdr = ReadOnly(&haystack[..]);
+            let expected = rfind(needle, &mut Cursor::new(&haystack))
+                .transpose()
+                .unwrap();
+            assert_eq!(rfind_buffering(needle, &mut rdr).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_find_iter_transformed() {
+        let mut f = std::fs::File::open("data/pride-and-prejudice.txt")
+            .expect("test file not found");
+        let mut buf = Vec::new();
+        f.read_to_end(&mut buf).unwrap();
+
+        let finder = StreamFinder::new(b"DEAR");
+        let matches: Vec<usize> = finder
+            .find_iter_transformed(&mut Cursor::new(&buf), |chunk| {
+                chunk.make_ascii_uppercase()
+            })
+            .map(|x| x.unwrap())
+            .collect();
+
+        let expected: Vec<usize> =
+            memmem::find_iter(&buf.to_ascii_uppercase(), b"DEAR").collect();
+        assert!(expected.len() > memmem::find_iter(&buf, b"DEAR").count());
+        assert_eq!(matches, expected);
+    }
+
+    #[test]
+    fn test_find_iter_short_streams() {
+        let mut f = std::fs::File::open("data/pride-and-prejudice.txt")
+            .expect("test file not found");
+        let mut text = Vec::new();
+        f.read_to_end(&mut text).unwrap();
+
+        let lens = [0, 1, 2, 3, 4, 100, 511, 512, 513, 1025, 4097, 8191];
+        for &len in lens.iter() {
+            for needle in [&b"e"[..], b"the", b"Elizabeth"] {
+                let haystack = &text[..len];
+                let matches: Vec<usize> =
+                    find_iter(needle, &mut &haystack[..])
+                        .map(|x| x.unwrap())
+                        .collect();
+                let expected: Vec<usize> =
+                    memmem::find_iter(haystack, needle).collect();
+                assert_eq!(matches, expected, "len: {}", len);
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_all_empty_needle() {
+        let finder = StreamFinder::new(b"");
+        assert_eq!(finder.find_all(&mut &b""[..]).unwrap(), vec![0]);
+        assert_eq!(
+            finder.find_all(&mut &b"rust"[..]).unwrap(),
+            vec![0, 1, 2, 3, 4]
+        );
+
+        let rdr = &mut FaultReader::new(&b"rust"[..]).error_at(2);
+        assert!(finder.find_all(rdr).is_err());
+    }
+
+    #[test]
+    fn test_find_iter_empty_needle() {
+        let haystack = b"ab\n".repeat(DEFAULT_BUFFER_CAPACITY);
+        let expected: Vec<usize> = (0..=haystack.len()).collect();
+        let finder = StreamFinder::new(b"");
+
+        let rdr = &mut FaultReader::new(&haystack[..]).short_reads(1000);
+        assert_eq!(finder.find_iter(rdr).into_offsets().unwrap(), expected);
+        let rdr = &mut &haystack[..];
+        assert_eq!(
+            finder.find_with_total(rdr).unwrap(),
+            Some((0, haystack.len() + 1))
+        );
+        let rdr = &mut &haystack[..];
+        assert_eq!(
+            finder.find_limited_counted(rdr, 3).unwrap(),
+            (vec![0, 1, 2], true)
+        );
+        let rdr = &mut &b"ab"[..];
+        assert_eq!(
+            finder.find_limited_counted(rdr, 3).unwrap(),
+            (vec![0, 1, 2], false)
+        );
+        let rdr = &mut &haystack[..];
+        assert_eq!(finder.find_last(rdr).unwrap().unwrap(), haystack.len());
+
+        let rdr = &mut &haystack[..];
+        let (counts, none) = finder.preceding_byte_histogram(rdr).unwrap();
+        let count = DEFAULT_BUFFER_CAPACITY as u64;
+        assert_eq!(none, 1);
+        assert_eq!(counts[b'a' as usize], count);
+        assert_eq!(counts[b'b' as usize], count);
+        assert_eq!(counts[b'\n' as usize], count);
+
+        let rdr = &mut &haystack[..];
+        let (matches, meta) = finder.scan_metadata(rdr).unwrap();
+        assert_eq!(matches, expected);
+        assert_eq!(meta.byte_count, haystack.len());
+
+        let rdr = &mut &haystack[..];
+        let lines = finder.find_all_with_lines(rdr).unwrap();
+        assert_eq!(lines.len(), expected.len());
+        assert_eq!(&lines[..4], &[(0, 1), (1, 1), (2, 1), (3, 2)]);
+        assert_eq!(
+            lines.last(),
+            Some(&(haystack.len(), DEFAULT_BUFFER_CAPACITY + 1))
+        );
+
+        let mut stream = Cursor::new(&haystack);
+        let mut iter = finder.rfind_iter(&mut stream).unwrap();
+        let mut offsets: Vec<usize> =
+            iter.by_ref().take(10).map(|x| x.unwrap()).collect();
+        let checkpoint = iter.save_position();
+        let mut iter = finder.rfind_iter(&mut stream).unwrap();
+        iter.restore_position(checkpoint);
+        offsets.extend(iter.map(|x| x.unwrap()));
+        offsets.reverse();
+        assert_eq!(offsets, expected);
+    }
+
+    #[test]
+    fn test_find_all_sorted_by() {
+        let haystack = b"ab---ab-ab-----ab--ab";
+        let mid = haystack.len() / 2;
+        let finder = StreamFinder::new(b"ab");
+
+        let all = finder.find_all(&mut Cursor::new(haystack)).unwrap();
+        assert_eq!(all, vec![0, 5, 8, 15, 19]);
+
+        let sorted = finder
+            .find_all_sorted_by(&mut Cursor::new(haystack), |pos| {
+                (pos as isize - mid as isize).abs()
+            })
+            .unwrap();
+        assert_eq!(sorted, vec![8, 5, 15, 19, 0]);
+    }
+
+    #[test]
+    fn test_find_iter_with_partial() {
+        let finder = StreamFinder::new(b"dear");
+        let collect = |haystack: &[u8]| -> Vec<Event> {
+            finder
+                .find_iter_with_partial(&mut Cursor::new(haystack))
+                .map(|x| x.unwrap())
+                .collect()
+        };
+
+        assert_eq!(
+            collect(b"dear me, de"),
+            vec![
+                Event::Match(0),
+                Event::PartialTail { offset: 9, matched_len: 2 }
+            ]
+        );
+        assert_eq!(collect(b"oh dear"), vec![Event::Match(3)]);
+        assert_eq!(collect(b"dear"), vec![Event::Match(0)]);
+        assert_eq!(
+            collect(b"d"),
+            vec![Event::PartialTail { offset: 0, matched_len: 1 }]
+        );
+        assert_eq!(collect(b""), vec![]);
+
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 2];
+        haystack.extend_from_slice(b"dearde");
+        assert_eq!(
+            collect(&haystack),
+            vec![
+                Event::Match(DEFAULT_BUFFER_CAPACITY * 2),
+                Event::PartialTail {
+                    offset: DEFAULT_BUFFER_CAPACITY * 2 + 4,
+                    matched_len: 2
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_iter_with_partial_no_overlap() {
+        // The trailing "aa" overlaps the full match, so it's not a partial match on its own.
+        let finder = StreamFinder::new(b"aaa");
+        let events: Vec<Event> = finder
+            .find_iter_with_partial(&mut Cursor::new(b"aaaa"))
+            .map(|x| x.unwrap())
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                Event::Match(0),
+                Event::PartialTail { offset: 3, matched_len: 1 }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_into_offsets() {
+        let mut f = std::fs::File::open("data/pride-and-prejudice.txt")
+            .expect("test file not found");
+        let mut buf = Vec::new();
+        f.read_to_end(&mut buf).unwrap();
+        let expected: Vec<usize> = memmem::find_iter(&buf, b"dear").collect();
+
+        let mut stream = Cursor::new(&buf);
+        let offsets = find_iter(b"dear", &mut stream)
+            .with_stream_len()
+            .unwrap()
+            .into_offsets()
+            .unwrap();
+        assert_eq!(offsets, expected);
+        assert!(offsets.capacity() >= MAX_RESERVED_OFFSETS);
+
+        // The reservation is capped, rather than taking up several times the size of the stream
+        // for a short needle.
+        let mut stream = Cursor::new(&buf);
+        let offsets = find_iter(b"\x01", &mut stream)
+            .with_stream_len()
+            .unwrap()
+            .into_offsets()
+            .unwrap();
+        assert_eq!(offsets, vec![]);
+        assert_eq!(offsets.capacity(), MAX_RESERVED_OFFSETS);
+
+        let mut stream = Cursor::new(b"dear");
+        let offsets = find_iter(b"", &mut stream)
+            .with_stream_len()
+            .unwrap()
+            .into_offsets()
+            .unwrap();
+        assert_eq!(offsets, vec![0, 1, 2, 3, 4]);
+        assert!(offsets.capacity() >= 5);
+
+        // Readers without `Seek` just grow the `Vec` as needed.
+        let offsets = find_iter(b"dear", &mut ReadOnly(&buf[..]))
+            .into_offsets()
+            .unwrap();
+        assert_eq!(offsets, expected);
+    }
+
+    #[test]
+    fn test_with_stream_len_after_reads() {
+        let mut stream = Cursor::new(vec![b'a'; DEFAULT_BUFFER_CAPACITY * 3]);
+        let mut iter = find_iter(b"aa", &mut stream);
+        assert_eq!(iter.next().transpose().unwrap(), Some(0));
+
+        let iter = iter.with_stream_len().unwrap();
+        assert_eq!(
+            iter.size_hint(),
+            (0, Some(DEFAULT_BUFFER_CAPACITY * 3 / 2 - 1))
+        );
+        assert_eq!(iter.count(), DEFAULT_BUFFER_CAPACITY * 3 / 2 - 1);
+
+        // An empty needle matches at every remaining offset, including the end.
+        let mut stream = Cursor::new(vec![b'a'; DEFAULT_BUFFER_CAPACITY * 3]);
+        let mut iter = find_iter(b"", &mut stream);
+        assert_eq!(iter.next().transpose().unwrap(), Some(0));
+
+        let mut iter = iter.with_stream_len().unwrap();
+        assert_eq!(iter.size_hint(), (0, Some(DEFAULT_BUFFER_CAPACITY * 3)));
+        assert_eq!(iter.by_ref().count(), DEFAULT_BUFFER_CAPACITY * 3);
+        assert_eq!(iter.size_hint(), (0, Some(0)));
+    }
+
+    #[test]
+    fn test_density_iter() {
+        // 3 matches in the first window, none in the second, 1 spanning the boundary of the third
+        // and fourth window, and a short last window.
+        let mut haystack = vec![b'-'; 4000 * 4 + 100];
+        for &pos in &[0, 10, 3996, 11998, 16050] {
+            haystack[pos..pos + 4].copy_from_slice(b"dear");
+        }
+        let finder = StreamFinder::new(b"dear");
+
+        let density: Vec<(usize, usize)> = finder
+            .density_iter(&mut Cursor::new(&haystack), 4000)
+            .map(|x| x.unwrap())
+            .collect();
+        assert_eq!(
+            density,
+            vec![(0, 3), (4000, 0), (8000, 1), (12000, 0), (16000, 1)]
+        );
+    }
+
+    #[test]
+    fn test_density_iter_empty_and_exact() {
+        let finder = StreamFinder::new(b"ab");
+
+        let density: Vec<(usize, usize)> = finder
+            .density_iter(&mut Cursor::new(b""), 4)
+            .map(|x| x.unwrap())
+            .collect();
+        assert_eq!(density, vec![]);
+
+        let density: Vec<(usize, usize)> = finder
+            .density_iter(&mut Cursor::new(b"ab--ab--"), 4)
+            .map(|x| x.unwrap())
+            .collect();
+        assert_eq!(density, vec![(0, 1), (4, 1)]);
+    }
+
+    #[test]
+    fn test_builder_max_needle_len() {
+        let mut builder = StreamFinderBuilder::new();
+        builder.max_needle_len(4);
+
+        let finder = builder.build(b"dear").unwrap();
+        assert_eq!(finder.needle(), b"dear");
+        assert!(builder.build(b"").is_ok());
+        match builder.build(b"dears") {
+            Err(SearchError::NeedleTooLong { len: 5, max: 4 }) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        let needle = vec![0; 1 << 20];
+        assert!(StreamFinderBuilder::new().build(&needle).is_ok());
+    }
+
+    #[test]
+    fn test_find_iter_from_current() {
+        let mut f = std::fs::File::open("data/pride-and-prejudice.txt")
+            .expect("test file not found");
+        let mut buf = Vec::new();
+        f.read_to_end(&mut buf).unwrap();
+        let expected: Vec<usize> = memmem::find_iter(&buf[100..], b"dear")
+            .map(|pos| pos + 100)
+            .collect();
+
+        f.seek(SeekFrom::Start(0)).unwrap();
+        f.read_exact(&mut [0; 100]).unwrap();
+        let finder = StreamFinder::new(b"dear");
+        let matches: Vec<usize> = finder
+            .find_iter_from_current(&mut f)
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect();
+        assert_eq!(matches, expected);
+
+        // The upper bound of matches takes the skipped bytes into account.
+        let mut stream = Cursor::new(vec![b'a'; 100]);
+        stream.read_exact(&mut [0; 40]).unwrap();
+        let iter = finder.find_iter_from_current(&mut stream).unwrap();
+        assert_eq!(iter.with_stream_len().unwrap().size_hint(), (0, Some(15)));
+    }
+
+    fn record_starts(
+        needle: &[u8],
+        delim: &[u8],
+        haystack: &[u8],
+    ) -> Vec<(usize, usize)> {
+        StreamFinder::new(needle)
+            .find_with_record_start_iter(&mut Cursor::new(haystack), delim)
+            .map(|x| x.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_find_with_record_start_iter() {
+        let haystack =
+            b"first line\nan error in the middle\nok\nerror, error\n";
+        assert_eq!(
+            record_starts(b"error", b"\n", haystack),
+            vec![(14, 11), (37, 37), (44, 37)]
+        );
+        assert_eq!(record_starts(b"line", b"\n", haystack), vec![(6, 0)]);
+        assert_eq!(record_starts(b"error", b"\r\n", haystack)[2], (44, 0));
+        // The delimiter must end before the match.
+        assert_eq!(record_starts(b"b\n", b"\n", b"a\nb\nc"), vec![(2, 2)]);
+    }
+
+    #[test]
+    fn test_find_with_record_start_iter_long_records() {
+        let mut haystack = Vec::new();
+        let mut expected = Vec::new();
+        for i in 0..10 {
+            let record_start = haystack.len();
+            haystack
+                .extend(repeat(&b'-').take(DEFAULT_BUFFER_CAPACITY / 3 * i));
+            expected.push((haystack.len(), record_start));
+            haystack.extend_from_slice(b"needle");
+            haystack.extend(repeat(&b'-').take(DEFAULT_BUFFER_CAPACITY / 2));
+            expected.push((haystack.len(), record_start));
+            haystack.extend_from_slice(b"needle--<>");
+        }
+        assert_eq!(record_starts(b"needle", b"<>", &haystack), expected);
+    }
+
+    #[test]
+    fn test_find_with_record_start_iter_from_current() {
+        let mut stream = Cursor::new(b"a\nxneedle\nneedle");
+        stream.set_position(3);
+        let matches: Vec<(usize, usize)> = StreamFinder::new(b"needle")
+            .find_with_record_start_iter(&mut stream, b"\n")
+            .map(|x| x.unwrap())
+            .collect();
+        assert_eq!(matches, vec![(0, 0), (7, 7)]);
+    }
+
+    #[test]
+    fn test_find_file_region() {
+        let path = "data/pride-and-prejudice.txt";
+        let len = std::fs::metadata(path).unwrap().len();
+        let mut f = File::open(path).unwrap();
+        let all = find_iter(b"Elizabeth", &mut f).into_offsets().unwrap();
+
+        let regions = [0..len, 0..100000, 123457..350001, len - 10..len, 5..5];
+        for region in regions.iter().cloned() {
+            let offsets =
+                find_file_region(path, b"Elizabeth", region.clone()).unwrap();
+            let expected: Vec<usize> = all
+                .iter()
+                .cloned()
+                .filter(|&pos| {
+                    pos as u64 >= region.start && pos as u64 + 9 <= region.end
+                })
+                .collect();
+            assert_eq!(offsets, expected, "region: {:?}", region);
+        }
+    }
+
+    #[test]
+    fn test_find_file_region_out_of_bounds() {
+        let path = "data/pride-and-prejudice.txt";
+        let len = std::fs::metadata(path).unwrap().len();
+        let err =
+            find_file_region(path, b"Elizabeth", 0..len + 1).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let inner = err.get_ref().unwrap().downcast_ref::<SearchError>();
+        assert!(matches!(inner, Some(SearchError::RegionOutOfBounds { .. })));
+    }
+
+    #[test]
+    fn test_followed_by() {
+        let finder = StreamFinder::new(b"error").followed_by(b": ");
+        let haystack = b"error: a, errors, error:b, error:: error: ";
+        assert_eq!(
+            finder.find_all(&mut Cursor::new(&haystack[..])).unwrap(),
+            vec![0, 35]
+        );
+        // The suffix doesn't fit at the end of the stream.
+        assert_eq!(
+            finder.find_all(&mut Cursor::new(&b"error: error:"[..])).unwrap(),
+            vec![0]
+        );
+        // The next match may start inside the suffix.
+        let finder = StreamFinder::new(b"ab").followed_by(b"ab");
+        assert_eq!(
+            finder.find_all(&mut Cursor::new(&b"ababab"[..])).unwrap(),
+            vec![0, 2]
+        );
+    }
+
+    #[test]
+    fn test_followed_by_across_rolls() {
+        let finder = StreamFinder::new(b"error").followed_by(b": ");
+        for shift in 0..8 {
+            let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 3];
+            let pos = DEFAULT_BUFFER_CAPACITY - shift;
+            haystack[pos..pos + 7].copy_from_slice(b"error: ");
+            haystack[pos + 7..pos + 13].copy_from_slice(b"error;");
+            let end = haystack.len() - 7;
+            haystack[end..].copy_from_slice(b"error: ");
+
+            let matches =
+                finder.find_all(&mut Cursor::new(&haystack)).unwrap();
+            assert_eq!(matches, vec![pos, end], "shift: {}", shift);
+        }
+    }
+
+    #[test]
+    fn test_preceded_by() {
+        let finder = StreamFinder::new(b"com").preceded_by(b".");
+        let haystack = b"com telecom example.com .co.com";
+        assert_eq!(
+            finder.find_all(&mut Cursor::new(&haystack[..])).unwrap(),
+            vec![20, 28]
+        );
+        // The prefix may overlap the previous match.
+        let finder = StreamFinder::new(b"a").preceded_by(b"a");
+        assert_eq!(
+            finder.find_all(&mut Cursor::new(&b"aaab"[..])).unwrap(),
+            vec![1, 2]
+        );
+        let finder =
+            StreamFinder::new(b"b").preceded_by(b"a").followed_by(b"c");
+        assert_eq!(
+            finder.find_all(&mut Cursor::new(&b"abc bc abd abc"[..])).unwrap(),
+            vec![1, 12]
+        );
+    }
+
+    #[test]
+    fn test_preceded_by_across_rolls() {
+        let finder = StreamFinder::new(b"com").preceded_by(b"example.");
+        for shift in 0..12 {
+            let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 3];
+            let pos = DEFAULT_BUFFER_CAPACITY - shift;
+            haystack[pos..pos + 11].copy_from_slice(b"example.com");
+            haystack[pos + 20..pos + 27].copy_from_slice(b"telecom");
+
+            let matches =
+                finder.find_all(&mut Cursor::new(&haystack)).unwrap();
+            assert_eq!(matches, vec![pos + 8], "shift: {}", shift);
+        }
+    }
+
+    #[test]
+    fn test_find_windows_iter() {
+        let mut f = File::open("data/pride-and-prejudice.txt").unwrap();
+        let mut haystack = Vec::new();
+        f.read_to_end(&mut haystack).unwrap();
+
+        for &window in &[0, 9, 64, DEFAULT_BUFFER_CAPACITY * 3] {
+            let finder = StreamFinder::new(b"Elizabeth");
+            let windows: Vec<(usize, Vec<u8>)> = finder
+                .find_windows_iter(&mut Cursor::new(&haystack), window)
+                .map(|x| x.unwrap())
+                .collect();
+            let expected: Vec<usize> =
+                memmem::find_iter(&haystack, b"Elizabeth").collect();
+            assert_eq!(windows.len(), expected.len());
+            for ((pos, bytes), expected) in windows.into_iter().zip(expected) {
+                assert_eq!(pos, expected);
+                let end = cmp::min(pos + window, haystack.len());
+                assert_eq!(bytes, &haystack[pos..end], "window: {}", window);
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_windows_iter_clamped_at_eof() {
+        let finder = StreamFinder::new(b"ab");
+        let windows: Vec<(usize, Vec<u8>)> = finder
+            .find_windows_iter(&mut Cursor::new(b"ab-ab-ab"), 4)
+            .map(|x| x.unwrap())
+            .collect();
+        assert_eq!(
+            windows,
+            vec![
+                (0, b"ab-a".to_vec()),
+                (3, b"ab-a".to_vec()),
+                (6, b"ab".to_vec())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_windows_iter_unbounded_window() {
+        let haystack = haystack_with_edges(b"ab", DEFAULT_BUFFER_CAPACITY * 3);
+        let finder = StreamFinder::new(b"ab");
+        let rdr = &mut FaultReader::new(&haystack[..]).short_reads(1000);
+        let windows: Vec<(usize, Vec<u8>)> = finder
+            .find_windows_iter(rdr, usize::MAX)
+            .map(|x| x.unwrap())
+            .collect();
+        let expected: Vec<usize> =
+            memmem::find_iter(&haystack, b"ab").collect();
+        assert_eq!(windows.len(), expected.len());
+        for ((pos, bytes), expected) in windows.into_iter().zip(expected) {
+            assert_eq!(pos, expected);
+            assert_eq!(bytes, &haystack[pos..]);
+        }
+    }
+
+    #[cfg(feature = "crc")]
+    #[test]
+    fn test_finish_crc() {
+        let mut f = File::open("data/pride-and-prejudice.txt").unwrap();
+        let mut haystack = Vec::new();
+        f.read_to_end(&mut haystack).unwrap();
+        let expected: Vec<usize> =
+            memmem::find_iter(&haystack, b"Elizabeth").collect();
+
+        let mut stream = Cursor::new(&haystack);
+        let mut iter = find_iter(b"Elizabeth", &mut stream).with_crc();
+        // Already yielded matches are not returned, but still checksummed.
+        assert_eq!(iter.next().unwrap().unwrap(), expected[0]);
+        let (offsets, crc) = iter.finish_crc().unwrap();
+        assert_eq!(offsets, &expected[1..]);
+        assert_eq!(crc, crc32fast::hash(&haystack));
+
+        // A stream that doesn't fill the buffer.
+        let (offsets, crc) = find_iter(b"ab", &mut Cursor::new(b"xxabx"))
+            .with_crc()
+            .finish_crc()
+            .unwrap();
+        assert_eq!((offsets, crc), (vec![2], crc32fast::hash(b"xxabx")));
+
+        let finder = StreamFinder::new(b"Elizabeth").followed_by(b" ");
+        let (_, crc) = finder
+            .find_iter(&mut Cursor::new(&haystack))
+            .with_crc()
+            .finish_crc()
+            .unwrap();
+        assert_eq!(crc, crc32fast::hash(&haystack));
+    }
+
+    #[cfg(feature = "crc")]
+    #[test]
+    fn test_crc_is_opt_in() {
+        let mut stream = Cursor::new(b"rusty rust");
+        let mut iter = find_iter(b"rust", &mut stream);
+        assert_eq!(iter.by_ref().count(), 2);
+        assert!(iter.crc.is_none());
+    }
+
+    #[cfg(feature = "crc")]
+    #[test]
+    #[should_panic]
+    fn test_with_crc_after_reading() {
+        let mut stream = Cursor::new(b"rusty rust");
+        let mut iter = find_iter(b"rust", &mut stream);
+        iter.next();
+        iter.with_crc();
+    }
+
+    #[cfg(feature = "crc")]
+    #[test]
+    #[should_panic]
+    fn test_finish_crc_without_with_crc() {
+        let _ = find_iter(b"rust", &mut Cursor::new(b"rust")).finish_crc();
+    }
+
+    #[test]
+    fn test_find_in_prefix() {
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 2];
+        haystack[DEFAULT_BUFFER_CAPACITY..][..4].copy_from_slice(b"\x7fELF");
+        let end = DEFAULT_BUFFER_CAPACITY + 4;
+
+        let mut stream = Cursor::new(&haystack);
+        assert_eq!(
+            find_in_prefix(b"\x7fELF", &mut stream, end).unwrap(),
+            Some(DEFAULT_BUFFER_CAPACITY)
+        );
+        // Nothing is read past the prefix.
+        assert_eq!(stream.position(), end as u64);
+
+        let mut stream = Cursor::new(&haystack);
+        assert_eq!(
+            find_in_prefix(b"\x7fELF", &mut stream, end - 1).unwrap(),
+            None
+        );
+        assert_eq!(stream.position(), end as u64 - 1);
+
+        let mut stream = Cursor::new(&haystack);
+        assert_eq!(find_in_prefix(b"\x7fELF", &mut stream, 0).unwrap(), None);
+    }
+
+    #[test]
+    fn test_find_iter_tee() {
+        let mut f = File::open("data/pride-and-prejudice.txt").unwrap();
+        let mut haystack = Vec::new();
+        f.read_to_end(&mut haystack).unwrap();
+
+        let mut copy = Vec::new();
+        let matches: Vec<usize> = StreamFinder::new(b"Elizabeth")
+            .find_iter_tee(&mut Cursor::new(&haystack), &mut copy)
+            .map(|x| x.unwrap())
+            .collect();
+        let expected: Vec<usize> =
+            memmem::find_iter(&haystack, b"Elizabeth").collect();
+        assert_eq!(matches, expected);
+        assert!(copy == haystack);
+    }
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_find_iter_tee_write_error() {
+        let finder = StreamFinder::new(b"rust");
+        let mut stream = Cursor::new(b"rusty rust");
+        let mut wtr = FailingWriter;
+        let mut iter = finder.find_iter_tee(&mut stream, &mut wtr);
+        let err = iter.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    fn fault_readers(haystack: &[u8]) -> Vec<FaultReader<Cursor<&[u8]>>> {
+        vec![
+            FaultReader::new(Cursor::new(haystack)).short_reads(1),
+            FaultReader::new(Cursor::new(haystack)).short_reads(7),
+            FaultReader::new(Cursor::new(haystack)).interrupt_every(2),
+            FaultReader::new(Cursor::new(haystack))
+                .short_reads(3)
+                .interrupt_every(5),
+        ]
+    }
+
+    #[test]
+    fn test_find_iter_short_and_interrupted_reads() {
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 2];
+        for pos in (0..haystack.len() - 4).step_by(333) {
+            haystack[pos..pos + 4].copy_from_slice(b"dear");
+        }
+        let expected: Vec<usize> =
+            memmem::find_iter(&haystack, b"dear").collect();
+
+        for mut stream in fault_readers(&haystack) {
+            let matches = find_iter(b"dear", &mut stream).into_offsets();
+            assert_eq!(matches.unwrap(), expected, "{:?}", stream);
+        }
+
+        for mut stream in fault_readers(&haystack) {
+            let windows: Vec<(usize, Vec<u8>)> = StreamFinder::new(b"dear")
+                .find_windows_iter(&mut stream, 400)
+                .map(|x| x.unwrap())
+                .collect();
+            assert_eq!(windows.len(), expected.len());
+            for (pos, bytes) in windows {
+                let end = cmp::min(pos + 400, haystack.len());
+                assert_eq!(bytes, &haystack[pos..end]);
+            }
+        }
+
+        for mut stream in fault_readers(b"key=value") {
+            let (key, pos) =
+                StreamFinder::new(b"=").find_and_split(&mut stream).unwrap();
+            assert_eq!((&key[..], pos), (&b"key"[..], Some(3)));
+        }
+    }
+
+    #[test]
+    fn test_find_iter_error_at() {
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 2];
+        haystack[100..104].copy_from_slice(b"dear");
+        haystack[DEFAULT_BUFFER_CAPACITY + 100..][..4]
+            .copy_from_slice(b"dear");
+
+        let mut stream = FaultReader::new(Cursor::new(&haystack[..]))
+            .error_at(DEFAULT_BUFFER_CAPACITY + 102);
+        let mut iter = find_iter(b"dear", &mut stream);
+        assert_eq!(iter.next().unwrap().unwrap(), 100);
+        let err = iter.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+    }
+
+    fn enclosing_block(haystack: &[u8]) -> Option<Range<usize>> {
+        StreamFinder::new(b"needle")
+            .enclosing_block(&mut Cursor::new(haystack), b"{", b"}")
+            .unwrap()
+    }
+
+    #[test]
+    fn test_enclosing_block() {
+        assert_eq!(enclosing_block(b"{ a { needle } b }"), Some(4..14));
+        assert_eq!(enclosing_block(b"{ {a} needle {b} }"), Some(0..18));
+        assert_eq!(enclosing_block(b"{a} needle {b}"), None);
+        assert_eq!(enclosing_block(b"{ needle"), None);
+        assert_eq!(enclosing_block(b"needle }"), None);
+        assert_eq!(enclosing_block(b"{ a }"), None);
+        // Only the first match counts.
+        assert_eq!(enclosing_block(b"needle { needle }"), None);
+    }
+
+    #[test]
+    fn test_enclosing_block_long_distances() {
+        let mut haystack = Vec::new();
+        haystack.extend_from_slice(b"BEGIN BEGIN END");
+        haystack.extend(repeat(&b'-').take(DEFAULT_BUFFER_CAPACITY * 2));
+        haystack.extend_from_slice(b"BEGIN ");
+        let block_start = haystack.len();
+        haystack.extend_from_slice(b"BEGIN BEGIN END ");
+        haystack.extend(repeat(&b'-').take(DEFAULT_BUFFER_CAPACITY * 3));
+        haystack.extend_from_slice(b" BEGIN END needle BEGIN END");
+        haystack.extend(repeat(&b'-').take(DEFAULT_BUFFER_CAPACITY));
+        haystack.extend_from_slice(b"END");
+        let block_end = haystack.len();
+        haystack.extend_from_slice(b" END END");
+
+        let mut stream = Cursor::new(&haystack);
+        let block = StreamFinder::new(b"needle")
+            .enclosing_block(&mut stream, b"BEGIN", b"END")
+            .unwrap();
+        assert_eq!(block, Some(block_start..block_end));
+    }
+
+    #[test]
+    fn test_estimate_count_exact_for_short_streams() {
+        let finder = StreamFinder::new(b"rust");
+        let mut stream = Cursor::new(b"xx rusty rust".to_vec());
+        stream.set_position(3);
+        assert_eq!(finder.estimate_count(&mut stream, 100).unwrap(), 2);
+        stream.set_position(3);
+        assert_eq!(finder.estimate_count(&mut stream, 10).unwrap(), 2);
+        let mut stream = Cursor::new(b"");
+        assert_eq!(finder.estimate_count(&mut stream, 1).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_estimate_count_short_sample() {
+        // A sample shorter than the needle is raised to its length.
+        let mut stream = Cursor::new(b"rust".repeat(100));
+        let estimate =
+            StreamFinder::new(b"rust").estimate_count(&mut stream, 2).unwrap();
+        assert_eq!(estimate, 100);
+
+        // An empty needle matches at every offset of the windows, but isn't counted twice where
+        // two windows meet.
+        let mut stream = Cursor::new(vec![b'-'; 100]);
+        let estimate =
+            StreamFinder::new(b"").estimate_count(&mut stream, 10).unwrap();
+        assert_eq!(estimate, 100);
+        let mut stream = Cursor::new(vec![b'-'; 100]);
+        let estimate =
+            StreamFinder::new(b"").estimate_count(&mut stream, 100).unwrap();
+        assert_eq!(estimate, 101);
+    }
+
+    #[test]
+    fn test_estimate_count_spreads_sample() {
+        // All matches are in the second half, so a sample taken only from the start would
+        // estimate 0.
+        let mut haystack = vec![b'-'; 100_000];
+        haystack.extend(b"rust--".repeat(10_000));
+        let mut stream = Cursor::new(&haystack);
+        let estimate = StreamFinder::new(b"rust")
+            .estimate_count(&mut stream, 8000)
+            .unwrap();
+        assert!((8000..=12_000).contains(&estimate), "{}", estimate);
+    }
+
+    #[test]
+    fn test_filter_offsets() {
+        let haystack =
+            b"ab ab ab ab ab ab".repeat(DEFAULT_BUFFER_CAPACITY / 8);
+        let wanted: BTreeSet<usize> =
+            [0, 3, 15, 17 * 21 + 12, haystack.len() - 2]
+                .iter()
+                .copied()
+                .collect();
+
+        let mut stream = Cursor::new(&haystack);
+        let offsets = find_iter(b"ab", &mut stream)
+            .filter_offsets(|pos| wanted.contains(&pos))
+            .collect::<io::Result<BTreeSet<_>>>()
+            .unwrap();
+        assert_eq!(offsets, wanted);
+    }
+
+    #[test]
+    fn test_filter_offsets_passes_errors_through() {
+        let mut stream = FaultReader::new(Cursor::new(b"ab ab ab ab"))
+            .short_reads(3)
+            .error_at(7);
+        let mut iter = find_iter(b"ab", &mut stream).filter_offsets(|_| false);
+        assert!(iter.next().unwrap().is_err());
+    }
+
+    /// A stream that can't seek relative to its end.
+    struct NoSeekFromEnd<R>(R);
+
+    impl<R: Read> Read for NoSeekFromEnd<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl<R: Seek> Seek for NoSeekFromEnd<R> {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            match pos {
+                SeekFrom::End(_) => panic!("seeked relative to the end"),
+                _ => self.0.seek(pos),
+            }
+        }
+    }
+
+    #[test]
+    fn test_rfind_iter_with_len() {
+        let mut haystack =
+            haystack_with_edges(b"dear", DEFAULT_BUFFER_CAPACITY * 3 + 7);
+        haystack[DEFAULT_BUFFER_CAPACITY..][..4].copy_from_slice(b"dear");
+        for needle in [&b"dear"[..], b"d", b"absent"] {
+            let finder = StreamFinder::new(needle);
+            let expected = finder
+                .rfind_iter(&mut Cursor::new(&haystack))
+                .unwrap()
+                .collect::<io::Result<Vec<_>>>()
+                .unwrap();
+
+            let mut stream = NoSeekFromEnd(Cursor::new(&haystack));
+            let matches = finder
+                .rfind_iter_with_len(&mut stream, haystack.len())
+                .collect::<io::Result<Vec<_>>>()
+                .unwrap();
+            assert_eq!(matches, expected);
+        }
+    }
+
+    #[test]
+    fn test_line_offset() {
+        let mut haystack = Vec::new();
+        let mut starts = Vec::new();
+        for i in 0..DEFAULT_BUFFER_CAPACITY / 4 {
+            starts.push(haystack.len());
+            haystack.extend(repeat(&b'x').take(i % 17));
+            haystack.push(b'\n');
+        }
+        starts.push(haystack.len());
+
+        let line_offset_in =
+            |line| line_offset(&mut Cursor::new(&haystack), line).unwrap();
+        assert_eq!(line_offset_in(1), Some(0));
+        assert_eq!(line_offset_in(2), Some(starts[1]));
+        assert_eq!(line_offset_in(1000), Some(starts[999]));
+        assert_eq!(line_offset_in(starts.len()), Some(haystack.len()));
+        assert_eq!(line_offset_in(starts.len() + 1), None);
+        assert_eq!(line_offset(&mut Cursor::new(b""), 1).unwrap(), Some(0));
+        assert_eq!(line_offset(&mut Cursor::new(b""), 2).unwrap(), None);
+    }
+
+    #[test]
+    fn test_line_offset_error() {
+        let mut stream =
+            FaultReader::new(Cursor::new(b"a\nb\nc\n")).error_at(3);
+        assert!(line_offset(&mut stream, 3).is_err());
+    }
+
+    #[test]
+    fn test_match_context() {
+        let mut haystack = Vec::new();
+        for i in 0..DEFAULT_BUFFER_CAPACITY / 4 {
+            haystack.extend_from_slice(format!("<{}>dear ", i).as_bytes());
+        }
+        let finder = StreamFinder::new(b"dear");
+        let mut stream = Cursor::new(&haystack);
+
+        let (pos, context) =
+            finder.match_context(&mut stream, 0, 10, 3).unwrap().unwrap();
+        assert_eq!(pos, 3);
+        assert_eq!(context, b"<0>dear <1");
+
+        stream.set_position(0);
+        let (pos, context) =
+            finder.match_context(&mut stream, 3, 3, 3).unwrap().unwrap();
+        assert_eq!(pos, 27);
+        assert_eq!(context, b"<3>dear <4");
+
+        stream.set_position(0);
+        let n = DEFAULT_BUFFER_CAPACITY / 4 - 1;
+        let (pos, context) =
+            finder.match_context(&mut stream, n, 0, 10).unwrap().unwrap();
+        assert_eq!(pos, haystack.len() - 5);
+        assert_eq!(context, b"dear ");
+
+        stream.set_position(0);
+        let (pos, context) = finder
+            .match_context(&mut stream, n, 0, usize::MAX)
+            .unwrap()
+            .unwrap();
+        assert_eq!(pos, haystack.len() - 5);
+        assert_eq!(context, b"dear ");
+
+        stream.set_position(0);
+        assert_eq!(
+            finder.match_context(&mut stream, n + 1, 1, 1).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_match_context_relative_to_position() {
+        let mut stream = Cursor::new(b"dear xx dear yy dear".to_vec());
+        stream.set_position(3);
+        let context =
+            StreamFinder::new(b"dear").match_context(&mut stream, 0, 2, 2);
+        assert_eq!(context.unwrap(), Some((5, b"x dear y".to_vec())));
+    }
+
+    #[test]
+    fn test_into_set() {
+        let haystack =
+            haystack_with_edges(b"dear", DEFAULT_BUFFER_CAPACITY * 2);
+        let finder = StreamFinder::new(b"dear");
+        let offsets = finder.find_all(&mut Cursor::new(&haystack)).unwrap();
+        let set =
+            finder.find_iter(&mut Cursor::new(&haystack)).into_set().unwrap();
+        assert_eq!(set.into_iter().collect::<Vec<_>>(), offsets);
+
+        let mut stream =
+            FaultReader::new(Cursor::new(&haystack)).error_at(100);
+        assert!(finder.find_iter(&mut stream).into_set().is_err());
+    }
+
+    #[test]
+    fn test_rfind_iter_from_end() {
+        let mut haystack =
+            haystack_with_edges(b"dear", DEFAULT_BUFFER_CAPACITY * 3);
+        haystack[DEFAULT_BUFFER_CAPACITY + 5..][..4].copy_from_slice(b"dear");
+        let finder = StreamFinder::new(b"dear");
+
+        let offsets = finder
+            .rfind_iter(&mut Cursor::new(&haystack))
+            .unwrap()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        let from_end = finder
+            .rfind_iter_from_end(&mut Cursor::new(&haystack))
+            .unwrap()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(from_end.len(), 3);
+        let ends: Vec<usize> =
+            from_end.iter().map(|&d| haystack.len() - d).collect();
+        let expected: Vec<usize> =
+            offsets.iter().map(|&pos| pos + 4).collect();
+        assert_eq!(ends, expected);
+        assert_eq!(from_end[0], 0);
+
+        let mut stream = Cursor::new(b"de");
+        let mut iter = finder.rfind_iter_from_end(&mut stream).unwrap();
+        assert!(iter.next().is_none());
+    }
+
+    /// A reader that generates a long stream on the fly without allocating, which contains
+    /// `needle` at the start of every `period` bytes and `-` everywhere else.
+    struct Generated {
+        needle: &'static [u8],
+        period: u64,
+        pos: u64,
+        len: u64,
+        /// The size of the largest buffer passed to `read`.
+        max_read: usize,
+    }
+
+    impl Read for Generated {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.max_read = cmp::max(self.max_read, buf.len());
+            let n = cmp::min(buf.len() as u64, self.len - self.pos) as usize;
+            let buf = &mut buf[..n];
+            buf.fill(b'-');
+            for (i, b) in buf.iter_mut().enumerate() {
+                let in_period = (self.pos + i as u64) % self.period;
+                if let Some(&nb) = self.needle.get(in_period as usize) {
+                    *b = nb;
+                }
+            }
+            self.pos += n as u64;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_memory_stays_bounded_on_huge_streams() {
+        const LEN: u64 = 100 * (1 << 20);
+        const PERIOD: u64 = 4096 + 7;
+        let mut stream = Generated {
+            needle: b"dear",
+            period: PERIOD,
+            pos: 0,
+            len: LEN,
+            max_read: 0,
+        };
+
+        let mut iter = find_iter(b"dear", &mut stream);
+        let mut count = 0;
+        while let Some(pos) = iter.next() {
+            assert_eq!(pos.unwrap() as u64 % PERIOD, 0);
+            count += 1;
+            if count % 1000 == 0 {
+                let allocated = iter.searcher.buf.allocated_len();
+                assert!(allocated <= DEFAULT_BUFFER_CAPACITY, "{}", allocated);
+            }
+        }
+        assert!(iter.searcher.buf.allocated_len() <= DEFAULT_BUFFER_CAPACITY);
+        drop(iter);
+        assert_eq!(count, (LEN - 4) / PERIOD + 1);
+        assert_eq!(stream.pos, LEN);
+        // The bound holds with the buffer at its full capacity.
+        assert_eq!(stream.max_read, DEFAULT_BUFFER_CAPACITY - 4);
+    }
+
+    #[test]
+    fn test_find_cluster() {
+        let mut haystack =
+            haystack_with_edges(b"dear", DEFAULT_BUFFER_CAPACITY * 2);
+        haystack[100..104].copy_from_slice(b"dear");
+        let finder = StreamFinder::new(b"dear");
+        let all = finder.find_all(&mut Cursor::new(&haystack)).unwrap();
+        assert_eq!(all.len(), 3);
+
+        let cluster = |count| {
+            finder.find_cluster(&mut Cursor::new(&haystack), count).unwrap()
+        };
+        assert_eq!(cluster(2), all[..2]);
+        assert_eq!(cluster(3), all);
+        assert_eq!(cluster(10), all);
+        assert_eq!(cluster(0), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_find_cluster_stops_reading() {
+        // Fails right after the first buffer, which holds the only needed match.
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 2];
+        haystack[10..14].copy_from_slice(b"dear");
+        let mut stream = FaultReader::new(Cursor::new(&haystack))
+            .short_reads(100)
+            .error_at(100);
+        let finder = StreamFinder::new(b"dear");
+        assert_eq!(finder.find_cluster(&mut stream, 1).unwrap(), vec![10]);
+        assert!(finder.find_cluster(&mut stream, 1).is_err());
+
+        let mut stream = FaultReader::new(Cursor::new(b"dear")).error_at(0);
+        assert_eq!(finder.find_cluster(&mut stream, 0).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_find_spans_iter() {
+        let mut haystack =
+            haystack_with_edges(b"dear", DEFAULT_BUFFER_CAPACITY * 2);
+        haystack[DEFAULT_BUFFER_CAPACITY - 2..][..4].copy_from_slice(b"dear");
+        let finder = StreamFinder::new(b"dear");
+        let offsets = finder.find_all(&mut Cursor::new(&haystack)).unwrap();
+
+        let spans = finder
+            .find_spans_iter(&mut Cursor::new(&haystack))
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        let mut rev_spans = finder
+            .rfind_spans_iter(&mut Cursor::new(&haystack))
+            .unwrap()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        rev_spans.reverse();
+        assert_eq!(spans, rev_spans);
+
+        assert_eq!(spans.iter().map(|s| s.start).collect::<Vec<_>>(), offsets);
+        for span in spans {
+            assert_eq!(span.end - span.start, 4);
+            assert_eq!(&haystack[span.as_range()], b"dear");
+            assert_eq!(crate::MatchSpan::from(span.as_range()), span);
+        }
+    }
+
+    #[test]
+    fn test_rev_checkpoint() {
+        let mut haystack = Vec::new();
+        for i in 0..DEFAULT_BUFFER_CAPACITY / 2 {
+            haystack.extend(repeat(&b'-').take(i % 11));
+            haystack.extend_from_slice(b"dear");
+        }
+        let finder = StreamFinder::new(b"dear");
+        let expected = finder
+            .rfind_iter(&mut Cursor::new(&haystack))
+            .unwrap()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+
+        for &skip in &[2, 500, expected.len()] {
+            let mut stream = Cursor::new(&haystack);
+            let mut iter = finder.rfind_iter(&mut stream).unwrap();
+            for _ in 0..skip {
+                iter.next().unwrap().unwrap();
+            }
+            let checkpoint = iter.save_position();
+            drop(iter);
+
+            let mut iter = finder.rfind_iter(&mut stream).unwrap();
+            iter.restore_position(checkpoint);
+            let remaining = iter.collect::<io::Result<Vec<_>>>().unwrap();
+            assert_eq!(remaining, expected[skip..]);
+        }
+    }
+
+    #[test]
+    fn test_coalesce_reads() {
+        let mut haystack = Vec::new();
+        for i in 0..DEFAULT_BUFFER_CAPACITY / 4 {
+            haystack.extend(repeat(&b'-').take(i % 7));
+            haystack.extend_from_slice(b"dear");
+        }
+        let expected: Vec<usize> =
+            memmem::find_iter(&haystack, b"dear").collect();
+
+        let mut searches = Vec::new();
+        for &min_chunk in &[0, 1, 3, 100, DEFAULT_BUFFER_CAPACITY * 2] {
+            let mut stream =
+                FaultReader::new(Cursor::new(&haystack)).short_reads(1);
+            let engine = CountingEngine::default();
+            let offsets = StreamFinder::new(b"dear")
+                .find_iter_with_engine(&mut stream, &engine)
+                .coalesce_reads(min_chunk)
+                .into_offsets()
+                .unwrap();
+            assert_eq!(offsets, expected);
+            searches.push(engine.searches.get());
+        }
+        // Coalescing doesn't change anything for chunks of up to a byte, but the bigger the
+        // chunks, the fewer searches are needed.
+        assert_eq!(searches[0], searches[1]);
+        assert!(searches[1] > searches[2], "searches: {:?}", searches);
+        assert!(searches[2] > searches[3], "searches: {:?}", searches);
+        assert!(searches[3] > searches[4], "searches: {:?}", searches);
+        assert!(searches[4] <= expected.len() * 2, "searches: {:?}", searches);
+    }
+
+    /// An engine that counts how many times it's been asked to search.
+    #[derive(Default)]
+    struct CountingEngine {
+        searches: std::cell::Cell<usize>,
+    }
+
+    impl MatchEngine for CountingEngine {
+        fn find(&self, haystack: &[u8], needle: &[u8]) -> Option<usize> {
+            self.searches.set(self.searches.get() + 1);
+            MemmemEngine.find(haystack, needle)
+        }
+
+        fn rfind(&self, haystack: &[u8], needle: &[u8]) -> Option<usize> {
+            self.searches.set(self.searches.get() + 1);
+            MemmemEngine.rfind(haystack, needle)
+        }
+    }
+
+    /// A naive engine that compares the needle at every position.
+    struct NaiveEngine;
+
+    impl MatchEngine for NaiveEngine {
+        fn find(&self, haystack: &[u8], needle: &[u8]) -> Option<usize> {
+            (0..=haystack.len().checked_sub(needle.len())?)
+                .find(|&i| haystack[i..].starts_with(needle))
+        }
+
+        fn rfind(&self, haystack: &[u8], needle: &[u8]) -> Option<usize> {
+            (0..=haystack.len().checked_sub(needle.len())?)
+                .rev()
+                .find(|&i| haystack[i..].starts_with(needle))
+        }
+    }
+
+    #[test]
+    fn test_custom_engine_matches_default() {
+        let needle = b"dear";
+        let mut haystack =
+            haystack_with_edges(needle, DEFAULT_BUFFER_CAPACITY * 3 + 5);
+        for &pos in
+            &[9, DEFAULT_BUFFER_CAPACITY - 2, DEFAULT_BUFFER_CAPACITY * 2]
+        {
+            haystack[pos..pos + needle.len()].copy_from_slice(needle);
+        }
+        let finder = StreamFinder::new(needle);
+
+        let expected = finder
+            .find_iter(&mut Cursor::new(&haystack))
+            .into_offsets()
+            .unwrap();
+        let actual = finder
+            .find_iter_with_engine(&mut Cursor::new(&haystack), NaiveEngine)
+            .into_offsets()
+            .unwrap();
+        assert_eq!(expected.len(), 5);
+        assert_eq!(actual, expected);
+
+        let expected = finder
+            .rfind_iter(&mut Cursor::new(&haystack))
+            .unwrap()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        let actual = finder
+            .rfind_iter_with_engine(&mut Cursor::new(&haystack), &NaiveEngine)
+            .unwrap()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    fn find_until(
+        needle: &[u8],
+        boundary: &[u8],
+        haystack: &[u8],
+    ) -> Vec<usize> {
+        StreamFinder::new(needle)
+            .find_until(&mut Cursor::new(haystack), boundary)
+            .map(|x| x.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_find_until_stops_at_boundary() {
+        let haystack = b"dear me, dear\n== END ==\ndear you";
+        assert_eq!(find_until(b"dear", b"== END ==", haystack), vec![0, 9]);
+        assert_eq!(
+            find_until(b"you", b"== END ==", haystack),
+            Vec::<usize>::new()
+        );
+        assert_eq!(find_until(b"dear", b"absent", haystack), vec![0, 9, 24]);
+        // A match that overlaps the boundary lies past its start.
+        assert_eq!(
+            find_until(b"dear\n=", b"== END ==", haystack),
+            Vec::<usize>::new()
+        );
+        assert_eq!(find_until(b"dear\n", b"== END ==", haystack), vec![9]);
+        assert_eq!(find_until(b"dear", b"", haystack), Vec::<usize>::new());
+        assert_eq!(
+            find_until(b"dear", b"dear", haystack),
+            Vec::<usize>::new()
+        );
+    }
+
+    #[test]
+    fn test_find_until_across_buffer_boundaries() {
+        let needle = b"dear";
+        let boundary = b"\n--- section ---\n";
+        for &boundary_pos in &[
+            DEFAULT_BUFFER_CAPACITY - 9,
+            DEFAULT_BUFFER_CAPACITY,
+            DEFAULT_BUFFER_CAPACITY * 3 + 1,
+        ] {
+            let mut haystack = haystack_with_edges(needle, boundary_pos * 2);
+            haystack[boundary_pos..boundary_pos + boundary.len()]
+                .copy_from_slice(boundary);
+            let before = boundary_pos - needle.len();
+            haystack[before..boundary_pos].copy_from_slice(needle);
+            let after = boundary_pos + boundary.len();
+            haystack[after..after + needle.len()].copy_from_slice(needle);
+
+            for k in 1..=3 {
+                let matches = StreamFinder::new(needle)
+                    .find_until(
+                        &mut FaultReader::new(&haystack[..])
+                            .short_reads(k)
+                            .interrupt_every(5),
+                        boundary,
+                    )
+                    .collect::<io::Result<Vec<_>>>()
+                    .unwrap();
+                assert_eq!(matches, vec![0, before]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_until_error() {
+        let mut rdr = FaultReader::new(&b"rusty rust"[..]).error_at(4);
+        let res = StreamFinder::new(b"rust")
+            .find_until(&mut rdr, b"---")
+            .collect::<io::Result<Vec<_>>>();
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_is_match_at() {
+        let mut stream = Cursor::new(b"rusty rust");
+        assert!(is_match_at(b"rust", &mut stream, 0).unwrap());
+        assert!(is_match_at(b"rust", &mut stream, 6).unwrap());
+        assert!(!is_match_at(b"rust", &mut stream, 1).unwrap());
+        // A truncated needle at the end doesn't match.
+        assert!(!is_match_at(b"rust!", &mut stream, 6).unwrap());
+        assert!(!is_match_at(b"rust", &mut stream, 100).unwrap());
+
+        assert_eq!(stream.position(), 0);
+
+        // Offsets are relative to the current position.
+        stream.set_position(1);
+        assert!(is_match_at(b"rust", &mut stream, 5).unwrap());
+    }
+
+    /// A stream whose reads fail before the given position.
+    struct FailingReadsBefore(Cursor<&'static [u8]>, u64);
+
+    impl Read for FailingReadsBefore {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.position() < self.1 {
+                return Err(io::Error::other("injected"));
+            }
+            self.0.read(buf)
+        }
+    }
+
+    impl Seek for FailingReadsBefore {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.0.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_is_match_at_errors_restore_position() {
+        let mut stream = Cursor::new(b"rusty rust");
+        stream.set_position(3);
+        let err = is_match_at(b"rust", &mut stream, u64::MAX).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        match err.get_ref().and_then(|err| err.downcast_ref()) {
+            Some(SearchError::OffsetOverflow { pos: 3, offset: u64::MAX }) => {
+            }
+            err => panic!("unexpected error: {:?}", err),
+        }
+        assert_eq!(stream.position(), 3);
+
+        // Reading the marker fails.
+        let mut stream = FailingReadsBefore(Cursor::new(b"rusty rust"), 10);
+        stream.0.set_position(3);
+        assert!(is_match_at(b"rust", &mut stream, 3).is_err());
+        assert_eq!(stream.0.position(), 3);
+        assert!(assert_marker_at(&mut stream, b"rust", 3).is_err());
+        assert_eq!(stream.0.position(), 3);
+
+        // Reading the marker succeeds, but searching the bytes before it fails.
+        let mut stream = FailingReadsBefore(Cursor::new(b"rusty rust"), 6);
+        stream.0.set_position(3);
+        assert!(is_match_at(b"rust", &mut stream, 3).unwrap());
+        assert!(assert_marker_at(&mut stream, b"rust", 3).is_err());
+        assert_eq!(stream.0.position(), 3);
+    }
+
+    #[test]
+    fn test_assert_marker_at() {
+        let marker = b"BEGIN";
+        let mut haystack =
+            haystack_with_edges(b"-", DEFAULT_BUFFER_CAPACITY * 2);
+        let first = DEFAULT_BUFFER_CAPACITY - 2;
+        let second = DEFAULT_BUFFER_CAPACITY + 10;
+        haystack[first..first + marker.len()].copy_from_slice(marker);
+        haystack[second..second + marker.len()].copy_from_slice(marker);
+
+        let mut stream = Cursor::new(&haystack);
+        assert!(assert_marker_at(&mut stream, marker, first as u64).unwrap());
+        assert!(!assert_marker_at(&mut stream, marker, second as u64).unwrap());
+        assert!(!assert_marker_at(&mut stream, marker, 3).unwrap());
+
+        // An earlier occurrence may overlap the marker.
+        let mut stream = Cursor::new(b"aaaa");
+        assert!(assert_marker_at(&mut stream, b"aa", 0).unwrap());
+        assert!(!assert_marker_at(&mut stream, b"aa", 1).unwrap());
+        assert!(!assert_marker_at(&mut stream, b"aa", 2).unwrap());
+    }
+
+    #[test]
+    fn test_find_iter_buffer_capacity() {
+        let needle = b"dear";
+        let mut haystack =
+            haystack_with_edges(needle, DEFAULT_BUFFER_CAPACITY * 5 + 3);
+        for pos in (7..haystack.len() - 8).step_by(1021) {
+            haystack[pos..pos + needle.len()].copy_from_slice(needle);
+        }
+        let expected = StreamFinder::new(needle)
+            .find_iter(&mut Cursor::new(&haystack))
+            .into_offsets()
+            .unwrap();
+
+        for &capacity in &[0, 5, 64, 1 << 10, 64 << 10, 256 << 10] {
+            let finder = StreamFinderBuilder::new()
+                .buffer_capacity(capacity)
+                .build(needle)
+                .unwrap();
+            let matches = finder
+                .find_iter(&mut Cursor::new(&haystack))
+                .into_offsets()
+                .unwrap();
+            assert_eq!(matches, expected, "capacity {}", capacity);
+
+            let mut stream = Cursor::new(&haystack);
+            let iter = finder.find_iter(&mut stream);
+            assert_eq!(
+                finder.buffer_capacity(),
+                iter.into_buffer().capacity()
+            );
+            assert_eq!(finder.buffer_capacity(), cmp::max(capacity, 5));
+        }
+
+        // The default capacity grows with the needle and its context.
+        let long = vec![b'x'; DEFAULT_BUFFER_CAPACITY];
+        for finder in &[StreamFinder::new(needle), StreamFinder::new(&long)] {
+            let mut stream = Cursor::new(&haystack);
+            let iter = finder.find_iter(&mut stream);
+            assert_eq!(
+                finder.buffer_capacity(),
+                iter.into_buffer().capacity()
+            );
+        }
+        for finder in &[
+            StreamFinder::new(needle).preceded_by(b"my ").followed_by(b"!"),
+            StreamFinder::new(&long).not_followed_by(b"!"),
+        ] {
+            let mut stream = Cursor::new(&haystack);
+            let iter = finder.find_iter(&mut stream);
+            assert_eq!(
+                finder.buffer_capacity(),
+                iter.into_buffer().capacity()
+            );
+        }
+        assert_eq!(
+            StreamFinder::new(needle).buffer_capacity(),
+            DEFAULT_BUFFER_CAPACITY
+        );
+        assert_eq!(
+            StreamFinder::new(&long).not_followed_by(b"!").buffer_capacity(),
+            (long.len() + 1) * 8
+        );
+    }
 
-impl<'n, 's, R: Read> FindIter<'n, 's, R> {
-    pub(crate) fn new(rdr: &'s mut R, fdr: &'n StreamFinder<'n>) -> Self {
-        let needle = fdr.needle();
-        let buf = Buffer::new(needle.len());
-        FindIter {
-            rdr,
-            needle,
-            buf,
-            search_pos: 0,
-            stream_pos: 0,
-            report_pos: 0,
+    #[test]
+    fn test_find_iter_reads_grow_to_buffer_capacity() {
+        // The buffer starts small, but every search rolls it after each fill, so it must still
+        // grow until reads use the whole configured capacity.
+        for &capacity in &[DEFAULT_BUFFER_CAPACITY, 64 << 10] {
+            let finder = StreamFinderBuilder::new()
+                .buffer_capacity(capacity)
+                .build(b"dear")
+                .unwrap();
+            let mut stream = Generated {
+                needle: b"dear",
+                period: 4096 + 7,
+                pos: 0,
+                len: 1 << 20,
+                max_read: 0,
+            };
+            let count = finder.find_iter(&mut stream).count();
+            assert_eq!(count as u64, ((1 << 20) - 4) / (4096 + 7) + 1);
+            assert_eq!(stream.max_read, capacity - 4, "capacity {}", capacity);
         }
     }
 
-    pub(crate) fn new_with_needle(rdr: &'s mut R, needle: &'n [u8]) -> Self {
-        let buf = Buffer::new(needle.len());
-        FindIter {
-            rdr,
-            needle,
-            buf,
-            search_pos: 0,
-            stream_pos: 0,
-            report_pos: 0,
+    #[test]
+    fn test_find_iter_tiny_buffer_capacity() {
+        // Overlapping candidates right at every roll are the worst case for a tiny buffer.
+        let mut haystack = b"abababa-".repeat(64);
+        haystack.extend_from_slice(b"abab");
+        let needle = b"abab";
+        let expected =
+            StreamFinder::new(needle).find_all(&mut &haystack[..]).unwrap();
+
+        for &capacity in &[0, needle.len(), needle.len() + 1] {
+            let finder = StreamFinderBuilder::new()
+                .buffer_capacity(capacity)
+                .build(needle)
+                .unwrap();
+            assert_eq!(
+                finder.buffer_capacity(),
+                needle.len() + 1,
+                "capacity {}",
+                capacity
+            );
+            for &k in &[1, 3, 1000] {
+                let rdr = &mut FaultReader::new(&haystack[..]).short_reads(k);
+                let matches = finder.find_iter(rdr).into_offsets().unwrap();
+                assert_eq!(
+                    matches, expected,
+                    "capacity {} reads {}",
+                    capacity, k
+                );
+            }
+
+            // The minimum is raised for the context of the needle as well.
+            let finder = finder.followed_by(b"a-");
+            assert_eq!(finder.buffer_capacity(), needle.len() + 3);
+            let default = StreamFinder::new(needle).followed_by(b"a-");
+            assert_eq!(
+                finder.find_all(&mut &haystack[..]).unwrap(),
+                default.find_all(&mut &haystack[..]).unwrap()
+            );
         }
     }
-}
 
-impl<'n, 's, R: Read + Seek> FindRevIter<'n, 's, R> {
-    pub(crate) fn new(
-        rdr: &'s mut R,
-        fdr: &'n StreamFinder<'n>,
-    ) -> io::Result<Self> {
-        let stream_len = rdr.seek(SeekFrom::End(0))?;
-        assert!(stream_len <= usize::MAX as u64);
-        let stream_len = stream_len as usize;
+    /// A reader that returns zero bytes once between two parts of its data.
+    struct ZeroReadGap<'a> {
+        parts: Vec<&'a [u8]>,
+    }
 
-        let needle = fdr.needle();
-        let buf = BufferRev::new(needle.len());
-        Ok(FindRevIter {
-            rdr,
-            needle,
-            buf,
-            search_pos: 0,
-            stream_pos: stream_len,
-            report_pos: 0,
-            seek_pos: stream_len,
-            stream_len,
-        })
+    impl<'a> Read for ZeroReadGap<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.parts.is_empty() {
+                return Ok(0);
+            }
+            let part = &mut self.parts[0];
+            let n = cmp::min(buf.len(), part.len());
+            buf[..n].copy_from_slice(&part[..n]);
+            *part = &part[n..];
+            if part.is_empty() {
+                self.parts.remove(0);
+            }
+            Ok(n)
+        }
     }
 
-    pub(crate) fn new_with_needle(
-        rdr: &'s mut R,
-        needle: &'n [u8],
-    ) -> io::Result<Self> {
-        let stream_len = rdr.seek(SeekFrom::End(0))?;
-        assert!(stream_len <= usize::MAX as u64);
-        let stream_len = stream_len as usize;
+    #[test]
+    fn test_find_iter_error_on_zero_read() {
+        let first = b"dear me, my dear";
+        let second = b"oh dear";
+        let gap = || ZeroReadGap { parts: vec![&first[..], &[], &second[..]] };
 
-        let buf = BufferRev::new(needle.len());
-        Ok(FindRevIter {
-            rdr,
-            needle,
-            buf,
-            search_pos: 0,
-            stream_pos: stream_len,
-            report_pos: 0,
-            seek_pos: stream_len,
-            stream_len,
-        })
+        // By default, the zero-length read ends the search.
+        let matches = find_iter(b"dear", &mut gap()).into_offsets().unwrap();
+        assert_eq!(matches, vec![0, 12]);
+
+        // In strict mode, it's reported, and the search can be resumed.
+        let mut rdr = gap();
+        let mut iter = find_iter(b"dear", &mut rdr).error_on_zero_read(true);
+        assert_eq!(iter.next().unwrap().unwrap(), 0);
+        assert_eq!(iter.next().unwrap().unwrap(), 12);
+        let err = iter.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(matches!(
+            err.get_ref().unwrap().downcast_ref::<SearchError>(),
+            Some(SearchError::DataAfterZeroRead)
+        ));
+        assert_eq!(iter.next().unwrap().unwrap(), 19);
+        assert!(iter.next().is_none());
+
+        // A stream without gaps isn't affected.
+        let mut stream = Cursor::new(&first[..]);
+        let matches = find_iter(b"dear", &mut stream)
+            .error_on_zero_read(true)
+            .into_offsets()
+            .unwrap();
+        assert_eq!(matches, vec![0, 12]);
     }
 
-    /// Returns the length of the underlying stream.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use std::io::{self, Read, Cursor};
-    ///
-    /// fn main() -> io::Result<()> {
-    ///     let mut stream = Cursor::new(b"hello rustaceans");
-    ///     let mut iter = xfind::rfind_iter(b"rust", &mut stream)?;
-    ///     assert_eq!(iter.stream_len(), 16);
-    ///
-    ///     Ok(())
-    /// }
-    /// ```
-    pub fn stream_len(&self) -> usize {
-        self.stream_len
+    #[test]
+    fn test_non_overlapping_rightmost() {
+        let leftmost = |needle: &[u8], haystack: &[u8]| {
+            StreamFinder::new(needle)
+                .find_all(&mut Cursor::new(haystack))
+                .unwrap()
+        };
+        let rightmost = |needle: &[u8], haystack: &[u8]| {
+            StreamFinder::new(needle)
+                .non_overlapping_rightmost(&mut Cursor::new(haystack))
+                .unwrap()
+        };
+
+        assert_eq!(leftmost(b"abab", b"ababab"), vec![0]);
+        assert_eq!(rightmost(b"abab", b"ababab"), vec![2]);
+        assert_eq!(leftmost(b"aa", b"aaaaa"), vec![0, 2]);
+        assert_eq!(rightmost(b"aa", b"aaaaa"), vec![1, 3]);
+        assert_eq!(leftmost(b"aba", b"ababa-aba"), vec![0, 6]);
+        assert_eq!(rightmost(b"aba", b"ababa-aba"), vec![2, 6]);
+        // Both modes agree for needles that can't overlap themselves.
+        assert_eq!(
+            rightmost(b"dear", b"dear, dear"),
+            leftmost(b"dear", b"dear, dear")
+        );
+        assert_eq!(rightmost(b"abab", b""), Vec::<usize>::new());
+
+        // The choice depends on the end of the stream, which may be far from the start.
+        let haystack = vec![b'a'; DEFAULT_BUFFER_CAPACITY * 3 + 1];
+        let matches = rightmost(b"aa", &haystack);
+        assert_eq!(matches.len(), haystack.len() / 2);
+        assert_eq!(matches[0], 1);
+        assert!(matches.windows(2).all(|w| w[1] == w[0] + 2));
     }
 
-    /// Moves the cursor of the underlying stream to the given position.
-    ///
-    /// This is equivalent to call `rdr.seek(SeekFrom::Start(pos))`.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use std::io::{self, Read, Cursor};
-    ///
-    /// fn main() -> io::Result<()> {
-    ///     let mut stream = Cursor::new(b"hello rustaceans");
-    ///     let mut iter = xfind::rfind_iter(b"rust", &mut stream)?;
-    ///     let mut buf = Vec::new();
-    ///
-    ///     let pos = iter.next().unwrap()?;
-    ///     iter.seek_to(pos)?;
-    ///     stream.read_to_end(&mut buf)?;
-    ///     assert_eq!(buf, b"rustaceans");
-    ///
-    ///     Ok(())
-    /// }
-    /// ```
-    pub fn seek_to(&mut self, pos: usize) -> io::Result<()> {
-        self.rdr.seek(SeekFrom::Start(pos as u64)).map(|_| ())
+    #[test]
+    fn test_find_rev_iter_seek_to_past_end() {
+        let mut stream = Cursor::new(b"rusty rust");
+        let mut iter = rfind_iter(b"rust", &mut stream).unwrap();
+        assert!(iter.seek_to(10).is_ok());
+
+        let err = iter.seek_to(11).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(matches!(
+            err.get_ref().unwrap().downcast_ref::<SearchError>(),
+            Some(SearchError::SeekOutOfBounds { pos: 11, len: 10 })
+        ));
     }
-}
 
-impl<'n, 's, R: Read> Iterator for FindIter<'n, 's, R> {
-    type Item = io::Result<usize>;
+    #[test]
+    fn test_find_rev_iter_continues_after_seek_to() {
+        let needle = b"dear";
+        let mut haystack =
+            haystack_with_edges(needle, DEFAULT_BUFFER_CAPACITY * 3);
+        let mid = DEFAULT_BUFFER_CAPACITY + 7;
+        haystack[mid..mid + needle.len()].copy_from_slice(needle);
+        let expected = vec![haystack.len() - needle.len(), mid, 0];
 
-    fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            if self.search_pos < self.buf.len() {
-                if let Some(mat) = memmem::find(
-                    &self.buf.buffer()[self.search_pos..],
-                    self.needle,
-                ) {
-                    self.report_pos = self.stream_pos + mat;
-                    self.stream_pos += mat + self.needle.len();
-                    self.search_pos += mat + self.needle.len();
-                    return Some(Ok(self.report_pos));
-                }
+        for &pos in &[0, 5, mid, haystack.len()] {
+            let mut stream = Cursor::new(&haystack);
+            let mut iter = rfind_iter(needle, &mut stream).unwrap();
+            let mut matches = vec![iter.next().unwrap().unwrap()];
+            iter.seek_to(pos).unwrap();
+            matches.extend(iter.map(|x| x.unwrap()));
+            assert_eq!(matches, expected);
+        }
+    }
+
+    #[test]
+    fn test_find_iter_with_neighbors() {
+        let neighbors = |needle: &[u8], haystack: &[u8]| {
+            find_iter(needle, &mut Cursor::new(haystack))
+                .with_neighbors()
+                .map(|x| x.unwrap())
+                .collect::<Vec<_>>()
+        };
 
-                self.stream_pos += self.buf.len() - self.search_pos;
-                self.search_pos = self.buf.len();
+        assert_eq!(
+            neighbors(b"rust", b"rust, trust rust"),
+            vec![
+                (None, 0, Some(b',')),
+                (Some(b't'), 7, Some(b' ')),
+                (Some(b' '), 12, None)
+            ]
+        );
+        assert_eq!(neighbors(b"rust", b"rust"), vec![(None, 0, None)]);
+        assert_eq!(
+            neighbors(b"aa", b"aaaaa"),
+            vec![(None, 0, Some(b'a')), (Some(b'a'), 2, Some(b'a'))]
+        );
+
+        // The byte before the current position of the stream isn't known.
+        let mut stream = Cursor::new(b"trusty");
+        stream.set_position(1);
+        let finder = StreamFinder::new(b"rust");
+        let mut iter = finder
+            .find_iter_from_current(&mut stream)
+            .unwrap()
+            .with_neighbors();
+        assert_eq!(iter.next().unwrap().unwrap(), (None, 1, Some(b'y')));
+    }
+
+    #[test]
+    fn test_find_iter_with_neighbors_across_reads() {
+        let needle = b"dear";
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 3];
+        let mut expected = Vec::new();
+        for (i, pos) in (0..haystack.len() - 5).step_by(1237).enumerate() {
+            haystack[pos..pos + needle.len()].copy_from_slice(needle);
+            let before = pos.checked_sub(1).map(|_| b'a' + (i % 26) as u8);
+            if let Some(b) = before {
+                haystack[pos - 1] = b;
             }
+            haystack[pos + needle.len()] = b'0' + (i % 10) as u8;
+            expected.push((before, pos, Some(haystack[pos + needle.len()])));
+        }
+        haystack.truncate(haystack.len() - 1);
+        haystack.extend_from_slice(b"dear");
+        expected.push((Some(b'-'), haystack.len() - 4, None));
 
-            // Roll our buffer if our buffer has at least the minimum amount of bytes in it.
-            if self.buf.len() >= self.buf.min_buffer_len() {
-                self.buf.roll();
-                if &self.buf.buffer()[..self.buf.min_buffer_len()]
-                    == self.needle
-                {
-                    self.search_pos = self.buf.min_buffer_len();
+        for mut rdr in fault_readers(&haystack) {
+            let matches = find_iter(needle, &mut rdr)
+                .with_neighbors()
+                .collect::<io::Result<Vec<_>>>()
+                .unwrap();
+            assert_eq!(matches, expected);
+        }
+    }
+
+    #[test]
+    fn test_rfind_hint_both_directions() {
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 3];
+        haystack[5..9].copy_from_slice(b"dear");
+        let mut haystacks = vec![haystack.clone()];
+        haystack[DEFAULT_BUFFER_CAPACITY - 2..DEFAULT_BUFFER_CAPACITY + 2]
+            .copy_from_slice(b"dear");
+        haystacks.push(haystack.clone());
+        haystacks.push(haystack_with_edges(b"dear", 100));
+        haystacks.push(b"aaaaa".to_vec());
+        haystacks.push(b"no match".to_vec());
+        haystacks.push(Vec::new());
+
+        for haystack in &haystacks {
+            for &needle in &[&b"dear"[..], b"aa", b""] {
+                let finder = StreamFinder::new(needle);
+                let mut stream = Cursor::new(haystack);
+                let expected = memmem::rfind(haystack, needle);
+
+                let near_end =
+                    finder.rfind_hint(&mut stream, true).transpose();
+                let near_start =
+                    finder.rfind_hint(&mut stream, false).transpose();
+                assert_eq!(near_end.unwrap(), expected);
+                assert_eq!(near_start.unwrap(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_last_short_reads() {
+        let haystack =
+            haystack_with_edges(b"dear", DEFAULT_BUFFER_CAPACITY * 2);
+        for mut rdr in fault_readers(&haystack) {
+            let pos = StreamFinder::new(b"dear").find_last(&mut rdr);
+            assert_eq!(pos.transpose().unwrap(), Some(haystack.len() - 4));
+        }
+    }
+
+    #[test]
+    fn test_find_iter_match_slices() {
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 2];
+        for &pos in &[0, DEFAULT_BUFFER_CAPACITY - 2, haystack.len() - 4] {
+            haystack[pos..pos + 4].copy_from_slice(b"dear");
+        }
+        let rdr = &mut FaultReader::new(&haystack[..]).short_reads(7);
+        let mut count = 0;
+        for res in find_iter(b"dear", rdr).match_slices() {
+            let (pos, bytes) = res.unwrap();
+            assert_eq!(bytes, &haystack[pos..pos + 4]);
+            count += 1;
+        }
+        assert_eq!(count, 3);
+
+        let rdr = &mut FaultReader::new(&haystack[..]).error_at(10);
+        let mut iter = find_iter(b"dear", rdr).match_slices();
+        assert_eq!(iter.next().unwrap().unwrap().0, 0);
+        assert!(iter.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_for_each_match() {
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 3];
+        for pos in (0..haystack.len() - 4).step_by(1021) {
+            haystack[pos..pos + 4].copy_from_slice(b"dear");
+        }
+        let expected = find_iter(b"dear", &mut Cursor::new(&haystack))
+            .into_offsets()
+            .unwrap();
+        let finder = StreamFinder::new(b"dear");
+
+        let mut offsets = Vec::new();
+        let rdr = &mut FaultReader::new(&haystack[..]).short_reads(7);
+        let res = finder.for_each_match(rdr, |pos, bytes| {
+            assert_eq!(bytes, &haystack[pos..pos + 4]);
+            offsets.push(pos);
+            ControlFlow::Continue(())
+        });
+        assert!(res.is_ok());
+        assert_eq!(offsets, expected);
+
+        let mut count = 0;
+        let rdr = &mut Cursor::new(&haystack);
+        finder
+            .for_each_match(rdr, |_, _| {
+                count += 1;
+                if count == 3 {
+                    ControlFlow::Break(())
                 } else {
-                    self.stream_pos -= self.buf.min_buffer_len();
-                    self.search_pos = 0;
+                    ControlFlow::Continue(())
                 }
-            }
-            match self.buf.fill(&mut self.rdr) {
-                // report any I/O errors.
-                Err(err) => return Some(Err(err)),
-                // we've reach EOF, return `None` now.
-                Ok(false) => {
-                    return None;
+            })
+            .unwrap();
+        assert_eq!(count, 3);
+
+        let rdr = &mut FaultReader::new(&haystack[..]).error_at(2000);
+        let mut count = 0;
+        let res = finder.for_each_match(rdr, |_, _| {
+            count += 1;
+            ControlFlow::Continue(())
+        });
+        assert!(res.is_err());
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn test_for_each_match_with_context() {
+        let finder =
+            StreamFinder::new(b"error").preceded_by(b"[").followed_by(b"]");
+        let mut offsets = Vec::new();
+        finder
+            .for_each_match(
+                &mut Cursor::new(b"error [error] [error"),
+                |pos, bytes| {
+                    assert_eq!(bytes, b"error");
+                    offsets.push(pos);
+                    ControlFlow::Continue(())
+                },
+            )
+            .unwrap();
+        assert_eq!(offsets, vec![7]);
+    }
+
+    #[test]
+    fn test_not_followed_by() {
+        let finder = StreamFinder::new(b"foo").not_followed_by(b"bar");
+        for (haystack, expected) in &[
+            (&b"foobar"[..], &[][..]),
+            (b"foobaz", &[0]),
+            (b"foo", &[0]),
+            (b"fooba", &[0]),
+            (b"foobar foobaz foo", &[7, 14]),
+            (b"foofoobar", &[0]),
+        ] {
+            let matches = finder.find_all(&mut Cursor::new(haystack)).unwrap();
+            assert_eq!(&matches[..], *expected);
+        }
+
+        let finder = StreamFinder::new(b"error")
+            .followed_by(b":")
+            .not_followed_by(b":!");
+        let matches = finder
+            .find_all(&mut Cursor::new(&b"error:! error: error"[..]))
+            .unwrap();
+        assert_eq!(matches, vec![8]);
+    }
+
+    #[test]
+    fn test_not_followed_by_across_rolls() {
+        let finder = StreamFinder::new(b"foo").not_followed_by(b"bar");
+        for shift in 0..8 {
+            let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 3];
+            let pos = DEFAULT_BUFFER_CAPACITY - shift;
+            haystack[pos..pos + 6].copy_from_slice(b"foobar");
+            haystack[pos + 6..pos + 12].copy_from_slice(b"foobaz");
+            let end = haystack.len() - 6;
+            haystack[end..].copy_from_slice(b"foobar");
+
+            let rdr = &mut FaultReader::new(&haystack[..]).short_reads(1);
+            let matches = finder.find_all(rdr).unwrap();
+            assert_eq!(matches, vec![pos + 6], "shift: {}", shift);
+        }
+    }
+
+    #[test]
+    fn test_not_followed_by_would_block() {
+        // The read after the candidate would block, which must not lose the candidate.
+        let finder = StreamFinder::new(b"foo").not_followed_by(b"bar");
+        for haystack in &[&b"xxxxxxfoobaz"[..], b"xxxxxxfoobar"] {
+            let mut rdr = FaultReader::new(*haystack)
+                .short_reads(9)
+                .would_block_every(2);
+            let mut iter = finder.find_iter(&mut rdr);
+            let (mut offsets, mut pending) = (Vec::new(), 0);
+            while let Some(poll) = iter.try_next().unwrap() {
+                match poll {
+                    PollMatch::Match(pos) => offsets.push(pos),
+                    PollMatch::Pending => pending += 1,
                 }
-                // fallthrough for another search.
-                Ok(true) => {}
+            }
+            assert!(pending > 0);
+            let expected = finder.find_all(&mut &haystack[..]).unwrap();
+            assert_eq!(offsets, expected);
+        }
+
+        let mut rdr = FaultReader::new(&b"xxxxxxfoobaz"[..])
+            .short_reads(9)
+            .would_block_every(2);
+        let mut neighbors = Vec::new();
+        for res in finder.find_iter(&mut rdr).with_neighbors() {
+            match res {
+                Ok(neighbor) => neighbors.push(neighbor),
+                Err(err) => assert_eq!(err.kind(), io::ErrorKind::WouldBlock),
             }
         }
+        assert_eq!(neighbors, vec![(Some(b'x'), 6, Some(b'b'))]);
     }
-}
 
-impl<'n, 's, R: Read + Seek> Iterator for FindRevIter<'n, 's, R> {
-    type Item = io::Result<usize>;
+    #[test]
+    fn test_not_followed_by_with_neighbors() {
+        let finder = StreamFinder::new(b"foo").not_followed_by(b"bar");
+        let haystack = b"foobar foobaz foobar foo";
+        let expected = finder.find_all(&mut &haystack[..]).unwrap();
+        assert_eq!(expected, vec![7, 21]);
 
-    fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            // If the contents of the buffer have not been consumed yet.
-            if self.search_pos < self.buf.len() {
-                if let Some(mat) = memmem::rfind(
-                    &self.buf.buffer()[..self.buf.len() - self.search_pos],
-                    self.needle,
-                ) {
-                    self.report_pos = self.stream_pos
-                        - (self.buf.len() - self.search_pos - mat);
-                    self.stream_pos -= self.buf.len() - self.search_pos - mat;
-                    self.search_pos += self.buf.len() - self.search_pos - mat;
-
-                    // FIXME: This is a quick and dirty hack to fix end-of-stream roll issues. We
-                    // should probably figure out a better way to handle this.
-                    if self.stream_len > self.buf.capacity()
-                        && self.seek_pos == 0
-                    {
-                        return Some(Ok(self.report_pos + self.needle.len()));
-                    }
+        for &k in &[1, 5, 1000] {
+            let rdr = &mut FaultReader::new(&haystack[..]).short_reads(k);
+            let neighbors = finder
+                .find_iter(rdr)
+                .with_neighbors()
+                .collect::<io::Result<Vec<_>>>()
+                .unwrap();
+            assert_eq!(
+                neighbors,
+                vec![(Some(b' '), 7, Some(b'b')), (Some(b' '), 21, None)],
+                "reads {}",
+                k
+            );
+        }
+    }
 
-                    return Some(Ok(self.report_pos));
-                }
+    #[cfg(feature = "logging")]
+    #[test]
+    fn test_logging_buffer_rolls() {
+        use std::sync::Mutex;
+
+        /// A logger that captures the trace messages of this crate.
+        struct Capture(Mutex<Vec<String>>);
 
-                self.stream_pos = self
-                    .stream_pos
-                    .saturating_sub(self.buf.len() - self.search_pos);
-                self.search_pos = self.buf.len();
+        impl log::Log for Capture {
+            fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+                metadata.target().starts_with("xfind")
             }
 
-            // We have nothing left to search if seek position is 0.
-            if self.seek_pos == 0 {
-                return None;
+            fn log(&self, record: &log::Record<'_>) {
+                if self.enabled(record.metadata()) {
+                    let mut messages =
+                        self.0.lock().unwrap_or_else(|err| err.into_inner());
+                    messages.push(record.args().to_string());
+                }
             }
 
-            // Roll our buffer if our buffer has at least the minimum amount of bytes in it.
-            if self.buf.len() >= self.buf.min_buffer_len() {
-                self.buf.roll_right();
+            fn flush(&self) {}
+        }
 
-                if &self.buf.buffer()
-                    [self.buf.len() - self.buf.min_buffer_len()..]
-                    == self.needle
-                {
-                    self.search_pos = self.buf.min_buffer_len();
-                } else {
-                    self.stream_pos += self.buf.min_buffer_len();
-                    self.search_pos = 0;
+        static CAPTURE: Capture = Capture(Mutex::new(Vec::new()));
+        log::set_logger(&CAPTURE).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 3];
+        haystack.extend_from_slice(b"dear");
+        let mut stream = Cursor::new(&haystack);
+        assert_eq!(
+            find_iter(b"dear", &mut stream).into_offsets().unwrap().len(),
+            1
+        );
+        assert_eq!(rfind_iter(b"dear", &mut stream).unwrap().count(), 1);
+
+        let messages = CAPTURE.0.lock().unwrap().clone();
+        assert!(messages.iter().any(|msg| msg.starts_with("rolled forward")));
+        assert!(messages.iter().any(|msg| msg.starts_with("filling forward")));
+        assert!(messages.iter().any(|msg| msg.starts_with("rolled backward")));
+        assert!(messages
+            .iter()
+            .any(|msg| msg.starts_with("filling backward")));
+    }
+
+    #[test]
+    fn test_find_iter_deltas() {
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 3];
+        for pos in (5..haystack.len() - 4).step_by(701) {
+            haystack[pos..pos + 4].copy_from_slice(b"dear");
+        }
+        haystack[DEFAULT_BUFFER_CAPACITY - 2..][..8]
+            .copy_from_slice(b"deardear");
+        let expected = find_iter(b"dear", &mut Cursor::new(&haystack))
+            .into_offsets()
+            .unwrap();
+
+        let rdr = &mut FaultReader::new(&haystack[..]).short_reads(7);
+        let deltas = find_iter(b"dear", rdr)
+            .deltas()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(deltas[0], 5);
+        assert!(deltas.contains(&4));
+        let offsets: Vec<usize> = deltas
+            .iter()
+            .scan(0, |sum, &delta| {
+                *sum += delta;
+                Some(*sum)
+            })
+            .collect();
+        assert_eq!(offsets, expected);
+
+        let mut stream = &b"dear"[..];
+        let deltas = find_iter(b"dear", &mut stream).deltas();
+        assert_eq!(deltas.collect::<io::Result<Vec<_>>>().unwrap(), vec![0]);
+
+        let rdr = &mut FaultReader::new(&haystack[..]).error_at(10);
+        let mut iter = find_iter(b"dear", rdr).deltas();
+        assert_eq!(iter.next().unwrap().unwrap(), 5);
+        assert!(iter.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_find_iter_running_count() {
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 3];
+        for pos in (0..haystack.len() - 4).step_by(701) {
+            haystack[pos..pos + 4].copy_from_slice(b"dear");
+        }
+        let expected = find_iter(b"dear", &mut Cursor::new(&haystack))
+            .into_offsets()
+            .unwrap();
+
+        // Errors are yielded, but they don't count as matches.
+        let rdr = &mut FaultReader::new(&haystack[..])
+            .short_reads(1000)
+            .would_block_every(2);
+        let mut errors = 0;
+        let mut matches = Vec::new();
+        for res in find_iter(b"dear", rdr).running_count() {
+            match res {
+                Ok((pos, count)) => {
+                    assert_eq!(count, matches.len() + 1);
+                    matches.push(pos);
+                }
+                Err(err) => {
+                    assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+                    errors += 1;
                 }
             }
+        }
+        assert!(errors > 0);
+        assert_eq!(matches, expected);
+    }
 
-            let free_buffer_len = self.buf.free_buffer().len();
-            let amount = if self.stream_pos > free_buffer_len {
-                self.seek_pos -= free_buffer_len;
-                free_buffer_len
-            } else {
-                self.seek_pos = 0;
-                self.stream_pos
-            };
-            match self.rdr.seek(SeekFrom::Start(self.seek_pos as u64)) {
-                Ok(_) => {}
-                Err(e) => return Some(Err(e)),
+    #[test]
+    fn test_find_iter_step_by_matches() {
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 3];
+        for pos in (0..haystack.len() - 4).step_by(701) {
+            haystack[pos..pos + 4].copy_from_slice(b"dear");
+        }
+        let expected = find_iter(b"dear", &mut Cursor::new(&haystack))
+            .into_offsets()
+            .unwrap();
+
+        for &step in &[1, 3, 10, expected.len() + 1] {
+            let rdr = &mut FaultReader::new(&haystack[..]).short_reads(7);
+            let matches = find_iter(b"dear", rdr)
+                .step_by_matches(step)
+                .collect::<io::Result<Vec<_>>>()
+                .unwrap();
+            let direct: Vec<usize> =
+                expected.iter().copied().step_by(step).collect();
+            assert_eq!(matches, direct, "step {}", step);
+        }
+
+        // Errors are yielded, but they don't count as matches.
+        let rdr = &mut FaultReader::new(&haystack[..])
+            .short_reads(1000)
+            .would_block_every(2);
+        let mut errors = 0;
+        let mut matches = Vec::new();
+        for res in find_iter(b"dear", rdr).step_by_matches(3) {
+            match res {
+                Ok(pos) => matches.push(pos),
+                Err(err) => {
+                    assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+                    errors += 1;
+                }
             }
-            match self.buf.fill_exact(&mut self.rdr, amount) {
-                // report any I/O errors.
-                Err(err) => return Some(Err(err)),
-                // we've reach EOF, return `None` now.
-                Ok(false) => {
-                    return None;
+        }
+        assert!(errors > 0);
+        let direct: Vec<usize> = expected.iter().copied().step_by(3).collect();
+        assert_eq!(matches, direct);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_find_iter_step_by_zero_matches() {
+        find_iter(b"dear", &mut &b"dear"[..]).step_by_matches(0);
+    }
+
+    #[test]
+    fn test_find_iter_timed() {
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 3];
+        for pos in (0..haystack.len() - 4).step_by(701) {
+            haystack[pos..pos + 4].copy_from_slice(b"dear");
+        }
+        let expected = find_iter(b"dear", &mut Cursor::new(&haystack))
+            .into_offsets()
+            .unwrap();
+
+        let start = Instant::now();
+        let rdr = &mut FaultReader::new(&haystack[..]).short_reads(7);
+        let mut total = Duration::from_secs(0);
+        let mut offsets = Vec::new();
+        for res in find_iter(b"dear", rdr).timed() {
+            let (pos, elapsed) = res.unwrap();
+            total += elapsed;
+            assert!(total <= start.elapsed());
+            offsets.push(pos);
+        }
+        assert_eq!(offsets, expected);
+
+        let rdr = &mut FaultReader::new(&haystack[..]).error_at(10);
+        let mut iter = find_iter(b"dear", rdr).timed();
+        assert_eq!(iter.next().unwrap().unwrap().0, 0);
+        assert!(iter.next().unwrap().is_err());
+    }
+
+    /// Returns haystacks made of repetitions of the period of `needle`, with a few disruptions,
+    /// such that occurrences of the needle overlap each other and span buffer boundaries.
+    fn periodic_haystacks(needle: &[u8], period: usize) -> Vec<Vec<u8>> {
+        let mut haystacks = Vec::new();
+        for &len in &[
+            DEFAULT_BUFFER_CAPACITY - 1,
+            DEFAULT_BUFFER_CAPACITY,
+            DEFAULT_BUFFER_CAPACITY * 2 + 1,
+            DEFAULT_BUFFER_CAPACITY * 3 + period,
+        ] {
+            for shift in 0..needle.len() {
+                let mut haystack: Vec<u8> = needle[..period]
+                    .iter()
+                    .copied()
+                    .cycle()
+                    .take(len)
+                    .collect();
+                // Break the period right before and after a buffer boundary, so that the
+                // retained bytes only partially match.
+                for &pos in
+                    &[DEFAULT_BUFFER_CAPACITY, DEFAULT_BUFFER_CAPACITY * 2]
+                {
+                    if let Some(byte) = haystack.get_mut(pos - shift) {
+                        *byte = b'-';
+                    }
                 }
-                // fallthrough for another search.
-                Ok(true) => {}
+                haystacks.push(haystack);
             }
         }
+        haystacks
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::buffer::DEFAULT_BUFFER_CAPACITY;
-    use std::io::Cursor;
-    use std::iter::repeat;
+    #[test]
+    fn test_periodic_needles() {
+        for &(needle, period) in &[
+            (&b"aaaa"[..], 1),
+            (b"aaaaaaaaa", 1),
+            (b"abab", 2),
+            (b"ababa", 2),
+            (b"abcabcab", 3),
+            (b"aabaabaab", 3),
+            (b"abcdeabcde", 5),
+        ] {
+            for haystack in periodic_haystacks(needle, period) {
+                let expected: Vec<usize> =
+                    memmem::find_iter(&haystack, needle).collect();
+                for &chunk in &[1, 7, DEFAULT_BUFFER_CAPACITY] {
+                    let rdr = &mut FaultReader::new(&haystack[..])
+                        .short_reads(chunk);
+                    assert_eq!(
+                        find_iter(needle, rdr).into_offsets().unwrap(),
+                        expected,
+                        "needle: {:?}, len: {}",
+                        needle,
+                        haystack.len()
+                    );
+                }
+                // The smallest buffer rolls after every byte read.
+                let finder = StreamFinderBuilder::new()
+                    .buffer_capacity(0)
+                    .build(needle)
+                    .unwrap();
+                let offsets = finder
+                    .find_iter(&mut Cursor::new(&haystack))
+                    .into_offsets()
+                    .unwrap();
+                assert_eq!(offsets, expected, "needle: {:?}", needle);
+
+                let expected: Vec<usize> =
+                    memmem::rfind_iter(&haystack, needle).collect();
+                let offsets = rfind_iter(needle, &mut Cursor::new(&haystack))
+                    .unwrap()
+                    .collect::<io::Result<Vec<_>>>()
+                    .unwrap();
+                assert_eq!(
+                    offsets,
+                    expected,
+                    "needle: {:?}, len: {}",
+                    needle,
+                    haystack.len()
+                );
+            }
+        }
+    }
 
     #[test]
-    fn test_find_iter_n1s1() {
-        let haystack = b"1";
-        let mut haystack = Cursor::new(haystack);
+    fn test_preceding_byte_histogram() {
+        let mut haystack = Vec::new();
+        std::fs::File::open("data/pride-and-prejudice.txt")
+            .unwrap()
+            .read_to_end(&mut haystack)
+            .unwrap();
+        for &needle in &[&b"Elizabeth"[..], b"e", b"the", b""] {
+            let mut expected = [0; 256];
+            let mut expected_none = 0;
+            for pos in memmem::find_iter(&haystack, needle) {
+                match pos {
+                    0 => expected_none += 1,
+                    _ => expected[haystack[pos - 1] as usize] += 1,
+                }
+            }
 
-        let finder = StreamFinder::new(b"1");
-        let matches: Vec<usize> =
-            finder.find_iter(&mut haystack).map(|x| x.unwrap()).collect();
-        let expected: Vec<usize> = vec![0];
-        assert_eq!(matches, expected);
+            let finder = StreamFinder::new(needle);
+            let rdr = &mut FaultReader::new(&haystack[..]).short_reads(997);
+            let (counts, none) = finder.preceding_byte_histogram(rdr).unwrap();
+            assert_eq!(&counts[..], &expected[..], "needle: {:?}", needle);
+            assert_eq!(none, expected_none, "needle: {:?}", needle);
+        }
 
-        let finder = StreamFinder::new(b"0");
-        let matches: Vec<usize> =
-            finder.find_iter(&mut haystack).map(|x| x.unwrap()).collect();
-        let expected: Vec<usize> = vec![];
-        assert_eq!(matches, expected);
+        // The byte before a match right after a buffer boundary is counted as well.
+        let finder = StreamFinder::new(b"ab");
+        let mut haystack = b"ab".repeat(DEFAULT_BUFFER_CAPACITY);
+        haystack[DEFAULT_BUFFER_CAPACITY - 1] = b'!';
+        let (counts, none) = finder
+            .preceding_byte_histogram(&mut Cursor::new(&haystack))
+            .unwrap();
+        assert_eq!(none, 1);
+        assert_eq!(counts[b'b' as usize], DEFAULT_BUFFER_CAPACITY as u64 - 3);
+        assert_eq!(counts[b'!' as usize], 1);
+        assert_eq!(
+            counts.iter().sum::<u64>(),
+            DEFAULT_BUFFER_CAPACITY as u64 - 2
+        );
+
+        let rdr = &mut FaultReader::new(&b"rusty rust"[..]).error_at(4);
+        assert!(StreamFinder::new(b"rust")
+            .preceding_byte_histogram(rdr)
+            .is_err());
     }
 
     #[test]
-    fn test_find_rev_iter_n1s1() {
-        let haystack = b"1";
-        let mut haystack = Cursor::new(haystack);
+    fn test_find_in_file() {
+        let path = "data/pride-and-prejudice.txt";
+        let mut haystack = Vec::new();
+        std::fs::File::open(path).unwrap().read_to_end(&mut haystack).unwrap();
+        let expected: Vec<usize> =
+            memmem::find_iter(&haystack, b"Elizabeth").collect();
 
-        let finder = StreamFinder::new(b"1");
-        let matches: Vec<usize> = finder
-            .rfind_iter(&mut haystack)
+        assert_eq!(
+            find_in_file(b"Elizabeth", path).unwrap(),
+            Some(expected[0])
+        );
+        assert_eq!(find_in_file(b"absent needle", path).unwrap(), None);
+        let offsets = find_iter_in_file(b"Elizabeth", path)
             .unwrap()
-            .map(|x| x.unwrap())
-            .collect();
-        let expected: Vec<usize> = vec![0];
-        assert_eq!(matches, expected);
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(offsets, expected);
 
-        let finder = StreamFinder::new(b"0");
-        let matches: Vec<usize> = finder
-            .rfind_iter(&mut haystack)
-            .unwrap()
-            .map(|x| x.unwrap())
-            .collect();
-        let expected: Vec<usize> = vec![];
-        assert_eq!(matches, expected);
+        let path = "data/nonexistent.txt";
+        let err = find_in_file(b"Elizabeth", path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        let err = find_iter_in_file(b"Elizabeth", path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
     }
 
     #[test]
-    fn test_find_iter_n2s1() {
-        let haystack = b"1";
-        let mut haystack = Cursor::new(haystack);
+    fn test_longest_run() {
+        let finder = StreamFinder::new(b"abc");
+        for (haystack, expected) in &[
+            (&b"abcabcabc x abcabc"[..], Some(0..9)),
+            (b"abc x abcabcabcabc x abcabcabcabc", Some(6..18)),
+            (b"xabcx", Some(1..4)),
+            (b"ab cab", None),
+            (b"", None),
+            // Runs consist of non-overlapping matches.
+            (b"abcabc", Some(0..6)),
+        ] {
+            let run = finder.longest_run(&mut Cursor::new(haystack)).unwrap();
+            assert_eq!(&run, expected, "haystack: {:?}", haystack);
+        }
+        let finder = StreamFinder::new(b"aa");
+        assert_eq!(
+            finder.longest_run(&mut Cursor::new(b"aaaaa b aaaa")).unwrap(),
+            Some(0..4)
+        );
+        let finder = StreamFinder::new(b"");
+        assert_eq!(
+            finder.longest_run(&mut Cursor::new(b"ab")).unwrap(),
+            Some(0..0)
+        );
 
-        let finder = StreamFinder::new(b"11");
-        let matches: Vec<usize> =
-            finder.find_iter(&mut haystack).map(|x| x.unwrap()).collect();
-        let expected: Vec<usize> = vec![];
-        assert_eq!(matches, expected);
+        // A run spanning several buffers.
+        let mut haystack = b"abc".repeat(DEFAULT_BUFFER_CAPACITY);
+        haystack.extend_from_slice(b" abcabc");
+        let finder = StreamFinder::new(b"abc");
+        let rdr = &mut FaultReader::new(&haystack[..]).short_reads(7);
+        assert_eq!(
+            finder.longest_run(rdr).unwrap(),
+            Some(0..DEFAULT_BUFFER_CAPACITY * 3)
+        );
+
+        let rdr = &mut FaultReader::new(&haystack[..]).error_at(10);
+        assert!(finder.longest_run(rdr).is_err());
     }
 
     #[test]
-    fn test_find_rev_iter_n2s1() {
-        let haystack = b"1";
-        let mut haystack = Cursor::new(haystack);
+    fn test_find_iter_with_buffer() {
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 3];
+        for pos in (0..haystack.len() - 8).step_by(1021) {
+            haystack[pos..pos + 7].copy_from_slice(b"error: ");
+        }
+        let finder = StreamFinder::new(b"error").followed_by(b": ");
+        let expected = finder
+            .find_iter(&mut Cursor::new(&haystack))
+            .into_offsets()
+            .unwrap();
 
-        let finder = StreamFinder::new(b"11");
-        let matches: Vec<usize> = finder
-            .rfind_iter(&mut haystack)
-            .unwrap()
-            .map(|x| x.unwrap())
-            .collect();
-        let expected: Vec<usize> = vec![];
-        assert_eq!(matches, expected);
+        let mut vec = Vec::with_capacity(64);
+        let ptr = vec.as_ptr();
+        for _ in 0..3 {
+            // The minimum length is raised to fit the needle and its suffix.
+            let buffer = Buffer::from_vec(vec, 1);
+            let mut stream = Cursor::new(&haystack);
+            let mut iter = finder.find_iter_with_buffer(&mut stream, buffer);
+            let offsets =
+                iter.by_ref().collect::<io::Result<Vec<_>>>().unwrap();
+            assert_eq!(offsets, expected);
+            vec = iter.into_buffer().into_vec();
+            assert_eq!((vec.as_ptr(), vec.capacity()), (ptr, 64));
+        }
+
+        // A used buffer is reset before searching.
+        let mut stream = Cursor::new(&haystack);
+        let mut iter =
+            finder.find_iter_with_buffer(&mut stream, Buffer::new(7));
+        assert_eq!(iter.nth(1).unwrap().unwrap(), expected[1]);
+        let buffer = iter.into_buffer();
+        let offsets = finder
+            .find_iter_with_buffer(&mut Cursor::new(&haystack), buffer)
+            .into_offsets()
+            .unwrap();
+        assert_eq!(offsets, expected);
     }
 
     #[test]
-    fn test_find_iter_n1s21() {
-        let haystack = b"11 - 1 - 11  - 1 - 11";
-        let mut haystack = Cursor::new(haystack);
+    fn test_find_iter_set_needle() {
+        // A needle that's longer than the default buffer, and spans several rolls.
+        let long: Vec<u8> = (0..DEFAULT_BUFFER_CAPACITY * 2)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 8];
+        haystack[100..104].copy_from_slice(b"dear");
+        for &pos in &[3000, DEFAULT_BUFFER_CAPACITY * 5 + 17] {
+            haystack[pos..pos + long.len()].copy_from_slice(&long);
+        }
+        let end = haystack.len() - 4;
+        haystack[end..].copy_from_slice(b"dear");
+        let expected: Vec<usize> = memmem::find_iter(&haystack[104..], &long)
+            .map(|pos| pos + 104)
+            .collect();
+        assert_eq!(expected.len(), 2);
 
-        let finder = StreamFinder::new(b"1");
-        let matches: Vec<usize> =
-            finder.find_iter(&mut haystack).map(|x| x.unwrap()).collect();
-        let expected: Vec<usize> = vec![0, 1, 5, 9, 10, 15, 19, 20];
-        assert_eq!(matches, expected);
+        for &k in &[1000, DEFAULT_BUFFER_CAPACITY] {
+            let rdr = &mut FaultReader::new(&haystack[..]).short_reads(k);
+            let mut iter = find_iter(b"dear", rdr);
+            assert_eq!(iter.next().unwrap().unwrap(), 100);
+
+            iter.set_needle(&long);
+            assert_eq!(iter.next().unwrap().unwrap(), expected[0]);
+            assert_eq!(iter.next().unwrap().unwrap(), expected[1]);
+
+            // Switching back to a shorter needle keeps working.
+            iter.set_needle(b"dear");
+            assert_eq!(iter.into_offsets().unwrap(), vec![end]);
+        }
+
+        // The context of the finder applies to the new needle, and a caller-provided buffer
+        // grows to hold it.
+        let finder = StreamFinder::new(b"dear").followed_by(b"!");
+        let mut stream = Cursor::new(b"dear! dear? 0123456789! 0123456789?");
+        let buffer = Buffer::from_vec(Vec::with_capacity(8), 4);
+        let mut iter = finder.find_iter_with_buffer(&mut stream, buffer);
+        assert_eq!(iter.next().unwrap().unwrap(), 0);
+        iter.set_needle(b"0123456789");
+        assert_eq!(
+            iter.by_ref().collect::<io::Result<Vec<_>>>().unwrap(),
+            vec![12]
+        );
+        assert!(iter.into_buffer().min_buffer_len() >= 11);
     }
 
     #[test]
-    fn test_find_rev_iter_n1s21() {
-        let haystack = b"11 - 1 - 11  - 1 - 11";
-        let mut haystack = Cursor::new(haystack);
+    fn test_find_iter_try_next() {
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 3];
+        for pos in (0..haystack.len() - 4).step_by(997) {
+            haystack[pos..pos + 4].copy_from_slice(b"dear");
+        }
+        let expected = find_iter(b"dear", &mut Cursor::new(&haystack))
+            .into_offsets()
+            .unwrap();
 
-        let finder = StreamFinder::new(b"1");
-        let matches: Vec<usize> = finder
-            .rfind_iter(&mut haystack)
-            .unwrap()
-            .map(|x| x.unwrap())
-            .collect();
-        let expected: Vec<usize> =
-            vec![0, 1, 5, 9, 10, 15, 19, 20].into_iter().rev().collect();
-        assert_eq!(matches, expected);
+        for &(chunk, m) in &[(7, 2), (1000, 3), (DEFAULT_BUFFER_CAPACITY, 2)] {
+            let mut rdr = FaultReader::new(&haystack[..])
+                .short_reads(chunk)
+                .would_block_every(m);
+            let mut iter = find_iter(b"dear", &mut rdr);
+            let (mut offsets, mut pending) = (Vec::new(), 0);
+            while let Some(poll) = iter.try_next().unwrap() {
+                match poll {
+                    PollMatch::Match(pos) => offsets.push(pos),
+                    PollMatch::Pending => pending += 1,
+                }
+                assert!(pending < 10_000, "no progress after WouldBlock");
+            }
+            assert_eq!(offsets, expected, "chunk: {}, m: {}", chunk, m);
+            assert!(pending > 0);
+        }
+
+        let rdr = &mut FaultReader::new(&haystack[..]).error_at(10);
+        let mut iter = find_iter(b"dear", rdr);
+        assert_eq!(iter.try_next().unwrap(), Some(PollMatch::Match(0)));
+        assert!(iter.try_next().is_err());
     }
 
     #[test]
-    fn test_find_iter_n1s8213() {
-        let haystack: Vec<u8> = repeat(&0u8)
-            .take(DEFAULT_BUFFER_CAPACITY)
-            .chain("42 0 42 42 0 42".as_bytes())
-            .copied()
-            .collect();
-        let mut haystack = Cursor::new(haystack);
+    fn test_find_with_total() {
+        let mut file = File::open("data/pride-and-prejudice.txt").unwrap();
+        let finder = StreamFinder::new(b"Darcy");
+        let all = finder.find_all(&mut file).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        assert_eq!(
+            finder.find_with_total(&mut file).unwrap(),
+            Some((all[0], all.len()))
+        );
 
-        let finder = StreamFinder::new(b"4");
-        let matches: Vec<usize> =
-            finder.find_iter(&mut haystack).map(|x| x.unwrap()).collect();
-        let expected: Vec<usize> = vec![0, 5, 8, 13]
-            .into_iter()
-            .map(|x| x + DEFAULT_BUFFER_CAPACITY)
-            .collect();
-        assert_eq!(matches, expected);
+        let finder = StreamFinder::new(b"dear").followed_by(b",");
+        let haystack = b"dear, dear; dear,";
+        assert_eq!(
+            finder.find_with_total(&mut &haystack[..]).unwrap(),
+            Some((0, 2))
+        );
+        let finder = StreamFinder::new(b"rust");
+        assert_eq!(finder.find_with_total(&mut &haystack[..]).unwrap(), None);
+        let finder = StreamFinder::new(b"");
+        assert_eq!(
+            finder.find_with_total(&mut &haystack[..]).unwrap(),
+            Some((0, 18))
+        );
+
+        let finder = StreamFinder::new(b"dear");
+        let rdr = &mut FaultReader::new(&haystack[..]).error_at(8);
+        assert!(finder.find_with_total(rdr).is_err());
     }
 
     #[test]
-    fn test_find_rev_iter_n1s8213() {
-        let haystack: Vec<u8> = repeat(&0u8)
-            .take(DEFAULT_BUFFER_CAPACITY)
-            .chain("42 0 42 42 0 42".as_bytes())
-            .copied()
-            .collect();
-        let mut haystack = Cursor::new(haystack);
+    fn test_scan_metadata() {
+        // Line endings of both styles, some of them split across buffer rolls.
+        let mut lf_haystack = Vec::new();
+        while lf_haystack.len() < DEFAULT_BUFFER_CAPACITY * 3 {
+            lf_haystack.extend_from_slice(b"dear reader, ");
+            lf_haystack.extend_from_slice(&vec![b'-'; lf_haystack.len() % 61]);
+            lf_haystack.push(b'\n');
+        }
+        let mut crlf_haystack = b"\xEF\xBB\xBF".to_vec();
+        for line in lf_haystack.split(|&b| b == b'\n') {
+            crlf_haystack.extend_from_slice(line);
+            crlf_haystack.extend_from_slice(b"\r\n");
+        }
+        crlf_haystack.extend_from_slice(b"\r\rdear\n");
 
-        let finder = StreamFinder::new(b"4");
-        let matches: Vec<usize> = finder
-            .rfind_iter(&mut haystack)
-            .unwrap()
-            .map(|x| x.unwrap())
-            .collect();
-        let expected: Vec<usize> = vec![0, 5, 8, 13]
-            .into_iter()
-            .map(|x| x + DEFAULT_BUFFER_CAPACITY)
-            .rev()
-            .collect();
-        assert_eq!(matches, expected);
+        let finder = StreamFinder::new(b"dear");
+        for &k in &[1, 997, DEFAULT_BUFFER_CAPACITY] {
+            for haystack in &[&lf_haystack, &crlf_haystack] {
+                let rdr = &mut FaultReader::new(&haystack[..]).short_reads(k);
+                let (matches, meta) = finder.scan_metadata(rdr).unwrap();
+                assert_eq!(
+                    matches,
+                    finder.find_all(&mut &haystack[..]).unwrap()
+                );
+
+                let newlines = memchr::memchr_iter(b'\n', haystack).count();
+                let crlf = memmem::find_iter(haystack, b"\r\n").count();
+                assert_eq!(
+                    (meta.crlf_count, meta.lf_count),
+                    (crlf, newlines - crlf)
+                );
+                assert_eq!(meta.byte_count, haystack.len());
+                assert_eq!(
+                    meta.has_bom,
+                    haystack.starts_with(b"\xEF\xBB\xBF")
+                );
+            }
+        }
+        let (_, meta) = finder.scan_metadata(&mut &lf_haystack[..]).unwrap();
+        assert_eq!(meta.crlf_count, 0);
+        let (_, meta) = finder.scan_metadata(&mut &crlf_haystack[..]).unwrap();
+        assert_eq!(meta.lf_count, 1);
+
+        // A partial byte order mark, or one that doesn't start the stream, doesn't count.
+        let rdr = &mut FaultReader::new(&b"\xEF\xBBdear"[..]).short_reads(1);
+        assert!(!finder.scan_metadata(rdr).unwrap().1.has_bom);
+        let rdr = &mut FaultReader::new(&b"-\xEF\xBB\xBF"[..]).short_reads(1);
+        assert!(!finder.scan_metadata(rdr).unwrap().1.has_bom);
+        let rdr = &mut FaultReader::new(&b"\xEF\xBB\xBF"[..]).short_reads(1);
+        assert!(finder.scan_metadata(rdr).unwrap().1.has_bom);
+
+        let (matches, meta) =
+            StreamFinder::new(b"").scan_metadata(&mut &b"a\r\n"[..]).unwrap();
+        assert_eq!(matches, vec![0, 1, 2, 3]);
+        assert_eq!(
+            meta,
+            StreamMeta {
+                crlf_count: 1,
+                lf_count: 0,
+                has_bom: false,
+                byte_count: 3
+            }
+        );
+
+        let rdr = &mut FaultReader::new(&crlf_haystack[..]).error_at(100);
+        assert!(finder.scan_metadata(rdr).is_err());
     }
 
     #[test]
-    fn test_find_iter_n2s8213() {
-        let haystack: Vec<u8> = repeat(&0u8)
-            .take(DEFAULT_BUFFER_CAPACITY)
-            .chain("42 0 42 42 0 42".as_bytes())
-            .copied()
-            .collect();
-        let mut haystack = Cursor::new(haystack);
+    fn test_find_all_with_lines() {
+        let mut haystack = Vec::new();
+        std::fs::File::open("data/pride-and-prejudice.txt")
+            .unwrap()
+            .read_to_end(&mut haystack)
+            .unwrap();
+        for &needle in &[&b"Elizabeth"[..], b"dear", b"\n", b"."] {
+            // Count the newlines before each match manually.
+            let (mut expected, mut line, mut counted) = (Vec::new(), 1, 0);
+            for pos in memmem::find_iter(&haystack, needle) {
+                line += haystack[counted..pos]
+                    .iter()
+                    .filter(|&&b| b == b'\n')
+                    .count();
+                counted = pos;
+                expected.push((pos, line));
+            }
+            let finder = StreamFinder::new(needle);
+            for &chunk in &[7, 1000, DEFAULT_BUFFER_CAPACITY] {
+                let rdr =
+                    &mut FaultReader::new(&haystack[..]).short_reads(chunk);
+                let matches = finder.find_all_with_lines(rdr).unwrap();
+                assert_eq!(matches, expected, "needle: {:?}", needle);
+            }
+        }
 
-        let finder = StreamFinder::new(b"42");
-        let matches: Vec<usize> =
-            finder.find_iter(&mut haystack).map(|x| x.unwrap()).collect();
-        let expected: Vec<usize> = vec![0, 5, 8, 13]
-            .into_iter()
-            .map(|x| x + DEFAULT_BUFFER_CAPACITY)
-            .collect();
-        assert_eq!(matches, expected);
+        let text = b"error: one\nwarning\n\nerror: two\nerror";
+        let finder = StreamFinder::new(b"error").followed_by(b": ");
+        let matches =
+            finder.find_all_with_lines(&mut Cursor::new(text)).unwrap();
+        assert_eq!(matches, vec![(0, 1), (20, 4)]);
+        let finder = StreamFinder::new(b"");
+        let matches =
+            finder.find_all_with_lines(&mut Cursor::new(b"a\nb")).unwrap();
+        assert_eq!(matches, vec![(0, 1), (1, 1), (2, 2), (3, 2)]);
+
+        let rdr = &mut FaultReader::new(&haystack[..]).error_at(1000);
+        assert!(StreamFinder::new(b"dear").find_all_with_lines(rdr).is_err());
     }
 
     #[test]
-    fn test_find_rev_iter_n2s8213() {
-        let haystack: Vec<u8> = repeat(&0u8)
-            .take(DEFAULT_BUFFER_CAPACITY)
-            .chain("42 0 42 42 0 42".as_bytes())
-            .copied()
-            .collect();
-        let mut haystack = Cursor::new(haystack);
+    fn test_find_iter_skip_header() {
+        // A 16-byte header that contains the needle, followed by the body.
+        let mut haystack = b"dear header dear".to_vec();
+        haystack.extend_from_slice(&vec![b'-'; DEFAULT_BUFFER_CAPACITY * 2]);
+        for pos in (16..haystack.len() - 4).step_by(997) {
+            haystack[pos..pos + 4].copy_from_slice(b"dear");
+        }
+        let expected =
+            find_iter(b"dear", &mut &haystack[16..]).into_offsets().unwrap();
 
-        let finder = StreamFinder::new(b"42");
-        let matches: Vec<usize> = finder
-            .rfind_iter(&mut haystack)
-            .unwrap()
-            .map(|x| x.unwrap())
-            .collect();
-        let expected: Vec<usize> = vec![0, 5, 8, 13]
-            .into_iter()
-            .map(|x| x + DEFAULT_BUFFER_CAPACITY)
-            .rev()
-            .collect();
-        assert_eq!(matches, expected);
+        let finder = StreamFinder::new(b"dear");
+        for &absolute in &[false, true] {
+            let rdr = &mut FaultReader::new(&haystack[..]).short_reads(7);
+            let offsets = finder
+                .find_iter_skip_header(rdr, 16, absolute)
+                .unwrap()
+                .into_offsets()
+                .unwrap();
+            let shift = if absolute { 16 } else { 0 };
+            let expected: Vec<_> =
+                expected.iter().map(|pos| pos + shift).collect();
+            assert_eq!(offsets, expected);
+        }
+
+        // A header longer than the stream leaves nothing to search.
+        let rdr = &mut &b"dear header"[..];
+        let mut iter = finder.find_iter_skip_header(rdr, 16, false).unwrap();
+        assert!(iter.next().is_none());
+
+        let rdr = &mut FaultReader::new(&haystack[..]).error_at(8);
+        assert!(finder.find_iter_skip_header(rdr, 16, false).is_err());
     }
 
     #[test]
-    fn test_find_iter_n2s8212() {
-        let haystack: Vec<u8> = repeat(&0u8)
-            .take(DEFAULT_BUFFER_CAPACITY - 1)
-            .chain("42 0 42 42 0 42".as_bytes())
-            .copied()
-            .collect();
-        let mut haystack = Cursor::new(haystack);
+    fn test_rfind_iter_seeks_per_fill() {
+        /// A stream that counts how often it's sought.
+        struct CountingSeeks<R> {
+            inner: R,
+            seeks: usize,
+        }
 
-        let finder = StreamFinder::new(b"42");
-        let matches: Vec<usize> =
-            finder.find_iter(&mut haystack).map(|x| x.unwrap()).collect();
-        let expected: Vec<usize> = vec![0, 5, 8, 13]
-            .into_iter()
-            .map(|x| x + DEFAULT_BUFFER_CAPACITY - 1)
-            .collect();
+        impl<R: Read> Read for CountingSeeks<R> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.inner.read(buf)
+            }
+        }
+
+        impl<R: Seek> Seek for CountingSeeks<R> {
+            fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+                self.seeks += 1;
+                self.inner.seek(pos)
+            }
+        }
+
+        // A very common needle, with many matches in every fill.
+        let haystack = b"the quick brown fox".repeat(DEFAULT_BUFFER_CAPACITY);
+        let expected = memmem::rfind_iter(&haystack, b"e").count();
+        let mut stream =
+            CountingSeeks { inner: Cursor::new(&haystack), seeks: 0 };
+        let matches = rfind_iter(b"e", &mut stream).unwrap().count();
         assert_eq!(matches, expected);
+        // One seek to find out the length of the stream, and one per fill.
+        let fills = (haystack.len() + DEFAULT_BUFFER_CAPACITY - 2)
+            / (DEFAULT_BUFFER_CAPACITY - 1);
+        assert!(stream.seeks <= fills + 1, "{} seeks", stream.seeks);
+        assert!(stream.seeks * 100 < matches);
+
+        // A stream that fits into the buffer is sought once, besides the explicit seek.
+        let mut stream =
+            CountingSeeks { inner: Cursor::new(b"rusty rust"), seeks: 0 };
+        let mut iter = FindRevIter::with_len(&mut stream, b"rust", 10);
+        assert_eq!(iter.next().unwrap().unwrap(), 6);
+        let pos = iter.next().unwrap().unwrap();
+        assert_eq!(pos, 0);
+        iter.seek_to(pos).unwrap();
+        assert!(iter.next().is_none());
+        assert_eq!(stream.seeks, 2);
     }
 
     #[test]
-    fn test_find_rev_iter_n2s8212() {
-        let haystack: Vec<u8> = repeat(&0u8)
-            .take(DEFAULT_BUFFER_CAPACITY - 1)
-            .chain("42 0 42 42 0 42".as_bytes())
-            .copied()
-            .collect();
-        let mut haystack = Cursor::new(haystack);
-
-        let finder = StreamFinder::new(b"42");
-        let matches: Vec<usize> = finder
-            .rfind_iter(&mut haystack)
+    fn test_find_limited_counted() {
+        let mut haystack = Vec::new();
+        File::open("data/pride-and-prejudice.txt")
             .unwrap()
-            .map(|x| x.unwrap())
-            .collect();
-        let expected: Vec<usize> = vec![0, 5, 8, 13]
-            .into_iter()
-            .map(|x| x + DEFAULT_BUFFER_CAPACITY - 1)
-            .rev()
-            .collect();
-        assert_eq!(matches, expected);
+            .read_to_end(&mut haystack)
+            .unwrap();
+        let finder = StreamFinder::new(b"Darcy");
+        let all = finder.find_all(&mut &haystack[..]).unwrap();
+
+        // Exactly `max` matches leave nothing beyond the limit.
+        let mut stream = Cursor::new(&haystack);
+        let (offsets, more) =
+            finder.find_limited_counted(&mut stream, all.len()).unwrap();
+        assert_eq!((offsets, more), (all.clone(), false));
+
+        // The probe for one more match stops reading once it has found it.
+        let max = all.len() / 2;
+        let mut stream = Cursor::new(&haystack);
+        let (offsets, more) =
+            finder.find_limited_counted(&mut stream, max).unwrap();
+        assert_eq!((&offsets[..], more), (&all[..max], true));
+        assert!(stream.position() < haystack.len() as u64);
+
+        let mut stream = Cursor::new(&haystack);
+        assert_eq!(
+            finder.find_limited_counted(&mut stream, 0).unwrap(),
+            (vec![], true)
+        );
+        let mut stream = Cursor::new(&haystack);
+        let (offsets, more) =
+            finder.find_limited_counted(&mut stream, usize::MAX).unwrap();
+        assert_eq!((offsets, more), (all, false));
+
+        let finder = StreamFinder::new(b"");
+        assert_eq!(
+            finder.find_limited_counted(&mut &b"abc"[..], 4).unwrap(),
+            (vec![0, 1, 2, 3], false)
+        );
+        assert_eq!(
+            finder.find_limited_counted(&mut &b"abc"[..], 3).unwrap(),
+            (vec![0, 1, 2], true)
+        );
+
+        let finder = StreamFinder::new(b"dear");
+        let rdr = &mut FaultReader::new(&b"dear, dear; dear,"[..]).error_at(8);
+        assert!(finder.find_limited_counted(rdr, 1).is_err());
     }
 
     #[test]
-    fn test_find_iter_n3s8212() {
-        let haystack: Vec<u8> = repeat(&0u8)
-            .take(DEFAULT_BUFFER_CAPACITY - 1)
-            .chain("42 0 42 42 0 42".as_bytes())
-            .copied()
-            .collect();
-        let mut haystack = Cursor::new(haystack);
+    fn test_find_iter_with_boundary_distance() {
+        let mut file = File::open("data/pride-and-prejudice.txt").unwrap();
+        let stream_len = file.metadata().unwrap().len() as usize;
+        let expected =
+            find_iter(b"Elizabeth", &mut file).into_offsets().unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
 
-        let finder = StreamFinder::new(b"42 ");
-        let matches: Vec<usize> =
-            finder.find_iter(&mut haystack).map(|x| x.unwrap()).collect();
-        let expected: Vec<usize> = vec![0, 5, 8]
-            .into_iter()
-            .map(|x| x + DEFAULT_BUFFER_CAPACITY - 1)
-            .collect();
-        assert_eq!(matches, expected);
+        let found = find_iter(b"Elizabeth", &mut file)
+            .with_boundary_distance()
+            .unwrap()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(found.len(), expected.len());
+        for (&(pos, from_start, to_end), &offset) in
+            found.iter().zip(&expected)
+        {
+            assert_eq!((pos, from_start), (offset, offset));
+            assert_eq!(from_start + b"Elizabeth".len() + to_end, stream_len);
+        }
+
+        let mut stream = Cursor::new(b"rust");
+        let mut iter =
+            find_iter(b"rust", &mut stream).with_boundary_distance().unwrap();
+        assert_eq!(iter.next().unwrap().unwrap(), (0, 0, 0));
+        assert!(iter.next().is_none());
+        let mut stream = Cursor::new(b"ru");
+        let mut iter =
+            find_iter(b"rust", &mut stream).with_boundary_distance().unwrap();
+        assert!(iter.next().is_none());
+
+        // A stream that grew after its length was queried.
+        struct Appended<'a>(&'a [u8]);
+        impl Read for Appended<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.0.read(buf)
+            }
+        }
+        impl Seek for Appended<'_> {
+            fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+                match pos {
+                    SeekFrom::End(_) => Ok(4),
+                    _ => Ok(0),
+                }
+            }
+        }
+        let mut stream = Appended(b"rusty rust");
+        let found = find_iter(b"rust", &mut stream)
+            .with_boundary_distance()
+            .unwrap()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(found, vec![(0, 0, 0), (6, 6, 0)]);
     }
 
     #[test]
-    fn test_find_rev_iter_n3s8212() {
-        let haystack: Vec<u8> = repeat(&0u8)
-            .take(DEFAULT_BUFFER_CAPACITY - 1)
-            .chain("42 0 42 42 0 42".as_bytes())
-            .copied()
-            .collect();
-        let mut haystack = Cursor::new(haystack);
+    fn test_find_minimal_read() {
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 4];
+        haystack[10..14].copy_from_slice(b"dear");
+        haystack[DEFAULT_BUFFER_CAPACITY * 3..][..5].copy_from_slice(b"dear,");
 
-        let finder = StreamFinder::new(b"42 ");
-        let matches: Vec<usize> = finder
-            .rfind_iter(&mut haystack)
-            .unwrap()
-            .map(|x| x.unwrap())
-            .collect();
-        let expected: Vec<usize> = vec![0, 5, 8]
-            .into_iter()
-            .map(|x| x + DEFAULT_BUFFER_CAPACITY - 1)
-            .rev()
-            .collect();
-        assert_eq!(matches, expected);
+        // An early match is found with fewer bytes read than by `find`.
+        let finder = StreamFinder::new(b"dear");
+        let rdr = &mut FaultReader::new(&haystack[..]);
+        assert_eq!(finder.find(rdr).unwrap().unwrap(), 10);
+        let read_by_find = rdr.position();
+        let rdr = &mut FaultReader::new(&haystack[..]);
+        assert_eq!(finder.find_minimal_read(rdr).unwrap().unwrap(), 10);
+        assert!(rdr.position() <= 10 + 8);
+        assert!(rdr.position() < read_by_find);
+
+        // Contexts are taken into account, and short reads don't matter.
+        let finder = StreamFinder::new(b"dear").followed_by(b",");
+        for &k in &[1, 3, 997] {
+            let rdr = &mut FaultReader::new(&haystack[..]).short_reads(k);
+            let pos = finder.find_minimal_read(rdr).unwrap().unwrap();
+            assert_eq!(pos, DEFAULT_BUFFER_CAPACITY * 3);
+            assert!(rdr.position() <= pos + 10);
+        }
+
+        let finder = StreamFinder::new(b"rust");
+        assert!(finder.find_minimal_read(&mut &haystack[..]).is_none());
+        let rdr = &mut FaultReader::new(&haystack[..]).error_at(12);
+        assert!(finder.find_minimal_read(rdr).unwrap().is_err());
     }
 }
@@ -0,0 +1,190 @@
+//! Provides a searcher for glob patterns with a single wildcard that operates on stream.
+use crate::finder::Searcher;
+use memchr::memchr;
+use std::cmp;
+use std::io::{self, Read};
+
+/// A searcher for glob patterns with a single `*` wildcard in streams.
+///
+/// The wildcard matches any number of bytes between the literal before it (the head) and the
+/// literal after it (the tail), e.g. `BEGIN*END` matches `BEGIN foo END`. Only the first `*` of
+/// the pattern is a wildcard, all other bytes are matched literally. A pattern without `*` is
+/// matched like a plain needle.
+///
+/// Matching is non-greedy: a match ends at the first occurrence of the tail after the head. The
+/// head may be empty (e.g. `*END`), in which case a match starts right where the previous match
+/// ended (or at the start of the stream). The tail may be empty as well (e.g. `BEGIN*`), in which
+/// case a match consists of the head alone. The pattern `*` only matches the empty span at the
+/// start of the stream.
+#[derive(Clone, Debug)]
+pub struct GlobFinder<'n> {
+    /// The literal before the wildcard.
+    head: &'n [u8],
+    /// The literal after the wildcard, if there is a wildcard.
+    tail: Option<&'n [u8]>,
+}
+
+impl<'n> GlobFinder<'n> {
+    /// Creates a new `GlobFinder` for the given pattern.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xfind::GlobFinder;
+    ///
+    /// let finder = GlobFinder::new(b"BEGIN*END");
+    /// ```
+    pub fn new(pattern: &'n [u8]) -> GlobFinder<'n> {
+        match memchr(b'*', pattern) {
+            Some(i) => GlobFinder {
+                head: &pattern[..i],
+                tail: Some(&pattern[i + 1..]),
+            },
+            None => GlobFinder { head: pattern, tail: None },
+        }
+    }
+
+    /// Returns an iterator over the non-overlapping matches of the pattern in the stream.
+    ///
+    /// Each match is reported as a `(start, end)` pair of offsets, where `end` is exclusive.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::GlobFinder;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"BEGIN a END BEGIN b END");
+    ///     let finder = GlobFinder::new(b"BEGIN*END");
+    ///
+    ///     let mut iter = finder.find_iter(&mut stream);
+    ///     assert_eq!(iter.next().transpose()?, Some((0, 11)));
+    ///     assert_eq!(iter.next().transpose()?, Some((12, 23)));
+    ///     assert_eq!(iter.next().transpose()?, None);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_iter<'s, R: Read>(
+        &'n self,
+        rdr: &'s mut R,
+    ) -> FindGlobIter<'n, 's, R> {
+        let tail_len = self.tail.map_or(0, <[u8]>::len);
+        FindGlobIter {
+            rdr,
+            head: self.head,
+            tail: self.tail,
+            searcher: Searcher::new(cmp::max(self.head.len(), tail_len)),
+            done: false,
+        }
+    }
+}
+
+/// A forward iterator over the non-overlapping matches of a glob pattern in a stream.
+///
+/// This iterator is created by [`GlobFinder::find_iter`].
+#[derive(Debug)]
+pub struct FindGlobIter<'n, 's, R: Read> {
+    /// The stream source we read from.
+    rdr: &'s mut R,
+    /// The literal before the wildcard.
+    head: &'n [u8],
+    /// The literal after the wildcard, if there is a wildcard.
+    tail: Option<&'n [u8]>,
+    /// The state of the search, which is shared by the head and the tail. Its buffer is big
+    /// enough for both of them.
+    searcher: Searcher,
+    /// Whether an empty match has been reported, which ends the iteration.
+    done: bool,
+}
+
+impl<'n, 's, R: Read> Iterator for FindGlobIter<'n, 's, R> {
+    type Item = io::Result<(usize, usize)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let start = if self.head.is_empty() {
+            self.searcher.position()
+        } else {
+            match self.searcher.next(&mut self.rdr, self.head)? {
+                Ok(pos) => pos,
+                Err(err) => return Some(Err(err)),
+            }
+        };
+        // The searcher resumes right after the head, which is where the tail search must start.
+        let end = match self.tail {
+            Some(tail) if !tail.is_empty() => {
+                match self.searcher.next(&mut self.rdr, tail)? {
+                    Ok(pos) => pos + tail.len(),
+                    Err(err) => return Some(Err(err)),
+                }
+            }
+            _ => start + self.head.len(),
+        };
+
+        self.done = start == end;
+        Some(Ok((start, end)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::DEFAULT_BUFFER_CAPACITY;
+    use std::io::Cursor;
+
+    fn glob(pattern: &[u8], haystack: &[u8]) -> Vec<(usize, usize)> {
+        GlobFinder::new(pattern)
+            .find_iter(&mut Cursor::new(haystack))
+            .map(|x| x.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_glob_non_greedy() {
+        let haystack = b"BEGIN foo END bar END";
+        assert_eq!(glob(b"BEGIN*END", haystack), vec![(0, 13)]);
+        assert_eq!(glob(b"BEGIN*END", b"BEGINEND"), vec![(0, 8)]);
+        assert_eq!(
+            glob(b"BEGIN*END", b"BEGIN a BEGIN b END BEGIN"),
+            vec![(0, 19)]
+        );
+        assert_eq!(glob(b"BEGIN*END", b"BEGIN foo"), vec![]);
+    }
+
+    #[test]
+    fn test_glob_leading_and_trailing_wildcard() {
+        let haystack = b"BEGIN foo END bar END";
+        assert_eq!(glob(b"*END", haystack), vec![(0, 13), (13, 21)]);
+        assert_eq!(glob(b"END*", haystack), vec![(10, 13), (18, 21)]);
+        assert_eq!(glob(b"*", haystack), vec![(0, 0)]);
+    }
+
+    #[test]
+    fn test_glob_without_wildcard() {
+        let haystack = b"BEGIN foo END bar END";
+        assert_eq!(glob(b"END", haystack), vec![(10, 13), (18, 21)]);
+        // Only the first `*` is a wildcard.
+        assert_eq!(glob(b"a*b*", b"a b* a b"), vec![(0, 4)]);
+    }
+
+    #[test]
+    fn test_glob_across_rolls() {
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 4];
+        haystack[10..15].copy_from_slice(b"BEGIN");
+        let end = DEFAULT_BUFFER_CAPACITY * 2 + 7;
+        haystack[end..end + 3].copy_from_slice(b"END");
+        let begin = DEFAULT_BUFFER_CAPACITY * 3 - 2;
+        haystack[begin..begin + 5].copy_from_slice(b"BEGIN");
+        haystack[begin + 5..begin + 8].copy_from_slice(b"END");
+
+        assert_eq!(
+            glob(b"BEGIN*END", &haystack),
+            vec![(10, end + 3), (begin, begin + 8)]
+        );
+    }
+}
@@ -0,0 +1,449 @@
+//! Provides grep-style routines that copy the lines of a stream matching a needle to a writer.
+use crate::finder::find;
+use crate::split::split;
+use memchr::{memchr, memmem};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+/// Copies every line of the stream that contains the needle to the writer, and returns the number
+/// of lines written.
+///
+/// Like `grep -c`, a line that contains the needle several times is counted once. Lines are
+/// separated by `\n`, and are written together with their newline, so a final line without a
+/// newline is written without one as well. The stream is processed line by line, so only the
+/// current line is buffered, however long it is. A needle that contains a newline never matches.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+///
+/// fn main() -> io::Result<()> {
+///     let mut stream = Cursor::new(b"rusty rust\nruby\ntrust");
+///     let mut out = Vec::new();
+///
+///     let count = xfind::grep(b"rust", &mut stream, &mut out)?;
+///     assert_eq!(count, 2);
+///     assert_eq!(out, b"rusty rust\ntrust");
+///
+///     Ok(())
+/// }
+/// ```
+pub fn grep<R, W>(needle: &[u8], rdr: &mut R, wtr: &mut W) -> io::Result<usize>
+where
+    R: Read,
+    W: Write,
+{
+    let finder = memmem::Finder::new(needle);
+    copy_lines_where(rdr, wtr, |line| finder.find(line).is_some())
+}
+
+/// Copies every line of the stream that does not contain the needle to the writer, and returns
+/// the number of lines written.
+///
+/// This is the inverse of [`grep`], like `grep -v`. Empty lines never contain a non-empty needle,
+/// so they are always written. Lines are handled like [`grep`] does.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+///
+/// fn main() -> io::Result<()> {
+///     let mut stream = Cursor::new(b"rusty rust\nruby\n\ntrust");
+///     let mut out = Vec::new();
+///
+///     let count = xfind::grep_invert(b"rust", &mut stream, &mut out)?;
+///     assert_eq!(count, 2);
+///     assert_eq!(out, b"ruby\n\n");
+///
+///     Ok(())
+/// }
+/// ```
+pub fn grep_invert<R, W>(
+    needle: &[u8],
+    rdr: &mut R,
+    wtr: &mut W,
+) -> io::Result<usize>
+where
+    R: Read,
+    W: Write,
+{
+    let finder = memmem::Finder::new(needle);
+    copy_lines_where(rdr, wtr, |line| finder.find(line).is_none())
+}
+
+/// Copies every line of a newline-delimited JSON stream that contains the needle to the writer,
+/// and returns the number of lines written.
+///
+/// This is a cheap substring prefilter that runs before actual JSON parsing downstream, so the
+/// lines aren't validated in any way. Unlike [`grep`], every line is written as a whole and
+/// followed by a newline, even the final line of a stream without a trailing newline, so that
+/// the output is valid newline-delimited JSON whenever its input is. Empty lines are skipped.
+/// Like [`grep`], only the current line is buffered, however long it is.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+///
+/// fn main() -> io::Result<()> {
+///     let mut stream = Cursor::new(b"{\"id\":1}\n\n{\"id\":2}\n{\"id\":12}");
+///     let mut out = Vec::new();
+///
+///     let count = xfind::filter_ndjson(b"1", &mut stream, &mut out)?;
+///     assert_eq!(count, 2);
+///     assert_eq!(out, b"{\"id\":1}\n{\"id\":12}\n");
+///
+///     Ok(())
+/// }
+/// ```
+pub fn filter_ndjson<R, W>(
+    needle: &[u8],
+    rdr: &mut R,
+    wtr: &mut W,
+) -> io::Result<usize>
+where
+    R: Read,
+    W: Write,
+{
+    let finder = memmem::Finder::new(needle);
+    let mut lines = split(b"\n", rdr);
+    let mut line = Vec::new();
+    let mut count = 0;
+
+    while lines.read_record(&mut line)?.is_some() {
+        if !line.is_empty() && finder.find(&line).is_some() {
+            wtr.write_all(&line)?;
+            wtr.write_all(b"\n")?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Returns the offset of the first line of a sorted stream that starts with the given prefix.
+///
+/// The lines of the stream must be sorted bytewise, like `LC_ALL=C sort` does. Like `look`, this
+/// does a binary search over the byte offsets of the stream: each step seeks to a midpoint,
+/// searches the newline that ends the line around it, and compares the start of the next line
+/// with the prefix. So it takes `O(log n)` seeks for a stream of `n` bytes, where each seek reads
+/// a single line and the prefix, instead of reading the whole stream. Lines are separated by
+/// `\n`, and a final line without a newline is searched as well. The reader is left at an
+/// unspecified position afterwards.
+///
+/// Returns `None` if no line starts with the prefix, which is always the case for an empty
+/// stream. The result is unspecified if the lines aren't sorted.
+///
+/// # Errors
+///
+/// Returns an I/O error if seeking or reading the stream failed.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+///
+/// fn main() -> io::Result<()> {
+///     let mut stream = Cursor::new(b"apple\nbanana\ncherry\ncherry pie\ndate");
+///
+///     assert_eq!(xfind::binary_search_prefix(&mut stream, b"cherry")?, Some(13));
+///     assert_eq!(xfind::binary_search_prefix(&mut stream, b"d")?, Some(31));
+///     assert_eq!(xfind::binary_search_prefix(&mut stream, b"blueberry")?, None);
+///
+///     Ok(())
+/// }
+/// ```
+pub fn binary_search_prefix<R: Read + Seek>(
+    rdr: &mut R,
+    prefix: &[u8],
+) -> io::Result<Option<u64>> {
+    let len = rdr.seek(SeekFrom::End(0))?;
+    let mut key = Vec::with_capacity(prefix.len());
+
+    // Find the smallest offset whose next line (or the end of the stream) isn't less than the
+    // prefix. Since the lines are sorted, this holds for every offset after it as well.
+    let (mut lo, mut hi) = (0, len);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        let start = next_line_start(rdr, mid, len)?;
+        if start < len
+            && read_key(rdr, start, prefix.len(), &mut key)? < prefix
+        {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+
+    let start = next_line_start(rdr, lo, len)?;
+    if start < len && read_key(rdr, start, prefix.len(), &mut key)? == prefix {
+        Ok(Some(start))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Returns the offset of the first line that starts at or after `pos`, or `len` if there is
+/// none.
+fn next_line_start<R: Read + Seek>(
+    rdr: &mut R,
+    pos: u64,
+    len: u64,
+) -> io::Result<u64> {
+    if pos == 0 {
+        return Ok(0);
+    }
+    // A line starts at `pos` if the byte before it is a newline.
+    rdr.seek(SeekFrom::Start(pos - 1))?;
+    match find(b"\n", rdr).transpose()? {
+        Some(newline) => Ok(pos + newline as u64),
+        None => Ok(len),
+    }
+}
+
+/// Reads up to `max_len` bytes of the line starting at `start` into `key`, and returns them.
+fn read_key<'k, R: Read + Seek>(
+    rdr: &mut R,
+    start: u64,
+    max_len: usize,
+    key: &'k mut Vec<u8>,
+) -> io::Result<&'k [u8]> {
+    rdr.seek(SeekFrom::Start(start))?;
+    key.clear();
+    rdr.take(max_len as u64).read_to_end(key)?;
+    if let Some(newline) = memchr(b'\n', key) {
+        key.truncate(newline);
+    }
+    Ok(key)
+}
+
+/// Copies every line of the stream for which `keep` returns `true` to the writer, and returns the
+/// number of lines written.
+fn copy_lines_where<R, W, F>(
+    rdr: &mut R,
+    wtr: &mut W,
+    mut keep: F,
+) -> io::Result<usize>
+where
+    R: Read,
+    W: Write,
+    F: FnMut(&[u8]) -> bool,
+{
+    let mut lines = split(b"\n", rdr);
+    let mut line = Vec::new();
+    let mut count = 0;
+
+    while let Some(terminated) = lines.read_record(&mut line)? {
+        if keep(&line) {
+            wtr.write_all(&line)?;
+            if terminated {
+                wtr.write_all(b"\n")?;
+            }
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::DEFAULT_BUFFER_CAPACITY;
+    use std::io::Cursor;
+
+    fn grep_str(needle: &str, haystack: &str) -> (usize, String) {
+        let mut out = Vec::new();
+        let count =
+            grep(needle.as_bytes(), &mut Cursor::new(haystack), &mut out)
+                .unwrap();
+        (count, String::from_utf8(out).unwrap())
+    }
+
+    fn grep_invert_str(needle: &str, haystack: &str) -> (usize, String) {
+        let mut out = Vec::new();
+        let count = grep_invert(
+            needle.as_bytes(),
+            &mut Cursor::new(haystack),
+            &mut out,
+        )
+        .unwrap();
+        (count, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_grep_counts_lines() {
+        let haystack = "dear me\nnothing\ndear dear\n\ndear";
+        assert_eq!(
+            grep_str("dear", haystack),
+            (3, "dear me\ndear dear\ndear".to_string())
+        );
+        assert_eq!(grep_str("me\nno", haystack), (0, String::new()));
+        assert_eq!(grep_str("dear", ""), (0, String::new()));
+        assert_eq!(grep_str("", "a\n\nb"), (3, "a\n\nb".to_string()));
+    }
+
+    #[test]
+    fn test_grep_long_lines() {
+        let mut haystack = "x".repeat(DEFAULT_BUFFER_CAPACITY * 3);
+        haystack.push_str("dear");
+        haystack.push_str(&"x".repeat(DEFAULT_BUFFER_CAPACITY));
+        let long_line = haystack.clone();
+        haystack.push_str("\nshort\n");
+        haystack.push_str(&"-".repeat(DEFAULT_BUFFER_CAPACITY * 2));
+        haystack.push_str("\ndear\n");
+
+        let expected = format!("{}\ndear\n", long_line);
+        assert_eq!(grep_str("dear", &haystack), (2, expected));
+    }
+
+    #[test]
+    fn test_grep_invert() {
+        let haystack = "dear me\nnothing\ndear dear\n\nend";
+        assert_eq!(
+            grep_invert_str("dear", haystack),
+            (3, "nothing\n\nend".to_string())
+        );
+        assert_eq!(grep_invert_str("dear", "\n\n"), (2, "\n\n".to_string()));
+        assert_eq!(grep_invert_str("", "a\n\nb"), (0, String::new()));
+        assert_eq!(grep_invert_str("dear", ""), (0, String::new()));
+    }
+
+    #[test]
+    fn test_grep_and_grep_invert_partition_lines() {
+        let mut haystack = String::new();
+        for i in 0..DEFAULT_BUFFER_CAPACITY / 4 {
+            haystack.push_str(&"ab".repeat(i % 13));
+            if i % 3 == 0 {
+                haystack.push_str("dear");
+            }
+            haystack.push('\n');
+        }
+
+        let (count, out) = grep_str("dear", &haystack);
+        let (invert_count, invert_out) = grep_invert_str("dear", &haystack);
+        assert_eq!(count + invert_count, DEFAULT_BUFFER_CAPACITY / 4);
+        assert_eq!(out.len() + invert_out.len(), haystack.len());
+    }
+
+    fn filter_ndjson_str(needle: &str, haystack: &str) -> (usize, String) {
+        let mut out = Vec::new();
+        let count = filter_ndjson(
+            needle.as_bytes(),
+            &mut Cursor::new(haystack),
+            &mut out,
+        )
+        .unwrap();
+        (count, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_filter_ndjson_terminates_lines() {
+        let haystack = "{\"id\":1}\n\n{\"id\":2}\r\n{\"id\":12}";
+        assert_eq!(
+            filter_ndjson_str("1", haystack),
+            (2, "{\"id\":1}\n{\"id\":12}\n".to_string())
+        );
+        assert_eq!(
+            filter_ndjson_str("\"id\"", haystack),
+            (3, "{\"id\":1}\n{\"id\":2}\r\n{\"id\":12}\n".to_string())
+        );
+        assert_eq!(filter_ndjson_str("", "\n\n"), (0, String::new()));
+    }
+
+    #[test]
+    fn test_filter_ndjson_long_lines() {
+        let long_value = "x".repeat(DEFAULT_BUFFER_CAPACITY * 3);
+        let long_line = format!("{{\"key\":\"{}\"}}", long_value);
+        let haystack = format!(
+            "{}\n{{\"other\":1}}\n{}\n{{\"key\":2}}",
+            long_line, long_line
+        );
+
+        let (count, out) = filter_ndjson_str("\"key\"", &haystack);
+        assert_eq!(count, 3);
+        assert_eq!(
+            out,
+            format!("{}\n{}\n{{\"key\":2}}\n", long_line, long_line)
+        );
+        assert!(out
+            .lines()
+            .all(|line| line.starts_with('{') && line.ends_with('}')));
+    }
+
+    /// Returns the offset of the first line starting with `prefix` by scanning every line.
+    fn linear_search_prefix(haystack: &[u8], prefix: &[u8]) -> Option<u64> {
+        let mut start = 0;
+        for line in haystack.split(|&b| b == b'\n') {
+            if start < haystack.len() && line.starts_with(prefix) {
+                return Some(start as u64);
+            }
+            start += line.len() + 1;
+        }
+        None
+    }
+
+    #[test]
+    fn test_binary_search_prefix() {
+        let mut words: Vec<String> = (0..3000)
+            .map(|i| format!("{:x}{}", i * 7919 % 4093, "z".repeat(i % 5)))
+            .collect();
+        // A line that is longer than the buffer of a newline search.
+        words.push(format!("7{}", "y".repeat(DEFAULT_BUFFER_CAPACITY * 2)));
+        words.sort();
+        let haystack = words.join("\n");
+
+        let mut stream = Cursor::new(haystack.as_bytes());
+        let prefixes = [
+            "",
+            "0",
+            "1",
+            "1f",
+            "7y",
+            "7yyyy",
+            "a",
+            "abc",
+            "fff",
+            "fffz",
+            "g",
+            "-",
+            &words[0],
+            &words[words.len() - 1],
+            &words[1234],
+        ];
+        for prefix in prefixes.iter() {
+            let expected =
+                linear_search_prefix(haystack.as_bytes(), prefix.as_bytes());
+            let found =
+                binary_search_prefix(&mut stream, prefix.as_bytes()).unwrap();
+            assert_eq!(found, expected, "prefix {:?}", prefix);
+        }
+
+        // The last line with and without a trailing newline.
+        let last = &words[words.len() - 1];
+        let mut terminated = haystack.clone();
+        terminated.push('\n');
+        let found = binary_search_prefix(
+            &mut Cursor::new(terminated.as_bytes()),
+            last.as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(found, Some((haystack.len() - last.len()) as u64));
+    }
+
+    #[test]
+    fn test_binary_search_prefix_small() {
+        let search = |haystack: &[u8], prefix: &[u8]| {
+            binary_search_prefix(&mut Cursor::new(haystack), prefix).unwrap()
+        };
+        assert_eq!(search(b"", b""), None);
+        assert_eq!(search(b"", b"a"), None);
+        assert_eq!(search(b"a", b""), Some(0));
+        assert_eq!(search(b"a", b"a"), Some(0));
+        assert_eq!(search(b"a", b"ab"), None);
+        assert_eq!(search(b"a\nb", b"b"), Some(2));
+        assert_eq!(search(b"a\nb\n", b"c"), None);
+        // Empty lines sort first.
+        assert_eq!(search(b"\n\na\na\nb", b"a"), Some(2));
+        assert_eq!(search(b"\n\na\na\nb", b""), Some(0));
+    }
+}
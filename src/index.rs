@@ -0,0 +1,203 @@
+//! Provides an index of the occurrences of a substring in a stream for answering repeated queries.
+use crate::source::{search, Stream};
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// The offsets of all non-overlapping occurrences of a needle in a stream.
+///
+/// Building an index scans the stream once, and records the offsets of all matches in ascending
+/// order. All queries are then answered from the recorded offsets in `O(log n)` time, where `n`
+/// is the number of matches, without reading the stream again. This amortizes the cost of the
+/// scan across many queries against a large file. An index only answers queries about the needle
+/// it was built for, and it holds one `usize` per match in memory.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+/// use xfind::Index;
+///
+/// fn main() -> io::Result<()> {
+///     let index = Index::build(&mut Cursor::new(b"rusty rust, rustacean"), b"rust")?;
+///
+///     assert_eq!(index.count(), 3);
+///     assert_eq!(index.nth(1), Some(6));
+///     assert_eq!(index.range(1, 12), &[6]);
+///     assert_eq!(index.nearest(11), Some(12));
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Index {
+    /// The needle whose occurrences are indexed.
+    needle: Vec<u8>,
+    /// The offsets of the occurrences, in ascending order.
+    offsets: Vec<usize>,
+}
+
+impl Index {
+    /// Builds an index of the given needle over the whole stream.
+    ///
+    /// The stream is searched from its start, regardless of its current position, and is left
+    /// at its end. Like [`search`], an empty needle matches at every offset, including the end
+    /// of the stream.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if seeking or reading the stream failed.
+    pub fn build<R: Read + Seek>(
+        rdr: &mut R,
+        needle: &[u8],
+    ) -> io::Result<Index> {
+        rdr.seek(SeekFrom::Start(0))?;
+        let offsets = search(Stream(rdr), needle)?;
+        Ok(Index { needle: needle.to_vec(), offsets })
+    }
+
+    /// Returns the needle that this index was built for.
+    pub fn needle(&self) -> &[u8] {
+        &self.needle
+    }
+
+    /// Returns the number of matches.
+    pub fn count(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Returns the offset of the `k`-th match, counting from 0.
+    pub fn nth(&self, k: usize) -> Option<usize> {
+        self.offsets.get(k).copied()
+    }
+
+    /// Returns the offsets of the matches that start within `lo..hi`, in ascending order.
+    ///
+    /// The range is empty if `hi` isn't bigger than `lo`.
+    pub fn range(&self, lo: usize, hi: usize) -> &[usize] {
+        if lo >= hi {
+            return &[];
+        }
+        let start = self.offsets.partition_point(|&pos| pos < lo);
+        let end = self.offsets.partition_point(|&pos| pos < hi);
+        &self.offsets[start..end]
+    }
+
+    /// Returns the offset of the match that starts closest to `offset`, preferring the earlier
+    /// match if two are equally close.
+    pub fn nearest(&self, offset: usize) -> Option<usize> {
+        let i = self.offsets.partition_point(|&pos| pos < offset);
+        let after = self.offsets.get(i).copied();
+        let before = i.checked_sub(1).map(|i| self.offsets[i]);
+        match (before, after) {
+            (Some(before), Some(after))
+                if after - offset < offset - before =>
+            {
+                Some(after)
+            }
+            (Some(before), _) => Some(before),
+            (None, after) => after,
+        }
+    }
+
+    /// Returns the offsets of all matches, in ascending order.
+    pub fn as_slice(&self) -> &[usize] {
+        &self.offsets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::FaultReader;
+    use crate::StreamFinder;
+    use std::fs::File;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_index_queries() {
+        let mut file = File::open("data/pride-and-prejudice.txt").unwrap();
+        let index = Index::build(&mut file, b"Elizabeth").unwrap();
+        let expected = StreamFinder::new(b"Elizabeth")
+            .find_all(&mut File::open("data/pride-and-prejudice.txt").unwrap())
+            .unwrap();
+        assert_eq!(index.needle(), b"Elizabeth");
+        assert_eq!(index.as_slice(), &expected[..]);
+
+        assert_eq!(index.count(), expected.len());
+        assert_eq!(index.nth(0), Some(expected[0]));
+        assert_eq!(index.nth(expected.len() - 1), expected.last().copied());
+        assert_eq!(index.nth(expected.len()), None);
+
+        for &(lo, hi) in
+            &[(0, 10_000), (5000, 300_000), (300_000, 5000), (0, usize::MAX)]
+        {
+            let direct: Vec<usize> = expected
+                .iter()
+                .copied()
+                .filter(|&pos| lo <= pos && pos < hi)
+                .collect();
+            assert_eq!(
+                index.range(lo, hi),
+                &direct[..],
+                "range {}..{}",
+                lo,
+                hi
+            );
+        }
+        assert_eq!(index.range(expected[3], expected[3] + 1), &[expected[3]]);
+
+        for offset in (0..800_000).step_by(7919) {
+            let direct = expected
+                .iter()
+                .copied()
+                .min_by_key(|&pos| (pos as isize - offset as isize).abs());
+            assert_eq!(index.nearest(offset), direct, "offset {}", offset);
+        }
+    }
+
+    #[test]
+    fn test_index_edge_cases() {
+        let index =
+            Index::build(&mut Cursor::new(b"ab    ab"), b"ab").unwrap();
+        // Ties are won by the earlier match.
+        assert_eq!(index.nearest(3), Some(0));
+        assert_eq!(index.nearest(4), Some(6));
+        assert_eq!(index.nearest(100), Some(6));
+
+        let index = Index::build(&mut Cursor::new(b"rusty"), b"crab").unwrap();
+        assert_eq!(
+            (index.count(), index.nth(0), index.nearest(2)),
+            (0, None, None)
+        );
+        assert!(index.range(0, 10).is_empty());
+
+        let index = Index::build(&mut Cursor::new(b"abc"), b"").unwrap();
+        assert_eq!(index.as_slice(), &[0, 1, 2, 3]);
+
+        // The stream is searched from its start.
+        let mut stream = Cursor::new(b"rust rust");
+        stream.set_position(5);
+        assert_eq!(Index::build(&mut stream, b"rust").unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_index_error() {
+        /// A stream that is positioned at its start and fails to read.
+        struct Failing(FaultReader<&'static [u8]>);
+
+        impl Read for Failing {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.0.read(buf)
+            }
+        }
+
+        impl Seek for Failing {
+            fn seek(&mut self, _: SeekFrom) -> io::Result<u64> {
+                Ok(0)
+            }
+        }
+
+        let mut rdr =
+            Failing(FaultReader::new(&b"rusty rust"[..]).error_at(4));
+        assert!(Index::build(&mut rdr, b"rust").is_err());
+    }
+}
@@ -5,7 +5,8 @@
 //! [`Read`] instances) rather than in-memory buffers.
 //!
 //! Note that this crate provides no advantage when searching substring in a source that is already
-//! in memory, in this case consider using the [`memchr`] library instead. Besides, if you want to
+//! in memory, in this case consider using the [`memchr`] library instead, or [`search`], which
+//! picks `memchr` for in-memory sources automatically. Besides, if you want to
 //! search multiple substrings at once, take a look at [`aho-corasick`].
 //!
 //! # Complexity
@@ -52,20 +53,65 @@
 //! (group 2, 4). The performances of `xfind` and `memmem` are pretty close, only memory usages
 //! differ.
 //!
+//! # Non-seekable streams
+//!
+//! Forward searches only require [`Read`], so they work on any stream, e.g. pipes, sockets or
+//! decompressors like [`flate2`](https://crates.io/crates/flate2), `zstd` or `bzip2`. Only reverse
+//! searches (and a few helpers that explicitly say so) require [`Seek`] as well.
+//!
+//! ```
+//! use flate2::read::{GzDecoder, GzEncoder};
+//! use flate2::Compression;
+//! use std::io::{self, Cursor};
+//!
+//! fn main() -> io::Result<()> {
+//!     let compressed = GzEncoder::new(&b"rusty rust"[..], Compression::fast());
+//!     let mut stream = GzDecoder::new(compressed);
+//!
+//!     let matches = xfind::find_iter(b"rust", &mut stream).into_offsets()?;
+//!     assert_eq!(matches, vec![0, 6]);
+//!
+//!     Ok(())
+//! }
+//! ```
+//!
+//! # Line endings
+//!
+//! Searches are byte-oriented and never translate line endings, so the offsets they report are
+//! raw byte offsets, which agree with `memchr::memmem` over the same bytes. `std::fs::File` reads
+//! files unchanged on every platform, including the `\r\n` line endings of files written on
+//! Windows. To search with offsets into the text as if its line endings were `\n`, wrap the
+//! stream in [`normalize_crlf`].
+//!
 //! [`memchr`]: https://crates.io/crates/memchr
 //! [`aho-corasick`]: https://crates.io/crates/aho-corasick
 //! [`Read`]: std::io::Read
+//! [`Seek`]: std::io::Seek
+//!
+//! # Crate features
+//!
+//! - `crc` - Enables `FindIter::with_crc` and `FindIter::finish_crc` for computing the CRC32
+//!   checksum of a stream while searching it, using the
+//!   [`crc32fast`](https://crates.io/crates/crc32fast) crate.
+//! - `encoding_rs` - Enables `find_iter_encoded` for searching text in encodings other than
+//!   UTF-8, using the [`encoding_rs`](https://crates.io/crates/encoding_rs) crate.
+//! - `logging` - Emits `trace!` messages with the positions involved whenever a forward or
+//!   backward search fills or rolls its buffer, using the [`log`](https://crates.io/crates/log)
+//!   facade. Without this feature, no logging code is compiled at all.
+//! - `safe-buffer` - Moves bytes around the internal buffers with safe code only, at a possible
+//!   minor performance cost. This is meant for safety audits.
+//! - `testutil` - Enables the [`testutil`] module with readers for testing code under adverse I/O
+//!   conditions.
+//! - `unicode` - Enables `find_iter_caseless` for searching case-insensitively with Unicode case
+//!   folding.
 //!
 //! # Examples
 //!
 //! - Checks if a substring exists in a file.
 //!
 //! ```no_run
-//! use std::fs::File;
-//!
 //! fn main() -> std::io::Result<()> {
-//!     let mut rdr = File::open("foo.txt")?;
-//!     let found = xfind::find(b"bar", &mut rdr).is_some();
+//!     let found = xfind::find_in_file(b"bar", "foo.txt")?.is_some();
 //!
 //!     Ok(())
 //! }
@@ -74,12 +120,10 @@
 //! - Gets the indexes of the first 10 occurrences of a substring in a file.
 //!
 //! ```no_run
-//! use std::fs::File;
 //! use std::io;
 //!
 //! fn main() -> io::Result<()> {
-//!     let mut rdr = File::open("foo.txt")?;
-//!     let indexes = xfind::find_iter(b"bar", &mut rdr)
+//!     let indexes = xfind::find_iter_in_file(b"bar", "foo.txt")?
 //!         .take(10)
 //!         .collect::<io::Result<Vec<usize>>>()?;
 //!
@@ -150,7 +194,57 @@
 //! ```
 #![deny(missing_docs)]
 
+/// Emits a `log::trace!` message if the `logging` feature is enabled, and compiles to nothing
+/// otherwise.
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "logging")]
+        log::trace!($($arg)*);
+    };
+}
+
+mod adapter;
 mod buffer;
+mod cached;
+#[cfg(feature = "unicode")]
+mod caseless;
+mod context;
+#[cfg(feature = "encoding_rs")]
+mod encoding;
+mod engine;
+mod error;
+mod escape;
 mod finder;
+mod glob;
+mod grep;
+mod index;
+mod multi;
+mod newline;
+mod rewrite;
+mod source;
+mod span;
+mod split;
+#[cfg(any(test, feature = "testutil"))]
+pub mod testutil;
 
+pub use adapter::*;
+pub use buffer::Buffer;
+pub use cached::*;
+#[cfg(feature = "unicode")]
+pub use caseless::*;
+pub use context::*;
+#[cfg(feature = "encoding_rs")]
+pub use encoding::*;
+pub use engine::*;
+pub use error::SearchError;
+pub use escape::*;
 pub use finder::*;
+pub use glob::*;
+pub use grep::*;
+pub use index::*;
+pub use multi::*;
+pub use newline::*;
+pub use rewrite::*;
+pub use source::*;
+pub use span::*;
+pub use split::*;
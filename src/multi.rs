@@ -0,0 +1,622 @@
+//! Provides a searcher for multiple substrings at once that operates on stream.
+use crate::buffer::Buffer;
+use memchr::memmem;
+use std::cmp;
+use std::io::{self, Read};
+use std::iter::Peekable;
+
+/// A match of one of the needles of a [`StreamMultiFinder`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NeedleMatch {
+    /// The offset at which the match begins.
+    pub offset: usize,
+    /// The index of the matching needle.
+    pub needle_index: usize,
+    /// The length of the matching needle.
+    pub len: usize,
+}
+
+/// A searcher for multiple substrings at once in streams.
+///
+/// This is meant for a handful of needles. If you want to search many needles at once, take a
+/// look at [`aho-corasick`](https://crates.io/crates/aho-corasick).
+#[derive(Clone, Debug)]
+pub struct StreamMultiFinder<'n> {
+    /// The strings we want to search.
+    needles: Vec<&'n [u8]>,
+}
+
+impl<'n> StreamMultiFinder<'n> {
+    /// Creates a new `StreamMultiFinder` for the given needles.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xfind::StreamMultiFinder;
+    ///
+    /// let finder = StreamMultiFinder::new(&[b"rust", b"crab"]);
+    /// ```
+    pub fn new(needles: &[&'n [u8]]) -> StreamMultiFinder<'n> {
+        StreamMultiFinder { needles: needles.to_vec() }
+    }
+
+    /// Returns the needles that this finder searches for.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xfind::StreamMultiFinder;
+    ///
+    /// let finder = StreamMultiFinder::new(&[b"rust", b"crab"]);
+    /// assert_eq!(finder.needles(), &[b"rust", b"crab"]);
+    /// ```
+    pub fn needles(&self) -> &[&'n [u8]] {
+        &self.needles
+    }
+
+    /// Returns an iterator over the non-overlapping matches of the needles in the stream, with
+    /// leftmost-first precedence.
+    ///
+    /// The precedence rule is the same as for an alternation in most regex engines:
+    ///
+    /// - The match that begins at the smallest offset is reported first.
+    /// - If several needles match at that offset, the one with the lowest index in
+    ///   [`needles`](StreamMultiFinder::needles) wins, even if a later needle is longer.
+    /// - The search then resumes at the end of the reported match, so the matches never overlap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::{NeedleMatch, StreamMultiFinder};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"abc");
+    ///     let finder = StreamMultiFinder::new(&[b"ab", b"abc"]);
+    ///
+    ///     let mut iter = finder.find_iter_leftmost_first(&mut stream);
+    ///     assert_eq!(
+    ///         iter.next().transpose()?,
+    ///         Some(NeedleMatch { offset: 0, needle_index: 0, len: 2 })
+    ///     );
+    ///     assert_eq!(iter.next().transpose()?, None);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_iter_leftmost_first<'s, R: Read>(
+        &'n self,
+        rdr: &'s mut R,
+    ) -> FindLeftmostFirstIter<'n, 's, R> {
+        let max_len = self.needles.iter().map(|n| n.len()).max().unwrap_or(0);
+        FindLeftmostFirstIter {
+            rdr,
+            needles: &self.needles,
+            buf: Buffer::new(max_len),
+            max_len,
+            buf_offset: 0,
+            search_pos: 0,
+            candidates: vec![Candidate::Unknown; self.needles.len()],
+        }
+    }
+
+    /// Returns an iterator over all matches of all needles in the stream, ordered by offset.
+    ///
+    /// Unlike [`find_iter_leftmost_first`](StreamMultiFinder::find_iter_leftmost_first), no
+    /// match hides another one: every occurrence of every needle is reported, even if it overlaps
+    /// an occurrence of the same or another needle. Matches at the same offset are ordered by the
+    /// index of their needle. This produces the complete list of token candidates for a lexer in a
+    /// single pass over the stream.
+    ///
+    /// An empty needle matches at every offset, including the end of the stream.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Cursor};
+    /// use xfind::{NeedleMatch, StreamMultiFinder};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut stream = Cursor::new(b"abcc");
+    ///     let finder = StreamMultiFinder::new(&[b"abc", b"ab", b"c"]);
+    ///
+    ///     let matches = finder.find_iter_all(&mut stream).collect::<io::Result<Vec<_>>>()?;
+    ///     assert_eq!(
+    ///         matches,
+    ///         vec![
+    ///             NeedleMatch { offset: 0, needle_index: 0, len: 3 },
+    ///             NeedleMatch { offset: 0, needle_index: 1, len: 2 },
+    ///             NeedleMatch { offset: 2, needle_index: 2, len: 1 },
+    ///             NeedleMatch { offset: 3, needle_index: 2, len: 1 },
+    ///         ]
+    ///     );
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn find_iter_all<'s, R: Read>(
+        &'n self,
+        rdr: &'s mut R,
+    ) -> FindAllIter<'n, 's, R> {
+        // Retaining at least one byte keeps the start of the next search inside the buffer.
+        let max_len = cmp::max(
+            self.needles.iter().map(|n| n.len()).max().unwrap_or(0),
+            1,
+        );
+        FindAllIter {
+            rdr,
+            needles: &self.needles,
+            buf: Buffer::new(max_len),
+            max_len,
+            buf_offset: 0,
+            search_pos: 0,
+            pending: Vec::new(),
+            done: false,
+        }
+    }
+}
+
+/// The next occurrence of a needle in the buffer, as far as we know.
+#[derive(Clone, Copy, Debug)]
+enum Candidate {
+    /// The buffer has changed since the last search.
+    Unknown,
+    /// The needle occurs at the given position of the buffer.
+    At(usize),
+    /// The needle doesn't occur fully in the buffer.
+    Absent,
+}
+
+/// A forward iterator over the non-overlapping matches of multiple needles in a stream, with
+/// leftmost-first precedence.
+///
+/// This iterator is created by [`StreamMultiFinder::find_iter_leftmost_first`].
+#[derive(Debug)]
+pub struct FindLeftmostFirstIter<'n, 's, R: Read> {
+    /// The stream source we read from.
+    rdr: &'s mut R,
+    /// The needles we search for.
+    needles: &'n [&'n [u8]],
+    /// A fixed size buffer that we actually search for. It must be big enough to hold the longest
+    /// needle.
+    buf: Buffer,
+    /// The length of the longest needle.
+    max_len: usize,
+    /// The absolute position of the start of `self.buf` in the stream.
+    buf_offset: usize,
+    /// The current position at which to start the next search in `self.buf`.
+    search_pos: usize,
+    /// The next occurrence of each needle at or after `search_pos`.
+    candidates: Vec<Candidate>,
+}
+
+impl<'n, 's, R: Read> Iterator for FindLeftmostFirstIter<'n, 's, R> {
+    type Item = io::Result<NeedleMatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let len = self.buf.len();
+            let mut best: Option<(usize, usize)> = None;
+            for (i, needle) in self.needles.iter().enumerate() {
+                let stale = match self.candidates[i] {
+                    Candidate::Unknown => true,
+                    Candidate::At(pos) => pos < self.search_pos,
+                    Candidate::Absent => false,
+                };
+                if stale {
+                    self.candidates[i] = match self
+                        .buf
+                        .buffer()
+                        .get(self.search_pos..)
+                        .and_then(|haystack| memmem::find(haystack, needle))
+                    {
+                        Some(mat) => Candidate::At(self.search_pos + mat),
+                        None => Candidate::Absent,
+                    };
+                }
+                if let Candidate::At(pos) = self.candidates[i] {
+                    // Ties are won by the needle with the lowest index.
+                    #[allow(clippy::unnecessary_map_or)]
+                    if best.map_or(true, |(best_pos, _)| pos < best_pos) {
+                        best = Some((pos, i));
+                    }
+                }
+            }
+
+            // A needle with a lower index might still match at an earlier or the same position,
+            // unless every needle starting there fits into the buffer.
+            if let Some((pos, i)) = best {
+                if pos + self.max_len <= len || self.buf.is_eof() {
+                    let needle_len = self.needles[i].len();
+                    self.search_pos = pos + cmp::max(1, needle_len);
+                    return Some(Ok(NeedleMatch {
+                        offset: self.buf_offset + pos,
+                        needle_index: i,
+                        len: needle_len,
+                    }));
+                }
+            }
+
+            if self.buf.is_eof() {
+                return None;
+            }
+
+            // Roll our buffer if our buffer has at least the minimum amount of bytes in it. All
+            // known candidates lie in the retained suffix, because none of them could be
+            // reported.
+            if len >= self.buf.retain_len() {
+                let dropped = len - self.buf.retain_len();
+                self.buf.roll();
+                self.buf_offset += dropped;
+                self.search_pos = self.search_pos.saturating_sub(dropped);
+                for candidate in self.candidates.iter_mut() {
+                    if let Candidate::At(pos) = candidate {
+                        *candidate = Candidate::At(*pos - dropped);
+                    }
+                }
+            }
+            match self.buf.fill(&mut self.rdr) {
+                // report any I/O errors.
+                Err(err) => return Some(Err(err)),
+                // we've reach EOF, check the remaining candidates for the last time.
+                Ok(false) => {}
+                // new bytes may contain needles that were absent so far.
+                Ok(true) => {
+                    for candidate in self.candidates.iter_mut() {
+                        if let Candidate::Absent = candidate {
+                            *candidate = Candidate::Unknown;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A forward iterator over all matches of multiple needles in a stream, ordered by offset.
+///
+/// This iterator is created by [`StreamMultiFinder::find_iter_all`].
+#[derive(Debug)]
+pub struct FindAllIter<'n, 's, R: Read> {
+    /// The stream source we read from.
+    rdr: &'s mut R,
+    /// The needles we search for.
+    needles: &'n [&'n [u8]],
+    /// A fixed size buffer that we actually search for. It must be big enough to hold the longest
+    /// needle.
+    buf: Buffer,
+    /// The length of the longest needle, but at least 1.
+    max_len: usize,
+    /// The absolute position of the start of `self.buf` in the stream.
+    buf_offset: usize,
+    /// The first position in `self.buf` at which matches haven't been collected yet.
+    search_pos: usize,
+    /// The collected matches that haven't been reported yet, in reverse order.
+    pending: Vec<NeedleMatch>,
+    /// Whether matches have been collected up to the end of the stream.
+    done: bool,
+}
+
+impl<'n, 's, R: Read> FindAllIter<'n, 's, R> {
+    /// Collects the matches of all needles that start in `self.buf` from `self.search_pos` up to
+    /// (excluding) `end`.
+    fn collect_until(&mut self, end: usize) {
+        let contents = self.buf.buffer();
+        for (i, needle) in self.needles.iter().enumerate() {
+            let window_end = cmp::min(end - 1 + needle.len(), contents.len());
+            let window = &contents[self.search_pos..window_end];
+            // Resuming right after the start of each match finds overlapping matches too.
+            let mut start = 0;
+            while let Some(mat) = window
+                .get(start..)
+                .and_then(|haystack| memmem::find(haystack, needle))
+            {
+                self.pending.push(NeedleMatch {
+                    offset: self.buf_offset + self.search_pos + start + mat,
+                    needle_index: i,
+                    len: needle.len(),
+                });
+                start += mat + 1;
+            }
+        }
+        self.pending.sort_unstable_by(|a, b| {
+            (b.offset, b.needle_index).cmp(&(a.offset, a.needle_index))
+        });
+        self.search_pos = end;
+    }
+}
+
+impl<'n, 's, R: Read> Iterator for FindAllIter<'n, 's, R> {
+    type Item = io::Result<NeedleMatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(mat) = self.pending.pop() {
+                return Some(Ok(mat));
+            }
+            if self.done {
+                return None;
+            }
+
+            // A match starting at `end - 1` or earlier fits into the buffer, unless the stream
+            // ends before it. At EOF, empty needles also match at the very end.
+            let len = self.buf.len();
+            if self.buf.is_eof() {
+                self.collect_until(len + 1);
+                self.done = true;
+                continue;
+            }
+            let end = (len + 1).saturating_sub(self.max_len);
+            if end > self.search_pos {
+                self.collect_until(end);
+                continue;
+            }
+
+            // Roll our buffer if our buffer has at least the minimum amount of bytes in it. The
+            // matches starting in the retained suffix haven't been collected yet.
+            if len >= self.buf.retain_len() {
+                let dropped = len - self.buf.retain_len();
+                self.buf.roll();
+                self.buf_offset += dropped;
+                self.search_pos -= dropped;
+            }
+            if let Err(err) = self.buf.fill(&mut self.rdr) {
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+/// Merges two iterators over match offsets into one iterator over sorted, labeled offsets.
+///
+/// This combines independent searches, e.g. two [`FindIter`](crate::FindIter)s over different
+/// streams or with different options, without the multi-needle machinery of
+/// [`StreamMultiFinder`]. Each input must yield its offsets in ascending order, like all forward
+/// iterators of this crate do. The merged iterator always advances the input with the smaller
+/// next offset, and prefers [`Side::Left`] on ties. Errors are passed through as soon as they are
+/// encountered.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+/// use xfind::Side;
+///
+/// fn main() -> io::Result<()> {
+///     let mut stream1 = Cursor::new(b"rusty crab");
+///     let mut stream2 = Cursor::new(b"rusty crab");
+///
+///     let merged = xfind::merge_iters(
+///         xfind::find_iter(b"crab", &mut stream1),
+///         xfind::find_iter(b"rust", &mut stream2),
+///     );
+///     assert_eq!(
+///         merged.collect::<io::Result<Vec<_>>>()?,
+///         vec![(0, Side::Right), (6, Side::Left)]
+///     );
+///
+///     Ok(())
+/// }
+/// ```
+pub fn merge_iters<A, B>(left: A, right: B) -> MergeIter<A, B>
+where
+    A: Iterator<Item = io::Result<usize>>,
+    B: Iterator<Item = io::Result<usize>>,
+{
+    MergeIter { left: left.peekable(), right: right.peekable() }
+}
+
+/// The input of [`merge_iters`] that an offset stems from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Side {
+    /// The first input.
+    Left,
+    /// The second input.
+    Right,
+}
+
+/// An iterator over the sorted offsets of two merged iterators.
+///
+/// This iterator is created by [`merge_iters`].
+#[derive(Debug)]
+pub struct MergeIter<A, B>
+where
+    A: Iterator<Item = io::Result<usize>>,
+    B: Iterator<Item = io::Result<usize>>,
+{
+    /// The first input.
+    left: Peekable<A>,
+    /// The second input.
+    right: Peekable<B>,
+}
+
+impl<A, B> Iterator for MergeIter<A, B>
+where
+    A: Iterator<Item = io::Result<usize>>,
+    B: Iterator<Item = io::Result<usize>>,
+{
+    type Item = io::Result<(usize, Side)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let side = match (self.left.peek(), self.right.peek()) {
+            (None, None) => return None,
+            (Some(Err(_)), _) | (Some(_), None) => Side::Left,
+            (_, Some(Err(_))) | (None, Some(_)) => Side::Right,
+            (Some(Ok(left)), Some(Ok(right))) => {
+                if left <= right {
+                    Side::Left
+                } else {
+                    Side::Right
+                }
+            }
+        };
+        let item = match side {
+            Side::Left => self.left.next()?,
+            Side::Right => self.right.next()?,
+        };
+        Some(item.map(|pos| (pos, side)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::DEFAULT_BUFFER_CAPACITY;
+    use crate::StreamFinder;
+    use std::io::Cursor;
+
+    fn leftmost_first(needles: &[&[u8]], haystack: &[u8]) -> Vec<NeedleMatch> {
+        StreamMultiFinder::new(needles)
+            .find_iter_leftmost_first(&mut Cursor::new(haystack))
+            .map(|x| x.unwrap())
+            .collect()
+    }
+
+    fn m(offset: usize, needle_index: usize, len: usize) -> NeedleMatch {
+        NeedleMatch { offset, needle_index, len }
+    }
+
+    #[test]
+    fn test_leftmost_first_precedence() {
+        assert_eq!(leftmost_first(&[b"ab", b"abc"], b"abc"), vec![m(0, 0, 2)]);
+        assert_eq!(leftmost_first(&[b"abc", b"ab"], b"abc"), vec![m(0, 0, 3)]);
+        assert_eq!(
+            leftmost_first(&[b"abc", b"ab"], b"ab abc"),
+            vec![m(0, 1, 2), m(3, 0, 3)]
+        );
+    }
+
+    #[test]
+    fn test_leftmost_first_earliest_position_wins() {
+        // `b` starts earlier than `cd`, so it wins despite its higher index.
+        assert_eq!(
+            leftmost_first(&[b"cd", b"b"], b"abcd"),
+            vec![m(1, 1, 1), m(2, 0, 2)]
+        );
+        // Matches don't overlap.
+        assert_eq!(leftmost_first(&[b"bc", b"ab"], b"abc"), vec![m(0, 1, 2)]);
+    }
+
+    #[test]
+    fn test_leftmost_first_across_rolls() {
+        let needles: &[&[u8]] = &[b"x", b"long-needle", b"long"];
+        for shift in 0..12 {
+            let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 2];
+            let pos = DEFAULT_BUFFER_CAPACITY - shift;
+            haystack[pos..pos + 11].copy_from_slice(b"long-needle");
+            haystack[pos + 20..pos + 24].copy_from_slice(b"long");
+            haystack[pos + 30] = b'x';
+
+            assert_eq!(
+                leftmost_first(needles, &haystack),
+                vec![m(pos, 1, 11), m(pos + 20, 2, 4), m(pos + 30, 0, 1)],
+                "shift: {}",
+                shift
+            );
+        }
+    }
+
+    #[test]
+    fn test_leftmost_first_no_needles() {
+        assert_eq!(leftmost_first(&[], b"abc"), vec![]);
+    }
+
+    #[test]
+    fn test_merge_iters() {
+        let haystack = b"Rust rust RUST rusty";
+        let (mut stream1, mut stream2) =
+            (Cursor::new(haystack), Cursor::new(haystack));
+        let upper = StreamFinder::new(b"RUST");
+        let merged: Vec<(usize, Side)> = merge_iters(
+            upper.find_iter_transformed(&mut stream1, |buf| {
+                buf.make_ascii_uppercase()
+            }),
+            crate::find_iter(b"rust", &mut stream2),
+        )
+        .map(|x| x.unwrap())
+        .collect();
+        assert_eq!(
+            merged,
+            vec![
+                (0, Side::Left),
+                (5, Side::Left),
+                (5, Side::Right),
+                (10, Side::Left),
+                (15, Side::Left),
+                (15, Side::Right),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_iters_errors_and_exhaustion() {
+        let left = vec![Ok(1), Ok(7)].into_iter();
+        let right =
+            vec![Ok(3), Err(io::ErrorKind::Other.into()), Ok(4)].into_iter();
+        let mut merged = merge_iters(left, right);
+        assert_eq!(merged.next().unwrap().unwrap(), (1, Side::Left));
+        assert_eq!(merged.next().unwrap().unwrap(), (3, Side::Right));
+        assert!(merged.next().unwrap().is_err());
+        assert_eq!(merged.next().unwrap().unwrap(), (4, Side::Right));
+        assert_eq!(merged.next().unwrap().unwrap(), (7, Side::Left));
+        assert!(merged.next().is_none());
+    }
+
+    /// Returns all matches of all needles by comparing them at every offset.
+    fn all_naive(needles: &[&[u8]], haystack: &[u8]) -> Vec<NeedleMatch> {
+        let mut matches = Vec::new();
+        for offset in 0..=haystack.len() {
+            for (i, needle) in needles.iter().enumerate() {
+                if haystack[offset..].starts_with(needle) {
+                    matches.push(m(offset, i, needle.len()));
+                }
+            }
+        }
+        matches
+    }
+
+    fn all(needles: &[&[u8]], haystack: &[u8]) -> Vec<NeedleMatch> {
+        StreamMultiFinder::new(needles)
+            .find_iter_all(&mut Cursor::new(haystack))
+            .map(|x| x.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_find_iter_all_overlapping() {
+        assert_eq!(
+            all(&[b"aa", b"a"], b"aaa"),
+            vec![m(0, 0, 2), m(0, 1, 1), m(1, 0, 2), m(1, 1, 1), m(2, 1, 1)]
+        );
+        assert_eq!(
+            all(&[b"bc", b"abcd", b"ab"], b"abcd"),
+            vec![m(0, 1, 4), m(0, 2, 2), m(1, 0, 2)]
+        );
+        assert_eq!(
+            all(&[b""], b"ab"),
+            vec![m(0, 0, 0), m(1, 0, 0), m(2, 0, 0)]
+        );
+        assert_eq!(all(&[], b"abc"), vec![]);
+        assert_eq!(all(&[b"abc"], b""), vec![]);
+    }
+
+    #[test]
+    fn test_find_iter_all_across_rolls() {
+        let needles: &[&[u8]] = &[b"x", b"long-needle", b"long", b"gg", b""];
+        for shift in 0..12 {
+            let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 2];
+            let pos = DEFAULT_BUFFER_CAPACITY - shift;
+            haystack[pos..pos + 11].copy_from_slice(b"long-needle");
+            haystack[pos + 20..pos + 24].copy_from_slice(b"long");
+            haystack[pos + 24..pos + 28].copy_from_slice(b"gggg");
+            haystack[pos + 30] = b'x';
+
+            assert_eq!(
+                all(needles, &haystack),
+                all_naive(needles, &haystack),
+                "shift: {}",
+                shift
+            );
+        }
+    }
+}
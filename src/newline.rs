@@ -0,0 +1,265 @@
+//! Provides stream searches that treat the different line endings as equivalent.
+use crate::buffer::Buffer;
+use crate::span::MatchSpan;
+use memchr::{memchr2, memmem};
+use std::io::{self, Read};
+
+/// Returns an iterator over all non-overlapping occurrences of the given needle in the stream,
+/// where every line ending of the needle matches `\n`, `\r\n` and a bare `\r` alike.
+///
+/// This finds multi-line needles in text with mixed line endings, e.g. files that have been
+/// edited on different platforms. Line endings in the needle are normalized first, so `\r\n` and
+/// `\r` in the needle match any line ending as well. A `\r\n` in the stream always counts as a
+/// single line ending.
+///
+/// Matches are reported as spans in the original stream, since a match is longer than the
+/// needle if it contains `\r\n` line endings.
+///
+/// # Panics
+///
+/// Panics if `needle` is empty.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+/// use xfind::MatchSpan;
+///
+/// fn main() -> io::Result<()> {
+///     let mut stream = Cursor::new(b"a\nb, a\r\nb, a\rb");
+///
+///     let spans = xfind::find_iter_any_newline(b"a\nb", &mut stream)
+///         .collect::<io::Result<Vec<_>>>()?;
+///     assert_eq!(spans[0], MatchSpan::new(0, 3));
+///     assert_eq!(spans[1], MatchSpan::new(5, 9));
+///     assert_eq!(spans[2], MatchSpan::new(11, 14));
+///
+///     Ok(())
+/// }
+/// ```
+pub fn find_iter_any_newline<'s, R: Read>(
+    needle: &[u8],
+    rdr: &'s mut R,
+) -> FindAnyNewlineIter<'s, R> {
+    assert!(!needle.is_empty(), "empty needle");
+    let needle = normalize(needle);
+    // Every line ending of the needle may take up two bytes in the stream.
+    let max_len =
+        needle.len() + needle.iter().filter(|&&b| b == b'\n').count();
+    FindAnyNewlineIter {
+        rdr,
+        head_len: memchr2(b'\n', b'\r', &needle).unwrap_or(needle.len()),
+        needle,
+        buf: Buffer::new(max_len),
+        max_len,
+        buf_offset: 0,
+        search_pos: 0,
+    }
+}
+
+/// A forward iterator over all non-overlapping occurrences of a substring in a stream, where
+/// all line endings are equivalent.
+///
+/// This iterator is created by [`find_iter_any_newline`].
+#[derive(Debug)]
+pub struct FindAnyNewlineIter<'s, R: Read> {
+    /// The stream source we read from.
+    rdr: &'s mut R,
+    /// The needle we search for, with its line endings normalized to `\n`.
+    needle: Vec<u8>,
+    /// The length of the part of the needle before its first line ending, whose occurrences are
+    /// the candidates for matches.
+    head_len: usize,
+    /// A fixed size buffer that we actually search for. It must be big enough to hold the longest
+    /// possible match.
+    buf: Buffer,
+    /// The length of the longest possible match.
+    max_len: usize,
+    /// The absolute position of the start of `self.buf` in the stream.
+    buf_offset: usize,
+    /// The first position in `self.buf` that hasn't been ruled out as the start of a match.
+    search_pos: usize,
+}
+
+impl<'s, R: Read> FindAnyNewlineIter<'s, R> {
+    /// Returns the start and the length of the first match that starts in `self.buf` from
+    /// `self.search_pos` up to (excluding) `end`.
+    fn find_before(&self, end: usize) -> Option<(usize, usize)> {
+        let contents = self.buf.buffer();
+        let head = &self.needle[..self.head_len];
+        let mut pos = self.search_pos;
+        while pos < end {
+            let candidate = if head.is_empty() {
+                memchr2(b'\n', b'\r', &contents[pos..])
+            } else {
+                memmem::find(&contents[pos..], head)
+            };
+            let start = pos + candidate?;
+            if start >= end {
+                return None;
+            }
+            if let Some(len) = verify(&contents[start..], &self.needle) {
+                return Some((start, len));
+            }
+            pos = start + 1;
+        }
+        None
+    }
+}
+
+impl<'s, R: Read> Iterator for FindAnyNewlineIter<'s, R> {
+    type Item = io::Result<MatchSpan>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // A match starting before `end` is verified with all of its bytes in the buffer,
+            // unless the stream ends before it.
+            let len = self.buf.len();
+            let end = if self.buf.is_eof() {
+                len
+            } else {
+                (len + 1).saturating_sub(self.max_len)
+            };
+            if let Some((start, match_len)) = self.find_before(end) {
+                self.search_pos = start + match_len;
+                let start = self.buf_offset + start;
+                return Some(Ok(MatchSpan::new(start, start + match_len)));
+            }
+            if self.buf.is_eof() {
+                return None;
+            }
+            self.search_pos = end.max(self.search_pos);
+
+            // Roll our buffer if our buffer has at least the minimum amount of bytes in it. The
+            // positions in the retained suffix haven't been ruled out yet.
+            if len >= self.buf.retain_len() {
+                let dropped = len - self.buf.retain_len();
+                self.buf.roll();
+                self.buf_offset += dropped;
+                self.search_pos -= dropped;
+            }
+            if let Err(err) = self.buf.fill(&mut self.rdr) {
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+/// Returns the needle with every `\r\n` and bare `\r` replaced by `\n`.
+fn normalize(needle: &[u8]) -> Vec<u8> {
+    let mut normalized = Vec::with_capacity(needle.len());
+    let mut bytes = needle.iter().copied().peekable();
+    while let Some(b) = bytes.next() {
+        if b == b'\r' {
+            bytes.next_if_eq(&b'\n');
+            normalized.push(b'\n');
+        } else {
+            normalized.push(b);
+        }
+    }
+    normalized
+}
+
+/// Returns the length of the match of the normalized needle at the start of `haystack`, if any.
+///
+/// The haystack must hold the longest possible match, or the rest of the stream.
+fn verify(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    let mut i = 0;
+    for &b in needle {
+        let next = *haystack.get(i)?;
+        if b != b'\n' {
+            if next != b {
+                return None;
+            }
+            i += 1;
+        } else if next == b'\n' {
+            i += 1;
+        } else if next == b'\r' {
+            i += 1;
+            if haystack.get(i) == Some(&b'\n') {
+                i += 1;
+            }
+        } else {
+            return None;
+        }
+    }
+    Some(i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::DEFAULT_BUFFER_CAPACITY;
+    use crate::testutil::FaultReader;
+
+    fn spans(needle: &[u8], haystack: &[u8]) -> Vec<(usize, usize)> {
+        let rdr = &mut FaultReader::new(haystack).short_reads(3);
+        find_iter_any_newline(needle, rdr)
+            .map(|span| span.map(|span| (span.start, span.end)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_any_newline() {
+        assert_eq!(
+            spans(b"a\nb", b"a\nb a\r\nb a\rb a\n\rb"),
+            vec![(0, 3), (4, 8), (9, 12),]
+        );
+        // The needle is normalized, and a `\r\n` in the stream is a single line ending.
+        assert_eq!(spans(b"a\r\nb", b"a\nb"), vec![(0, 3)]);
+        assert_eq!(spans(b"a\n\nb", b"a\r\n\rb a\r\nb"), vec![(0, 5)]);
+        // Needles may start and end with line endings.
+        assert_eq!(
+            spans(b"\nx", b"x\r\nx\rx\n\nx"),
+            vec![(1, 4), (4, 6), (7, 9)]
+        );
+        assert_eq!(spans(b"x\n", b"x\r\nx\rx"), vec![(0, 3), (3, 5)]);
+        assert_eq!(spans(b"x\n", b"x\r"), vec![(0, 2)]);
+        // Needles without line endings are matched literally.
+        assert_eq!(spans(b"aa", b"aaaaa"), vec![(0, 2), (2, 4)]);
+        // A failed candidate doesn't hide an overlapping match.
+        assert_eq!(spans(b"aa\nb", b"aaa\r\nb"), vec![(1, 6)]);
+    }
+
+    #[test]
+    fn test_any_newline_mixed_fixture() {
+        let paragraph =
+            b"It is a truth universally acknowledged,\nthat a single man";
+        let mut haystack = Vec::new();
+        let mut expected = Vec::new();
+        let endings: [&[u8]; 3] = [b"\n", b"\r\n", b"\r"];
+        while haystack.len() < DEFAULT_BUFFER_CAPACITY * 4 {
+            let ending = endings[expected.len() % 3];
+            let start = haystack.len();
+            haystack
+                .extend_from_slice(b"It is a truth universally acknowledged,");
+            haystack.extend_from_slice(ending);
+            haystack.extend_from_slice(b"that a single man... ");
+            expected.push((start, start + paragraph.len() - 1 + ending.len()));
+            haystack.extend_from_slice(b"It is a truth");
+            haystack.extend_from_slice(ending);
+        }
+
+        for &k in &[1, 997, DEFAULT_BUFFER_CAPACITY] {
+            let rdr = &mut FaultReader::new(&haystack[..]).short_reads(k);
+            let found = find_iter_any_newline(paragraph, rdr)
+                .map(|span| span.map(|span| (span.start, span.end)).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn test_any_newline_error() {
+        let rdr = &mut FaultReader::new(&b"a\r\nb a\nb"[..]).error_at(5);
+        let mut iter = find_iter_any_newline(b"a\nb", rdr);
+        assert_eq!(iter.next().unwrap().unwrap(), MatchSpan::new(0, 4));
+        assert!(iter.next().unwrap().is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_any_newline_empty_needle() {
+        find_iter_any_newline(b"", &mut &b"a"[..]);
+    }
+}
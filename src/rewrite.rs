@@ -0,0 +1,317 @@
+//! Provides routines that copy a stream to a writer while rewriting the matches.
+use crate::finder::Searcher;
+use std::io::{self, Read, Write};
+
+/// Copies the stream to the writer, surrounding every occurrence of the needle with `prefix` and
+/// `suffix`, and returns the number of matches.
+///
+/// All other bytes are passed through unchanged. This is handy for highlighting matches in
+/// terminal output, e.g. by using ANSI color codes as `prefix` and `suffix`.
+///
+/// An empty needle matches at every offset, including the end of the stream, so `prefix` and
+/// `suffix` are written around every byte boundary.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+///
+/// fn main() -> io::Result<()> {
+///     let mut stream = Cursor::new(b"rusty rust");
+///     let mut out = Vec::new();
+///
+///     let count = xfind::highlight_stream(b"rust", &mut stream, &mut out, b"[", b"]")?;
+///     assert_eq!(count, 2);
+///     assert_eq!(out, b"[rust]y [rust]");
+///
+///     Ok(())
+/// }
+/// ```
+pub fn highlight_stream<R, W>(
+    needle: &[u8],
+    rdr: &mut R,
+    wtr: &mut W,
+    prefix: &[u8],
+    suffix: &[u8],
+) -> io::Result<usize>
+where
+    R: Read,
+    W: Write,
+{
+    rewrite_stream(needle, rdr, wtr, |wtr, _, mat| {
+        wtr.write_all(prefix)?;
+        wtr.write_all(mat)?;
+        wtr.write_all(suffix)
+    })
+}
+
+/// Copies the stream to the writer, replacing every occurrence of the needle with
+/// `replacement`, and returns the number of replacements.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+///
+/// fn main() -> io::Result<()> {
+///     let mut stream = Cursor::new(b"rusty rust");
+///     let mut out = Vec::new();
+///
+///     let count = xfind::replace_stream(b"rust", &mut stream, &mut out, b"dust")?;
+///     assert_eq!(count, 2);
+///     assert_eq!(out, b"dusty dust");
+///
+///     Ok(())
+/// }
+/// ```
+pub fn replace_stream<R, W>(
+    needle: &[u8],
+    rdr: &mut R,
+    wtr: &mut W,
+    replacement: &[u8],
+) -> io::Result<usize>
+where
+    R: Read,
+    W: Write,
+{
+    rewrite_stream(needle, rdr, wtr, |wtr, _, _| wtr.write_all(replacement))
+}
+
+/// Copies the stream to the writer, replacing every occurrence of the needle with the bytes
+/// returned by `f`, and returns the number of replacements.
+///
+/// `f` is called with the offset of each match, which allows per-occurrence replacements like
+/// numbering or templating. Apart from the matches, only the bytes needed to detect a match are
+/// buffered, as with [`replace_stream`].
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+///
+/// fn main() -> io::Result<()> {
+///     let mut stream = Cursor::new(b"rusty rust");
+///     let mut out = Vec::new();
+///
+///     let count = xfind::replace_stream_with(b"rust", &mut stream, &mut out, |pos| {
+///         format!("<{}>", pos).into_bytes()
+///     })?;
+///     assert_eq!(count, 2);
+///     assert_eq!(out, b"<0>y <6>");
+///
+///     Ok(())
+/// }
+/// ```
+pub fn replace_stream_with<R, W, F>(
+    needle: &[u8],
+    rdr: &mut R,
+    wtr: &mut W,
+    mut f: F,
+) -> io::Result<usize>
+where
+    R: Read,
+    W: Write,
+    F: FnMut(usize) -> Vec<u8>,
+{
+    rewrite_stream(needle, rdr, wtr, |wtr, pos, _| wtr.write_all(&f(pos)))
+}
+
+/// Copies the stream to the writer, but lets `on_match` write each match (given its offset and
+/// contents) instead of copying it. Returns the number of matches.
+fn rewrite_stream<R, W, F>(
+    needle: &[u8],
+    rdr: &mut R,
+    wtr: &mut W,
+    mut on_match: F,
+) -> io::Result<usize>
+where
+    R: Read,
+    W: Write,
+    F: FnMut(&mut W, usize, &[u8]) -> io::Result<()>,
+{
+    let mut searcher = Searcher::new(needle.len());
+    // The offset up to which the stream has been copied to the writer.
+    let mut written = 0;
+    let mut count = 0;
+
+    while let Some(pos) = searcher
+        .next_with(rdr, needle, |offset, bytes| {
+            copy_until(wtr, &mut written, offset, bytes, offset + bytes.len())
+        })
+        .transpose()?
+    {
+        let (offset, contents) = searcher.buffered();
+        copy_until(wtr, &mut written, offset, contents, pos)?;
+        let mat = &contents[pos - offset..pos - offset + needle.len()];
+        on_match(wtr, pos, mat)?;
+        written = pos + needle.len();
+        count += 1;
+    }
+
+    let (offset, contents) = searcher.buffered();
+    copy_until(wtr, &mut written, offset, contents, offset + contents.len())?;
+    Ok(count)
+}
+
+/// Writes the bytes of `contents` (which starts at `offset` in the stream) between `written` and
+/// `end`, and advances `written` accordingly.
+fn copy_until<W: Write>(
+    wtr: &mut W,
+    written: &mut usize,
+    offset: usize,
+    contents: &[u8],
+    end: usize,
+) -> io::Result<()> {
+    if *written < end {
+        wtr.write_all(&contents[*written - offset..end - offset])?;
+        *written = end;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::DEFAULT_BUFFER_CAPACITY;
+    use std::io::Cursor;
+
+    const RED: &[u8] = b"\x1b[31m";
+    const RESET: &[u8] = b"\x1b[0m";
+
+    #[test]
+    fn test_highlight_stream_ansi() {
+        let mut haystack = Cursor::new(b"dear me, dear dear".to_vec());
+        let mut out = Vec::new();
+
+        let count =
+            highlight_stream(b"dear", &mut haystack, &mut out, RED, RESET)
+                .unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(
+            out,
+            b"\x1b[31mdear\x1b[0m me, \x1b[31mdear\x1b[0m \x1b[31mdear\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_highlight_stream_across_rolls() {
+        let mut haystack = Vec::new();
+        let mut expected = Vec::new();
+        for i in 0..DEFAULT_BUFFER_CAPACITY / 2 {
+            let filler = vec![b'a' + (i % 26) as u8; i % 7];
+            haystack.extend_from_slice(&filler);
+            haystack.extend_from_slice(b"dear");
+            expected.extend_from_slice(&filler);
+            expected.extend_from_slice(RED);
+            expected.extend_from_slice(b"dear");
+            expected.extend_from_slice(RESET);
+        }
+        haystack.extend_from_slice(b"dea");
+        expected.extend_from_slice(b"dea");
+
+        let mut out = Vec::new();
+        let count = highlight_stream(
+            b"dear",
+            &mut Cursor::new(&haystack),
+            &mut out,
+            RED,
+            RESET,
+        )
+        .unwrap();
+        assert_eq!(count, DEFAULT_BUFFER_CAPACITY / 2);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_highlight_stream_no_match() {
+        let haystack: Vec<u8> = (0..=255).cycle().take(20000).collect();
+        let mut out = Vec::new();
+
+        let count = highlight_stream(
+            b"needle",
+            &mut Cursor::new(&haystack),
+            &mut out,
+            RED,
+            RESET,
+        )
+        .unwrap();
+        assert_eq!(count, 0);
+        assert_eq!(out, haystack);
+    }
+
+    #[test]
+    fn test_rewrite_stream_empty_needle() {
+        let haystack = b"ab".repeat(DEFAULT_BUFFER_CAPACITY);
+
+        let mut out = Vec::new();
+        let count =
+            highlight_stream(b"", &mut &b"ab"[..], &mut out, b"[", b"]")
+                .unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(out, b"[]a[]b[]");
+
+        let mut out = Vec::new();
+        let count =
+            replace_stream(b"", &mut Cursor::new(&haystack), &mut out, b"-")
+                .unwrap();
+        let mut expected = b"-a-b".repeat(DEFAULT_BUFFER_CAPACITY);
+        expected.push(b'-');
+        assert_eq!(count, haystack.len() + 1);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_replace_stream() {
+        let mut out = Vec::new();
+        let count = replace_stream(
+            b"dear",
+            &mut Cursor::new(b"dear me, dear"),
+            &mut out,
+            b"",
+        )
+        .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(out, b" me, ");
+    }
+
+    #[test]
+    fn test_replace_stream_with_index() {
+        let mut haystack = Vec::new();
+        let mut expected = Vec::new();
+        for i in 1..=DEFAULT_BUFFER_CAPACITY / 4 {
+            haystack.extend_from_slice(b"- item -");
+            expected.extend_from_slice(format!("- {} -", i).as_bytes());
+        }
+
+        let mut index = 0;
+        let mut out = Vec::new();
+        let count = replace_stream_with(
+            b"item",
+            &mut Cursor::new(&haystack),
+            &mut out,
+            |_| {
+                index += 1;
+                index.to_string().into_bytes()
+            },
+        )
+        .unwrap();
+        assert_eq!(count, DEFAULT_BUFFER_CAPACITY / 4);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_replace_stream_with_empty_needle() {
+        let mut offsets = Vec::new();
+        let mut out = Vec::new();
+        let count =
+            replace_stream_with(b"", &mut &b"ab"[..], &mut out, |pos| {
+                offsets.push(pos);
+                assert!(offsets.len() <= 3, "callback ran too often");
+                pos.to_string().into_bytes()
+            })
+            .unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(offsets, vec![0, 1, 2]);
+        assert_eq!(out, b"0a1b2");
+    }
+}
@@ -0,0 +1,142 @@
+//! Provides a single entry point for searching in-memory buffers and streams alike.
+use crate::finder::find_iter;
+use memchr::memmem;
+use std::io::{self, Read};
+
+/// A source of bytes that can be searched by [`search`].
+///
+/// Searching a buffer that is already in memory with the stream searchers of this crate is
+/// needlessly slow, so in-memory sources are searched with [`memchr::memmem`] directly, and only
+/// streams are searched with [`find_iter`](crate::find_iter). Which path a source takes is known
+/// at compile time from [`IN_MEMORY`](Source::IN_MEMORY).
+///
+/// Since `&[u8]` implements [`Read`] as well, readers must be wrapped in [`Stream`] to tell them
+/// apart from in-memory buffers.
+///
+/// [`memchr::memmem`]: https://docs.rs/memchr/latest/memchr/memmem/
+pub trait Source {
+    /// Whether the source is searched in memory, without buffering.
+    const IN_MEMORY: bool;
+
+    /// Returns the indexes of all non-overlapping occurrences of the given needle in the source.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if reading the source failed.
+    fn find_all(self, needle: &[u8]) -> io::Result<Vec<usize>>;
+}
+
+/// A stream that is searched as a [`Source`].
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+/// use xfind::{Source, Stream};
+///
+/// fn main() -> io::Result<()> {
+///     assert!(!<Stream<Cursor<Vec<u8>>> as Source>::IN_MEMORY);
+///     assert!(<&[u8] as Source>::IN_MEMORY);
+///
+///     let mut stream = Cursor::new(b"rusty rust");
+///     assert_eq!(xfind::search(Stream(&mut stream), b"rust")?, vec![0, 6]);
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Stream<R>(pub R);
+
+impl<R: Read> Source for Stream<R> {
+    const IN_MEMORY: bool = false;
+
+    fn find_all(mut self, needle: &[u8]) -> io::Result<Vec<usize>> {
+        find_iter(needle, &mut self.0).into_offsets()
+    }
+}
+
+impl Source for &[u8] {
+    const IN_MEMORY: bool = true;
+
+    fn find_all(self, needle: &[u8]) -> io::Result<Vec<usize>> {
+        Ok(memmem::find_iter(self, needle).collect())
+    }
+}
+
+impl Source for &Vec<u8> {
+    const IN_MEMORY: bool = true;
+
+    fn find_all(self, needle: &[u8]) -> io::Result<Vec<usize>> {
+        self.as_slice().find_all(needle)
+    }
+}
+
+/// Returns the indexes of all non-overlapping occurrences of the given needle in the source,
+/// using the fastest search for it.
+///
+/// In-memory buffers are searched with [`memchr::memmem`], and streams wrapped in [`Stream`] are
+/// searched with [`find_iter`](crate::find_iter). See [`Source`] for details. Both report the same
+/// offsets for the same bytes.
+///
+/// # Errors
+///
+/// Returns an I/O error if reading the source failed.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+/// use xfind::Stream;
+///
+/// fn main() -> io::Result<()> {
+///     let haystack = b"rusty rust";
+///
+///     assert_eq!(xfind::search(&haystack[..], b"rust")?, vec![0, 6]);
+///     assert_eq!(xfind::search(Stream(Cursor::new(haystack)), b"rust")?, vec![0, 6]);
+///
+///     Ok(())
+/// }
+/// ```
+///
+/// [`memchr::memmem`]: https://docs.rs/memchr/latest/memchr/memmem/
+pub fn search<S: Source>(source: S, needle: &[u8]) -> io::Result<Vec<usize>> {
+    source.find_all(needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::DEFAULT_BUFFER_CAPACITY;
+    use crate::testutil::FaultReader;
+
+    fn is_in_memory<S: Source>(_: &S) -> bool {
+        S::IN_MEMORY
+    }
+
+    #[test]
+    fn test_search_paths_agree() {
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 2];
+        for pos in (0..haystack.len() - 4).step_by(997) {
+            haystack[pos..pos + 4].copy_from_slice(b"dear");
+        }
+        haystack.extend_from_slice(b"aaaaa");
+
+        for &needle in &[&b"dear"[..], b"aa", b"", b"absent"] {
+            let in_memory = &haystack[..];
+            assert!(is_in_memory(&in_memory));
+            let expected = search(in_memory, needle).unwrap();
+            assert_eq!(search(&haystack, needle).unwrap(), expected);
+
+            let stream =
+                Stream(FaultReader::new(&haystack[..]).short_reads(7));
+            assert!(!is_in_memory(&stream));
+            assert_eq!(search(stream, needle).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_search_stream_error() {
+        let rdr = FaultReader::new(&b"rusty rust"[..]).error_at(4);
+        assert!(search(Stream(rdr), b"rust").is_err());
+    }
+}
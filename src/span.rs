@@ -0,0 +1,261 @@
+//! Provides a span type for reporting matches by their start and end offsets.
+use crate::finder::{FindIter, FindRevIter};
+use std::io::{self, Read, Seek};
+use std::ops::Range;
+
+/// The span of a match in a stream.
+///
+/// `start` is inclusive and `end` is exclusive, like a [`Range`], so `end - start` is the length
+/// of the needle. This is the convention of e.g. `regex::Match` and `aho_corasick::Match`.
+///
+/// # Examples
+///
+/// ```
+/// use xfind::MatchSpan;
+///
+/// let span = MatchSpan::new(6, 10);
+/// assert_eq!(span.len(), 4);
+/// assert!(span.contains(9));
+/// assert!(!span.contains(10));
+/// assert_eq!(span.as_range(), 6..10);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MatchSpan {
+    /// The offset of the first byte of the match.
+    pub start: usize,
+    /// The offset right after the last byte of the match.
+    pub end: usize,
+}
+
+impl MatchSpan {
+    /// Creates a new span from `start` (inclusive) to `end` (exclusive).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `end` is smaller than `start`.
+    pub fn new(start: usize, end: usize) -> MatchSpan {
+        assert!(start <= end, "span ends before it starts");
+        MatchSpan { start, end }
+    }
+
+    /// Returns the length of the span.
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Returns `true` if the span is empty, which is the case for matches of an empty needle.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Returns `true` if the given offset lies within the span.
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset < self.end
+    }
+
+    /// Returns the span as a range.
+    pub fn as_range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+}
+
+impl From<MatchSpan> for Range<usize> {
+    fn from(span: MatchSpan) -> Range<usize> {
+        span.as_range()
+    }
+}
+
+impl From<Range<usize>> for MatchSpan {
+    fn from(range: Range<usize>) -> MatchSpan {
+        MatchSpan::new(range.start, range.end)
+    }
+}
+
+/// A forward iterator over all non-overlapping occurrences of a substring in a stream, which
+/// reports matches by their spans.
+///
+/// This iterator is created by
+/// [`StreamFinder::find_spans_iter`](crate::StreamFinder::find_spans_iter).
+#[derive(Debug)]
+pub struct FindSpansIter<'n, 's, R: Read> {
+    /// The iterator over the start offsets of the matches.
+    iter: FindIter<'n, 's, R>,
+    /// The length of the needle.
+    needle_len: usize,
+}
+
+impl<'n, 's, R: Read> FindSpansIter<'n, 's, R> {
+    pub(crate) fn new(iter: FindIter<'n, 's, R>, needle_len: usize) -> Self {
+        FindSpansIter { iter, needle_len }
+    }
+}
+
+impl<'n, 's, R: Read> Iterator for FindSpansIter<'n, 's, R> {
+    type Item = io::Result<MatchSpan>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let needle_len = self.needle_len;
+        self.iter
+            .next()
+            .map(|res| res.map(|pos| MatchSpan::new(pos, pos + needle_len)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// A backward iterator over all non-overlapping occurrences of a substring in a stream, which
+/// reports matches by their spans.
+///
+/// This iterator is created by
+/// [`StreamFinder::rfind_spans_iter`](crate::StreamFinder::rfind_spans_iter).
+#[derive(Debug)]
+pub struct FindRevSpansIter<'n, 's, R: Read + Seek> {
+    /// The iterator over the start offsets of the matches.
+    iter: FindRevIter<'n, 's, R>,
+    /// The length of the needle.
+    needle_len: usize,
+}
+
+impl<'n, 's, R: Read + Seek> FindRevSpansIter<'n, 's, R> {
+    pub(crate) fn new(
+        iter: FindRevIter<'n, 's, R>,
+        needle_len: usize,
+    ) -> Self {
+        FindRevSpansIter { iter, needle_len }
+    }
+}
+
+impl<'n, 's, R: Read + Seek> Iterator for FindRevSpansIter<'n, 's, R> {
+    type Item = io::Result<MatchSpan>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let needle_len = self.needle_len;
+        self.iter
+            .next()
+            .map(|res| res.map(|pos| MatchSpan::new(pos, pos + needle_len)))
+    }
+}
+
+/// A forward iterator over the clusters of occurrences of a substring in a stream.
+///
+/// This iterator is created by
+/// [`StreamFinder::find_clusters_iter`](crate::StreamFinder::find_clusters_iter).
+#[derive(Debug)]
+pub struct FindClustersIter<'n, 's, R: Read> {
+    /// The iterator over the start offsets of the matches.
+    iter: FindIter<'n, 's, R>,
+    /// The length of the needle.
+    needle_len: usize,
+    /// The maximum number of bytes between two matches of the same cluster.
+    max_gap: usize,
+    /// The span of the cluster that is being collected, if any.
+    cluster: Option<Range<usize>>,
+}
+
+impl<'n, 's, R: Read> FindClustersIter<'n, 's, R> {
+    pub(crate) fn new(
+        iter: FindIter<'n, 's, R>,
+        needle_len: usize,
+        max_gap: usize,
+    ) -> Self {
+        FindClustersIter { iter, needle_len, max_gap, cluster: None }
+    }
+}
+
+impl<'n, 's, R: Read> Iterator for FindClustersIter<'n, 's, R> {
+    type Item = io::Result<Range<usize>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let pos = match self.iter.next() {
+                Some(Ok(pos)) => pos,
+                Some(Err(err)) => return Some(Err(err)),
+                None => return self.cluster.take().map(Ok),
+            };
+            let span = pos..pos + self.needle_len;
+            match self.cluster {
+                Some(ref mut cluster) if pos - cluster.end <= self.max_gap => {
+                    cluster.end = span.end;
+                }
+                _ => {
+                    if let Some(cluster) = self.cluster.replace(span) {
+                        return Some(Ok(cluster));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_span() {
+        let span = MatchSpan::new(3, 7);
+        assert_eq!(span.len(), 4);
+        assert!(!span.is_empty());
+        assert!(span.contains(3) && span.contains(6));
+        assert!(!span.contains(2) && !span.contains(7));
+        assert_eq!(MatchSpan::from(span.as_range()), span);
+        assert_eq!(Range::from(span), 3..7);
+
+        let empty = MatchSpan::new(5, 5);
+        assert!(empty.is_empty());
+        assert!(!empty.contains(5));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_match_span_reversed() {
+        MatchSpan::new(7, 3);
+    }
+
+    #[test]
+    fn test_find_clusters_iter() {
+        use crate::buffer::DEFAULT_BUFFER_CAPACITY;
+        use crate::testutil::FaultReader;
+        use crate::StreamFinder;
+
+        // Two clusters, the first one spanning a buffer boundary, and a lone match.
+        let mut haystack = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 4];
+        let first = DEFAULT_BUFFER_CAPACITY - 10;
+        for i in 0..5 {
+            let pos = first + i * 7;
+            haystack[pos..pos + 4].copy_from_slice(b"dear");
+        }
+        let second = DEFAULT_BUFFER_CAPACITY * 3;
+        for i in 0..3 {
+            let pos = second + i * 4;
+            haystack[pos..pos + 4].copy_from_slice(b"dear");
+        }
+        let lone = haystack.len() - 4;
+        haystack[lone..].copy_from_slice(b"dear");
+
+        let finder = StreamFinder::new(b"dear");
+        let rdr = &mut FaultReader::new(&haystack[..]).short_reads(7);
+        let clusters = finder
+            .find_clusters_iter(rdr, 3)
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            clusters,
+            vec![first..first + 32, second..second + 12, lone..lone + 4]
+        );
+
+        // A smaller gap splits the first cluster into lone matches.
+        let clusters = finder
+            .find_clusters_iter(&mut &haystack[..], 2)
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(clusters.len(), 7);
+        assert_eq!(clusters[5], second..second + 12);
+
+        let rdr = &mut FaultReader::new(&haystack[..]).error_at(second);
+        let mut iter = finder.find_clusters_iter(rdr, 3);
+        assert!(iter.next().unwrap().is_err());
+    }
+}
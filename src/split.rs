@@ -0,0 +1,609 @@
+//! Provides routines that split a stream into records separated by a delimiter.
+use crate::buffer::Buffer;
+use crate::finder::{Searcher, StreamFinder};
+use std::io::{self, Read, Write};
+use std::mem;
+use std::vec;
+
+/// Returns an iterator over the records of the stream that are separated by the given delimiter.
+///
+/// The records don't include the delimiter. A delimiter at the very end of the stream doesn't
+/// start another (empty) record, so splitting `a\nb\n` by `\n` yields `a` and `b`, like
+/// [`BufRead::lines`](std::io::BufRead::lines) does. Only the bytes of the current record are
+/// buffered, apart from the searcher's own fixed-size buffer.
+///
+/// # Panics
+///
+/// Panics if `delim` is empty.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+///
+/// fn main() -> io::Result<()> {
+///     let mut stream = Cursor::new(b"foo, bar, , baz");
+///
+///     let records = xfind::split(b", ", &mut stream).collect::<io::Result<Vec<_>>>()?;
+///     assert_eq!(records, vec![&b"foo"[..], b"bar", b"", b"baz"]);
+///
+///     Ok(())
+/// }
+/// ```
+pub fn split<'d, 's, R: Read>(
+    delim: &'d [u8],
+    rdr: &'s mut R,
+) -> SplitIter<'d, 's, R> {
+    assert!(!delim.is_empty(), "empty record delimiter");
+    SplitIter {
+        rdr,
+        delim,
+        searcher: Searcher::new(delim.len()),
+        record_start: 0,
+        done: false,
+    }
+}
+
+/// Copies the records of the stream that are separated by the given delimiter to the writer,
+/// skipping every record that is identical to the one before it, and returns the number of
+/// records written.
+///
+/// Like `uniq`, only adjacent duplicates are removed, so `a\na\nb\na\n` becomes `a\nb\na\n`.
+/// Every record written is followed by the delimiter, except for a final record that isn't
+/// followed by one in the stream either. Only one record is buffered at a time.
+///
+/// # Panics
+///
+/// Panics if `delim` is empty.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+///
+/// fn main() -> io::Result<()> {
+///     let mut stream = Cursor::new(b"a\na\nb\na\na\n");
+///     let mut out = Vec::new();
+///
+///     let count = xfind::dedup_records(b"\n", &mut stream, &mut out)?;
+///     assert_eq!(count, 3);
+///     assert_eq!(out, b"a\nb\na\n");
+///
+///     Ok(())
+/// }
+/// ```
+pub fn dedup_records<R, W>(
+    delim: &[u8],
+    rdr: &mut R,
+    wtr: &mut W,
+) -> io::Result<usize>
+where
+    R: Read,
+    W: Write,
+{
+    let mut records = split(delim, rdr);
+    let mut prev = Vec::new();
+    let mut record = Vec::new();
+    let mut count = 0;
+
+    while let Some(terminated) = records.read_record(&mut record)? {
+        if count == 0 || record != prev {
+            wtr.write_all(&record)?;
+            if terminated {
+                wtr.write_all(delim)?;
+            }
+            count += 1;
+            mem::swap(&mut prev, &mut record);
+        }
+    }
+    Ok(count)
+}
+
+/// An iterator over the records of a stream that are separated by a delimiter.
+///
+/// This iterator is created by [`split`].
+#[derive(Debug)]
+pub struct SplitIter<'d, 's, R> {
+    /// The stream source we read from.
+    rdr: &'s mut R,
+    /// The delimiter between records.
+    delim: &'d [u8],
+    /// The state of the delimiter search.
+    searcher: Searcher,
+    /// The offset of the first byte of the current record.
+    record_start: usize,
+    /// Whether the end of the stream has been reached.
+    done: bool,
+}
+
+impl<'d, 's, R: Read> SplitIter<'d, 's, R> {
+    /// Reads the next record into `record`, replacing its contents, and returns whether the record
+    /// was followed by the delimiter. Returns `None` once the stream is exhausted.
+    ///
+    /// Unlike `next`, this reuses the allocation of `record`.
+    pub(crate) fn read_record(
+        &mut self,
+        record: &mut Vec<u8>,
+    ) -> io::Result<Option<bool>> {
+        record.clear();
+        if self.done {
+            return Ok(None);
+        }
+
+        let record_start = self.record_start;
+        let found = self
+            .searcher
+            .next_with(&mut self.rdr, self.delim, |offset, bytes| {
+                append_from(record, record_start, offset, bytes, bytes.len());
+                Ok(())
+            })
+            .transpose()?;
+
+        let (offset, contents) = self.searcher.buffered();
+        match found {
+            Some(pos) => {
+                append_from(
+                    record,
+                    record_start,
+                    offset,
+                    contents,
+                    pos - offset,
+                );
+                self.record_start = pos + self.delim.len();
+                Ok(Some(true))
+            }
+            None => {
+                append_from(
+                    record,
+                    record_start,
+                    offset,
+                    contents,
+                    contents.len(),
+                );
+                self.done = true;
+                let end = offset + contents.len();
+                Ok(if end > record_start { Some(false) } else { None })
+            }
+        }
+    }
+}
+
+impl<'d, 's, R: Read> Iterator for SplitIter<'d, 's, R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut record = Vec::new();
+        match self.read_record(&mut record) {
+            Ok(Some(_)) => Some(Ok(record)),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+/// An iterator over the occurrences of a substring within the frames of a stream.
+///
+/// This iterator is created by
+/// [`StreamFinder::find_in_frames`](crate::StreamFinder::find_in_frames).
+#[derive(Debug)]
+pub struct FindInFramesIter<'n, 'd, 's, R> {
+    /// The finder that searches each frame.
+    finder: &'n StreamFinder<'n>,
+    /// The iterator over the frames.
+    frames: SplitIter<'d, 's, R>,
+    /// The current frame.
+    frame: Vec<u8>,
+    /// The number of frames read so far.
+    frame_count: usize,
+    /// The offsets of the matches in the current frame that haven't been reported yet.
+    matches: vec::IntoIter<usize>,
+}
+
+impl<'n, 'd, 's, R: Read> FindInFramesIter<'n, 'd, 's, R> {
+    pub(crate) fn new(
+        finder: &'n StreamFinder<'n>,
+        frames: SplitIter<'d, 's, R>,
+    ) -> Self {
+        FindInFramesIter {
+            finder,
+            frames,
+            frame: Vec::new(),
+            frame_count: 0,
+            matches: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl<'n, 'd, 's, R: Read> Iterator for FindInFramesIter<'n, 'd, 's, R> {
+    type Item = io::Result<(usize, usize)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(pos) = self.matches.next() {
+                return Some(Ok((self.frame_count - 1, pos)));
+            }
+            match self.frames.read_record(&mut self.frame) {
+                Ok(Some(_)) => self.frame_count += 1,
+                Ok(None) => return None,
+                Err(err) => return Some(Err(err)),
+            }
+            // Searching the frame on its own rules out matches that span a delimiter.
+            match self.finder.find_iter(&mut &self.frame[..]).into_offsets() {
+                Ok(offsets) => self.matches = offsets.into_iter(),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+/// An iterator over the number of occurrences of a substring in each line of a stream.
+///
+/// This iterator is created by
+/// [`StreamFinder::matches_per_line_iter`](crate::StreamFinder::matches_per_line_iter).
+#[derive(Debug)]
+pub struct MatchesPerLineIter<'n, 's, R> {
+    /// The finder that searches each line.
+    finder: &'n StreamFinder<'n>,
+    /// The iterator over the lines.
+    lines: SplitIter<'static, 's, R>,
+    /// The current line.
+    line: Vec<u8>,
+    /// The number of lines read so far.
+    line_count: usize,
+    /// The buffer that is reused for searching every line.
+    buffer: Option<Buffer>,
+    /// Whether lines without a match are skipped.
+    nonzero_only: bool,
+}
+
+impl<'n, 's, R: Read> MatchesPerLineIter<'n, 's, R> {
+    pub(crate) fn new(
+        finder: &'n StreamFinder<'n>,
+        lines: SplitIter<'static, 's, R>,
+    ) -> Self {
+        MatchesPerLineIter {
+            finder,
+            lines,
+            line: Vec::new(),
+            line_count: 0,
+            buffer: None,
+            nonzero_only: false,
+        }
+    }
+
+    /// Makes the iterator skip the lines without a match.
+    pub fn nonzero_only(mut self) -> Self {
+        self.nonzero_only = true;
+        self
+    }
+
+    /// Returns the number of matches in the current line.
+    fn count_matches(&mut self) -> io::Result<usize> {
+        let capacity = self.finder.buffer_capacity();
+        let buffer = self
+            .buffer
+            .take()
+            .unwrap_or_else(|| Buffer::with_capacity(0, capacity));
+        let mut line = &self.line[..];
+        let mut iter = self.finder.find_iter_with_buffer(&mut line, buffer);
+        let mut count = 0;
+        for res in iter.by_ref() {
+            res?;
+            count += 1;
+        }
+        self.buffer = Some(iter.into_buffer());
+        Ok(count)
+    }
+}
+
+impl<'n, 's, R: Read> Iterator for MatchesPerLineIter<'n, 's, R> {
+    type Item = io::Result<(usize, usize)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.lines.read_record(&mut self.line) {
+                Ok(Some(_)) => self.line_count += 1,
+                Ok(None) => return None,
+                Err(err) => return Some(Err(err)),
+            }
+            match self.count_matches() {
+                Ok(0) if self.nonzero_only => {}
+                Ok(count) => return Some(Ok((self.line_count, count))),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+/// An iterator over the lines of a stream that contain a substring.
+///
+/// This iterator is created by
+/// [`StreamFinder::matching_lines_iter`](crate::StreamFinder::matching_lines_iter).
+#[derive(Debug)]
+pub struct MatchingLinesIter<'n, 's, R> {
+    /// The iterator over the lines with at least one match.
+    lines: MatchesPerLineIter<'n, 's, R>,
+}
+
+impl<'n, 's, R: Read> MatchingLinesIter<'n, 's, R> {
+    pub(crate) fn new(lines: MatchesPerLineIter<'n, 's, R>) -> Self {
+        MatchingLinesIter { lines }
+    }
+}
+
+impl<'n, 's, R: Read> Iterator for MatchingLinesIter<'n, 's, R> {
+    type Item = io::Result<(usize, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.lines.next()? {
+            Ok((number, _)) => {
+                Some(Ok((number, mem::take(&mut self.lines.line))))
+            }
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+/// Appends `contents[..end]` (where `contents` starts at `offset` in the stream) to `record`,
+/// skipping the bytes before `record_start`.
+fn append_from(
+    record: &mut Vec<u8>,
+    record_start: usize,
+    offset: usize,
+    contents: &[u8],
+    end: usize,
+) {
+    let start = record_start.saturating_sub(offset);
+    if start < end {
+        record.extend_from_slice(&contents[start..end]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::DEFAULT_BUFFER_CAPACITY;
+    use crate::testutil::FaultReader;
+    use std::io::Cursor;
+
+    fn records(delim: &[u8], haystack: &[u8]) -> Vec<Vec<u8>> {
+        split(delim, &mut Cursor::new(haystack)).map(|x| x.unwrap()).collect()
+    }
+
+    fn uniq(haystack: &[u8]) -> (usize, Vec<u8>) {
+        let mut out = Vec::new();
+        let count = dedup_records(b"\n", &mut Cursor::new(haystack), &mut out)
+            .unwrap();
+        (count, out)
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(
+            records(b"\n", b"a\nb\n"),
+            vec![b"a".to_vec(), b"b".to_vec()]
+        );
+        assert_eq!(
+            records(b"\n", b"\na\n\nb"),
+            vec![b"".to_vec(), b"a".to_vec(), b"".to_vec(), b"b".to_vec()]
+        );
+        assert_eq!(
+            records(b"\r\n", b"a\r\n\rb\n"),
+            vec![b"a".to_vec(), b"\rb\n".to_vec()]
+        );
+        assert_eq!(records(b"\n", b""), Vec::<Vec<u8>>::new());
+        assert_eq!(records(b"\n", b"\n"), vec![b"".to_vec()]);
+    }
+
+    #[test]
+    fn test_split_long_records() {
+        let long = vec![b'x'; DEFAULT_BUFFER_CAPACITY * 3 + 5];
+        let mut haystack = long.clone();
+        haystack.extend_from_slice(b"--");
+        haystack.extend_from_slice(b"short--");
+        haystack.extend_from_slice(&long);
+
+        let mut stream = FaultReader::new(Cursor::new(&haystack))
+            .short_reads(1000)
+            .interrupt_every(3);
+        let records =
+            split(b"--", &mut stream).collect::<io::Result<Vec<_>>>().unwrap();
+        assert_eq!(records, vec![long.clone(), b"short".to_vec(), long]);
+    }
+
+    #[test]
+    fn test_split_error() {
+        let mut stream =
+            FaultReader::new(Cursor::new(b"a\nb\nc\n")).error_at(3);
+        let mut iter = split(b"\n", &mut stream);
+        assert_eq!(iter.next().unwrap().unwrap(), b"a");
+        assert!(iter.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_dedup_records_like_uniq() {
+        assert_eq!(uniq(b"a\na\nb\na\na\n"), (3, b"a\nb\na\n".to_vec()));
+        assert_eq!(uniq(b"a\nb\nc\n"), (3, b"a\nb\nc\n".to_vec()));
+        assert_eq!(uniq(b"\n\n\na\n"), (2, b"\na\n".to_vec()));
+        assert_eq!(uniq(b""), (0, b"".to_vec()));
+        // A missing newline at the end doesn't make a record unique.
+        assert_eq!(uniq(b"a\na"), (1, b"a\n".to_vec()));
+        assert_eq!(uniq(b"a\nb"), (2, b"a\nb".to_vec()));
+        // Records are compared in full, not just their prefixes.
+        assert_eq!(uniq(b"ab\na\nab\n"), (3, b"ab\na\nab\n".to_vec()));
+    }
+
+    #[test]
+    fn test_dedup_records_long_records() {
+        let long = vec![b'x'; DEFAULT_BUFFER_CAPACITY * 2];
+        let mut haystack = Vec::new();
+        for _ in 0..3 {
+            haystack.extend_from_slice(&long);
+            haystack.push(b'\n');
+        }
+        haystack.extend_from_slice(b"end\n");
+
+        let mut expected = long;
+        expected.extend_from_slice(b"\nend\n");
+        assert_eq!(uniq(&haystack), (2, expected));
+    }
+
+    #[test]
+    fn test_find_in_frames() {
+        // The first frame is longer than the buffer, and a match straddles its end.
+        let mut haystack = b"dear\r-".to_vec();
+        haystack.extend(vec![b'-'; DEFAULT_BUFFER_CAPACITY * 2]);
+        haystack.extend_from_slice(b"dear\r\nxdear\r\r\ndear\r");
+
+        let finder = StreamFinder::new(b"dear\r");
+        let mut stream = FaultReader::new(&haystack[..]).short_reads(1000);
+        let matches = finder
+            .find_in_frames(&mut stream, b"\r\n")
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(matches, vec![(0, 0), (1, 1), (2, 0)]);
+
+        // Without frames, the straddling match is found as well.
+        let all = finder.find_all(&mut &haystack[..]).unwrap();
+        assert_eq!(all.len(), 4);
+
+        // The context of the finder applies within frames.
+        let finder = StreamFinder::new(b"dear\r").followed_by(b"-");
+        let mut stream = &haystack[..];
+        let matches = finder
+            .find_in_frames(&mut stream, b"\r\n")
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(matches, vec![(0, 0)]);
+
+        let finder = StreamFinder::new(b"dear\r");
+        let rdr =
+            &mut FaultReader::new(&haystack[..]).error_at(haystack.len() - 3);
+        let mut iter = finder.find_in_frames(rdr, b"\r\n");
+        assert_eq!(iter.next().unwrap().unwrap(), (0, 0));
+        assert_eq!(iter.next().unwrap().unwrap(), (1, 1));
+        assert!(iter.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_matches_per_line_iter() {
+        let mut file =
+            std::fs::File::open("data/pride-and-prejudice.txt").unwrap();
+        let finder = StreamFinder::new(b"dear");
+        let counts = finder
+            .matches_per_line_iter(&mut file)
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        let haystack = std::fs::read("data/pride-and-prejudice.txt").unwrap();
+        let lines: Vec<&[u8]> = haystack.split(|&b| b == b'\n').collect();
+        // The file ends with a newline, which doesn't start another line.
+        assert_eq!(counts.len(), lines.len() - 1);
+        for (&(number, count), (i, line)) in
+            counts.iter().zip(lines.iter().enumerate())
+        {
+            assert_eq!(
+                (number, count),
+                (i + 1, memchr::memmem::find_iter(line, b"dear").count())
+            );
+        }
+        assert!(counts.iter().any(|&(_, count)| count > 1));
+
+        let nonzero = finder
+            .matches_per_line_iter(&mut &haystack[..])
+            .nonzero_only()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        let direct: Vec<_> =
+            counts.iter().copied().filter(|&(_, count)| count > 0).collect();
+        assert_eq!(nonzero, direct);
+    }
+
+    #[test]
+    fn test_matches_per_line_iter_edges() {
+        let counts = |finder: &StreamFinder<'_>, haystack: &[u8]| {
+            let mut stream = FaultReader::new(haystack).short_reads(3);
+            finder
+                .matches_per_line_iter(&mut stream)
+                .collect::<io::Result<Vec<_>>>()
+                .unwrap()
+        };
+        let finder = StreamFinder::new(b"aa");
+        assert_eq!(
+            counts(&finder, b"aaaaa\n\naa"),
+            vec![(1, 2), (2, 0), (3, 1)]
+        );
+        assert_eq!(
+            counts(&finder, b"aaaaa\n\naa\n"),
+            vec![(1, 2), (2, 0), (3, 1)]
+        );
+        assert_eq!(counts(&finder, b""), vec![]);
+        assert_eq!(counts(&finder, b"a\na"), vec![(1, 0), (2, 0)]);
+
+        // The context of the finder applies within lines, and long lines are searched whole.
+        let finder = StreamFinder::new(b"aa").followed_by(b"!");
+        let mut long = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 3];
+        long.extend_from_slice(b"aa!aa");
+        long.extend_from_slice(b"\naa!\n!");
+        let mut stream = FaultReader::new(&long[..]).short_reads(3);
+        let context_counts = finder
+            .matches_per_line_iter(&mut stream)
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(context_counts, vec![(1, 1), (2, 1), (3, 0)]);
+
+        let finder = StreamFinder::new(b"");
+        assert_eq!(counts(&finder, b"ab\n\nc"), vec![(1, 3), (2, 1), (3, 2)]);
+
+        let finder = StreamFinder::new(b"aa");
+        let rdr = &mut FaultReader::new(&b"aa\naa\naa"[..]).error_at(4);
+        let mut iter = finder.matches_per_line_iter(rdr);
+        assert_eq!(iter.next().unwrap().unwrap(), (1, 1));
+        assert!(iter.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_matching_lines_iter() {
+        let finder = StreamFinder::new(b"Elizabeth");
+        let path = "data/pride-and-prejudice.txt";
+        let mut out = Vec::new();
+        let count = crate::grep(
+            b"Elizabeth",
+            &mut std::fs::File::open(path).unwrap(),
+            &mut out,
+        )
+        .unwrap();
+        let lines = finder
+            .matching_lines_iter(&mut std::fs::File::open(path).unwrap())
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(lines.len(), count);
+        let joined: Vec<u8> = lines
+            .iter()
+            .flat_map(|(_, line)| line.iter().chain(b"\n"))
+            .copied()
+            .collect();
+        assert_eq!(joined, out);
+
+        let haystack = std::fs::read(path).unwrap();
+        let all: Vec<&[u8]> = haystack.split(|&b| b == b'\n').collect();
+        for (number, line) in &lines {
+            assert_eq!(line, all[number - 1]);
+        }
+
+        // Whole lines are yielded, even if they are longer than the buffer.
+        let mut long = vec![b'-'; DEFAULT_BUFFER_CAPACITY * 3];
+        long.extend_from_slice(b"dear");
+        let mut haystack = b"dear\nnothing\n".to_vec();
+        haystack.extend_from_slice(&long);
+        haystack.extend_from_slice(b"\n\ndear dear");
+        let rdr = &mut FaultReader::new(&haystack[..]).short_reads(1000);
+        let lines = StreamFinder::new(b"dear")
+            .matching_lines_iter(rdr)
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            lines,
+            vec![(1, b"dear".to_vec()), (3, long), (5, b"dear dear".to_vec())]
+        );
+    }
+}
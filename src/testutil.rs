@@ -0,0 +1,280 @@
+//! Provides utilities for testing code that searches streams under adverse I/O conditions.
+//!
+//! This module is only available if the `testutil` feature is enabled.
+use std::io::{self, Read};
+
+/// A reader that injects faults into the reads of another reader.
+///
+/// By default, a `FaultReader` behaves exactly like the reader it wraps. Each fault mode is
+/// enabled by one of its builder methods, and the modes can be combined:
+///
+/// - [`error_at`](FaultReader::error_at) fails every read once the given offset is reached.
+/// - [`short_reads`](FaultReader::short_reads) limits the number of bytes per read.
+/// - [`interrupt_every`](FaultReader::interrupt_every) fails every m-th read with
+///   [`ErrorKind::Interrupted`](io::ErrorKind::Interrupted), without consuming any bytes.
+/// - [`would_block_every`](FaultReader::would_block_every) fails every m-th read with
+///   [`ErrorKind::WouldBlock`](io::ErrorKind::WouldBlock), like a nonblocking socket without
+///   pending data, without consuming any bytes.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Cursor};
+/// use xfind::testutil::FaultReader;
+///
+/// fn main() -> io::Result<()> {
+///     let mut stream = FaultReader::new(Cursor::new(b"rusty rust"))
+///         .short_reads(1)
+///         .interrupt_every(2);
+///
+///     let matches = xfind::find_iter(b"rust", &mut stream).into_offsets()?;
+///     assert_eq!(matches, vec![0, 6]);
+///
+///     let mut stream = FaultReader::new(Cursor::new(b"rusty rust")).error_at(8);
+///     let mut iter = xfind::find_iter(b"rust", &mut stream);
+///     assert_eq!(iter.next().transpose()?, Some(0));
+///     assert!(iter.next().unwrap().is_err());
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct FaultReader<R> {
+    /// The reader that provides the actual bytes.
+    rdr: R,
+    /// The number of bytes read so far.
+    pos: usize,
+    /// The offset at which reads start to fail, if any.
+    error_at: Option<usize>,
+    /// The maximum number of bytes per read, if any.
+    max_read: Option<usize>,
+    /// Every how many reads a read is interrupted, if at all.
+    interrupt_every: Option<usize>,
+    /// Every how many reads a read would block, if at all.
+    would_block_every: Option<usize>,
+    /// The number of calls to `read` so far.
+    reads: usize,
+}
+
+impl<R: Read> FaultReader<R> {
+    /// Creates a new `FaultReader` that wraps the given reader without injecting any faults.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use xfind::testutil::FaultReader;
+    ///
+    /// let stream = FaultReader::new(Cursor::new(b"rusty rust"));
+    /// ```
+    pub fn new(rdr: R) -> FaultReader<R> {
+        FaultReader {
+            rdr,
+            pos: 0,
+            error_at: None,
+            max_read: None,
+            interrupt_every: None,
+            would_block_every: None,
+            reads: 0,
+        }
+    }
+
+    /// Makes every read fail once `offset` bytes have been read. A read that would cross `offset`
+    /// is shortened to end right there.
+    ///
+    /// The error is of kind [`ErrorKind::Other`](io::ErrorKind::Other).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{Cursor, Read};
+    /// use xfind::testutil::FaultReader;
+    ///
+    /// let mut stream = FaultReader::new(Cursor::new(b"rusty rust")).error_at(5);
+    /// let mut buf = Vec::new();
+    /// assert!(stream.read_to_end(&mut buf).is_err());
+    /// assert_eq!(buf, b"rusty");
+    /// ```
+    pub fn error_at(mut self, offset: usize) -> FaultReader<R> {
+        self.error_at = Some(offset);
+        self
+    }
+
+    /// Makes every read return at most `k` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{Cursor, Read};
+    /// use xfind::testutil::FaultReader;
+    ///
+    /// let mut stream = FaultReader::new(Cursor::new(b"rusty rust")).short_reads(3);
+    /// let mut buf = [0; 8];
+    /// assert_eq!(stream.read(&mut buf).unwrap(), 3);
+    /// ```
+    pub fn short_reads(mut self, k: usize) -> FaultReader<R> {
+        assert!(k > 0, "short reads must return at least 1 byte");
+        self.max_read = Some(k);
+        self
+    }
+
+    /// Makes every `m`-th read fail with [`ErrorKind::Interrupted`](io::ErrorKind::Interrupted).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `m` is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{Cursor, ErrorKind, Read};
+    /// use xfind::testutil::FaultReader;
+    ///
+    /// let mut stream = FaultReader::new(Cursor::new(b"rusty rust")).interrupt_every(1);
+    /// let err = stream.read(&mut [0; 8]).unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::Interrupted);
+    /// ```
+    pub fn interrupt_every(mut self, m: usize) -> FaultReader<R> {
+        assert!(m > 0, "interrupt_every must be at least 1");
+        self.interrupt_every = Some(m);
+        self
+    }
+
+    /// Makes every `m`-th read fail with [`ErrorKind::WouldBlock`](io::ErrorKind::WouldBlock).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `m` is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{Cursor, ErrorKind, Read};
+    /// use xfind::testutil::FaultReader;
+    ///
+    /// let mut stream = FaultReader::new(Cursor::new(b"rusty rust")).would_block_every(2);
+    /// assert_eq!(stream.read(&mut [0; 8]).unwrap(), 8);
+    /// let err = stream.read(&mut [0; 8]).unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::WouldBlock);
+    /// ```
+    pub fn would_block_every(mut self, m: usize) -> FaultReader<R> {
+        assert!(m > 0, "would_block_every must be at least 1");
+        self.would_block_every = Some(m);
+        self
+    }
+
+    /// Returns the number of bytes that have been read so far.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{Cursor, Read};
+    /// use xfind::testutil::FaultReader;
+    ///
+    /// let mut stream = FaultReader::new(Cursor::new(b"rusty rust")).short_reads(3);
+    /// stream.read(&mut [0; 8]).unwrap();
+    /// assert_eq!(stream.position(), 3);
+    /// ```
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Unwraps this `FaultReader`, returning the underlying reader.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use xfind::testutil::FaultReader;
+    ///
+    /// let stream = FaultReader::new(Cursor::new(b"rusty rust"));
+    /// assert_eq!(stream.into_inner().position(), 0);
+    /// ```
+    pub fn into_inner(self) -> R {
+        self.rdr
+    }
+}
+
+impl<R: Read> Read for FaultReader<R> {
+    // `is_multiple_of` is only stable on recent toolchains.
+    #[allow(clippy::manual_is_multiple_of)]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reads += 1;
+        if let Some(m) = self.interrupt_every {
+            if self.reads % m == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::Interrupted,
+                    "injected interruption",
+                ));
+            }
+        }
+        if let Some(m) = self.would_block_every {
+            if self.reads % m == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    "injected would block",
+                ));
+            }
+        }
+
+        let mut len = buf.len();
+        if let Some(k) = self.max_read {
+            len = len.min(k);
+        }
+        if let Some(offset) = self.error_at {
+            if self.pos >= offset && !buf.is_empty() {
+                return Err(io::Error::other("injected fault"));
+            }
+            len = len.min(offset - self.pos);
+        }
+
+        let n = self.rdr.read(&mut buf[..len])?;
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_fault_reader_passthrough() {
+        let mut stream = FaultReader::new(Cursor::new(b"rusty rust"));
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, b"rusty rust");
+        assert_eq!(stream.position(), 10);
+    }
+
+    #[test]
+    fn test_fault_reader_error_at() {
+        let mut stream = FaultReader::new(Cursor::new(b"rusty rust"))
+            .short_reads(4)
+            .error_at(6);
+        let mut buf = [0; 10];
+        assert_eq!(stream.read(&mut buf).unwrap(), 4);
+        assert_eq!(stream.read(&mut buf).unwrap(), 2);
+        let err = stream.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+        assert!(stream.read(&mut buf).is_err());
+        // Empty reads never fail.
+        assert_eq!(stream.read(&mut []).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_fault_reader_interrupt_every() {
+        let mut stream =
+            FaultReader::new(Cursor::new(b"rusty rust")).interrupt_every(3);
+        let mut buf = [0; 1];
+        let kinds: Vec<bool> =
+            (0..6).map(|_| stream.read(&mut buf).is_ok()).collect();
+        assert_eq!(kinds, vec![true, true, false, true, true, false]);
+        assert_eq!(stream.position(), 4);
+    }
+}
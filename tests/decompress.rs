@@ -0,0 +1,52 @@
+//! We test that forward searches work on non-seekable decompressors, which are among the most
+//! common stream sources.
+use flate2::read::{GzDecoder, GzEncoder};
+use flate2::Compression;
+use std::fs::File;
+use std::io::prelude::*;
+
+fn gzip_fixture() -> (Vec<u8>, Vec<u8>) {
+    let mut f = File::open("data/pride-and-prejudice.txt")
+        .expect("test file not found");
+    let mut buf = Vec::with_capacity(1000000);
+    f.read_to_end(&mut buf).unwrap();
+
+    let mut compressed = Vec::new();
+    GzEncoder::new(&buf[..], Compression::default())
+        .read_to_end(&mut compressed)
+        .unwrap();
+    (buf, compressed)
+}
+
+#[test]
+fn test_find_iter_gz_decoder() {
+    let (buf, compressed) = gzip_fixture();
+
+    for needle in [&b"a"[..], b"dear", b"Elizabeth"] {
+        let mut stream = GzDecoder::new(std::io::Cursor::new(&compressed));
+        let matches: Vec<usize> = xfind::find_iter(needle, &mut stream)
+            .map(|x| x.expect("I/O operation failed"))
+            .collect();
+        let expected: Vec<usize> =
+            memchr::memmem::find_iter(&buf, needle).collect();
+
+        assert_eq!(matches, expected);
+    }
+}
+
+#[test]
+fn test_stream_finder_gz_decoder() {
+    let (buf, compressed) = gzip_fixture();
+    let finder = xfind::StreamFinder::new(b"Darcy");
+
+    let mut stream = GzDecoder::new(&compressed[..]);
+    let pos = finder.find(&mut stream).transpose().unwrap();
+    assert_eq!(pos, memchr::memmem::find(&buf, b"Darcy"));
+
+    let mut stream = GzDecoder::new(&compressed[..]);
+    let matches = finder.find_all(&mut stream).unwrap();
+    assert_eq!(
+        matches.len(),
+        memchr::memmem::find_iter(&buf, b"Darcy").count()
+    );
+}
@@ -161,3 +161,113 @@ fn test_rfind_iter_ch4() {
 
     assert_eq!(matches, expected);
 }
+
+#[test]
+fn test_estimate_count() {
+    let needle = b"the";
+
+    let mut f = File::open("data/pride-and-prejudice.txt")
+        .expect("test file not found");
+    let mut buf = Vec::with_capacity(1000000);
+    f.read_to_end(&mut buf).unwrap();
+    let expected = memchr::memmem::find_iter(&buf, needle).count();
+
+    f.seek(SeekFrom::Start(0)).expect("I/O operation failed");
+    let estimate = xfind::StreamFinder::new(needle)
+        .estimate_count(&mut f, 100_000)
+        .expect("I/O operation failed");
+
+    // The estimate should be within 20% of the true count.
+    assert!(estimate * 10 >= expected * 8, "{} vs {}", estimate, expected);
+    assert!(estimate * 10 <= expected * 12, "{} vs {}", estimate, expected);
+}
+
+#[test]
+fn test_grep() {
+    let needle = b"dear";
+
+    let mut f = File::open("data/pride-and-prejudice.txt")
+        .expect("test file not found");
+    let mut buf = Vec::with_capacity(1000000);
+    f.read_to_end(&mut buf).unwrap();
+
+    // The fixture ends with a newline, so every line is written with one.
+    let mut expected = Vec::new();
+    let mut expected_count = 0;
+    for line in buf.split(|&b| b == b'\n') {
+        if memchr::memmem::find(line, needle).is_some() {
+            expected.extend_from_slice(line);
+            expected.push(b'\n');
+            expected_count += 1;
+        }
+    }
+    // Same as `grep -c dear data/pride-and-prejudice.txt`.
+    assert_eq!(expected_count, 163);
+
+    f.seek(SeekFrom::Start(0)).expect("I/O operation failed");
+    let mut out = Vec::new();
+    let count =
+        xfind::grep(needle, &mut f, &mut out).expect("I/O operation failed");
+    assert_eq!(count, expected_count);
+    assert_eq!(out, expected);
+}
+
+#[test]
+fn test_grep_invert() {
+    let needle = b"dear";
+
+    let mut f = File::open("data/pride-and-prejudice.txt")
+        .expect("test file not found");
+    let mut buf = Vec::with_capacity(1000000);
+    f.read_to_end(&mut buf).unwrap();
+
+    // The fixture ends with a newline, which doesn't start another line.
+    let mut expected = Vec::new();
+    let mut expected_count = 0;
+    for line in buf[..buf.len() - 1].split(|&b| b == b'\n') {
+        if memchr::memmem::find(line, needle).is_none() {
+            expected.extend_from_slice(line);
+            expected.push(b'\n');
+            expected_count += 1;
+        }
+    }
+    // Same as `grep -v -c dear data/pride-and-prejudice.txt`.
+    assert_eq!(expected_count, 14416);
+
+    f.seek(SeekFrom::Start(0)).expect("I/O operation failed");
+    let mut out = Vec::new();
+    let count = xfind::grep_invert(needle, &mut f, &mut out)
+        .expect("I/O operation failed");
+    assert_eq!(count, expected_count);
+    assert_eq!(out, expected);
+}
+
+#[test]
+fn test_filter_ndjson() {
+    let mut f = File::open("data/events.ndjson").expect("test file not found");
+    let mut out = Vec::new();
+    let count = xfind::filter_ndjson(b"\"level\":\"error\"", &mut f, &mut out)
+        .expect("I/O operation failed");
+    assert_eq!(count, 4);
+
+    // Every line written is a whole record of the fixture, followed by a newline.
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.ends_with("}\n"));
+    let msgs: Vec<&str> = out
+        .lines()
+        .map(|line| {
+            assert!(line.starts_with("{\"ts\":") && line.ends_with('}'));
+            let start = line.find("\"msg\":\"").unwrap() + 7;
+            &line[start..start + line[start..].find('"').unwrap()]
+        })
+        .collect();
+    assert_eq!(
+        msgs,
+        vec![
+            "config key missing",
+            "connection refused",
+            "request failed",
+            "shutting down"
+        ]
+    );
+}